@@ -0,0 +1,14 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Into OUT_DIR, as a build must leave the source tree alone.
+    #[cfg(feature = "ffi")]
+    cbindgen::generate(std::env::var("CARGO_MANIFEST_DIR")?)?
+        .write_to_file(std::path::Path::new(&std::env::var("OUT_DIR")?).join("solana_tss.h"));
+    // The gRPC service is part of the client, which the wasm build leaves out.
+    if std::env::var_os("CARGO_FEATURE_CLIENT").is_none() {
+        return Ok(());
+    }
+    // So building doesn't need protoc installed.
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::compile_protos("proto/solana_tss.proto")?;
+    Ok(())
+}
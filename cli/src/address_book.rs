@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use solana_sdk::pubkey::Pubkey;
+
+use solana_tss::Error;
+
+use crate::config::config_dir;
+
+/// Names given to the addresses of recurring counterparties, which every flag taking an address accepts in its place
+pub struct AddressBook {
+    path: PathBuf,
+    aliases: BTreeMap<String, Pubkey>,
+}
+
+impl AddressBook {
+    /// The address book of `~/.config/solana-tss/address_book.toml`, which is empty until an alias is added
+    pub fn load() -> Result<Self, Error> {
+        Self::open(config_dir()?.join("address_book.toml"))
+    }
+
+    fn open(path: PathBuf) -> Result<Self, Error> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(Error::AddressBookFailed(format!("{}: {}", path.display(), e))),
+        };
+        let error = |e: String| Error::AddressBookFailed(format!("{}: {}", path.display(), e));
+        let entries: BTreeMap<String, String> = toml::from_str(&contents).map_err(|e| error(e.to_string()))?;
+        let aliases = entries
+            .into_iter()
+            .map(|(alias, address)| match Pubkey::from_str(&address) {
+                Ok(address) => Ok((alias, address)),
+                Err(_) => Err(error(format!("invalid address of {}: {}", alias, address))),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { path, aliases })
+    }
+
+    /// The address `alias` stands for
+    pub fn resolve(&self, alias: &str) -> Result<Pubkey, Error> {
+        self.aliases.get(alias).copied().ok_or_else(|| Error::UnknownAlias(alias.to_string()))
+    }
+
+    /// The aliases with their addresses, by alias
+    pub fn aliases(&self) -> impl Iterator<Item = (&str, &Pubkey)> {
+        self.aliases.iter().map(|(alias, address)| (alias.as_str(), address))
+    }
+
+    /// Save `address` under `alias`, which mustn't be taken already nor be an address itself, so the flags can't
+    /// mistake one for the other
+    pub fn add(&mut self, alias: &str, address: Pubkey) -> Result<(), Error> {
+        if alias.is_empty() || alias.contains(char::is_whitespace) || Pubkey::from_str(alias).is_ok() {
+            return Err(Error::WrongAlias(alias.to_string()));
+        }
+        if self.aliases.contains_key(alias) {
+            return Err(Error::AliasExists(alias.to_string()));
+        }
+        self.aliases.insert(alias.to_string(), address);
+        self.save()
+    }
+
+    /// Remove `alias`, returning the address it stood for
+    pub fn remove(&mut self, alias: &str) -> Result<Pubkey, Error> {
+        let address = self.aliases.remove(alias).ok_or_else(|| Error::UnknownAlias(alias.to_string()))?;
+        self.save()?;
+        Ok(address)
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let error = |e: String| Error::AddressBookFailed(format!("{}: {}", self.path.display(), e));
+        let entries: BTreeMap<_, _> =
+            self.aliases.iter().map(|(alias, address)| (alias, address.to_string())).collect();
+        let contents = toml::to_string(&entries).map_err(|e| error(e.to_string()))?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| error(e.to_string()))?;
+        }
+        fs::write(&self.path, contents).map_err(|e| error(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+    use solana_tss::serialization::SessionId;
+    use solana_tss::Error;
+
+    use crate::address_book::AddressBook;
+
+    #[test]
+    fn test_address_book() {
+        let dir = std::env::temp_dir().join(format!("solana-tss-address-book-{}", SessionId::random()));
+        let path = dir.join("address_book.toml");
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let mut book = AddressBook::open(path.clone()).unwrap();
+        assert_eq!(book.aliases().count(), 0);
+        book.add("alice", alice).unwrap();
+        book.add("bob-cold", bob).unwrap();
+        assert!(matches!(book.add("alice", bob), Err(Error::AliasExists(..))));
+        assert!(matches!(book.add(&bob.to_string(), alice), Err(Error::WrongAlias(..))));
+        assert!(matches!(book.add("al ice", alice), Err(Error::WrongAlias(..))));
+
+        // Saved as it changes.
+        let mut book = AddressBook::open(path.clone()).unwrap();
+        assert_eq!(book.resolve("alice").unwrap(), alice);
+        assert_eq!(book.aliases().collect::<Vec<_>>(), vec![("alice", &alice), ("bob-cold", &bob)]);
+        assert_eq!(book.remove("bob-cold").unwrap(), bob);
+        assert!(matches!(book.remove("bob-cold"), Err(Error::UnknownAlias(..))));
+        assert!(matches!(AddressBook::open(path).unwrap().resolve("bob-cold"), Err(Error::UnknownAlias(..))));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
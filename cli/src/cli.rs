@@ -0,0 +1,2109 @@
+use std::io::Read;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use bincode::Options as _;
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+use libp2p::Multiaddr;
+use reqwest::{Proxy, Url};
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use solana_sdk::derivation_path::DerivationPath;
+use solana_sdk::hash::Hash;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::signer::keypair::read_keypair_file;
+use solana_sdk::stake::state::StakeAuthorize;
+use solana_sdk::transaction::VersionedTransaction;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    system_program,
+};
+use spl_token_2022::instruction::AuthorityType;
+use tracing::warn;
+use zeroize::Zeroizing;
+
+use solana_tss::governance::{self, Vote};
+use solana_tss::keychain::{self, KeychainEntry};
+use solana_tss::keystore::{self, Keystore};
+use solana_tss::ledger::LedgerKey;
+use solana_tss::network::Network;
+use solana_tss::seal::{parse_sealable, Sealable};
+use solana_tss::serialization::{
+    AggMessage1, DkgMessage1, DkgMessage2, DkgSecretStepOne, Encoding, Format, KeyPop, PartialSignature,
+    SecretAggStepOne, Serialize, SessionId, ThresholdKeyShare, ThresholdMessage1,
+};
+use solana_tss::transaction::parse_unsigned_tx;
+use solana_tss::Error;
+
+use crate::address_book::AddressBook;
+
+#[derive(Debug, Parser)]
+#[clap(about, version, author)]
+pub struct Cli {
+    /// Take the defaults of the flags from this TOML file instead of `~/.config/solana-tss/config.toml`
+    #[clap(long, global = true, env = "SOLANA_TSS_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// The commitment to read the chain and confirm transactions with: processed/confirmed/finalized
+    #[clap(long, global = true, env = "SOLANA_TSS_COMMITMENT", default_value = "finalized", parse(try_from_str = parse_commitment))]
+    pub commitment: CommitmentConfig,
+    /// How many seconds to wait for the RPC node to answer a request
+    #[clap(long, global = true, env = "SOLANA_TSS_RPC_TIMEOUT", default_value_t = 30)]
+    pub rpc_timeout: u64,
+    /// How many times to retry a request that failed on the way to the RPC node, waiting twice as long every time
+    #[clap(long, global = true, env = "SOLANA_TSS_RPC_RETRIES", default_value_t = 3)]
+    pub rpc_retries: u32,
+    /// Send the RPC requests through this proxy, e.g. socks5h://127.0.0.1:9050 for Tor.
+    /// The HTTPS_PROXY, HTTP_PROXY and ALL_PROXY environment variables are used when not given
+    #[clap(long, global = true, env = "SOLANA_TSS_PROXY", parse(try_from_str = parse_proxy))]
+    pub proxy: Option<Proxy>,
+    /// Append the messages sent and received in signing sessions, and the transactions signed, to this hash-chained
+    /// JSON lines file for reviewing them later
+    #[clap(long, global = true, env = "SOLANA_TSS_AUDIT_LOG")]
+    pub audit_log: Option<PathBuf>,
+    /// Check the transactions signed against this policy file instead of `~/.solana-tss/policy.toml`
+    #[clap(long, global = true, env = "SOLANA_TSS_POLICY_FILE")]
+    pub policy_file: Option<PathBuf>,
+    /// Keep the sessions of `--session` in this sled database instead of the files of `~/.solana-tss/sessions`
+    #[clap(long, global = true, env = "SOLANA_TSS_SESSION_DB")]
+    pub session_db: Option<PathBuf>,
+    /// Log more of what happens, `-vv` for everything. RUST_LOG overrides it, e.g. RUST_LOG=solana_tss=debug
+    #[clap(short, long, global = true, parse(from_occurrences))]
+    pub verbose: u8,
+    /// The format of the logs on the standard error: text/json, json has a JSON object per line for log collectors
+    #[clap(long, global = true, env = "SOLANA_TSS_LOG_FORMAT", default_value = "text")]
+    pub log_format: Format,
+    /// The format of the results on the standard output: text/json, json prints a single JSON object per command for
+    /// scripts, and the errors as JSON objects on the standard error
+    #[clap(long, global = true, env = "SOLANA_TSS_OUTPUT", default_value = "text")]
+    pub output: Format,
+    #[clap(subcommand)]
+    pub command: Options,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Subcommand)]
+pub enum Options {
+    /// Generate a pair of keys.
+    #[clap(display_order = 1)]
+    Generate {
+        /// Derive the keys from a new seed phrase, printed to back them up like the keys of any other wallet
+        #[clap(long)]
+        mnemonic: bool,
+        /// The number of words of the seed phrase: 12/15/18/21/24
+        #[clap(long, default_value_t = 12, requires = "mnemonic")]
+        word_count: usize,
+        /// The derivation path of the keys, m/44'/501'/0'/0' when not given like Phantom and Solflare
+        #[clap(long, requires = "mnemonic", parse(try_from_str = parse_derivation_path))]
+        derivation_path: Option<DerivationPath>,
+        /// Write the secret key to this JSON keypair file, in the format of solana-keygen's id.json, instead of printing it
+        #[clap(long)]
+        outfile: Option<PathBuf>,
+        /// Store the secret key in the OS keychain as `keychain:<name>` instead of printing it
+        #[clap(long, conflicts_with = "outfile")]
+        save_key: Option<KeychainEntry>,
+        /// Keep generating keys until the address starts with this Base58 prefix, every character takes about 58 times longer
+        #[clap(long, conflicts_with = "mnemonic")]
+        starts_with: Option<String>,
+        /// Match the prefix regardless of the case of its letters, which is a lot faster
+        #[clap(long, requires = "starts-with")]
+        ignore_case: bool,
+        /// With `--starts-with`, match the aggregated address of these keys followed by the new one instead.
+        /// Pass them in the order `aggregate-keys` will be given them, with the new key last
+        #[clap(parse(try_from_str = parse_address), long, requires = "starts-with", min_values = 1)]
+        aggregate_with: Vec<Pubkey>,
+        /// The number of threads to generate keys on, one per CPU when not given
+        #[clap(long, requires = "starts-with")]
+        threads: Option<usize>,
+    },
+    /// Recover the pair of keys of a seed phrase, e.g. one backed up in another wallet. The phrase is read from the standard input.
+    #[clap(display_order = 1)]
+    Recover {
+        /// The derivation path of the keys, m/44'/501'/0'/0' when not given like Phantom and Solflare.
+        /// Another account of theirs is at m/44'/501'/<account>'/0', of `solana-keygen` with `?key=<account>/<change>` at m/44'/501'/<account>'/<change>'
+        #[clap(long, parse(try_from_str = parse_derivation_path))]
+        derivation_path: Option<DerivationPath>,
+        /// Use the seed itself as the secret key, like `solana-keygen new` and `solana-keygen recover` without a derivation path
+        #[clap(long, conflicts_with = "derivation-path")]
+        no_derivation_path: bool,
+    },
+    /// Split a secret key into backup shares, any `threshold` of which restore it, to keep them in different places
+    #[clap(display_order = 1)]
+    BackupSplit {
+        /// The number of shares needed to restore the key
+        #[clap(long)]
+        threshold: u8,
+        /// The number of shares to split the key into
+        #[clap(long)]
+        shares: u8,
+        /// A Base58 secret key, the path of a JSON keypair file like solana-keygen's id.json, or `keychain:<name>`. `SOLANA_TSS_KEYPAIR`, the `keypair` of the configuration file, or prompted for, when not given
+        #[clap(parse(try_from_str = parse_keypair), long, visible_alias = "key-source")]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json, or of a keystore whose passphrase is then prompted for
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+    },
+    /// Restore a secret key from its backup shares, which are read from the standard input
+    #[clap(display_order = 1)]
+    BackupRestore {
+        /// Write the secret key to this JSON keypair file, in the format of solana-keygen's id.json, instead of printing it
+        #[clap(long)]
+        outfile: Option<PathBuf>,
+    },
+    /// Encrypt a secret key with a passphrase into a keystore file, which every command taking `--keypair-file` accepts too
+    #[clap(display_order = 1)]
+    KeystoreCreate {
+        /// The path of the keystore file to create, an existing file is never overwritten
+        path: PathBuf,
+        /// A Base58 secret key, the path of a JSON keypair file like solana-keygen's id.json, or `keychain:<name>`. `SOLANA_TSS_KEYPAIR`, the `keypair` of the configuration file, or prompted for, when not given
+        #[clap(parse(try_from_str = parse_keypair), long, visible_alias = "key-source")]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+    },
+    /// Check the passphrase of a keystore file, and print the public key it holds the secret key of
+    #[clap(display_order = 1)]
+    KeystoreUnlock {
+        /// The path of the keystore file
+        path: PathBuf,
+    },
+    /// Decrypt the secret key of a keystore file, to use it somewhere that doesn't take keystores
+    #[clap(display_order = 1)]
+    KeystoreExport {
+        /// The path of the keystore file
+        path: PathBuf,
+        /// Write the secret key to this JSON keypair file, in the format of solana-keygen's id.json, instead of printing it
+        #[clap(long)]
+        outfile: Option<PathBuf>,
+    },
+    /// Store a secret key in the OS keychain, to pass it to the commands taking `--keypair` as `keychain:<name>`
+    #[clap(display_order = 1)]
+    KeychainStore {
+        /// The `keychain:<name>` to store it as, an existing entry is never overwritten
+        entry: KeychainEntry,
+        /// A Base58 secret key, or the path of a JSON keypair file like solana-keygen's id.json. `SOLANA_TSS_KEYPAIR`, the `keypair` of the configuration file, or prompted for, when not given
+        #[clap(parse(try_from_str = parse_keypair), long)]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json, or of a keystore whose passphrase is then prompted for
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+    },
+    /// Remove a secret key, secret state or key share from the OS keychain
+    #[clap(display_order = 1)]
+    KeychainDelete {
+        /// The `keychain:<name>` to remove
+        entry: KeychainEntry,
+    },
+    /// Print the address of a key held by a Ledger. It can't be one of the aggregated keys, the Ledger co-signs whole transactions with `ledger-sign` instead
+    #[clap(display_order = 1)]
+    LedgerPubkey {
+        /// The key on the Ledger: usb://ledger?key=<account>[/<change>] like the Solana CLI, m/44'/501' without `?key=`
+        key: LedgerKey,
+        /// Show the address on the device too, to check it against the printed one
+        #[clap(long)]
+        confirm: bool,
+    },
+    /// Co-sign a transaction with a key held by a Ledger, which shows the transaction for approval before signing it
+    #[clap(display_order = 1)]
+    LedgerSign {
+        /// The key on the Ledger: usb://ledger?key=<account>[/<change>] like the Solana CLI, m/44'/501' without `?key=`
+        key: LedgerKey,
+        /// The base64 transaction, e.g. a partially signed one printed by the aggregation commands with `--no-send`
+        #[clap(parse(try_from_str = parse_partially_signed_tx))]
+        tx: VersionedTransaction,
+        /// Print the signatures as `PUBKEY=SIGNATURE` for the `--signer` of the aggregation commands and the Solana CLI,
+        /// instead of the transaction
+        #[clap(long)]
+        sign_only: bool,
+        /// The output format: text/json
+        #[clap(long, default_value = "text")]
+        format: Format,
+    },
+    /// Check the balance of an address.
+    #[clap(display_order = 2)]
+    Balance {
+        /// The address to check the balance of
+        #[clap(parse(try_from_str = parse_address))]
+        address: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Request an airdrop from a faucet.
+    #[clap(display_order = 3)]
+    Airdrop {
+        /// Address of the recipient
+        #[clap(parse(try_from_str = parse_address), long)]
+        to: Pubkey,
+        /// The amount of SOL you want to send.
+        #[clap(long)]
+        amount: f64,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Send a transaction using a single private key.
+    #[clap(display_order = 4)]
+    SendSingle {
+        /// A Base58 secret key, the path of a JSON keypair file like solana-keygen's id.json, or `keychain:<name>`. `SOLANA_TSS_KEYPAIR`, the `keypair` of the configuration file, or prompted for, when not given
+        #[clap(parse(try_from_str = parse_keypair), long, visible_alias = "key-source")]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json, or of a keystore whose passphrase is then prompted for
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+        /// The amount of SOL (or of the token with `--token-mint`) you want to send.
+        #[clap(long, required_unless_present = "all")]
+        amount: Option<f64>,
+        /// Send all the SOL in the account, minus the fee
+        #[clap(long, conflicts_with_all = &["amount", "token-mint"])]
+        all: bool,
+        /// Send this SPL token instead of SOL, between the associated token accounts of the addresses
+        #[clap(parse(try_from_str = parse_address), long)]
+        token_mint: Option<Pubkey>,
+        /// The number of decimals of the token, looked up from the mint when not given
+        #[clap(long, requires = "token-mint")]
+        token_decimals: Option<u8>,
+        /// The mint is owned by the Token-2022 program, looked up from the mint when `--token-decimals` isn't given
+        #[clap(long, requires = "token-decimals")]
+        token_2022: bool,
+        /// The transfer fee the Token-2022 mint withholds, in units of the token, looked up when `--token-decimals` isn't given
+        #[clap(long, requires = "token-2022")]
+        token_fee: Option<f64>,
+        /// Create the recipient's associated token account if it doesn't exist, the sender pays for it
+        #[clap(long, requires = "token-mint")]
+        fund_recipient: bool,
+        /// Address of the recipient
+        #[clap(parse(try_from_str = parse_address), long)]
+        to: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+        /// Send the transaction without simulating it first
+        #[clap(long)]
+        skip_preflight: bool,
+        /// The commitment to simulate the transaction with before sending: processed/confirmed/finalized, `--commitment` when not given
+        #[clap(long, conflicts_with = "skip-preflight", parse(try_from_str = parse_commitment))]
+        preflight_commitment: Option<CommitmentConfig>,
+        /// Only simulate the signed transaction and print what it would do, instead of sending it
+        #[clap(long, conflicts_with = "skip-preflight")]
+        simulate_only: bool,
+        /// Add a memo to the transaction
+        #[clap(long)]
+        memo: Option<String>,
+    },
+    /// Print the hash of a recent block, can be used to pass to the `agg-send` steps
+    #[clap(display_order = 8)]
+    RecentBlockHash {
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Aggregate a list of addresses into a single address that they can all sign on together
+    #[clap(display_order = 5)]
+    AggregateKeys {
+        /// List of addresses
+        #[clap(parse(try_from_str = parse_address), min_values = 2, required = true)]
+        keys: Vec<Pubkey>,
+        /// Refuse to aggregate keys that don't come with a valid proof of possession
+        #[clap(long, requires = "pops")]
+        verify_pop: bool,
+        /// A list of the proofs of possession of the keys, produced by `key-pop`
+        #[clap(long, min_values = 2, forbid_empty_values = true, parse(try_from_str = Serialize::parse))]
+        pops: Vec<KeyPop>,
+    },
+    /// Prove possession of a key, so the other parties can check it when aggregating keys with `--verify-pop`
+    #[clap(display_order = 7)]
+    KeyPop {
+        /// Deprecated, use `--keypair-file`, `--keypair-stdin` or `SOLANA_TSS_KEYPAIR` to keep the secret key out of the shell history
+        #[clap(parse(try_from_str = parse_keypair), conflicts_with_all = &["keypair", "keypair-file", "keypair-stdin"])]
+        secret_key: Option<Keypair>,
+        /// A Base58 secret key, the path of a JSON keypair file like solana-keygen's id.json, or `keychain:<name>`. `SOLANA_TSS_KEYPAIR`, the `keypair` of the configuration file, or prompted for, when not given
+        #[clap(parse(try_from_str = parse_keypair), long, visible_alias = "key-source")]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json, or of a keystore whose passphrase is then prompted for
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+        /// The encoding of the printed messages: bs58/base64/hex, messages in any of them are accepted as input
+        #[clap(long, default_value = "bs58")]
+        encoding: Encoding,
+    },
+    /// Start aggregate signing
+    #[clap(display_order = 6)]
+    AggSendStepOne {
+        /// Deprecated, use `--keypair-file`, `--keypair-stdin` or `SOLANA_TSS_KEYPAIR` to keep the secret key out of the shell history
+        #[clap(parse(try_from_str = parse_keypair), conflicts_with_all = &["keypair", "keypair-file", "keypair-stdin"])]
+        secret_key: Option<Keypair>,
+        /// A Base58 secret key of the party signing, the path of its JSON keypair file like solana-keygen's id.json, or `keychain:<name>`. `SOLANA_TSS_KEYPAIR`, the `keypair` of the configuration file, or prompted for, when not given
+        #[clap(parse(try_from_str = parse_keypair), long, visible_alias = "key-source")]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json, or of a keystore whose passphrase is then prompted for
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+        /// The session ID printed by the first party to run step 1, leave empty to start a new session
+        #[clap(long)]
+        session_id: Option<SessionId>,
+        /// Store the secret state in the OS keychain as `keychain:<name>`, and print that instead of the secret state
+        #[clap(long)]
+        save_secret_state: Option<KeychainEntry>,
+        /// Encrypt the secret state with a passphrase prompted for, the next step asks for it to decrypt it
+        #[clap(long)]
+        protect: bool,
+        /// Keep the secret state in the session store under this name instead of printing it, for `--session` of step 2
+        #[clap(long, conflicts_with_all = &["save-secret-state", "protect"])]
+        session: Option<String>,
+        /// Seal the first message to these addresses, the other parties, so only they can read it
+        #[clap(parse(try_from_str = parse_address), long, min_values = 1)]
+        encrypt_to: Vec<Pubkey>,
+        /// Post the first message to this relay started with `relay-serve`, for the other parties' step 2 to fetch it
+        #[clap(long)]
+        relay: Option<Url>,
+        /// Write the first message to a file in this directory, e.g. one shared with the other parties, for their `--in-dir`
+        #[clap(long)]
+        out_dir: Option<PathBuf>,
+        /// Also show the first message as QR codes, for an air-gapped machine to be scanned off with `scan`
+        #[clap(long)]
+        qr: bool,
+        /// Post the first message on chain as a memo transaction on this network: Mainnet/Testnet/Devnet/Localnet or the
+        /// URL of an RPC node, for the other parties' step 2 to read it back from the history of the session's address
+        #[clap(long)]
+        board: Option<Network>,
+        /// The keypair paying for the memo transactions of `--board`, a Base58 secret key, the path of a JSON keypair
+        /// file or `keychain:<name>`, the party's keypair when not given
+        #[clap(long, requires = "board", parse(try_from_str = parse_keypair))]
+        board_payer: Option<Keypair>,
+        /// The output format: text/json
+        #[clap(long, default_value = "text")]
+        format: Format,
+        /// The encoding of the printed messages: bs58/base64/hex, messages in any of them are accepted as input
+        #[clap(long, default_value = "bs58")]
+        encoding: Encoding,
+    },
+    /// Step 2 of aggregate signing, you should pass in the secret data from step 1.
+    /// It's important that all parties pass in exactly the same transaction details (amount,to,net,memo,recent_block_hash,nonce_account,lookup_table,unsigned_tx)
+    #[clap(display_order = 9)]
+    AggSendStepTwo {
+        /// A Base58 secret key of the party signing, the path of its JSON keypair file like solana-keygen's id.json, or `keychain:<name>`. `SOLANA_TSS_KEYPAIR`, the `keypair` of the configuration file, or prompted for, when not given
+        #[clap(parse(try_from_str = parse_keypair), long, visible_alias = "key-source")]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json, or of a keystore whose passphrase is then prompted for
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+        /// The amount of SOL (or of the token with `--token-mint`) you want to send.
+        #[clap(long, required_unless_present_any = &["unsigned-tx", "all"])]
+        amount: Option<f64>,
+        /// Send all the SOL in the wallet minus the fee, as read from `--net`. All parties *must* sign before the balance changes.
+        #[clap(long, conflicts_with_all = &["amount", "token-mint", "unsigned-tx"])]
+        all: bool,
+        /// Send this SPL token instead of SOL, between the associated token accounts of the addresses
+        #[clap(parse(try_from_str = parse_address), long)]
+        token_mint: Option<Pubkey>,
+        /// The number of decimals of the token, looked up from the mint on `--net` when not given. All parties *must* pass in the same decimals.
+        #[clap(long, requires = "token-mint")]
+        token_decimals: Option<u8>,
+        /// The mint is owned by the Token-2022 program, looked up from the mint when `--token-decimals` isn't given
+        #[clap(long, requires = "token-decimals")]
+        token_2022: bool,
+        /// The transfer fee the Token-2022 mint withholds, in units of the token, looked up when `--token-decimals` isn't given. All parties *must* pass in the same fee.
+        #[clap(long, requires = "token-2022")]
+        token_fee: Option<f64>,
+        /// Create the recipient's associated token account if it doesn't exist, the sender pays for it
+        #[clap(long, requires = "token-mint")]
+        fund_recipient: bool,
+        /// Address of the recipient
+        #[clap(parse(try_from_str = parse_address), long, required_unless_present = "unsigned-tx")]
+        to: Option<Pubkey>,
+        /// Add a memo to the transaction
+        #[clap(long)]
+        memo: Option<String>,
+        /// A hash of a recent block, can be obtained by calling `recent-block-hash`, all parties *must* pass in the same hash.
+        /// It replaces the one in `--unsigned-tx` if given. With `--nonce-account`, the nonce printed by `nonce-show` instead.
+        #[clap(long, required_unless_present = "unsigned-tx")]
+        recent_block_hash: Option<Hash>,
+        /// Use the durable nonce of this account instead of a recent block hash, so the signing can take as long as it needs.
+        /// The aggregated address must be its authority.
+        #[clap(parse(try_from_str = parse_address), long, conflicts_with = "unsigned-tx")]
+        nonce_account: Option<Pubkey>,
+        /// Build a v0 transaction that refers to accounts through these address lookup tables, read from `--net`.
+        /// All parties *must* pass in the same tables.
+        #[clap(parse(try_from_str = parse_address), long, conflicts_with = "unsigned-tx")]
+        lookup_table: Vec<Pubkey>,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node, only used to read the `--lookup-table`s, the balance for `--all` and the mint without `--token-decimals`. Without them it isn't contacted, so this step can run air-gapped
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+        /// Sign this base64 (legacy or v0) transaction or message instead of a transfer, e.g. one built by a dApp. Its instructions are printed before signing,
+        /// and the aggregated address must be one of its signers. All parties *must* pass in the same transaction.
+        #[clap(long, conflicts_with_all = &["amount", "to", "memo", "token-mint"], parse(try_from_str = parse_unsigned_tx))]
+        unsigned_tx: Option<VersionedMessage>,
+        /// List of addresses that are part of this
+        #[clap(parse(try_from_str = parse_address), long, required = true, min_values = 2)]
+        keys: Vec<Pubkey>,
+        /// If this party signs for an aggregated address that is itself one of the `keys`, the addresses making up that aggregated address
+        #[clap(parse(try_from_str = parse_address), long, min_values = 2)]
+        inner_keys: Vec<Pubkey>,
+        /// A list of all the first messages received in step 1, sealed ones are opened with the keypair
+        #[clap(long, required_unless_present_any = &["relay", "p2p", "in-dir", "board"], min_values = 1, forbid_empty_values = true, parse(try_from_str = parse_sealable))]
+        first_messages: Vec<Sealable<AggMessage1>>,
+        /// Fetch the first messages of the other parties from this relay started with `relay-serve`, waiting for all of
+        /// them, and post the partial signature to it
+        #[clap(long, conflicts_with_all = &["first-messages", "inner-keys"])]
+        relay: Option<Url>,
+        /// Exchange the first messages and the partial signature with the other parties directly over a peer-to-peer
+        /// network, they're asked for if they don't arrive
+        #[clap(long, conflicts_with_all = &["first-messages", "inner-keys", "relay"])]
+        p2p: bool,
+        /// The address of a peer to connect to with `--p2p`, or of a libp2p relay ending in `/p2p-circuit` to be
+        /// reached through, when the parties aren't on the same local network
+        #[clap(long, requires = "p2p")]
+        p2p_peer: Vec<Multiaddr>,
+        /// Read the first messages of the other parties from the files `--out-dir` of their step 1 wrote to this directory
+        #[clap(long, conflicts_with_all = &["first-messages", "inner-keys", "relay", "p2p"])]
+        in_dir: Option<PathBuf>,
+        /// Write the partial signature to a file in this directory, for the `--in-dir` of the aggregation
+        #[clap(long)]
+        out_dir: Option<PathBuf>,
+        /// Also show the partial signature as QR codes, for an air-gapped machine to be scanned off with `scan`
+        #[clap(long)]
+        qr: bool,
+        /// Read the first messages of the other parties from the memo transactions their step 1 posted on this network
+        /// with `--board`, waiting for all of them, and post the partial signature the same way
+        #[clap(long, conflicts_with_all = &["first-messages", "inner-keys", "relay", "p2p", "in-dir"])]
+        board: Option<Network>,
+        /// The keypair paying for the memo transactions of `--board`, a Base58 secret key, the path of a JSON keypair
+        /// file or `keychain:<name>`, the party's keypair when not given
+        #[clap(long, requires = "board", parse(try_from_str = parse_keypair))]
+        board_payer: Option<Keypair>,
+        /// The session ID of step 1, to bind the nonces of `--from-pool` to when the first messages aren't passed in
+        #[clap(long)]
+        session_id: Option<SessionId>,
+        /// The secret state received in step 2 or the `keychain:<name>` it was saved to, prompted for without echoing it when neither it nor `--from-pool` is given
+        #[clap(long, forbid_empty_values = true, parse(try_from_str = parse_secret))]
+        secret_state: Option<SecretAggStepOne>,
+        /// Take nonces from the pool filled by `agg-nonce-pool` instead, skipping step 1: Message 1 is printed and sent along
+        #[clap(long, conflicts_with = "secret-state")]
+        from_pool: bool,
+        /// Take the secret state from the session of this name started with `--session` in step 1, which is deleted once signed
+        #[clap(long, conflicts_with_all = &["secret-state", "from-pool"])]
+        session: Option<String>,
+        /// Sign even if the secret state was already used, this is only safe when signing the exact same transaction again
+        #[clap(long)]
+        force: bool,
+        /// Seal the partial signature to these addresses, whoever aggregates the signatures, so only they can read it
+        #[clap(parse(try_from_str = parse_address), long, min_values = 1)]
+        encrypt_to: Vec<Pubkey>,
+        /// The output format: text/json
+        #[clap(long, default_value = "text")]
+        format: Format,
+        /// The encoding of the printed messages: bs58/base64/hex, messages in any of them are accepted as input
+        #[clap(long, default_value = "bs58")]
+        encoding: Encoding,
+    },
+    /// Aggregate all the partial signatures together into a full signature, and send the transaction to Solana
+    #[clap(display_order = 10)]
+    AggregateSignaturesAndBroadcast {
+        // A list of all partial signatures produced in step three.
+        #[clap(long, required_unless_present_any = &["relay", "p2p", "in-dir", "board"], min_values = 2, forbid_empty_values = true, parse(try_from_str = parse_sealable))]
+        signatures: Vec<Sealable<PartialSignature>>,
+        /// Fetch the partial signatures of all parties from this relay started with `relay-serve`, waiting for all of
+        /// them, and their first messages to find out which party caused a failure
+        #[clap(long, requires = "session-id", conflicts_with_all = &["signatures", "first-messages"])]
+        relay: Option<Url>,
+        /// Receive the partial signatures of all parties directly over a peer-to-peer network, they're asked for if
+        /// they don't arrive
+        #[clap(long, requires = "session-id", conflicts_with_all = &["signatures", "first-messages", "relay"])]
+        p2p: bool,
+        /// The address of a peer to connect to with `--p2p`, or of a libp2p relay ending in `/p2p-circuit` to be
+        /// reached through, when the parties aren't on the same local network
+        #[clap(long, requires = "p2p")]
+        p2p_peer: Vec<Multiaddr>,
+        /// Read the partial signatures of all parties from the files `--out-dir` of their step 2 wrote to this directory,
+        /// and their first messages to find out which party caused a failure
+        #[clap(long, requires = "session-id", conflicts_with_all = &["signatures", "first-messages", "relay", "p2p"])]
+        in_dir: Option<PathBuf>,
+        /// Read the partial signatures of all parties from the memo transactions their step 2 posted on this network with
+        /// `--board`, waiting for all of them, and their first messages to find out which party caused a failure
+        #[clap(long, requires = "session-id", conflicts_with_all = &["signatures", "first-messages", "relay", "p2p", "in-dir"])]
+        board: Option<Network>,
+        /// The session ID of step 1, to find the session on the relay, the peer-to-peer network, in `--in-dir` or on the `--board`
+        #[clap(long)]
+        session_id: Option<SessionId>,
+        /// The keypair the partial signatures or first messages were sealed to with `--encrypt-to`, a Base58 secret key,
+        /// the path of a JSON keypair file or `keychain:<name>`
+        #[clap(long, parse(try_from_str = parse_keypair))]
+        decryption_keypair: Option<Keypair>,
+        /// The amount of SOL (or of the token with `--token-mint`) you want to send.
+        #[clap(long, required_unless_present_any = &["unsigned-tx", "all"])]
+        amount: Option<f64>,
+        /// Send all the SOL in the wallet minus the fee, all parties *must* have signed with `--all` too.
+        #[clap(long, conflicts_with_all = &["amount", "token-mint", "unsigned-tx"])]
+        all: bool,
+        /// Send this SPL token instead of SOL, between the associated token accounts of the addresses
+        #[clap(parse(try_from_str = parse_address), long)]
+        token_mint: Option<Pubkey>,
+        /// The number of decimals of the token, looked up from the mint when not given
+        #[clap(long, requires = "token-mint")]
+        token_decimals: Option<u8>,
+        /// The mint is owned by the Token-2022 program, looked up from the mint when `--token-decimals` isn't given
+        #[clap(long, requires = "token-decimals")]
+        token_2022: bool,
+        /// The transfer fee the Token-2022 mint withholds, in units of the token, looked up when `--token-decimals` isn't given
+        #[clap(long, requires = "token-2022")]
+        token_fee: Option<f64>,
+        /// Create the recipient's associated token account if it doesn't exist, the sender pays for it
+        #[clap(long, requires = "token-mint")]
+        fund_recipient: bool,
+        /// Address of the recipient
+        #[clap(parse(try_from_str = parse_address), long, required_unless_present = "unsigned-tx")]
+        to: Option<Pubkey>,
+        /// Add a memo to the transaction
+        #[clap(long, forbid_empty_values = true)]
+        memo: Option<String>,
+        /// A hash of a recent block, can be obtained by calling `recent-block-hash`, all parties *must* pass in the same hash.
+        /// It replaces the one in `--unsigned-tx` if given. With `--nonce-account` the nonce is read from the account when not given.
+        #[clap(long, required_unless_present_any = &["unsigned-tx", "nonce-account"])]
+        recent_block_hash: Option<Hash>,
+        /// Use the durable nonce of this account instead of a recent block hash, all parties *must* pass in the same account.
+        #[clap(parse(try_from_str = parse_address), long, conflicts_with = "unsigned-tx")]
+        nonce_account: Option<Pubkey>,
+        /// Build a v0 transaction that refers to accounts through these address lookup tables, all parties *must* pass in the same tables.
+        #[clap(parse(try_from_str = parse_address), long, conflicts_with = "unsigned-tx")]
+        lookup_table: Vec<Pubkey>,
+        /// Sign this base64 (legacy or v0) transaction or message instead of a transfer, e.g. one built by a dApp. Its instructions are printed before signing,
+        /// and the aggregated address must be one of its signers. All parties *must* pass in the same transaction.
+        #[clap(long, conflicts_with_all = &["amount", "to", "memo", "token-mint"], parse(try_from_str = parse_unsigned_tx))]
+        unsigned_tx: Option<VersionedMessage>,
+        /// List of addresses that are part of this
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+        /// Send the transaction without simulating it first
+        #[clap(long)]
+        skip_preflight: bool,
+        /// The commitment to simulate the transaction with before sending: processed/confirmed/finalized, `--commitment` when not given
+        #[clap(long, conflicts_with = "skip-preflight", parse(try_from_str = parse_commitment))]
+        preflight_commitment: Option<CommitmentConfig>,
+        /// Only simulate the signed transaction and print what it would do, instead of sending it
+        #[clap(long, conflicts_with = "skip-preflight")]
+        simulate_only: bool,
+        /// Print the signed transaction base64 encoded instead of sending it, to send it some other way
+        #[clap(long, conflicts_with_all = &["skip-preflight", "preflight-commitment", "simulate-only"])]
+        no_send: bool,
+        /// Print the signatures as PUBKEY=SIGNATURE like the Solana CLI's `--sign-only` instead of sending the transaction,
+        /// for another signer to pass to its `--signer`
+        #[clap(long, conflicts_with_all = &["skip-preflight", "preflight-commitment", "simulate-only", "no-send"])]
+        sign_only: bool,
+        /// The signature of another signer of `--unsigned-tx` as PUBKEY=SIGNATURE, like the Solana CLI's `--signer`
+        #[clap(long = "signer", parse(try_from_str = parse_signer))]
+        signers: Vec<(Pubkey, Signature)>,
+        /// List of addresses
+        #[clap(parse(try_from_str = parse_address), long, required = true, min_values = 2)]
+        keys: Vec<Pubkey>,
+        /// The messages all parties sent in step one, if the signing fails these are used to find out which party caused it.
+        #[clap(long, forbid_empty_values = true, parse(try_from_str = parse_sealable))]
+        first_messages: Vec<Sealable<AggMessage1>>,
+        /// The output format: text/json
+        #[clap(long, default_value = "text")]
+        format: Format,
+    },
+    /// Run step 1, step 2 and the aggregation in one go, exchanging the messages with the other parties over a
+    /// transport, and send the transaction. Every party runs it with the same session ID and transaction details.
+    #[clap(display_order = 10)]
+    AggSend {
+        /// A Base58 secret key of the party signing, the path of its JSON keypair file like solana-keygen's id.json, or `keychain:<name>`. `SOLANA_TSS_KEYPAIR`, the `keypair` of the configuration file, or prompted for, when not given
+        #[clap(parse(try_from_str = parse_keypair), long, visible_alias = "key-source")]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json, or of a keystore whose passphrase is then prompted for
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+        /// The session ID all parties agreed on for this transaction, a new one every time, e.g. printed by `session-id-new`
+        #[clap(long)]
+        session_id: SessionId,
+        /// The amount of SOL (or of the token with `--token-mint`) you want to send.
+        #[clap(long, required_unless_present = "unsigned-tx")]
+        amount: Option<f64>,
+        /// Send this SPL token instead of SOL, between the associated token accounts of the addresses
+        #[clap(parse(try_from_str = parse_address), long)]
+        token_mint: Option<Pubkey>,
+        /// The number of decimals of the token, looked up from the mint when not given
+        #[clap(long, requires = "token-mint")]
+        token_decimals: Option<u8>,
+        /// The mint is owned by the Token-2022 program, looked up from the mint when `--token-decimals` isn't given
+        #[clap(long, requires = "token-decimals")]
+        token_2022: bool,
+        /// The transfer fee the Token-2022 mint withholds, in units of the token, looked up when `--token-decimals` isn't given
+        #[clap(long, requires = "token-2022")]
+        token_fee: Option<f64>,
+        /// Create the recipient's associated token account if it doesn't exist, the sender pays for it
+        #[clap(long, requires = "token-mint")]
+        fund_recipient: bool,
+        /// Address of the recipient
+        #[clap(parse(try_from_str = parse_address), long, required_unless_present = "unsigned-tx")]
+        to: Option<Pubkey>,
+        /// Add a memo to the transaction
+        #[clap(long, forbid_empty_values = true)]
+        memo: Option<String>,
+        /// A hash of a recent block, can be obtained by calling `recent-block-hash`, all parties *must* pass in the same hash.
+        /// It replaces the one in `--unsigned-tx` if given. With `--nonce-account` the nonce is read from the account when not given.
+        #[clap(long, required_unless_present_any = &["unsigned-tx", "nonce-account"])]
+        recent_block_hash: Option<Hash>,
+        /// Use the durable nonce of this account instead of a recent block hash, all parties *must* pass in the same account.
+        #[clap(parse(try_from_str = parse_address), long, conflicts_with = "unsigned-tx")]
+        nonce_account: Option<Pubkey>,
+        /// Build a v0 transaction that refers to accounts through these address lookup tables, all parties *must* pass in the same tables.
+        #[clap(parse(try_from_str = parse_address), long, conflicts_with = "unsigned-tx")]
+        lookup_table: Vec<Pubkey>,
+        /// Sign this base64 (legacy or v0) transaction or message instead of a transfer, e.g. one built by a dApp. Its instructions are printed before signing,
+        /// and the aggregated address must be one of its signers. All parties *must* pass in the same transaction.
+        #[clap(long, conflicts_with_all = &["amount", "to", "memo", "token-mint"], parse(try_from_str = parse_unsigned_tx))]
+        unsigned_tx: Option<VersionedMessage>,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+        /// Send the transaction without simulating it first
+        #[clap(long)]
+        skip_preflight: bool,
+        /// The commitment to simulate the transaction with before sending: processed/confirmed/finalized, `--commitment` when not given
+        #[clap(long, conflicts_with = "skip-preflight", parse(try_from_str = parse_commitment))]
+        preflight_commitment: Option<CommitmentConfig>,
+        /// List of addresses that are part of this
+        #[clap(parse(try_from_str = parse_address), long, required = true, min_values = 2)]
+        keys: Vec<Pubkey>,
+        /// Exchange the messages over this relay started with `relay-serve`
+        #[clap(long, required_unless_present_any = &["p2p", "dir", "board"], conflicts_with_all = &["p2p", "dir", "board"])]
+        relay: Option<Url>,
+        /// Exchange the messages with the other parties directly over a peer-to-peer network
+        #[clap(long, conflicts_with_all = &["dir", "board"])]
+        p2p: bool,
+        /// The address of a peer to connect to with `--p2p`, or of a libp2p relay ending in `/p2p-circuit` to be
+        /// reached through, when the parties aren't on the same local network
+        #[clap(long, requires = "p2p")]
+        p2p_peer: Vec<Multiaddr>,
+        /// Exchange the messages as files in this directory, shared with the other parties
+        #[clap(long, conflicts_with = "board")]
+        dir: Option<PathBuf>,
+        /// Exchange the messages as memo transactions on this network: Mainnet/Testnet/Devnet/Localnet or the URL of an
+        /// RPC node
+        #[clap(long)]
+        board: Option<Network>,
+        /// The keypair paying for the memo transactions of `--board`, a Base58 secret key, the path of a JSON keypair
+        /// file or `keychain:<name>`, the party's keypair when not given
+        #[clap(long, requires = "board", parse(try_from_str = parse_keypair))]
+        board_payer: Option<Keypair>,
+        /// The output format: text/json
+        #[clap(long, default_value = "text")]
+        format: Format,
+        /// The encoding of the messages sent: bs58/base64/hex, messages in any of them are accepted
+        #[clap(long, default_value = "bs58")]
+        encoding: Encoding,
+    },
+    /// Sign a transaction step by step in a full-screen wizard: review it, exchange the messages with the other parties
+    /// by pasting them or over a relay, and approve sending it. The messages are those of the three signing steps, so
+    /// the other parties can use either.
+    #[clap(display_order = 10)]
+    Tui {
+        /// A Base58 secret key of the party signing, the path of its JSON keypair file like solana-keygen's id.json, or `keychain:<name>`. `SOLANA_TSS_KEYPAIR`, the `keypair` of the configuration file, or prompted for, when not given
+        #[clap(parse(try_from_str = parse_keypair), long, visible_alias = "key-source")]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json, or of a keystore whose passphrase is then prompted for
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+        /// The session ID of the party that started the ceremony, leave empty to start a new one and pass the ID shown on
+        #[clap(long)]
+        session_id: Option<SessionId>,
+        /// The base64 (legacy or v0) transaction or message to sign, pasted in the wizard when not given
+        #[clap(long, parse(try_from_str = parse_unsigned_tx))]
+        unsigned_tx: Option<VersionedMessage>,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+        /// List of addresses that are part of this
+        #[clap(parse(try_from_str = parse_address), long, required = true, min_values = 2)]
+        keys: Vec<Pubkey>,
+        /// Also exchange the messages over this relay started with `relay-serve`
+        #[clap(long)]
+        relay: Option<Url>,
+        /// The encoding of the messages shown: bs58/base64/hex, messages in any of them are accepted
+        #[clap(long, default_value = "bs58")]
+        encoding: Encoding,
+    },
+    /// Abort a signing session abandoned midway, so its secret state is refused by step 2 from then on, even with `--force`
+    #[clap(display_order = 10)]
+    AggAbort {
+        /// The secret state of the session from step 1 or the `keychain:<name>` it was saved to, prompted for without echoing it when not given
+        #[clap(long, forbid_empty_values = true, parse(try_from_str = parse_secret))]
+        secret_state: Option<SecretAggStepOne>,
+        /// The public key of the party, for an aggregate signing session
+        #[clap(parse(try_from_str = parse_address), long, required_unless_present_any = &["share", "session"], conflicts_with = "share")]
+        key: Option<Pubkey>,
+        /// The key share of the party or the `keychain:<name>` it was saved to, for a threshold signing session
+        #[clap(long, forbid_empty_values = true, parse(try_from_str = parse_secret))]
+        share: Option<ThresholdKeyShare>,
+        /// Abort the session of this name started with `--session` in step 1 instead, and delete it
+        #[clap(long, conflicts_with_all = &["secret-state", "key", "share"])]
+        session: Option<String>,
+    },
+    /// Hold the key of a party and sign for it as a service until interrupted: sessions are created for a transaction,
+    /// the other parties' first messages are submitted to them, and their partial signatures fetched once approved.
+    /// Over gRPC, or over HTTP with JSON
+    #[clap(display_order = 10)]
+    Daemon {
+        /// A Base58 secret key of the party signing, the path of its JSON keypair file like solana-keygen's id.json, or `keychain:<name>`. `SOLANA_TSS_KEYPAIR`, the `keypair` of the configuration file, or prompted for, when not given
+        #[clap(parse(try_from_str = parse_keypair), long, visible_alias = "key-source")]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json, or of a keystore whose passphrase is then prompted for
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+        /// Serve the `SigningService` of `proto/solana_tss.proto` over gRPC on this address
+        #[clap(long, required_unless_present_any = &["http", "print-openapi"])]
+        grpc: Option<SocketAddr>,
+        /// Serve a JSON API mirroring the commands over HTTP on this address, described in its `/openapi.json`
+        #[clap(long)]
+        http: Option<SocketAddr>,
+        /// Print the OpenAPI description of the JSON API and exit
+        #[clap(long, conflicts_with_all = &["grpc", "http"])]
+        print_openapi: bool,
+        /// The network the transactions are for, checked against the networks of the signing policy
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+        /// The encoding of the messages returned: bs58/base64/hex, messages in any of them are accepted
+        #[clap(long, default_value = "bs58")]
+        encoding: Encoding,
+        /// How many seconds a session can go without any request for it before it expires with its secret state
+        #[clap(long, default_value_t = 3600)]
+        session_timeout: u64,
+    },
+    /// List the signing sessions of a daemon serving `--http`, with their parties and when they expire
+    #[clap(display_order = 10)]
+    DaemonSessions {
+        /// The URL of the daemon's JSON API, e.g. http://127.0.0.1:8080
+        url: String,
+    },
+    /// Relay the messages of signing sessions between the parties over HTTP, for their `--relay`, until interrupted
+    #[clap(display_order = 10)]
+    RelayServe {
+        /// The address to listen on
+        #[clap(long, default_value = "127.0.0.1:8420")]
+        listen: SocketAddr,
+    },
+    /// Put a message shown with `--qr` back together from the QR codes decoded off a camera, one per line of the
+    /// standard input like `zbarcam --raw` prints them, and print it
+    #[clap(display_order = 10)]
+    Scan,
+    /// Print a new random session ID, for the parties of an `agg-send` to agree on
+    #[clap(display_order = 10)]
+    SessionIdNew,
+    /// List the sessions started with `--session` that weren't signed with yet
+    #[clap(display_order = 10)]
+    SessionList,
+    /// Print the details of a session started with `--session`, its first message included
+    #[clap(display_order = 10)]
+    SessionShow {
+        /// The name of the session
+        name: String,
+    },
+    /// Delete a session started with `--session`, use `agg-abort --session` instead to also make sure it's never signed with
+    #[clap(display_order = 10)]
+    SessionDelete {
+        /// The name of the session
+        name: String,
+    },
+    /// Name the addresses of recurring counterparties, every flag taking an address then takes the name in its place
+    #[clap(display_order = 10)]
+    AddressBook {
+        #[clap(subcommand)]
+        command: AddressBookCommand,
+    },
+    /// Check that an audit log written with `--audit-log` wasn't changed after the fact
+    #[clap(display_order = 10)]
+    AuditVerify {
+        /// The path of the audit log
+        path: PathBuf,
+    },
+    /// Print the completion script of a shell, e.g. `source <(solana-tss completions bash)` in ~/.bashrc. It completes
+    /// the network names and the sessions kept with `--session` when it runs, so load it anew rather than saving it.
+    #[clap(display_order = 10)]
+    Completions {
+        /// The shell: bash/zsh/fish/powershell
+        #[clap(arg_enum)]
+        shell: Shell,
+    },
+    /// Generate a new key split into shares, so that any `threshold` of the `parties` can sign together.
+    /// Whoever runs this sees the whole key, so run it on a trusted machine and hand out the shares privately.
+    #[clap(display_order = 11)]
+    ThresholdKeygen {
+        /// The number of parties required to sign a transaction
+        #[clap(long)]
+        threshold: u16,
+        /// The total number of parties holding a share
+        #[clap(long)]
+        parties: u16,
+        /// The encoding of the printed messages: bs58/base64/hex, messages in any of them are accepted as input
+        #[clap(long, default_value = "bs58")]
+        encoding: Encoding,
+    },
+    /// Start threshold signing
+    #[clap(display_order = 12)]
+    ThresholdAggSendStepOne {
+        /// A Base58 key share of the party signing, as received from `threshold-keygen`, or the `keychain:<name>` it was saved to
+        #[clap(forbid_empty_values = true, parse(try_from_str = parse_secret))]
+        share: ThresholdKeyShare,
+        /// The session ID printed by the first party to run step 1, leave empty to start a new session
+        #[clap(long)]
+        session_id: Option<SessionId>,
+        /// Store the secret state in the OS keychain as `keychain:<name>`, and print that instead of the secret state
+        #[clap(long)]
+        save_secret_state: Option<KeychainEntry>,
+        /// Encrypt the secret state with a passphrase prompted for, the next step asks for it to decrypt it
+        #[clap(long)]
+        protect: bool,
+        /// Keep the secret state in the session store under this name instead of printing it, for `--session` of step 2
+        #[clap(long, conflicts_with_all = &["save-secret-state", "protect"])]
+        session: Option<String>,
+        /// The output format: text/json
+        #[clap(long, default_value = "text")]
+        format: Format,
+        /// The encoding of the printed messages: bs58/base64/hex, messages in any of them are accepted as input
+        #[clap(long, default_value = "bs58")]
+        encoding: Encoding,
+    },
+    /// Step 2 of threshold signing, you should pass in the secret data from step 1.
+    /// It's important that all signing parties pass in exactly the same transaction details (amount,to,net,memo,recent_block_hash,nonce_account,lookup_table,unsigned_tx)
+    #[clap(display_order = 13)]
+    ThresholdAggSendStepTwo {
+        /// A Base58 key share of the party signing, as received from `threshold-keygen`, or the `keychain:<name>` it was saved to
+        #[clap(long, forbid_empty_values = true, parse(try_from_str = parse_secret))]
+        share: ThresholdKeyShare,
+        /// The amount of SOL (or of the token with `--token-mint`) you want to send.
+        #[clap(long, required_unless_present_any = &["unsigned-tx", "all"])]
+        amount: Option<f64>,
+        /// Send all the SOL in the wallet minus the fee, as read from `--net`. All parties *must* sign before the balance changes.
+        #[clap(long, conflicts_with_all = &["amount", "token-mint", "unsigned-tx"])]
+        all: bool,
+        /// Send this SPL token instead of SOL, between the associated token accounts of the addresses
+        #[clap(parse(try_from_str = parse_address), long)]
+        token_mint: Option<Pubkey>,
+        /// The number of decimals of the token, looked up from the mint on `--net` when not given. All parties *must* pass in the same decimals.
+        #[clap(long, requires = "token-mint")]
+        token_decimals: Option<u8>,
+        /// The mint is owned by the Token-2022 program, looked up from the mint when `--token-decimals` isn't given
+        #[clap(long, requires = "token-decimals")]
+        token_2022: bool,
+        /// The transfer fee the Token-2022 mint withholds, in units of the token, looked up when `--token-decimals` isn't given. All parties *must* pass in the same fee.
+        #[clap(long, requires = "token-2022")]
+        token_fee: Option<f64>,
+        /// Create the recipient's associated token account if it doesn't exist, the sender pays for it
+        #[clap(long, requires = "token-mint")]
+        fund_recipient: bool,
+        /// Address of the recipient
+        #[clap(parse(try_from_str = parse_address), long, required_unless_present = "unsigned-tx")]
+        to: Option<Pubkey>,
+        /// Add a memo to the transaction
+        #[clap(long)]
+        memo: Option<String>,
+        /// A hash of a recent block, can be obtained by calling `recent-block-hash`, all parties *must* pass in the same hash.
+        /// It replaces the one in `--unsigned-tx` if given. With `--nonce-account`, the nonce printed by `nonce-show` instead.
+        #[clap(long, required_unless_present = "unsigned-tx")]
+        recent_block_hash: Option<Hash>,
+        /// Use the durable nonce of this account instead of a recent block hash, so the signing can take as long as it needs.
+        /// The aggregated address must be its authority.
+        #[clap(parse(try_from_str = parse_address), long, conflicts_with = "unsigned-tx")]
+        nonce_account: Option<Pubkey>,
+        /// Build a v0 transaction that refers to accounts through these address lookup tables, read from `--net`.
+        /// All parties *must* pass in the same tables.
+        #[clap(parse(try_from_str = parse_address), long, conflicts_with = "unsigned-tx")]
+        lookup_table: Vec<Pubkey>,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node, only used to read the `--lookup-table`s, the balance for `--all` and the mint without `--token-decimals`. Without them it isn't contacted, so this step can run air-gapped
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+        /// Sign this base64 (legacy or v0) transaction or message instead of a transfer, e.g. one built by a dApp. Its instructions are printed before signing,
+        /// and the aggregated address must be one of its signers. All parties *must* pass in the same transaction.
+        #[clap(long, conflicts_with_all = &["amount", "to", "memo", "token-mint"], parse(try_from_str = parse_unsigned_tx))]
+        unsigned_tx: Option<VersionedMessage>,
+        /// A list of the first messages received in step 1 from all the other signing parties
+        #[clap(long, required = true, min_values = 1, forbid_empty_values = true, parse(try_from_str = Serialize::parse))]
+        first_messages: Vec<ThresholdMessage1>,
+        /// The secret state received in step 1 or the `keychain:<name>` it was saved to, prompted for without echoing it when not given
+        #[clap(long, forbid_empty_values = true, parse(try_from_str = parse_secret))]
+        secret_state: Option<SecretAggStepOne>,
+        /// Take the secret state from the session of this name started with `--session` in step 1, which is deleted once signed
+        #[clap(long, conflicts_with = "secret-state")]
+        session: Option<String>,
+        /// Sign even if the secret state was already used, this is only safe when signing the exact same transaction again
+        #[clap(long)]
+        force: bool,
+        /// The output format: text/json
+        #[clap(long, default_value = "text")]
+        format: Format,
+        /// The encoding of the printed messages: bs58/base64/hex, messages in any of them are accepted as input
+        #[clap(long, default_value = "bs58")]
+        encoding: Encoding,
+    },
+    /// Aggregate the partial signatures of the signing parties into a full signature, and send the transaction to Solana
+    #[clap(display_order = 14)]
+    ThresholdAggregateSignaturesAndBroadcast {
+        // A list of all partial signatures produced in step two.
+        #[clap(long, required = true, min_values = 2, forbid_empty_values = true, parse(try_from_str = Serialize::parse))]
+        signatures: Vec<PartialSignature>,
+        /// The amount of SOL (or of the token with `--token-mint`) you want to send.
+        #[clap(long, required_unless_present_any = &["unsigned-tx", "all"])]
+        amount: Option<f64>,
+        /// Send all the SOL in the wallet minus the fee, all parties *must* have signed with `--all` too.
+        #[clap(long, conflicts_with_all = &["amount", "token-mint", "unsigned-tx"])]
+        all: bool,
+        /// Send this SPL token instead of SOL, between the associated token accounts of the addresses
+        #[clap(parse(try_from_str = parse_address), long)]
+        token_mint: Option<Pubkey>,
+        /// The number of decimals of the token, looked up from the mint when not given
+        #[clap(long, requires = "token-mint")]
+        token_decimals: Option<u8>,
+        /// The mint is owned by the Token-2022 program, looked up from the mint when `--token-decimals` isn't given
+        #[clap(long, requires = "token-decimals")]
+        token_2022: bool,
+        /// The transfer fee the Token-2022 mint withholds, in units of the token, looked up when `--token-decimals` isn't given
+        #[clap(long, requires = "token-2022")]
+        token_fee: Option<f64>,
+        /// Create the recipient's associated token account if it doesn't exist, the sender pays for it
+        #[clap(long, requires = "token-mint")]
+        fund_recipient: bool,
+        /// Address of the recipient
+        #[clap(parse(try_from_str = parse_address), long, required_unless_present = "unsigned-tx")]
+        to: Option<Pubkey>,
+        /// Add a memo to the transaction
+        #[clap(long, forbid_empty_values = true)]
+        memo: Option<String>,
+        /// A hash of a recent block, can be obtained by calling `recent-block-hash`, all parties *must* pass in the same hash.
+        /// It replaces the one in `--unsigned-tx` if given. With `--nonce-account` the nonce is read from the account when not given.
+        #[clap(long, required_unless_present_any = &["unsigned-tx", "nonce-account"])]
+        recent_block_hash: Option<Hash>,
+        /// Use the durable nonce of this account instead of a recent block hash, all parties *must* pass in the same account.
+        #[clap(parse(try_from_str = parse_address), long, conflicts_with = "unsigned-tx")]
+        nonce_account: Option<Pubkey>,
+        /// Build a v0 transaction that refers to accounts through these address lookup tables, all parties *must* pass in the same tables.
+        #[clap(parse(try_from_str = parse_address), long, conflicts_with = "unsigned-tx")]
+        lookup_table: Vec<Pubkey>,
+        /// Sign this base64 (legacy or v0) transaction or message instead of a transfer, e.g. one built by a dApp. Its instructions are printed before signing,
+        /// and the aggregated address must be one of its signers. All parties *must* pass in the same transaction.
+        #[clap(long, conflicts_with_all = &["amount", "to", "memo", "token-mint"], parse(try_from_str = parse_unsigned_tx))]
+        unsigned_tx: Option<VersionedMessage>,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+        /// Send the transaction without simulating it first
+        #[clap(long)]
+        skip_preflight: bool,
+        /// The commitment to simulate the transaction with before sending: processed/confirmed/finalized, `--commitment` when not given
+        #[clap(long, conflicts_with = "skip-preflight", parse(try_from_str = parse_commitment))]
+        preflight_commitment: Option<CommitmentConfig>,
+        /// Only simulate the signed transaction and print what it would do, instead of sending it
+        #[clap(long, conflicts_with = "skip-preflight")]
+        simulate_only: bool,
+        /// Print the signed transaction base64 encoded instead of sending it, to send it some other way
+        #[clap(long, conflicts_with_all = &["skip-preflight", "preflight-commitment", "simulate-only"])]
+        no_send: bool,
+        /// Print the signatures as PUBKEY=SIGNATURE like the Solana CLI's `--sign-only` instead of sending the transaction,
+        /// for another signer to pass to its `--signer`
+        #[clap(long, conflicts_with_all = &["skip-preflight", "preflight-commitment", "simulate-only", "no-send"])]
+        sign_only: bool,
+        /// The signature of another signer of `--unsigned-tx` as PUBKEY=SIGNATURE, like the Solana CLI's `--signer`
+        #[clap(long = "signer", parse(try_from_str = parse_signer))]
+        signers: Vec<(Pubkey, Signature)>,
+        /// The aggregated public key printed by `threshold-keygen`
+        #[clap(parse(try_from_str = parse_address), long)]
+        group_key: Pubkey,
+        /// The output format: text/json
+        #[clap(long, default_value = "text")]
+        format: Format,
+    },
+    /// Start a distributed key generation for a threshold wallet, no party ever learns the whole key
+    #[clap(display_order = 15)]
+    DkgStepOne {
+        /// The number of parties required to sign a transaction
+        #[clap(long)]
+        threshold: u16,
+        /// The total number of parties holding a share
+        #[clap(long)]
+        parties: u16,
+        /// The index of this party, between 1 and `parties`, every party must pick a different one
+        #[clap(long)]
+        index: u16,
+        /// Store the secret state in the OS keychain as `keychain:<name>`, and print that instead of the secret state
+        #[clap(long)]
+        save_secret_state: Option<KeychainEntry>,
+        /// Encrypt the secret state with a passphrase prompted for, the next step asks for it to decrypt it
+        #[clap(long)]
+        protect: bool,
+        /// The encoding of the printed messages: bs58/base64/hex, messages in any of them are accepted as input
+        #[clap(long, default_value = "bs58")]
+        encoding: Encoding,
+    },
+    /// Step 2 of the distributed key generation, you should pass in the secret data from step 1.
+    #[clap(display_order = 16)]
+    DkgStepTwo {
+        /// The secret state received in step 1 or the `keychain:<name>` it was saved to, prompted for without echoing it when not given
+        #[clap(long, forbid_empty_values = true, parse(try_from_str = parse_secret))]
+        secret_state: Option<DkgSecretStepOne>,
+        /// A list of the first messages received in step 1 from all the other parties
+        #[clap(long, required = true, min_values = 1, forbid_empty_values = true, parse(try_from_str = Serialize::parse))]
+        first_messages: Vec<DkgMessage1>,
+        /// The encoding of the printed messages: bs58/base64/hex, messages in any of them are accepted as input
+        #[clap(long, default_value = "bs58")]
+        encoding: Encoding,
+    },
+    /// Step 3 of the distributed key generation, prints this party's key share
+    #[clap(display_order = 17)]
+    DkgStepThree {
+        /// The secret state received in step 1 or the `keychain:<name>` it was saved to, prompted for without echoing it when not given
+        #[clap(long, forbid_empty_values = true, parse(try_from_str = parse_secret))]
+        secret_state: Option<DkgSecretStepOne>,
+        /// A list of the first messages received in step 1 from all the other parties
+        #[clap(long, required = true, min_values = 1, forbid_empty_values = true, parse(try_from_str = Serialize::parse))]
+        first_messages: Vec<DkgMessage1>,
+        /// A list of the second messages sent to this party in step 2
+        #[clap(long, required = true, min_values = 1, forbid_empty_values = true, parse(try_from_str = Serialize::parse))]
+        second_messages: Vec<DkgMessage2>,
+        /// Store the key share in the OS keychain as `keychain:<name>`, and print that instead of the key share
+        #[clap(long)]
+        save_share: Option<KeychainEntry>,
+        /// The encoding of the printed messages: bs58/base64/hex, messages in any of them are accepted as input
+        #[clap(long, default_value = "bs58")]
+        encoding: Encoding,
+    },
+    /// Re-deal the key of a threshold wallet to a new set of parties, the wallet's address stays the same.
+    /// At least `threshold` share holders must run this, to refresh the shares reshare to the same parties.
+    #[clap(display_order = 18)]
+    ReshareStepOne {
+        /// The current Base58 key share of the party dealing, or the `keychain:<name>` it was saved to
+        #[clap(long, forbid_empty_values = true, parse(try_from_str = parse_secret))]
+        share: ThresholdKeyShare,
+        /// The indices of all the current share holders that are dealing
+        #[clap(long, required = true, min_values = 2)]
+        dealers: Vec<u16>,
+        /// The number of parties required to sign a transaction after resharing, defaults to the current threshold
+        #[clap(long)]
+        threshold: Option<u16>,
+        /// The total number of parties that will hold a new share
+        #[clap(long)]
+        parties: u16,
+        /// The encoding of the printed messages: bs58/base64/hex, messages in any of them are accepted as input
+        #[clap(long, default_value = "bs58")]
+        encoding: Encoding,
+    },
+    /// Step 2 of resharing, run by every party receiving a new share
+    #[clap(display_order = 19)]
+    ReshareStepTwo {
+        /// The aggregated public key of the wallet being reshared
+        #[clap(parse(try_from_str = parse_address), long)]
+        group_key: Pubkey,
+        /// The new index of this party, between 1 and the number of parties
+        #[clap(long)]
+        index: u16,
+        /// A list of the first messages of all the dealers
+        #[clap(long, required = true, min_values = 2, forbid_empty_values = true, parse(try_from_str = Serialize::parse))]
+        first_messages: Vec<DkgMessage1>,
+        /// A list of the second messages sent to this party by the dealers
+        #[clap(long, required = true, min_values = 2, forbid_empty_values = true, parse(try_from_str = Serialize::parse))]
+        second_messages: Vec<DkgMessage2>,
+        /// Store the key share in the OS keychain as `keychain:<name>`, and print that instead of the key share
+        #[clap(long)]
+        save_share: Option<KeychainEntry>,
+        /// The encoding of the printed messages: bs58/base64/hex, messages in any of them are accepted as input
+        #[clap(long, default_value = "bs58")]
+        encoding: Encoding,
+    },
+    /// Generate nonces ahead of time, for parties that are only online at times to sign without step 1.
+    /// They are kept encrypted in `~/.solana-tss/pools` until `agg-send-step-two --from-pool` binds one to its session
+    #[clap(display_order = 20)]
+    AggNoncePool {
+        /// Deprecated, use `--keypair-file`, `--keypair-stdin` or `SOLANA_TSS_KEYPAIR` to keep the secret key out of the shell history
+        #[clap(parse(try_from_str = parse_keypair), conflicts_with_all = &["keypair", "keypair-file", "keypair-stdin"])]
+        secret_key: Option<Keypair>,
+        /// A Base58 secret key of the party signing, the path of its JSON keypair file like solana-keygen's id.json, or `keychain:<name>`. `SOLANA_TSS_KEYPAIR`, the `keypair` of the configuration file, or prompted for, when not given
+        #[clap(parse(try_from_str = parse_keypair), long, visible_alias = "key-source")]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json, or of a keystore whose passphrase is then prompted for
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+        /// The number of sessions to generate nonces for
+        #[clap(long, default_value = "10")]
+        count: usize,
+    },
+    /// Print a transaction creating a durable nonce account for the wallet, to sign with `--unsigned-tx`.
+    /// Its address is derived from the wallet's address and the seed, so the wallet is the only signer.
+    #[clap(display_order = 21)]
+    NonceCreate {
+        /// The aggregated address of the wallet, which pays for the account
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// Create a different account for every seed, to sign in several sessions at the same time
+        #[clap(long, default_value = "nonce")]
+        seed: String,
+        /// The address allowed to advance the nonce, defaults to the wallet
+        #[clap(parse(try_from_str = parse_address), long)]
+        nonce_authority: Option<Pubkey>,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction advancing the nonce of a nonce account, to sign with `--unsigned-tx`.
+    /// This invalidates anything signed with the current nonce that wasn't sent yet.
+    #[clap(display_order = 22)]
+    NonceAdvance {
+        /// The address of the nonce account
+        #[clap(parse(try_from_str = parse_address))]
+        nonce_account: Pubkey,
+        /// The Base58 secret key, JSON keypair file or `keychain:<name>` of the nonce authority, to send it right away when the authority isn't the wallet
+        #[clap(parse(try_from_str = parse_keypair), long, visible_alias = "key-source")]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json, or of a keystore whose passphrase is then prompted for
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction withdrawing from a nonce account, to sign with `--unsigned-tx`
+    #[clap(display_order = 23)]
+    NonceWithdraw {
+        /// The address of the nonce account
+        #[clap(parse(try_from_str = parse_address))]
+        nonce_account: Pubkey,
+        /// The amount of SOL to withdraw, the account has to keep enough to stay rent exempt
+        #[clap(long, required_unless_present = "all")]
+        amount: Option<f64>,
+        /// Withdraw the whole balance, closing the account
+        #[clap(long, conflicts_with = "amount")]
+        all: bool,
+        /// Where to withdraw to, defaults to the nonce authority
+        #[clap(parse(try_from_str = parse_address), long)]
+        to: Option<Pubkey>,
+        /// The Base58 secret key, JSON keypair file or `keychain:<name>` of the nonce authority, to send it right away when the authority isn't the wallet
+        #[clap(parse(try_from_str = parse_keypair), long, visible_alias = "key-source")]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json, or of a keystore whose passphrase is then prompted for
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print the current nonce of a nonce account, to pass to the `agg-send` steps as the recent block hash
+    #[clap(display_order = 24)]
+    NonceShow {
+        /// The address of the nonce account
+        #[clap(parse(try_from_str = parse_address))]
+        nonce_account: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction creating an address lookup table owned by the wallet, to sign with `--unsigned-tx`.
+    /// It must be sent within a few minutes, as the table's address is derived from a recent slot.
+    #[clap(display_order = 25)]
+    LookupTableCreate {
+        /// The aggregated address of the wallet, which pays for the table and can extend it
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction adding addresses to an address lookup table, to sign with `--unsigned-tx`
+    #[clap(display_order = 26)]
+    LookupTableExtend {
+        /// The address of the lookup table
+        #[clap(parse(try_from_str = parse_address))]
+        lookup_table: Pubkey,
+        /// The addresses to add to the table
+        #[clap(parse(try_from_str = parse_address), long, required = true, min_values = 1)]
+        addresses: Vec<Pubkey>,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction creating a stake account with the wallet as its staker and withdrawer, to sign with `--unsigned-tx`.
+    /// Its address is derived from the wallet's address and the seed, so the wallet is the only signer.
+    #[clap(display_order = 27)]
+    StakeCreate {
+        /// The aggregated address of the wallet, which funds the stake account
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The amount of SOL to stake
+        #[clap(long)]
+        amount: f64,
+        /// Create a different account for every seed
+        #[clap(long, default_value = "stake")]
+        seed: String,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction delegating a stake account to a validator, to sign with `--unsigned-tx`
+    #[clap(display_order = 28)]
+    StakeDelegate {
+        /// The address of the stake account
+        #[clap(parse(try_from_str = parse_address))]
+        stake_account: Pubkey,
+        /// The aggregated address of the wallet, which must be the staker
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The vote account of the validator to delegate to
+        #[clap(parse(try_from_str = parse_address), long)]
+        vote_account: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction deactivating a stake account, to sign with `--unsigned-tx`.
+    /// The stake can be withdrawn once it's fully deactivated, at the end of the epoch.
+    #[clap(display_order = 29)]
+    StakeDeactivate {
+        /// The address of the stake account
+        #[clap(parse(try_from_str = parse_address))]
+        stake_account: Pubkey,
+        /// The aggregated address of the wallet, which must be the staker
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction withdrawing from a stake account, to sign with `--unsigned-tx`
+    #[clap(display_order = 30)]
+    StakeWithdraw {
+        /// The address of the stake account
+        #[clap(parse(try_from_str = parse_address))]
+        stake_account: Pubkey,
+        /// The aggregated address of the wallet, which must be the withdrawer
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The amount of SOL to withdraw
+        #[clap(long)]
+        amount: f64,
+        /// Where to withdraw to, defaults to the wallet
+        #[clap(parse(try_from_str = parse_address), long)]
+        to: Option<Pubkey>,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction handing the staker or withdrawer authority of a stake account to another address, to sign with `--unsigned-tx`
+    #[clap(display_order = 31)]
+    StakeAuthorize {
+        /// The address of the stake account
+        #[clap(parse(try_from_str = parse_address))]
+        stake_account: Pubkey,
+        /// The aggregated address of the wallet, which must hold the authority
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The authority to hand over: staker/withdrawer
+        #[clap(long, parse(try_from_str = parse_stake_authorize))]
+        authority: StakeAuthorize,
+        /// The address getting the authority
+        #[clap(parse(try_from_str = parse_address), long)]
+        new_authority: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction withdrawing from a vote account whose withdraw authority is the wallet, to sign with `--unsigned-tx`
+    #[clap(display_order = 32)]
+    VoteWithdraw {
+        /// The address of the vote account
+        #[clap(parse(try_from_str = parse_address))]
+        vote_account: Pubkey,
+        /// The aggregated address of the wallet, which must be the withdraw authority
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The amount of SOL to withdraw
+        #[clap(long)]
+        amount: f64,
+        /// Where to withdraw to, defaults to the wallet
+        #[clap(parse(try_from_str = parse_address), long)]
+        to: Option<Pubkey>,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction handing the withdraw authority of a vote account to another address, to sign with `--unsigned-tx`
+    #[clap(display_order = 33)]
+    VoteAuthorizeWithdrawer {
+        /// The address of the vote account
+        #[clap(parse(try_from_str = parse_address))]
+        vote_account: Pubkey,
+        /// The current withdraw authority, usually the aggregated address of the wallet
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The address getting the withdraw authority
+        #[clap(parse(try_from_str = parse_address), long)]
+        new_authority: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction upgrading a program whose upgrade authority is the wallet, to sign with `--unsigned-tx`
+    #[clap(display_order = 34)]
+    ProgramUpgrade {
+        /// The address of the program
+        #[clap(parse(try_from_str = parse_address))]
+        program: Pubkey,
+        /// The buffer account holding the new program
+        #[clap(parse(try_from_str = parse_address), long)]
+        buffer: Pubkey,
+        /// The aggregated address of the wallet, which must be the upgrade authority
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// Where the buffer's SOL go after the upgrade, defaults to the wallet
+        #[clap(parse(try_from_str = parse_address), long)]
+        spill: Option<Pubkey>,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction handing the upgrade authority of a program to another address, to sign with `--unsigned-tx`
+    #[clap(display_order = 35)]
+    ProgramSetAuthority {
+        /// The address of the program
+        #[clap(parse(try_from_str = parse_address))]
+        program: Pubkey,
+        /// The current upgrade authority, usually the aggregated address of the wallet
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The address getting the upgrade authority
+        #[clap(parse(try_from_str = parse_address), long, required_unless_present = "make-final")]
+        new_authority: Option<Pubkey>,
+        /// Make the program immutable instead, it can never be upgraded again
+        #[clap(long = "final", conflicts_with = "new-authority")]
+        make_final: bool,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction minting tokens of a mint whose mint authority is the wallet, to sign with `--unsigned-tx`
+    #[clap(display_order = 36)]
+    TokenMintTo {
+        /// The address of the token mint
+        #[clap(parse(try_from_str = parse_address))]
+        mint: Pubkey,
+        /// The aggregated address of the wallet, which must be the mint authority
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The amount of the token to mint
+        #[clap(long)]
+        amount: f64,
+        /// Whose associated token account to mint to, defaults to the wallet
+        #[clap(parse(try_from_str = parse_address), long)]
+        to: Option<Pubkey>,
+        /// Create the recipient's associated token account if it doesn't exist, the wallet pays for it
+        #[clap(long)]
+        fund_recipient: bool,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction handing the mint or freeze authority of a mint to another address, to sign with `--unsigned-tx`
+    #[clap(display_order = 37)]
+    TokenSetAuthority {
+        /// The address of the token mint
+        #[clap(parse(try_from_str = parse_address))]
+        mint: Pubkey,
+        /// The aggregated address of the wallet, which must hold the authority
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The authority to hand over: mint/freeze
+        #[clap(long, parse(try_from_str = parse_token_authority))]
+        authority: AuthorityType,
+        /// The address getting the authority
+        #[clap(parse(try_from_str = parse_address), long, required_unless_present = "disable")]
+        new_authority: Option<Pubkey>,
+        /// Disable the authority instead, nobody can ever mint or freeze again
+        #[clap(long, conflicts_with = "new-authority")]
+        disable: bool,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction freezing a token account of a mint whose freeze authority is the wallet, to sign with `--unsigned-tx`
+    #[clap(display_order = 38)]
+    TokenFreeze {
+        /// The address of the token account
+        #[clap(parse(try_from_str = parse_address))]
+        account: Pubkey,
+        /// The address of the token mint
+        #[clap(parse(try_from_str = parse_address), long)]
+        mint: Pubkey,
+        /// The aggregated address of the wallet, which must be the freeze authority
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction thawing a frozen token account, to sign with `--unsigned-tx`
+    #[clap(display_order = 39)]
+    TokenThaw {
+        /// The address of the token account
+        #[clap(parse(try_from_str = parse_address))]
+        account: Pubkey,
+        /// The address of the token mint
+        #[clap(parse(try_from_str = parse_address), long)]
+        mint: Pubkey,
+        /// The aggregated address of the wallet, which must be the freeze authority
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction wrapping SOL into the wallet's wrapped SOL account, to sign with `--unsigned-tx` or right away with `--keypair`
+    #[clap(display_order = 40)]
+    WrapSol {
+        /// The amount of SOL to wrap
+        #[clap(long)]
+        amount: f64,
+        /// The aggregated address of the wallet
+        #[clap(
+            parse(try_from_str = parse_address),
+            long,
+            required_unless_present_any = &["keypair", "keypair-file", "keypair-stdin"],
+            conflicts_with_all = &["keypair", "keypair-file", "keypair-stdin"]
+        )]
+        wallet: Option<Pubkey>,
+        /// The Base58 secret key, JSON keypair file or `keychain:<name>` of a single wallet
+        #[clap(parse(try_from_str = parse_keypair), long, visible_alias = "key-source")]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json, or of a keystore whose passphrase is then prompted for
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction unwrapping all the wallet's wrapped SOL, to sign with `--unsigned-tx` or right away with `--keypair`
+    #[clap(display_order = 41)]
+    UnwrapSol {
+        /// The aggregated address of the wallet
+        #[clap(
+            parse(try_from_str = parse_address),
+            long,
+            required_unless_present_any = &["keypair", "keypair-file", "keypair-stdin"],
+            conflicts_with_all = &["keypair", "keypair-file", "keypair-stdin"]
+        )]
+        wallet: Option<Pubkey>,
+        /// The Base58 secret key, JSON keypair file or `keychain:<name>` of a single wallet
+        #[clap(parse(try_from_str = parse_keypair), long, visible_alias = "key-source")]
+        keypair: Option<Keypair>,
+        /// The path of a JSON keypair file like solana-keygen's id.json, or of a keystore whose passphrase is then prompted for
+        #[clap(parse(try_from_str = parse_keypair_file), long, conflicts_with = "keypair")]
+        keypair_file: Option<Keypair>,
+        /// Read the Base58 secret key or the JSON keypair from the standard input
+        #[clap(long, conflicts_with_all = &["keypair", "keypair-file"])]
+        keypair_stdin: bool,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print transactions closing token, nonce or stake accounts of the wallet and reclaiming their rent, to sign with `--unsigned-tx`
+    #[clap(display_order = 42)]
+    CloseAccounts {
+        /// The addresses of the accounts to close
+        #[clap(parse(try_from_str = parse_address), required_unless_present = "empty-token-accounts")]
+        accounts: Vec<Pubkey>,
+        /// Close all the wallet's token accounts that hold no tokens
+        #[clap(long)]
+        empty_token_accounts: bool,
+        /// The aggregated address of the wallet, which must be allowed to close the accounts
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// Burn any tokens left in the given token accounts, so they can be closed
+        #[clap(long)]
+        burn: bool,
+        /// Where the reclaimed SOL go, defaults to the wallet
+        #[clap(parse(try_from_str = parse_address), long)]
+        to: Option<Pubkey>,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print the address of the sub-account derived from the wallet's address and a seed, no signing needed
+    #[clap(display_order = 43)]
+    SeedAddress {
+        /// The aggregated address of the wallet
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The seed of the sub-account, up to 32 bytes
+        #[clap(long)]
+        seed: String,
+        /// The program owning the sub-account
+        #[clap(parse(try_from_str = parse_address), long, default_value_t = system_program::id())]
+        owner: Pubkey,
+    },
+    /// Print a transaction creating and funding a sub-account derived from the wallet's address and a seed, to sign with `--unsigned-tx`
+    #[clap(display_order = 44)]
+    SeedCreate {
+        /// The aggregated address of the wallet, which funds the sub-account
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The seed of the sub-account, up to 32 bytes
+        #[clap(long)]
+        seed: String,
+        /// The amount of SOL to fund it with
+        #[clap(long)]
+        amount: f64,
+        /// The bytes of data to allocate for the owner
+        #[clap(long, default_value = "0")]
+        space: u64,
+        /// The program owning the sub-account
+        #[clap(parse(try_from_str = parse_address), long, default_value_t = system_program::id())]
+        owner: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction sending SOL out of a sub-account derived from the wallet's address and a seed, to sign with `--unsigned-tx`
+    #[clap(display_order = 45)]
+    SeedTransfer {
+        /// The aggregated address of the wallet, which signs for the sub-account
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The seed of the sub-account
+        #[clap(long)]
+        seed: String,
+        /// The amount of SOL to send
+        #[clap(long)]
+        amount: f64,
+        /// Address of the recipient
+        #[clap(parse(try_from_str = parse_address), long)]
+        to: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction sending an NFT, including programmable NFTs, through Token Metadata, to sign with `--unsigned-tx`
+    #[clap(display_order = 46)]
+    NftTransfer {
+        /// The mint of the NFT
+        #[clap(parse(try_from_str = parse_address))]
+        mint: Pubkey,
+        /// The aggregated address of the wallet holding the NFT
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// Address of the recipient, its token account is created if needed
+        #[clap(parse(try_from_str = parse_address), long)]
+        to: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction creating a yes/no proposal in an SPL Governance realm and starting the vote, to sign with `--unsigned-tx`
+    #[clap(display_order = 47)]
+    GovernancePropose {
+        /// The address of the realm
+        #[clap(parse(try_from_str = parse_address), long)]
+        realm: Pubkey,
+        /// The governance the proposal is for
+        #[clap(parse(try_from_str = parse_address), long)]
+        governance: Pubkey,
+        /// The mint of the governing tokens the wallet deposited, council or community
+        #[clap(parse(try_from_str = parse_address), long)]
+        governing_token_mint: Pubkey,
+        /// The aggregated address of the wallet, which owns the token owner record
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The name of the proposal
+        #[clap(long)]
+        name: String,
+        /// A link to the description of the proposal
+        #[clap(long, default_value = "")]
+        description: String,
+        /// The SPL Governance program of the realm
+        #[clap(parse(try_from_str = parse_address), long, default_value_t = governance::GOVERNANCE_PROGRAM_ID)]
+        program_id: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction casting the wallet's vote on an SPL Governance proposal, to sign with `--unsigned-tx`
+    #[clap(display_order = 48)]
+    GovernanceVote {
+        /// The address of the proposal
+        #[clap(parse(try_from_str = parse_address))]
+        proposal: Pubkey,
+        /// The aggregated address of the wallet, which owns the token owner record
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The vote: yes/no/abstain/veto
+        #[clap(long, parse(try_from_str = parse_vote))]
+        vote: Vote,
+        /// The SPL Governance program of the realm
+        #[clap(parse(try_from_str = parse_address), long, default_value_t = governance::GOVERNANCE_PROGRAM_ID)]
+        program_id: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Print a transaction executing a transaction of an approved SPL Governance proposal, to sign with `--unsigned-tx`
+    #[clap(display_order = 49)]
+    GovernanceExecute {
+        /// The address of the proposal transaction
+        #[clap(parse(try_from_str = parse_address))]
+        proposal_transaction: Pubkey,
+        /// The aggregated address of the wallet, which pays the fee
+        #[clap(parse(try_from_str = parse_address), long)]
+        wallet: Pubkey,
+        /// The SPL Governance program of the realm
+        #[clap(parse(try_from_str = parse_address), long, default_value_t = governance::GOVERNANCE_PROGRAM_ID)]
+        program_id: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Simulate a transaction, printing its program logs, the compute units it consumes and the balances it changes
+    #[clap(display_order = 50)]
+    Simulate {
+        /// The base64 (legacy or v0) transaction or message, e.g. the one all parties are about to sign with `--unsigned-tx`
+        #[clap(long, parse(try_from_str = parse_unsigned_tx))]
+        unsigned_tx: VersionedMessage,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+        /// The output format: text/json
+        #[clap(long, default_value = "text")]
+        format: Format,
+    },
+    /// Print the fee of a transfer or of a transaction, as the aggregation commands would send it
+    #[clap(display_order = 51)]
+    Fee {
+        /// The amount of SOL (or of the token with `--token-mint`) to send
+        #[clap(long, required_unless_present = "unsigned-tx")]
+        amount: Option<f64>,
+        /// Send this SPL token instead of SOL, between the associated token accounts of the addresses
+        #[clap(parse(try_from_str = parse_address), long)]
+        token_mint: Option<Pubkey>,
+        /// The number of decimals of the token, looked up from the mint when not given
+        #[clap(long, requires = "token-mint")]
+        token_decimals: Option<u8>,
+        /// The mint is owned by the Token-2022 program, looked up from the mint when `--token-decimals` isn't given
+        #[clap(long, requires = "token-decimals")]
+        token_2022: bool,
+        /// The transfer fee the Token-2022 mint withholds, in units of the token, looked up when `--token-decimals` isn't given
+        #[clap(long, requires = "token-2022")]
+        token_fee: Option<f64>,
+        /// Create the recipient's associated token account if it doesn't exist, the sender pays for it
+        #[clap(long, requires = "token-mint")]
+        fund_recipient: bool,
+        /// Address of the recipient
+        #[clap(parse(try_from_str = parse_address), long, required_unless_present = "unsigned-tx")]
+        to: Option<Pubkey>,
+        /// Add a memo to the transaction
+        #[clap(long, forbid_empty_values = true)]
+        memo: Option<String>,
+        /// Use the durable nonce of this account instead of a recent block hash
+        #[clap(parse(try_from_str = parse_address), long, conflicts_with = "unsigned-tx")]
+        nonce_account: Option<Pubkey>,
+        /// Build a v0 transaction that refers to accounts through these address lookup tables
+        #[clap(parse(try_from_str = parse_address), long, conflicts_with = "unsigned-tx")]
+        lookup_table: Vec<Pubkey>,
+        /// The base64 (legacy or v0) transaction or message to price instead of a transfer
+        #[clap(long, conflicts_with_all = &["amount", "to", "memo", "token-mint"], parse(try_from_str = parse_unsigned_tx))]
+        unsigned_tx: Option<VersionedMessage>,
+        /// List of addresses making up the aggregated address that sends the transfer
+        #[clap(parse(try_from_str = parse_address), long, min_values = 2, required_unless_present_any = &["group-key", "unsigned-tx"])]
+        keys: Vec<Pubkey>,
+        /// The aggregated public key of the threshold wallet that sends the transfer
+        #[clap(parse(try_from_str = parse_address), long, conflicts_with = "keys")]
+        group_key: Option<Pubkey>,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+        /// The output format: text/json
+        #[clap(long, default_value = "text")]
+        format: Format,
+    },
+    /// Send a signed transaction, like the one printed by the aggregation commands with `--no-send`
+    #[clap(display_order = 52)]
+    Broadcast {
+        /// The base64 signed transaction
+        #[clap(parse(try_from_str = parse_signed_tx))]
+        signed_tx: VersionedTransaction,
+        /// Check the transaction is signed by this key, e.g. the aggregated public key, before sending it
+        #[clap(parse(try_from_str = parse_address), long)]
+        key: Option<Pubkey>,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+        /// Send the transaction without simulating it first
+        #[clap(long)]
+        skip_preflight: bool,
+        /// The commitment to simulate the transaction with before sending: processed/confirmed/finalized, `--commitment` when not given
+        #[clap(long, conflicts_with = "skip-preflight", parse(try_from_str = parse_commitment))]
+        preflight_commitment: Option<CommitmentConfig>,
+        /// Only simulate the signed transaction and print what it would do, instead of sending it
+        #[clap(long, conflicts_with = "skip-preflight")]
+        simulate_only: bool,
+        /// The output format: text/json
+        #[clap(long, default_value = "text")]
+        format: Format,
+    },
+    /// List the latest transactions of an address, newest first, with the SOL and tokens they moved and their memos
+    #[clap(display_order = 53)]
+    History {
+        /// The address to list the transactions of, e.g. the aggregated public key
+        #[clap(parse(try_from_str = parse_address))]
+        address: Pubkey,
+        /// How many transactions to list, at most 1000
+        #[clap(long, default_value_t = 10)]
+        limit: usize,
+        /// List the transactions before this one, to page through older transactions
+        #[clap(long)]
+        before: Option<Signature>,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+        /// The output format: text/json
+        #[clap(long, default_value = "text")]
+        format: Format,
+    },
+    /// Print the balance changes and the transactions of an address as they happen, until interrupted
+    #[clap(display_order = 54)]
+    Watch {
+        /// The address to watch, e.g. the aggregated public key
+        #[clap(parse(try_from_str = parse_address))]
+        address: Pubkey,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+    },
+    /// Look up a transaction by its ID and print its status, fee, balance changes and memos
+    #[clap(display_order = 55)]
+    Confirm {
+        /// The transaction ID, e.g. the one printed by the aggregation commands
+        signature: Signature,
+        /// Choose the desired network: Mainnet/Testnet/Devnet/Localnet, or the URL of an RPC node
+        #[clap(default_value = "testnet", long, visible_alias = "url")]
+        net: Network,
+        /// The output format: text/json
+        #[clap(long, default_value = "text")]
+        format: Format,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AddressBookCommand {
+    /// Save an address under an alias
+    Add {
+        /// The alias, which can't be an address itself
+        alias: String,
+        /// The address it stands for
+        address: Pubkey,
+    },
+    /// List the aliases with their addresses
+    List,
+    /// Remove an alias
+    Remove {
+        /// The alias
+        alias: String,
+    },
+}
+
+fn parse_proxy(s: &str) -> Result<Proxy, Error> {
+    Proxy::all(s).map_err(|_| Error::WrongProxy(s.to_string()))
+}
+
+fn parse_commitment(s: &str) -> Result<CommitmentConfig, Error> {
+    let commitment = match s {
+        "processed" => CommitmentLevel::Processed,
+        "confirmed" => CommitmentLevel::Confirmed,
+        "finalized" => CommitmentLevel::Finalized,
+        _ => return Err(Error::WrongCommitment(s.to_string())),
+    };
+    Ok(CommitmentConfig { commitment })
+}
+
+fn parse_stake_authorize(s: &str) -> Result<StakeAuthorize, Error> {
+    match s {
+        "staker" => Ok(StakeAuthorize::Staker),
+        "withdrawer" => Ok(StakeAuthorize::Withdrawer),
+        _ => Err(Error::WrongStakeAuthority(s.to_string())),
+    }
+}
+
+fn parse_token_authority(s: &str) -> Result<AuthorityType, Error> {
+    match s {
+        "mint" => Ok(AuthorityType::MintTokens),
+        "freeze" => Ok(AuthorityType::FreezeAccount),
+        _ => Err(Error::WrongTokenAuthority(s.to_string())),
+    }
+}
+
+fn parse_vote(s: &str) -> Result<Vote, Error> {
+    match s {
+        "yes" => Ok(Vote::Yes),
+        "no" => Ok(Vote::No),
+        "abstain" => Ok(Vote::Abstain),
+        "veto" => Ok(Vote::Veto),
+        _ => Err(Error::WrongVote(s.to_string())),
+    }
+}
+
+/// The environment variable commands that need a secret key read it from when not given one
+pub const KEYPAIR_ENV: &str = "SOLANA_TSS_KEYPAIR";
+
+/// The secret key of a command that needs one: the one of `--keypair`, `--keypair-file`, `--keypair-stdin` or the
+/// deprecated positional secret key that was given (clap allows only one), otherwise the one in `SOLANA_TSS_KEYPAIR`,
+/// otherwise `default`, the `keypair` of the configuration file
+pub fn required_keypair(
+    secret_key: Option<Keypair>,
+    keypair: Option<Keypair>,
+    keypair_file: Option<Keypair>,
+    keypair_stdin: bool,
+    default: Option<&str>,
+) -> Result<Keypair, Error> {
+    if secret_key.is_some() {
+        warn!(
+            "Passing the secret key as an argument is deprecated, it ends up in the shell history and is visible to \
+             other users in `ps`. Use `--keypair-file`, `--keypair-stdin` or {} instead.",
+            KEYPAIR_ENV
+        );
+    }
+    if let Some(keypair) = optional_keypair(secret_key.or(keypair), keypair_file, keypair_stdin)? {
+        return Ok(keypair);
+    }
+    match std::env::var(KEYPAIR_ENV).map(Zeroizing::new) {
+        Ok(secret) => parse_keypair(secret.trim()),
+        Err(_) => match default {
+            Some(default) => parse_keypair(&expand_home(default)),
+            None => parse_secret_key(&prompt_secret("Secret key: ", Error::MissingKeypair)?),
+        },
+    }
+}
+
+/// The secret state given on the command line, otherwise the one typed at a prompt that doesn't echo it
+pub fn secret_state_or_prompt<T: Serialize>(secret_state: Option<T>) -> Result<T, Error> {
+    match secret_state {
+        Some(secret_state) => Ok(secret_state),
+        None => parse_secret(&prompt_secret("Secret state: ", Error::MissingSecretState)?),
+    }
+}
+
+/// The secret key of `keystore`, decrypted with a passphrase prompted for
+pub fn unlock_keystore(keystore: &Keystore) -> Result<Keypair, Error> {
+    let prompt = format!("Passphrase of the keystore of {}: ", keystore.pubkey);
+    keystore.decrypt(&prompt_secret(&prompt, Error::MissingPassphrase)?)
+}
+
+/// A new passphrase for a keystore or a protected secret state, prompted for twice so a typo can't lock the key away
+pub fn prompt_new_passphrase() -> Result<Zeroizing<String>, Error> {
+    let passphrase = prompt_secret("New passphrase: ", Error::MissingPassphrase)?;
+    if passphrase.is_empty() {
+        return Err(Error::EmptyPassphrase);
+    }
+    if prompt_secret("Repeat the passphrase: ", Error::MissingPassphrase)? != passphrase {
+        return Err(Error::PassphraseMismatch);
+    }
+    Ok(passphrase)
+}
+
+/// Read a secret from the terminal with echo disabled, so it's neither shown nor kept in the history.
+/// Fails with `missing` when there's no terminal to prompt at, e.g. in scripts.
+fn prompt_secret(prompt: &str, missing: Error) -> Result<Zeroizing<String>, Error> {
+    let secret = Zeroizing::new(rpassword::prompt_password(prompt).map_err(|_| missing)?);
+    Ok(Zeroizing::new(secret.trim().to_string()))
+}
+
+/// The secret key of a command that can do without one, which is never taken from `SOLANA_TSS_KEYPAIR`
+pub fn optional_keypair(
+    keypair: Option<Keypair>,
+    keypair_file: Option<Keypair>,
+    keypair_stdin: bool,
+) -> Result<Option<Keypair>, Error> {
+    if keypair_stdin {
+        let mut secret = Zeroizing::new(String::new());
+        std::io::stdin().read_to_string(&mut secret).map_err(Error::StdinFailed)?;
+        return parse_secret_key(secret.trim()).map(Some);
+    }
+    Ok(keypair.or(keypair_file))
+}
+
+/// A Base58 secret key, the path of a JSON keypair file as written by `solana-keygen` and `generate --outfile`,
+/// or a `keychain:<name>` holding either
+fn parse_keypair(s: &str) -> Result<Keypair, Error> {
+    if keychain::is_entry(s) {
+        return parse_secret_key(Zeroizing::new(KeychainEntry::from_str(s)?.load()?).trim());
+    }
+    // Paths have characters that Base58 doesn't, so a mistyped one is reported as a missing file.
+    if Path::new(s).is_file() || s.contains(&['/', '\\', '.'][..]) {
+        return parse_keypair_file(s);
+    }
+    parse_secret_key(s)
+}
+
+/// A JSON keypair file, or a keystore file, which is unlocked with a passphrase prompted for
+fn parse_keypair_file(s: &str) -> Result<Keypair, Error> {
+    let error = |e: String| Error::KeypairFileFailed { path: s.to_string(), error: e };
+    let contents = std::fs::read_to_string(s).map_err(|e| error(e.to_string()))?;
+    // A keystore is a JSON object, a keypair file a JSON array of bytes.
+    if contents.trim_start().starts_with('{') {
+        return unlock_keystore(&Keystore::parse(&contents)?);
+    }
+    read_keypair_file(s).map_err(|e| error(e.to_string()))
+}
+
+/// `path` with a leading `~/` made the home directory, as the shell does for a flag
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), dirs_next::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().into_owned(),
+        _ => path.to_string(),
+    }
+}
+
+/// A Base58 secret key, or the JSON array of bytes of a keypair file's contents
+fn parse_secret_key(s: &str) -> Result<Keypair, Error> {
+    let decoded = Zeroizing::new(if s.starts_with('[') {
+        serde_json::from_str::<Vec<u8>>(s).map_err(|_| Error::InvalidKeypairJson)?
+    } else {
+        bs58::decode(s).into_vec()?
+    });
+    Ok(Keypair::from_bytes(&decoded)?)
+}
+
+/// A secret state or key share, or the `keychain:<name>` it was saved to.
+/// A secret state printed with `--protect` is decrypted with a passphrase prompted for.
+fn parse_secret<T: Serialize>(s: &str) -> Result<T, Error> {
+    if keychain::is_entry(s) {
+        return parse_secret(&Zeroizing::new(KeychainEntry::from_str(s)?.load()?));
+    }
+    if keystore::is_protected(s) {
+        return keystore::unprotect(s, &prompt_secret("Passphrase of the secret state: ", Error::MissingPassphrase)?);
+    }
+    T::parse(s).with_field("secret")
+}
+
+/// An address, or its alias in the address book
+fn parse_address(s: &str) -> Result<Pubkey, Error> {
+    match Pubkey::from_str(s) {
+        Ok(address) => Ok(address),
+        Err(_) => AddressBook::load()?.resolve(s),
+    }
+}
+
+fn parse_derivation_path(s: &str) -> Result<DerivationPath, Error> {
+    DerivationPath::from_absolute_path_str(s).map_err(|_| Error::WrongDerivationPath(s.to_string()))
+}
+
+fn parse_signer(s: &str) -> Result<(Pubkey, Signature), Error> {
+    let (key, signature) = s.split_once('=').ok_or_else(|| Error::WrongSigner(s.to_string()))?;
+    match (Pubkey::from_str(key), Signature::from_str(signature)) {
+        (Ok(key), Ok(signature)) => Ok((key, signature)),
+        _ => Err(Error::WrongSigner(s.to_string())),
+    }
+}
+
+fn parse_signed_tx(s: &str) -> Result<VersionedTransaction, Error> {
+    let tx = parse_partially_signed_tx(s)?;
+    if tx.signatures.iter().any(|sig| *sig == Signature::default()) {
+        return Err(Error::InvalidSignedTransaction);
+    }
+    Ok(tx)
+}
+
+/// A base64 transaction whose absent signatures are zeroes, as printed by `--no-send`
+fn parse_partially_signed_tx(s: &str) -> Result<VersionedTransaction, Error> {
+    let bytes = base64::decode(s.trim()).map_err(|_| Error::InvalidSignedTransaction)?;
+    let options = bincode::DefaultOptions::new().with_fixint_encoding().reject_trailing_bytes();
+    let tx: VersionedTransaction = options.deserialize(&bytes).map_err(|_| Error::InvalidSignedTransaction)?;
+    tx.sanitize(true).map_err(|_| Error::InvalidSignedTransaction)?;
+    Ok(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::hash::Hash;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::{Keypair, Signature, Signer};
+    use solana_sdk::signer::keypair::write_keypair_file;
+    use solana_sdk::system_instruction;
+    use solana_sdk::transaction::{Transaction, VersionedTransaction};
+
+    use solana_tss::Error;
+
+    use crate::cli::{parse_keypair, parse_signed_tx};
+
+    #[test]
+    fn test_parse_keypair() {
+        let keypair = Keypair::new();
+        assert_eq!(parse_keypair(&keypair.to_base58_string()).unwrap().pubkey(), keypair.pubkey());
+
+        let path = std::env::temp_dir().join(format!("solana-tss-{}.json", keypair.pubkey()));
+        write_keypair_file(&keypair, &path).unwrap();
+        let parsed = parse_keypair(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(parsed.unwrap().pubkey(), keypair.pubkey());
+        // Gone now, which is reported as such rather than as bad Base58.
+        assert!(matches!(parse_keypair(path.to_str().unwrap()), Err(Error::KeypairFileFailed { .. })));
+
+        // The contents of a keypair file, as given on the standard input or in the environment.
+        let json = serde_json::to_string(&keypair.to_bytes().to_vec()).unwrap();
+        assert_eq!(parse_keypair(&json).unwrap().pubkey(), keypair.pubkey());
+        assert!(matches!(parse_keypair("[1, 2, 3"), Err(Error::InvalidKeypairJson)));
+    }
+
+    #[test]
+    fn test_parse_signed_tx() {
+        let keypair = Keypair::new();
+        let instructions = [system_instruction::transfer(&keypair.pubkey(), &Pubkey::new_unique(), 1)];
+        let tx =
+            Transaction::new_signed_with_payer(&instructions, Some(&keypair.pubkey()), &[&keypair], Hash::new_unique());
+        let tx = VersionedTransaction::from(tx);
+        let encoded = base64::encode(bincode::serialize(&tx).unwrap());
+        assert_eq!(parse_signed_tx(&encoded).unwrap(), tx);
+
+        // An unsigned transaction can't be sent.
+        let unsigned = VersionedTransaction { signatures: vec![Signature::default()], message: tx.message };
+        let encoded = base64::encode(bincode::serialize(&unsigned).unwrap());
+        assert!(matches!(parse_signed_tx(&encoded), Err(Error::InvalidSignedTransaction)));
+        assert!(matches!(parse_signed_tx("not base64"), Err(Error::InvalidSignedTransaction)));
+    }
+}
@@ -0,0 +1,82 @@
+use std::io::Write;
+
+use clap::{Command, CommandFactory};
+use clap_complete::Shell;
+
+use solana_tss::session::Session;
+
+use crate::cli::Cli;
+use crate::config::leak;
+
+/// The name of the binary, which the completions are for
+const BIN_NAME: &str = "solana-tss";
+/// The clusters `--net` takes by name, it takes the URL of any RPC node too
+const NETWORKS: [&str; 4] = ["mainnet", "testnet", "devnet", "localnet"];
+
+/// Write the completion script of `shell` to `out`, completing the names of the networks and of the kept `sessions`
+/// and their session IDs as well as the commands and flags, except in PowerShell which only completes the latter.
+/// The sessions are those there are when it runs, so the script is best loaded anew by each shell.
+pub fn generate(shell: Shell, sessions: &[Session], out: &mut dyn Write) {
+    let mut command = Cli::command();
+    let names: Vec<_> = sessions.iter().map(|session| leak(&session.name)).collect();
+    let session_ids: Vec<_> = sessions.iter().map(|session| leak(&session.session_id)).collect();
+    complete_values(&mut command, &names, &session_ids);
+    clap_complete::generate(shell, &mut command, BIN_NAME, out);
+}
+
+/// Make the values to complete the possible values of the arguments taking them, in `command` and all its subcommands.
+/// Only the completions see them, the arguments still take any other value when parsed.
+fn complete_values(command: &mut Command<'static>, names: &[&'static str], session_ids: &[&'static str]) {
+    for subcommand in command.get_subcommands_mut() {
+        let takes_name = matches!(subcommand.get_name(), "session-show" | "session-delete");
+        let ids: Vec<_> = subcommand.get_arguments().map(|arg| arg.get_id()).collect();
+        for id in ids {
+            let values = match id {
+                "net" => &NETWORKS[..],
+                "session" => names,
+                "name" if takes_name => names,
+                "session_id" => session_ids,
+                _ => continue,
+            };
+            if values.is_empty() {
+                continue;
+            }
+            *subcommand = std::mem::take(subcommand).mut_arg(id, |arg| arg.possible_values(values));
+        }
+        complete_values(subcommand, names, session_ids);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap_complete::Shell;
+    use solana_sdk::signature::Keypair;
+    use solana_tss::serialization::{Encoding, Serialize, SessionId};
+    use solana_tss::session::Session;
+    use solana_tss::tss;
+
+    use crate::completions::generate;
+
+    #[test]
+    fn test_completions() {
+        let keypair = Keypair::new();
+        let (first_msg, secret) = tss::step_one(&keypair, SessionId::random());
+        let first_msg = first_msg.serialize_encoded(Encoding::Bs58).to_string();
+        let secret_key = keypair.secret().to_bytes();
+        let session = Session::new("payroll-1", "party".to_string(), &secret_key, first_msg, &secret).unwrap();
+
+        let script = |shell| {
+            let mut script = Vec::new();
+            generate(shell, &[session.clone()], &mut script);
+            String::from_utf8(script).unwrap()
+        };
+        assert!(script(Shell::PowerShell).contains("agg-send-step-two"));
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = script(shell);
+            assert!(script.contains("agg-send-step-two"), "{}", shell);
+            assert!(script.contains("devnet"), "{}", shell);
+            assert!(script.contains("payroll-1"), "{}", shell);
+            assert!(script.contains(&session.session_id), "{}", shell);
+        }
+    }
+}
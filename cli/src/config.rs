@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use clap::{Command, CommandFactory, FromArgMatches};
+use reqwest::Url;
+
+use solana_tss::network::Network;
+use solana_tss::serialization::{Encoding, Format};
+use solana_tss::Error;
+
+use crate::cli::Cli;
+
+/// The environment variable of the configuration file, when `--config` isn't given
+pub const CONFIG_ENV: &str = "SOLANA_TSS_CONFIG";
+/// The environment variables overriding the settings of the configuration file
+pub const NETWORK_ENV: &str = "SOLANA_TSS_NETWORK";
+pub const RPC_URL_ENV: &str = "SOLANA_TSS_RPC_URL";
+pub const RELAY_ENV: &str = "SOLANA_TSS_RELAY";
+pub const FORMAT_ENV: &str = "SOLANA_TSS_FORMAT";
+pub const ENCODING_ENV: &str = "SOLANA_TSS_ENCODING";
+
+/// The defaults of the command line, so the same flags don't need to be passed to every step. Flags given override
+/// them, every setting is optional.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The `--net` of the commands that take one: a cluster name or the URL of an RPC node
+    network: Option<String>,
+    /// The RPC node to use for a cluster instead of its public one, by cluster name
+    #[serde(default)]
+    pub rpc_urls: HashMap<String, String>,
+    /// The secret key of the commands that need one, as `--keypair` takes it, when none is given
+    pub keypair: Option<String>,
+    /// The `--relay` of the commands that take one, unless another way of exchanging the messages is given
+    relay: Option<String>,
+    /// The `--format` of the commands that take one
+    format: Option<String>,
+    /// The `--encoding` of the commands that take one
+    encoding: Option<String>,
+}
+
+impl Config {
+    /// The configuration in `path`, or in `~/.config/solana-tss/config.toml` when it exists, or an empty one, with
+    /// the settings of the environment variables set overriding those of the file
+    pub fn load(path: Option<&Path>) -> Result<Self, Error> {
+        Self::read(path)?.with_env(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+    }
+
+    fn read(path: Option<&Path>) -> Result<Self, Error> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => (config_dir()?.join("config.toml"), false),
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(Error::ConfigFailed(format!("{}: {}", path.display(), e))),
+        };
+        let config: Self =
+            toml::from_str(&contents).map_err(|e| Error::ConfigFailed(format!("{}: {}", path.display(), e)))?;
+        config.check().map_err(|e| Error::ConfigFailed(format!("{}: {}", path.display(), e)))?;
+        Ok(config)
+    }
+
+    /// The configuration with the settings of the environment variables `var` has. `SOLANA_TSS_RPC_URL` is the node
+    /// of the cluster of the network if it names one, otherwise the network itself like `--url`.
+    fn with_env(mut self, var: impl Fn(&str) -> Option<String>) -> Result<Self, Error> {
+        if let Some(network) = var(NETWORK_ENV) {
+            self.network = Some(network);
+        }
+        if let Some(url) = var(RPC_URL_ENV) {
+            let cluster = self.network.as_deref().and_then(|net| Network::from_str(net).ok()?.cluster_name());
+            match cluster {
+                Some(cluster) => {
+                    self.rpc_urls.insert(cluster.to_string(), url);
+                }
+                None => self.network = Some(url),
+            }
+        }
+        for (name, setting) in
+            [(RELAY_ENV, &mut self.relay), (FORMAT_ENV, &mut self.format), (ENCODING_ENV, &mut self.encoding)]
+        {
+            if let Some(value) = var(name) {
+                *setting = Some(value);
+            }
+        }
+        // The file was checked already, so what's wrong is in the environment.
+        self.check().map_err(|e| Error::ConfigFailed(format!("the environment variables: {}", e)))?;
+        Ok(self)
+    }
+
+    /// Fail on a setting the flag it stands for wouldn't take, rather than on every command using it
+    fn check(&self) -> Result<(), String> {
+        let invalid = |field: &str, value: &str| format!("invalid {}: {}", field, value);
+        if let Some(network) = &self.network {
+            Network::from_str(network).map_err(|_| invalid("network", network))?;
+        }
+        for (cluster, url) in &self.rpc_urls {
+            match Network::from_str(cluster) {
+                Ok(net) if net.cluster_name() == Some(cluster.as_str()) => {}
+                _ => return Err(invalid("rpc_urls", cluster)),
+            }
+            if !matches!(Network::from_str(url), Ok(Network::Url(_))) {
+                return Err(invalid("rpc_urls", url));
+            }
+        }
+        if let Some(relay) = &self.relay {
+            Url::parse(relay).map_err(|_| invalid("relay", relay))?;
+        }
+        if let Some(format) = &self.format {
+            Format::from_str(format).map_err(|_| invalid("format", format))?;
+        }
+        if let Some(encoding) = &self.encoding {
+            Encoding::from_str(encoding).map_err(|_| invalid("encoding", encoding))?;
+        }
+        Ok(())
+    }
+
+    /// Parse `args` like `Cli::parse` does, exiting with the usage if they're wrong
+    pub fn parse_args(&self, args: Vec<OsString>) -> Cli {
+        self.try_parse_args(args).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parse `args` with the defaults of the configuration. The relay is passed as `--relay` to the commands taking
+    /// one, unless that conflicts with the flags given, e.g. `--first-messages`.
+    fn try_parse_args(&self, args: Vec<OsString>) -> Result<Cli, clap::Error> {
+        let mut command = Cli::command();
+        let defaults: Vec<_> = [("net", &self.network), ("format", &self.format), ("encoding", &self.encoding)]
+            .iter()
+            .filter_map(|(id, value)| Some((*id, leak(value.as_deref()?))))
+            .collect();
+        set_defaults(&mut command, &defaults);
+        let matches = match &self.relay {
+            Some(relay) => {
+                let with_relay = args.iter().cloned().chain(["--relay".into(), relay.into()]).collect::<Vec<_>>();
+                command.try_get_matches_from_mut(with_relay).or_else(|_| command.try_get_matches_from_mut(args))
+            }
+            None => command.try_get_matches_from_mut(args),
+        };
+        Cli::from_arg_matches(&matches?)
+    }
+}
+
+/// The `--config` given in `args`, which is needed before they can be parsed, otherwise `SOLANA_TSS_CONFIG`
+pub fn config_arg(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter().take_while(|arg| *arg != "--");
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--config=")) {
+            return Some(PathBuf::from(path));
+        }
+    }
+    std::env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()).map(PathBuf::from)
+}
+
+/// The directory of the configuration, `$XDG_CONFIG_HOME/solana-tss` or `~/.config/solana-tss`
+pub fn config_dir() -> Result<PathBuf, Error> {
+    let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => dirs_next::home_dir().ok_or(Error::NoHomeDir)?.join(".config"),
+    };
+    Ok(dir.join("solana-tss"))
+}
+
+/// Make `value` the default of the arguments `id` of every subcommand
+fn set_defaults(command: &mut Command<'static>, defaults: &[(&'static str, &'static str)]) {
+    for subcommand in command.get_subcommands_mut() {
+        for (id, value) in defaults {
+            if subcommand.get_arguments().any(|arg| arg.get_id() == *id) {
+                *subcommand = std::mem::take(subcommand).mut_arg(*id, |arg| arg.default_value(value));
+            }
+        }
+        set_defaults(subcommand, defaults);
+    }
+}
+
+/// clap keeps the defaults by reference, and the configuration is needed for the whole run anyway
+pub fn leak(value: &str) -> &'static str {
+    Box::leak(value.to_string().into_boxed_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use solana_sdk::hash::Hash;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Keypair;
+    use solana_tss::network::Network;
+
+    use crate::cli::Options;
+    use crate::config::{config_arg, Config, FORMAT_ENV, NETWORK_ENV, RELAY_ENV, RPC_URL_ENV};
+
+    #[test]
+    fn test_config_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+            network = "devnet"
+            relay = "https://relay.example.com"
+
+            [rpc_urls]
+            mainnet = "https://mainnet.example.com"
+            "#,
+        )
+        .unwrap();
+        config.check().unwrap();
+        let args = |args: &[&str]| -> Vec<OsString> { args.iter().map(OsString::from).collect() };
+        let address = Pubkey::new_unique().to_string();
+
+        let cli = config.try_parse_args(args(&["solana-tss", "balance", &address])).unwrap();
+        assert!(matches!(cli.command, Options::Balance { net: Network::Devnet, .. }));
+        // A flag given wins.
+        let cli = config.try_parse_args(args(&["solana-tss", "balance", &address, "--net", "testnet"])).unwrap();
+        assert!(matches!(cli.command, Options::Balance { net: Network::Testnet, .. }));
+
+        let keypair = Keypair::new().to_base58_string();
+        let (to, hash) = (Pubkey::new_unique().to_string(), Hash::new_unique().to_string());
+        let (key1, key2) = (Pubkey::new_unique().to_string(), Pubkey::new_unique().to_string());
+        let step_two = |extra: &[&str]| {
+            let mut step_two = vec!["solana-tss", "agg-send-step-two", "--keypair", keypair.as_str(), "--amount", "1"];
+            step_two.extend(["--to", to.as_str(), "--recent-block-hash", hash.as_str(), "--keys", &key1, &key2]);
+            step_two.extend(extra);
+            config.try_parse_args(args(&step_two)).unwrap().command
+        };
+        let relay = match step_two(&[]) {
+            Options::AggSendStepTwo { relay, .. } => relay,
+            _ => unreachable!(),
+        };
+        assert_eq!(relay.unwrap().as_str(), "https://relay.example.com/");
+        // Not when another way of exchanging the messages is given.
+        assert!(matches!(step_two(&["--p2p"]), Options::AggSendStepTwo { relay: None, p2p: true, .. }));
+
+        assert_eq!(config_arg(&args(&["solana-tss", "--config", "a.toml", "balance"])), Some("a.toml".into()));
+        assert_eq!(config_arg(&args(&["solana-tss", "balance", "--config=b.toml"])), Some("b.toml".into()));
+        assert_eq!(config_arg(&args(&["solana-tss", "--", "--config", "c.toml"])), None);
+
+        let wrong: Config = toml::from_str("[rpc_urls]\nmoon = \"https://moon.example.com\"").unwrap();
+        assert!(wrong.check().is_err());
+
+        // The environment overrides the file, the RPC URL is the node of the network's cluster.
+        let env = |vars: &'static [(&str, &str)]| {
+            move |name: &str| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+        };
+        let config = config.with_env(env(&[(RPC_URL_ENV, "https://devnet.example.com")])).unwrap();
+        assert_eq!(config.network.as_deref(), Some("devnet"));
+        assert_eq!(config.rpc_urls["devnet"], "https://devnet.example.com");
+        let config =
+            config.with_env(env(&[(NETWORK_ENV, "mainnet"), (RELAY_ENV, "https://other.example.com")])).unwrap();
+        assert_eq!(config.network.as_deref(), Some("mainnet"));
+        assert_eq!(config.relay.as_deref(), Some("https://other.example.com"));
+        let config = Config::default().with_env(env(&[(RPC_URL_ENV, "https://rpc.example.com")])).unwrap();
+        assert_eq!(config.network.as_deref(), Some("https://rpc.example.com"));
+        assert!(Config::default().with_env(env(&[(FORMAT_ENV, "yaml")])).is_err());
+    }
+}
@@ -0,0 +1,2119 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use libp2p::Multiaddr;
+use serde_json::json;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::address_lookup_table_account::AddressLookupTableAccount;
+use solana_sdk::bpf_loader_upgradeable;
+use solana_sdk::derivation_path::DerivationPath;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::loader_upgradeable_instruction::UpgradeableLoaderInstruction;
+use solana_sdk::message::{v0, Message, VersionedMessage};
+use solana_sdk::program_utils::limited_deserialize;
+use solana_sdk::signature::{Signature, Signer};
+use solana_sdk::signer::keypair::{write_keypair_file, Keypair};
+use solana_sdk::stake::instruction::{self as stake_instruction, StakeInstruction};
+use solana_sdk::system_instruction::{self, SystemInstruction};
+use solana_sdk::transaction::{Transaction, TransactionError, VersionedTransaction};
+use solana_sdk::vote::instruction::{self as vote_instruction, VoteInstruction};
+use solana_sdk::vote::state::VoteAuthorize;
+use solana_sdk::{native_token, system_program};
+use spl_memo::solana_program::pubkey::Pubkey;
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
+use zeroize::Zeroizing;
+
+use crate::address_book::AddressBook;
+use crate::cli::{AddressBookCommand, Cli, Options};
+use crate::config::Config;
+use crate::output::Output;
+use crate::tui::{Outcome, Wizard};
+use solana_tss::audit::AuditLog;
+use solana_tss::backup::BackupShare;
+use solana_tss::board::Board;
+use solana_tss::daemon::Daemon;
+use solana_tss::journal::NonceJournal;
+use solana_tss::keychain::KeychainEntry;
+use solana_tss::keystore::Keystore;
+use solana_tss::message_dir::MessageDir;
+use solana_tss::network::{Network, RpcOptions};
+use solana_tss::nft::Nft;
+use solana_tss::p2p::P2p;
+use solana_tss::policy::Policy;
+use solana_tss::pool::NoncePool;
+use solana_tss::relay::RelayClient;
+use solana_tss::send::Preflight;
+use solana_tss::serialization::{
+    secret_bytes, AggMessage1, DkgMessage2, Encoding, Format, PartialSignature, Serialize, SessionId,
+};
+use solana_tss::session::Session;
+use solana_tss::token::Token;
+use solana_tss::transaction::{compile_message, create_unsigned_transaction, transfer_instructions};
+use solana_tss::transport::{Manual, Relayed, Round, Transport};
+use solana_tss::vanity::Vanity;
+use solana_tss::{
+    audit, backup, close, dkg, governance, grpc, history, keystore, ledger, lookup_table, mnemonic, nonce, offline,
+    program, qr, relay, rest, seal, seed, send, session, stake, threshold, token, transport, tss, vanity, watch, Error,
+};
+
+mod address_book;
+mod cli;
+mod completions;
+mod config;
+mod output;
+mod tui;
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<_> = std::env::args_os().collect();
+    let config = match Config::load(config::config_arg(&args).as_deref()) {
+        Ok(config) => config,
+        Err(e) => Output::new(Format::Text).fail(&e),
+    };
+    let cli = config.parse_args(args);
+    let mut output = Output::new(cli.output);
+    match run(cli, &config, &mut output).await {
+        Ok(()) => output.finish(),
+        Err(e) => output.fail(&e),
+    }
+}
+
+async fn run(cli: Cli, config: &Config, output: &mut Output) -> Result<(), Error> {
+    let Cli {
+        config: _,
+        output: _,
+        commitment,
+        rpc_timeout,
+        rpc_retries,
+        proxy,
+        audit_log,
+        policy_file,
+        session_db,
+        verbose,
+        log_format,
+        command,
+    } = cli;
+    // The wizard draws over the whole terminal, which logging to the standard error would scramble.
+    if !matches!(command, Options::Tui { .. }) {
+        init_logging(verbose, log_format);
+    }
+    let rpc_options = RpcOptions {
+        commitment,
+        timeout: Duration::from_secs(rpc_timeout),
+        retries: rpc_retries,
+        proxy,
+        rpc_urls: config.rpc_urls.clone(),
+    };
+    let audit = AuditLog::new(audit_log);
+    match command {
+        Options::Generate {
+            mnemonic,
+            word_count,
+            derivation_path,
+            outfile,
+            save_key,
+            starts_with,
+            ignore_case,
+            aggregate_with,
+            threads,
+        } => {
+            let keypair = if mnemonic {
+                let phrase = mnemonic::generate(word_count)?;
+                output.line(
+                    format!(
+                        "seed phrase: {} (write it down and keep it a secret, `recover` gets the keys back)",
+                        phrase
+                    ),
+                    "seed_phrase",
+                    phrase.as_str(),
+                );
+                mnemonic::keypair(&phrase, "", Some(derivation_path.unwrap_or_else(default_derivation_path)))?
+            } else if let Some(prefix) = starts_with {
+                let vanity = Vanity::new(&prefix, ignore_case)?;
+                let threads = threads.unwrap_or_else(vanity::default_threads);
+                info!("Searching for an address starting with {} on {} threads", prefix, threads);
+                if aggregate_with.is_empty() {
+                    vanity.grind(threads)
+                } else {
+                    let keypair = vanity.grind_aggregated(threads, &aggregate_with)?;
+                    let mut keys = aggregate_with;
+                    keys.push(keypair.pubkey());
+                    let aggpubkey = tss::aggregate_pubkey(keys)?;
+                    output.line(
+                        format!("The Aggregated Public Key: {}", aggpubkey),
+                        "aggregated_key",
+                        aggpubkey.to_string(),
+                    );
+                    keypair
+                }
+            } else {
+                Keypair::generate(&mut rand07::thread_rng())
+            };
+            match save_key {
+                Some(entry) => {
+                    entry.store(&keypair.to_base58_string())?;
+                    output.line(
+                        format!("secret share: stored in the keychain as {}", entry),
+                        "keychain_entry",
+                        entry.to_string(),
+                    );
+                }
+                None => print_secret_share(output, &keypair, outfile)?,
+            }
+            print_public_share(output, &keypair);
+        }
+        Options::Recover { derivation_path, no_derivation_path } => {
+            let phrase = read_line("Seed phrase: ")?;
+            let passphrase = read_line("Passphrase, if the seed phrase has one (press enter otherwise): ")?;
+            let derivation_path =
+                if no_derivation_path { None } else { Some(derivation_path.unwrap_or_else(default_derivation_path)) };
+            let keypair = mnemonic::keypair(&phrase, &passphrase, derivation_path)?;
+            print_secret_share(output, &keypair, None)?;
+            print_public_share(output, &keypair);
+        }
+        Options::BackupSplit { threshold, shares, keypair, keypair_file, keypair_stdin } => {
+            let keypair = cli::required_keypair(None, keypair, keypair_file, keypair_stdin, config.keypair.as_deref())?;
+            for share in backup::split(&keypair, threshold, shares)? {
+                let words = share.to_words();
+                output.item(
+                    format!(
+                        "Backup share {} of {}: {} (needs {} shares to restore, keep each in a different place)",
+                        share.index, shares, words, threshold
+                    ),
+                    "backup_shares",
+                    json!({ "index": share.index, "threshold": threshold, "words": words }),
+                );
+            }
+            print_public_share(output, &keypair);
+        }
+        Options::BackupRestore { outfile } => {
+            let first = BackupShare::from_words(&read_line("Backup share: ")?)?;
+            let mut shares = vec![first.clone()];
+            while shares.len() < usize::from(first.threshold) {
+                let prompt = format!("Backup share ({} more needed): ", usize::from(first.threshold) - shares.len());
+                shares.push(BackupShare::from_words(&read_line(&prompt)?)?);
+            }
+            let keypair = backup::restore(&shares)?;
+            print_secret_share(output, &keypair, outfile)?;
+            print_public_share(output, &keypair);
+        }
+        Options::KeystoreCreate { path, keypair, keypair_file, keypair_stdin } => {
+            let keypair = cli::required_keypair(None, keypair, keypair_file, keypair_stdin, config.keypair.as_deref())?;
+            Keystore::encrypt(&keypair, &cli::prompt_new_passphrase()?)?.write(&path)?;
+            output.line(
+                format!("The secret key of {} is now encrypted in {}", keypair.pubkey(), path.display()),
+                "keystore",
+                json!({ "public_share": keypair.pubkey().to_string(), "path": path }),
+            );
+        }
+        Options::KeystoreUnlock { path } => {
+            let keypair = cli::unlock_keystore(&Keystore::read(&path)?)?;
+            output.line(
+                format!("The passphrase unlocks the secret key of {}", keypair.pubkey()),
+                "public_share",
+                keypair.pubkey().to_string(),
+            );
+        }
+        Options::KeystoreExport { path, outfile } => {
+            let keypair = cli::unlock_keystore(&Keystore::read(&path)?)?;
+            print_secret_share(output, &keypair, outfile)?;
+            print_public_share(output, &keypair);
+        }
+        Options::KeychainStore { entry, keypair, keypair_file, keypair_stdin } => {
+            let keypair = cli::required_keypair(None, keypair, keypair_file, keypair_stdin, config.keypair.as_deref())?;
+            entry.store(&keypair.to_base58_string())?;
+            output.line(
+                format!("The secret key of {} is now stored in the keychain as {}", keypair.pubkey(), entry),
+                "keychain_entry",
+                json!({ "public_share": keypair.pubkey().to_string(), "entry": entry.to_string() }),
+            );
+        }
+        Options::KeychainDelete { entry } => {
+            entry.delete()?;
+            output.line(format!("Removed {} from the keychain", entry), "removed", entry.to_string());
+        }
+        Options::LedgerPubkey { key, confirm } => {
+            let ledger = key.connect(confirm)?;
+            output.line(
+                format!(
+                    "Ledger address: {} (it co-signs with `ledger-sign`, don't pass it to `aggregate-keys`)",
+                    ledger.pubkey()
+                ),
+                "ledger_address",
+                ledger.pubkey().to_string(),
+            );
+        }
+        Options::LedgerSign { key, mut tx, sign_only, format } => {
+            let format = output.format(format);
+            let ledger = key.connect(false)?;
+            eprintln!("Approve the transaction on the Ledger to sign it as {}", ledger.pubkey());
+            ledger::sign(&ledger, &mut tx)?;
+            if sign_only {
+                offline::print_sign_only(&tx, format);
+            } else {
+                print_signed_tx(&tx, format);
+            }
+        }
+        Options::Balance { address, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let balance = rpc_client.get_balance(&address).await.map_err(Error::BalaceFailed)?;
+            output.line(
+                format!("The balance of {} is: {}", address, balance),
+                "balance",
+                json!({ "address": address.to_string(), "lamports": balance }),
+            );
+        }
+        Options::Airdrop { to, amount, net } => {
+            // TODO: Check balance before and after, and if didn't change verify with get_signature_statuses_with_history
+            let rpc_client = net.connect(&rpc_options).await?;
+            let amount = native_token::sol_to_lamports(amount);
+            let sig = rpc_client.request_airdrop(&to, amount).await.map_err(Error::AirdropFailed)?;
+            output.line(format!("Airdrop transaction ID: {}", sig), "transaction_id", sig.to_string());
+            let recent_hash = rpc_client.get_latest_blockhash().await.map_err(Error::RecentHashFailed)?;
+            send::confirm(&rpc_client, &sig, &recent_hash, None).await?;
+        }
+        Options::SendSingle {
+            keypair,
+            keypair_file,
+            keypair_stdin,
+            amount,
+            all: _,
+            token_mint,
+            token_decimals,
+            token_2022,
+            token_fee,
+            fund_recipient,
+            to,
+            net,
+            skip_preflight,
+            preflight_commitment,
+            simulate_only,
+            memo,
+        } => {
+            let keypair = cli::required_keypair(None, keypair, keypair_file, keypair_stdin, config.keypair.as_deref())?;
+            let rpc_client = net.connect(&rpc_options).await?;
+            // clap requires either an amount or `--all`.
+            let amount = match amount {
+                Some(amount) => amount,
+                None => sweep_amount(&rpc_client, &keypair.pubkey()).await?,
+            };
+            let token =
+                lookup_token(&rpc_client, token_mint, token_decimals, token_2022, token_fee, amount, &to, &memo)
+                    .await?
+                    .map(|token| Token { fund_recipient, ..token });
+            let mut tx = create_unsigned_transaction(amount, token, &to, memo, None, &keypair.pubkey());
+            let policy = Policy::load(policy_file.as_deref())?;
+            let spent =
+                policy.check(&VersionedMessage::Legacy(tx.message.clone()), &keypair.pubkey(), Some(&to), &net)?;
+            let recent_hash = rpc_client.get_latest_blockhash().await.map_err(Error::RecentHashFailed)?;
+            tx.sign(&[&keypair], recent_hash);
+            policy.record(&keypair.pubkey(), &spent)?;
+            let tx = VersionedTransaction::from(tx);
+            if simulate_only {
+                send::simulate(&rpc_client, &tx, true).await?.print(output.format(Format::Text));
+                return Ok(());
+            }
+            let sig = send::send_transaction(
+                &rpc_client,
+                &tx,
+                Preflight { skip: skip_preflight, commitment: preflight_commitment },
+            )
+            .await?;
+            output.line(format!("Transaction ID: {}", sig), "transaction_id", sig.to_string());
+            send::confirm(&rpc_client, &sig, &recent_hash, Some(&tx)).await?;
+        }
+        Options::RecentBlockHash { net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let recent_hash = rpc_client.get_latest_blockhash().await.map_err(Error::RecentHashFailed)?;
+            output.line(format!("recent block hash: {}", recent_hash), "recent_block_hash", recent_hash.to_string());
+        }
+        Options::AggregateKeys { keys, verify_pop, pops } => {
+            if verify_pop {
+                tss::verify_key_pops(&keys, &pops)?;
+            }
+            let aggpubkey = tss::aggregate_pubkey(keys)?;
+            output.line(format!("The Aggregated Public Key: {}", aggpubkey), "aggregated_key", aggpubkey.to_string());
+        }
+        Options::KeyPop { secret_key, keypair, keypair_file, keypair_stdin, encoding } => {
+            let keypair =
+                cli::required_keypair(secret_key, keypair, keypair_file, keypair_stdin, config.keypair.as_deref())?;
+            let pop = tss::key_pop(&keypair);
+            let pop = pop.serialize_encoded(encoding).to_string();
+            output.line(
+                format!("Proof of possession: {} (send to all other parties)", pop),
+                "proof_of_possession",
+                pop,
+            );
+        }
+        Options::AggSendStepOne {
+            secret_key,
+            keypair,
+            keypair_file,
+            keypair_stdin,
+            session_id,
+            save_secret_state,
+            protect,
+            session,
+            encrypt_to,
+            relay,
+            out_dir,
+            qr,
+            board,
+            board_payer,
+            format,
+            encoding,
+        } => {
+            let format = output.format(format);
+            let keypair =
+                cli::required_keypair(secret_key, keypair, keypair_file, keypair_stdin, config.keypair.as_deref())?;
+            let session_id = session_id.unwrap_or_else(SessionId::random);
+            let (first_msg, secret) = tss::step_one(&keypair, session_id);
+            let (secret_line, secret_json) = match &session {
+                Some(name) => {
+                    let secret_key = Zeroizing::new(keypair.secret().to_bytes());
+                    let first = first_msg.serialize_encoded(encoding).to_string();
+                    let session = Session::new(name, keypair.pubkey().to_string(), &*secret_key, first, &secret)?;
+                    session::open(session_db.as_deref())?.put(&session)?;
+                    let line = format!(
+                        "Secret state: kept in session {} (pass `--session {}` to `agg-send-step-two`)",
+                        name, name
+                    );
+                    (line, json!({ "session": name }))
+                }
+                None => {
+                    let (secret_text, secret_json) = keep_secret(&secret, save_secret_state, protect, encoding)?;
+                    let line = format!(
+                        "Secret state: {} (keep this a secret, and pass it back to `agg-send-step-two`)",
+                        secret_text
+                    );
+                    (line, secret_json)
+                }
+            };
+            audit.record(
+                "agg-send-step-one",
+                Some(session_id),
+                json!({ "sent": audit::message(keypair.pubkey(), &first_msg) }),
+            )?;
+
+            let sealed = if encrypt_to.is_empty() { None } else { Some(seal::seal(&first_msg, &encrypt_to)?) };
+            let message = sealed.clone().unwrap_or_else(|| first_msg.serialize_encoded(encoding).to_string());
+            match format {
+                Format::Text => {
+                    let to = if relay.is_some() { "posted to the relay" } else { "send to all other parties" };
+                    println!("Message 1: {} ({})", message, to);
+                    println!("Session ID: {} (pass it to `--session-id` of all other parties' step 1)", session_id);
+                    println!("{}", secret_line);
+                }
+                Format::Json => println!(
+                    "{}",
+                    json!({
+                        "message_1": sealed.map_or_else(|| first_msg.serialize_json(encoding), |sealed| json!(sealed)),
+                        "session_id": session_id.to_string(),
+                        "secret_state": secret_json,
+                    })
+                ),
+            }
+            // Sent once the secret state is kept, so it isn't lost if the message can't be sent.
+            let party = Some(keypair.pubkey());
+            let mut transports: Vec<Box<dyn Transport>> = Vec::new();
+            if let Some(url) = relay {
+                transports.push(Box::new(RelayClient::new(url.as_str(), session_id, party)?));
+            }
+            if let Some(dir) = out_dir {
+                transports.push(Box::new(MessageDir::new(dir, session_id, party)));
+            }
+            if let Some(board) = board {
+                let payer = board_payer.unwrap_or_else(|| copy_keypair(&keypair));
+                transports.push(Box::new(Board::new(board.connect(&rpc_options).await?, session_id, Some(payer))));
+            }
+            for transport in &mut transports {
+                transport.send(Round::FirstMessages, &message).await?;
+            }
+            if qr {
+                qr::show(&message)?;
+            }
+        }
+        Options::AggSendStepTwo {
+            keypair,
+            keypair_file,
+            keypair_stdin,
+            amount,
+            all,
+            token_mint,
+            token_decimals,
+            token_2022,
+            token_fee,
+            fund_recipient,
+            to,
+            memo,
+            recent_block_hash,
+            nonce_account,
+            lookup_table,
+            net,
+            unsigned_tx,
+            keys,
+            inner_keys,
+            first_messages,
+            secret_state,
+            from_pool,
+            session,
+            force,
+            encrypt_to,
+            relay,
+            p2p,
+            p2p_peer,
+            in_dir,
+            out_dir,
+            qr,
+            board,
+            board_payer,
+            session_id,
+            format,
+            encoding,
+        } => {
+            let format = output.format(format);
+            let keypair = cli::required_keypair(None, keypair, keypair_file, keypair_stdin, config.keypair.as_deref())?;
+            let others: Vec<_> = keys.iter().filter(|key| **key != keypair.pubkey()).copied().collect();
+            let keys_len = keys.len();
+            let mut first_messages = seal::open_all(first_messages, Some(&keypair))?;
+            let lookup_decimals = token_mint.is_some() && token_decimals.is_none();
+            let rpc_client =
+                connect_to_read(&net, &rpc_options, all || !lookup_table.is_empty() || lookup_decimals).await?;
+            let secret_state = match (secret_state, &session) {
+                (Some(secret_state), _) => secret_state,
+                (None, Some(name)) => {
+                    let secret_key = Zeroizing::new(keypair.secret().to_bytes());
+                    session::open(session_db.as_deref())?.get(name)?.secret_state(&*secret_key)?
+                }
+                (None, None) if from_pool => {
+                    let session_id = session_id.or_else(|| first_messages.first().map(|msg| msg.session_id));
+                    NoncePool::open(&keypair)?.take(session_id.ok_or(Error::MissingSessionId)?)?
+                }
+                (None, None) => cli::secret_state_or_prompt(None)?,
+            };
+            let session_id = secret_state.session_id;
+            let party = Some(keypair.pubkey());
+            // The partial signature is sent back over the relay or the board, a directory is only read from.
+            let mut transport: Option<(Box<dyn Transport>, bool)> = match (relay, in_dir, board) {
+                (Some(url), _, _) => Some((Box::new(RelayClient::new(url.as_str(), session_id, party)?), true)),
+                (_, Some(dir), _) => Some((Box::new(MessageDir::new(dir, session_id, party)), false)),
+                (_, _, Some(board)) => {
+                    // Step two takes the keypair, the partial signature is posted after it.
+                    let payer = board_payer.unwrap_or_else(|| copy_keypair(&keypair));
+                    Some((Box::new(Board::new(board.connect(&rpc_options).await?, session_id, Some(payer))), true))
+                }
+                _ => None,
+            };
+            // Pooled nonces only get their session now, so the other parties still need the first message of it.
+            if from_pool {
+                let first_msg = tss::first_message(&keypair, &secret_state);
+                let message = first_msg.serialize_encoded(encoding);
+                match format {
+                    Format::Text => println!("Message 1: {} (send to all other parties)", message.as_str()),
+                    Format::Json => println!("{}", json!({ "message_1": first_msg.serialize_json(encoding) })),
+                }
+                if let Some(dir) = &out_dir {
+                    MessageDir::new(dir.clone(), session_id, party).send(Round::FirstMessages, &message).await?;
+                }
+                if let Some((transport, true)) = &mut transport {
+                    transport.send(Round::FirstMessages, &message).await?;
+                }
+            }
+            if let Some((transport, _)) = &mut transport {
+                first_messages =
+                    transport::recv(transport.as_mut(), Round::FirstMessages, &others, Some(&keypair)).await?;
+            }
+            // Step one isn't needed online, its message is published again from the secret state.
+            let mut node = if p2p { join_p2p(session_id, &p2p_peer).await } else { None };
+            if p2p {
+                let first_msg = tss::first_message(&keypair, &secret_state).serialize_encoded(encoding).to_string();
+                first_messages =
+                    exchange(node.as_mut(), session_id, Round::FirstMessages, Some(first_msg), &others, Some(&keypair))
+                        .await?;
+            }
+            let aggpubkey = tss::aggregate_pubkey(keys.clone())?;
+            let amount = if all { Some(sweep_amount(&rpc_client, &aggpubkey).await?) } else { amount };
+            let lookup_tables = fetch_lookup_tables(&rpc_client, &lookup_table).await?;
+            // Only a transfer is built out of the amount and recipient, `--unsigned-tx` conflicts with them.
+            let token = match amount.zip(to) {
+                Some((amount, to)) => {
+                    lookup_token(&rpc_client, token_mint, token_decimals, token_2022, token_fee, amount, &to, &memo)
+                        .await?
+                }
+                None => None,
+            }
+            .map(|token| Token { fund_recipient, ..token });
+            let message = signing_message(unsigned_tx, amount, to, recent_block_hash, |amount, to| {
+                compile_message(
+                    &transfer_instructions(amount, token, &to, memo, nonce_account, &aggpubkey),
+                    &aggpubkey,
+                    &lookup_tables,
+                )
+            })?;
+            let policy = Policy::load(policy_file.as_deref())?;
+            let spent = policy.check(&message, &aggpubkey, to.as_ref(), &net)?;
+            let received: Vec<_> = first_messages.iter().map(|msg| audit::message(msg.sender, msg)).collect();
+            let transaction = audit::transaction(&message);
+            NonceJournal::open(&keypair.pubkey().to_string())?.consume(&secret_state.public_nonces, force)?;
+            let sig = tss::step_two(keypair, message, keys, inner_keys, first_messages, secret_state)?;
+            policy.record(&aggpubkey, &spent)?;
+            audit.record(
+                "agg-send-step-two",
+                Some(session_id),
+                json!({ "received": received, "transaction": transaction, "sent": audit::message(sig.sender, &sig) }),
+            )?;
+            // The secret state is used up, and the journal refuses it from now on.
+            if let Some(name) = session {
+                session::open(session_db.as_deref())?.delete(&name)?;
+            }
+            let sealed = if encrypt_to.is_empty() { None } else { Some(seal::seal(&sig, &encrypt_to)?) };
+            match &sealed {
+                None => print_partial_signature(&sig, format, encoding),
+                Some(sealed) => print_sealed_partial_signature(sealed, format),
+            }
+            let posted = sealed.unwrap_or_else(|| sig.serialize_encoded(encoding).to_string());
+            if let Some(dir) = out_dir {
+                MessageDir::new(dir, session_id, Some(sig.sender)).send(Round::PartialSignatures, &posted).await?;
+            }
+            if qr {
+                qr::show(&posted)?;
+            }
+            if let Some((mut transport, true)) = transport {
+                transport.send(Round::PartialSignatures, &posted).await?;
+            }
+            if let Some(mut node) = node {
+                node.send(Round::PartialSignatures, &posted).await?;
+                info!("Waiting for the partial signatures of the other parties to go out...");
+                node.linger(Round::PartialSignatures, keys_len).await;
+            }
+        }
+        Options::AggregateSignaturesAndBroadcast {
+            signatures,
+            decryption_keypair,
+            amount,
+            all,
+            token_mint,
+            token_decimals,
+            token_2022,
+            token_fee,
+            fund_recipient,
+            to,
+            memo,
+            recent_block_hash,
+            nonce_account,
+            lookup_table,
+            unsigned_tx,
+            net,
+            skip_preflight,
+            preflight_commitment,
+            simulate_only,
+            no_send,
+            sign_only,
+            signers,
+            keys,
+            first_messages,
+            relay,
+            p2p,
+            p2p_peer,
+            in_dir,
+            board,
+            session_id,
+            format,
+        } => {
+            let format = output.format(format);
+            let decryption_keypair = decryption_keypair.as_ref();
+            // clap requires the session ID together with any of them.
+            let transport: Option<Box<dyn Transport>> = match (session_id, relay, in_dir, board) {
+                (Some(session_id), Some(url), _, _) => {
+                    Some(Box::new(RelayClient::new(url.as_str(), session_id, None)?))
+                }
+                (Some(session_id), _, Some(dir), _) => Some(Box::new(MessageDir::new(dir, session_id, None))),
+                (Some(session_id), _, _, Some(board)) => {
+                    Some(Box::new(Board::new(board.connect(&rpc_options).await?, session_id, None)))
+                }
+                _ => None,
+            };
+            let (signatures, first_messages): (Vec<PartialSignature>, _) = match transport {
+                Some(mut transport) => {
+                    let signatures =
+                        transport::recv(transport.as_mut(), Round::PartialSignatures, &keys, decryption_keypair)
+                            .await?;
+                    // They're only used to find out which party caused a failure, and may be sealed to the others only.
+                    let first_messages = match transport.list(Round::FirstMessages).await {
+                        Ok(posted) => transport::accept(posted, transport.session_id(), &keys, decryption_keypair)
+                            .unwrap_or_default(),
+                        Err(_) => Vec::new(),
+                    };
+                    (signatures, first_messages)
+                }
+                // clap requires the session ID together with `--p2p`.
+                None if p2p => {
+                    let session_id = session_id.unwrap();
+                    let mut node = join_p2p(session_id, &p2p_peer).await;
+                    let signatures =
+                        exchange(node.as_mut(), session_id, Round::PartialSignatures, None, &keys, decryption_keypair)
+                            .await?;
+                    // Only used to find out which party caused a failure, like with the other transports.
+                    let first_messages = match &mut node {
+                        Some(node) => transport::accept(
+                            node.list(Round::FirstMessages).await?,
+                            session_id,
+                            &keys,
+                            decryption_keypair,
+                        )
+                        .unwrap_or_default(),
+                        None => Vec::new(),
+                    };
+                    (signatures, first_messages)
+                }
+                None => (
+                    seal::open_all(signatures, decryption_keypair)?,
+                    seal::open_all(first_messages, decryption_keypair)?,
+                ),
+            };
+            let rpc_client = net.connect(&rpc_options).await?;
+            let aggpubkey = tss::aggregate_pubkey(keys.clone())?;
+            let recent_block_hash = match recent_block_hash {
+                Some(recent_block_hash) => Some(recent_block_hash),
+                None => nonce_value(&rpc_client, nonce_account).await?,
+            };
+            let amount = if all { Some(sweep_amount(&rpc_client, &aggpubkey).await?) } else { amount };
+            let lookup_tables = fetch_lookup_tables(&rpc_client, &lookup_table).await?;
+            // Only a transfer is built out of the amount and recipient, `--unsigned-tx` conflicts with them.
+            let token = match amount.zip(to) {
+                Some((amount, to)) => {
+                    lookup_token(&rpc_client, token_mint, token_decimals, token_2022, token_fee, amount, &to, &memo)
+                        .await?
+                }
+                None => None,
+            }
+            .map(|token| Token { fund_recipient, ..token });
+            let message = signing_message(unsigned_tx, amount, to, recent_block_hash, |amount, to| {
+                compile_message(
+                    &transfer_instructions(amount, token, &to, memo, nonce_account, &aggpubkey),
+                    &aggpubkey,
+                    &lookup_tables,
+                )
+            })?;
+            send::check_block_hash(&rpc_client, &message).await?;
+            let received: Vec<_> = signatures.iter().map(|sig| audit::message(sig.sender, sig)).collect();
+            let transaction = audit::transaction(&message);
+            let session_id = signatures.first().ok_or(Error::NoPartialSignatures)?.session_id;
+            let mut tx = tss::sign_and_broadcast(message, keys, first_messages, signatures)?;
+            let signature = audit::signature(&tx, aggpubkey)?;
+            audit.record(
+                "aggregate-signatures-and-broadcast",
+                Some(session_id),
+                json!({ "received": received, "transaction": transaction, "signature": signature }),
+            )?;
+            offline::add_signers(&mut tx, &signers)?;
+            if sign_only {
+                offline::print_sign_only(&tx, format);
+            } else if no_send {
+                print_signed_tx(&tx, format);
+            } else if simulate_only {
+                offline::check_complete(&tx)?;
+                send::simulate(&rpc_client, &tx, true).await?.print(format);
+            } else {
+                offline::check_complete(&tx)?;
+                broadcast(
+                    &tx,
+                    &rpc_client,
+                    Preflight { skip: skip_preflight, commitment: preflight_commitment },
+                    format,
+                )
+                .await?;
+            }
+        }
+        Options::AggSend {
+            keypair,
+            keypair_file,
+            keypair_stdin,
+            session_id,
+            amount,
+            token_mint,
+            token_decimals,
+            token_2022,
+            token_fee,
+            fund_recipient,
+            to,
+            memo,
+            recent_block_hash,
+            nonce_account,
+            lookup_table,
+            unsigned_tx,
+            net,
+            skip_preflight,
+            preflight_commitment,
+            keys,
+            relay,
+            p2p,
+            p2p_peer,
+            dir,
+            board,
+            board_payer,
+            format,
+            encoding,
+        } => {
+            let format = output.format(format);
+            let keypair = cli::required_keypair(None, keypair, keypair_file, keypair_stdin, config.keypair.as_deref())?;
+            let party = keypair.pubkey();
+            let others: Vec<_> = keys.iter().filter(|key| **key != party).copied().collect();
+            let rpc_client = net.connect(&rpc_options).await?;
+            let aggpubkey = tss::aggregate_pubkey(keys.clone())?;
+            // The transaction is built before any nonce is made, so none is wasted if it can't be.
+            let recent_block_hash = match recent_block_hash {
+                Some(recent_block_hash) => Some(recent_block_hash),
+                None => nonce_value(&rpc_client, nonce_account).await?,
+            };
+            let lookup_tables = fetch_lookup_tables(&rpc_client, &lookup_table).await?;
+            let token = match amount.zip(to) {
+                Some((amount, to)) => {
+                    lookup_token(&rpc_client, token_mint, token_decimals, token_2022, token_fee, amount, &to, &memo)
+                        .await?
+                }
+                None => None,
+            }
+            .map(|token| Token { fund_recipient, ..token });
+            let message = signing_message(unsigned_tx, amount, to, recent_block_hash, |amount, to| {
+                compile_message(
+                    &transfer_instructions(amount, token, &to, memo, nonce_account, &aggpubkey),
+                    &aggpubkey,
+                    &lookup_tables,
+                )
+            })?;
+            let policy = Policy::load(policy_file.as_deref())?;
+            let spent = policy.check(&message, &aggpubkey, to.as_ref(), &net)?;
+
+            // clap requires one of them.
+            let mut transport: Box<dyn Transport> = match (relay, dir, board) {
+                (Some(url), _, _) => Box::new(RelayClient::new(url.as_str(), session_id, Some(party))?),
+                (_, Some(dir), _) => Box::new(MessageDir::new(dir, session_id, Some(party))),
+                (_, _, Some(board)) => {
+                    let payer = board_payer.unwrap_or_else(|| copy_keypair(&keypair));
+                    Box::new(Board::new(board.connect(&rpc_options).await?, session_id, Some(payer)))
+                }
+                _ => Box::new(P2p::join(session_id, &p2p_peer).await?),
+            };
+            let (first_msg, secret_state) = tss::step_one(&keypair, session_id);
+            transport.send(Round::FirstMessages, &first_msg.serialize_encoded(encoding)).await?;
+            info!("Sent Message 1, waiting for the other parties...");
+            let first_messages: Vec<AggMessage1> =
+                transport::recv(transport.as_mut(), Round::FirstMessages, &others, Some(&keypair)).await?;
+
+            NonceJournal::open(&party.to_string())?.consume(&secret_state.public_nonces, false)?;
+            let received: Vec<_> = first_messages.iter().map(|msg| audit::message(msg.sender, msg)).collect();
+            let transaction = audit::transaction(&message);
+            let sig = tss::step_two(
+                keypair,
+                message.clone(),
+                keys.clone(),
+                Vec::new(),
+                first_messages.clone(),
+                secret_state,
+            )?;
+            policy.record(&aggpubkey, &spent)?;
+            transport.send(Round::PartialSignatures, &sig.serialize_encoded(encoding)).await?;
+            info!("Sent the partial signature, waiting for the other parties...");
+            let mut signatures: Vec<PartialSignature> =
+                transport::recv(transport.as_mut(), Round::PartialSignatures, &others, None).await?;
+
+            // Every party aggregates the same signatures into the same transaction, whoever sends it first lands it.
+            let sent = audit::message(sig.sender, &sig);
+            signatures.push(sig);
+            let first_messages = first_messages.into_iter().chain([first_msg]).collect();
+            send::check_block_hash(&rpc_client, &message).await?;
+            let tx = tss::sign_and_broadcast(message, keys, first_messages, signatures)?;
+            audit.record(
+                "agg-send",
+                Some(session_id),
+                json!({
+                    "received": received,
+                    "transaction": transaction,
+                    "sent": sent,
+                    "signature": audit::signature(&tx, aggpubkey)?,
+                }),
+            )?;
+            broadcast(&tx, &rpc_client, Preflight { skip: skip_preflight, commitment: preflight_commitment }, format)
+                .await?;
+        }
+        Options::SessionIdNew => {
+            let session_id = SessionId::random();
+            output.line(session_id, "session_id", session_id.to_string());
+        }
+        Options::Tui { keypair, keypair_file, keypair_stdin, session_id, unsigned_tx, net, keys, relay, encoding } => {
+            let keypair = cli::required_keypair(None, keypair, keypair_file, keypair_stdin, config.keypair.as_deref())?;
+            let session_id = session_id.unwrap_or_else(SessionId::random);
+            let rpc_client = net.connect(&rpc_options).await?;
+            let transport: Option<Box<dyn Transport>> = match relay {
+                Some(url) => Some(Box::new(RelayClient::new(url.as_str(), session_id, Some(keypair.pubkey()))?)),
+                None => None,
+            };
+            let policy = Policy::load(policy_file.as_deref())?;
+            let wizard = Wizard::new(keypair, keys, session_id, unsigned_tx, net, policy, audit, encoding)?;
+            match tui::run(wizard, transport, &rpc_client).await? {
+                Outcome::Sent(sig) => {
+                    output.line(format!("Transaction ID: {}", sig), "transaction_id", sig.to_string())
+                }
+                Outcome::Signed(tx) => print_signed_tx(&tx, output.format(Format::Text)),
+                Outcome::Unfinished => {
+                    output.line("Left before the transaction was signed by all the parties", "signed", false)
+                }
+            }
+        }
+        Options::AggAbort { secret_state, key, share, session } => {
+            if let Some(name) = session {
+                // The first message has the nonces, the secret key isn't needed to burn them.
+                let store = session::open(session_db.as_deref())?;
+                let session = store.get(&name)?;
+                NonceJournal::open(&session.party)?.abort(&session.public_nonces()?)?;
+                store.delete(&name)?;
+                audit.record("agg-abort", session.session_id.parse().ok(), json!({}))?;
+                output.line(format!("Aborted session {}, and deleted it", name), "aborted", name);
+                return Ok(());
+            }
+            let secret_state = cli::secret_state_or_prompt(secret_state)?;
+            // clap requires either a key or a share.
+            let name = match key {
+                Some(key) => key.to_string(),
+                None => share.map(|share| format!("{}-{}", share.group_key, share.index)).unwrap(),
+            };
+            NonceJournal::open(&name)?.abort(&secret_state.public_nonces)?;
+            audit.record("agg-abort", Some(secret_state.session_id), json!({}))?;
+            output.line(
+                format!(
+                    "Aborted session {}, its secret state can't be used for signing anymore",
+                    secret_state.session_id
+                ),
+                "aborted",
+                secret_state.session_id.to_string(),
+            );
+        }
+        Options::Daemon {
+            keypair,
+            keypair_file,
+            keypair_stdin,
+            grpc,
+            http,
+            print_openapi,
+            net,
+            encoding,
+            session_timeout,
+        } => {
+            if print_openapi {
+                let openapi = rest::openapi();
+                let pretty = serde_json::to_string_pretty(&openapi).expect("serializing to memory can't fail");
+                output.line(pretty, "openapi", openapi);
+                return Ok(());
+            }
+            let keypair = cli::required_keypair(None, keypair, keypair_file, keypair_stdin, config.keypair.as_deref())?;
+            // Only the JSON API reads the network.
+            let http = match http {
+                Some(addr) => Some((addr, net.connect(&rpc_options).await?)),
+                None => None,
+            };
+            let daemon = Daemon::new(keypair, net, Policy::load(policy_file.as_deref())?, audit)
+                .with_session_timeout(Duration::from_secs(session_timeout));
+            let daemon = Arc::new(daemon);
+            // clap requires at least one of them, both share the sessions.
+            let grpc = async {
+                match grpc {
+                    Some(addr) => grpc::serve(addr, daemon.clone(), encoding).await,
+                    None => futures::future::pending().await,
+                }
+            };
+            let http = async {
+                match http {
+                    Some((addr, rpc_client)) => rest::serve(addr, daemon.clone(), rpc_client, encoding).await,
+                    None => futures::future::pending().await,
+                }
+            };
+            // Expired sessions are dropped right away, rather than their secret states waiting in memory for the next
+            // session to be created.
+            let expire = async {
+                let mut interval = tokio::time::interval(Duration::from_secs(60));
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = daemon.expire_sessions() {
+                        break Err::<(), _>(e);
+                    }
+                }
+            };
+            tokio::try_join!(grpc, http, expire)?;
+        }
+        Options::DaemonSessions { url } => {
+            let sessions = rest::list_sessions(&url).await?;
+            output.list("sessions");
+            if sessions.is_empty() {
+                output.text("No sessions");
+            }
+            for session in sessions {
+                let mut text = format!(
+                    "{}: {}, {} parties, expires in {} s",
+                    session.session_id,
+                    session.state,
+                    session.keys.len(),
+                    session.expires_in_secs
+                );
+                if !session.missing.is_empty() {
+                    text += &format!("\n  Waiting for the first messages of {}", session.missing.join(", "));
+                }
+                let json = json!({
+                    "session_id": session.session_id,
+                    "state": session.state,
+                    "keys": session.keys,
+                    "received": session.received,
+                    "missing": session.missing,
+                    "expires_in_secs": session.expires_in_secs,
+                });
+                output.item(text, "sessions", json);
+            }
+        }
+        Options::RelayServe { listen } => relay::serve(listen).await?,
+        Options::Scan => {
+            let scanned = qr::scan(std::io::stdin().lock())?;
+            output.line(&scanned, "message", &scanned);
+        }
+        Options::SessionList => {
+            output.list("sessions");
+            for session in session::open(session_db.as_deref())?.list()? {
+                let text = format!(
+                    "{}: session {} of {}, started at {}",
+                    session.name, session.session_id, session.party, session.created
+                );
+                output.item(text, "sessions", session_json(&session));
+            }
+        }
+        Options::SessionShow { name } => {
+            let session = session::open(session_db.as_deref())?.get(&name)?;
+            let text = [
+                format!("Session ID: {}", session.session_id),
+                format!("Party: {}", session.party),
+                format!("Started at: {} (seconds since the Unix epoch)", session.created),
+                format!("Message 1: {} (send to all other parties)", session.first_message),
+            ]
+            .join("\n");
+            output.line(text, "session", session_json(&session));
+        }
+        Options::SessionDelete { name } => {
+            session::open(session_db.as_deref())?.delete(&name)?;
+            output.line(format!("Deleted session {}", name), "deleted", name);
+        }
+        Options::AddressBook { command } => {
+            let mut book = AddressBook::load()?;
+            match command {
+                AddressBookCommand::Add { alias, address } => {
+                    book.add(&alias, address)?;
+                    output.line(
+                        format!("{} now stands for {}", alias, address),
+                        "added",
+                        json!({ "alias": alias, "address": address.to_string() }),
+                    );
+                }
+                AddressBookCommand::List => {
+                    output.list("aliases");
+                    for (alias, address) in book.aliases() {
+                        let item = json!({ "alias": alias, "address": address.to_string() });
+                        output.item(format!("{}: {}", alias, address), "aliases", item);
+                    }
+                }
+                AddressBookCommand::Remove { alias } => {
+                    let address = book.remove(&alias)?;
+                    output.line(
+                        format!("Removed {}, which stood for {}", alias, address),
+                        "removed",
+                        json!({ "alias": alias, "address": address.to_string() }),
+                    );
+                }
+            }
+        }
+        Options::AuditVerify { path } => {
+            let entries = audit::verify(&path)?;
+            output.line(
+                format!("The audit log has {} entries and its hash chain is intact", entries),
+                "entries",
+                entries,
+            );
+        }
+        Options::Completions { shell } => {
+            // Completing without the sessions beats not completing at all.
+            let sessions = session::open(session_db.as_deref()).and_then(|store| store.list()).unwrap_or_else(|e| {
+                warn!("{}, the sessions won't be completed", e);
+                Vec::new()
+            });
+            completions::generate(shell, &sessions, &mut std::io::stdout());
+        }
+        Options::ThresholdKeygen { threshold, parties, encoding } => {
+            let shares = threshold::keygen(threshold, parties)?;
+            let group_key = shares[0].group_key.to_string();
+            output.line(format!("The Aggregated Public Key: {}", group_key), "aggregated_key", group_key);
+            for share in shares {
+                let encoded = share.serialize_encoded(encoding);
+                output.item(
+                    format!("Share {}: {} (send privately to party {})", share.index, encoded.as_str(), share.index),
+                    "shares",
+                    json!({ "index": share.index, "share": encoded.as_str() }),
+                );
+            }
+        }
+        Options::ThresholdAggSendStepOne {
+            share,
+            session_id,
+            save_secret_state,
+            protect,
+            session,
+            format,
+            encoding,
+        } => {
+            let format = output.format(format);
+            let session_id = session_id.unwrap_or_else(SessionId::random);
+            let (first_msg, secret) = threshold::step_one(&share, session_id);
+            let (secret_line, secret_json) = match &session {
+                Some(name) => {
+                    let party = format!("{}-{}", share.group_key, share.index);
+                    let first = first_msg.serialize_encoded(encoding).to_string();
+                    let session = Session::new(name, party, &*secret_bytes(&share.secret_share), first, &secret)?;
+                    session::open(session_db.as_deref())?.put(&session)?;
+                    let line = format!(
+                        "Secret state: kept in session {} (pass `--session {}` to `threshold-agg-send-step-two`)",
+                        name, name
+                    );
+                    (line, json!({ "session": name }))
+                }
+                None => {
+                    let (secret_text, secret_json) = keep_secret(&secret, save_secret_state, protect, encoding)?;
+                    let line = format!(
+                        "Secret state: {} (keep this a secret, and pass it back to `threshold-agg-send-step-two`)",
+                        secret_text
+                    );
+                    (line, secret_json)
+                }
+            };
+            audit.record(
+                "threshold-agg-send-step-one",
+                Some(session_id),
+                json!({ "sent": audit::message(share.index, &first_msg) }),
+            )?;
+
+            match format {
+                Format::Text => {
+                    println!(
+                        "Message 1: {} (send to all other signing parties)",
+                        first_msg.serialize_encoded(encoding).as_str()
+                    );
+                    println!(
+                        "Session ID: {} (pass it to `--session-id` of all other signing parties' step 1)",
+                        session_id
+                    );
+                    println!("{}", secret_line);
+                }
+                Format::Json => println!(
+                    "{}",
+                    json!({
+                        "message_1": first_msg.serialize_json(encoding),
+                        "session_id": session_id.to_string(),
+                        "secret_state": secret_json,
+                    })
+                ),
+            }
+        }
+        Options::ThresholdAggSendStepTwo {
+            share,
+            amount,
+            all,
+            token_mint,
+            token_decimals,
+            token_2022,
+            token_fee,
+            fund_recipient,
+            to,
+            memo,
+            recent_block_hash,
+            nonce_account,
+            lookup_table,
+            net,
+            unsigned_tx,
+            first_messages,
+            secret_state,
+            session,
+            force,
+            format,
+            encoding,
+        } => {
+            let format = output.format(format);
+            let secret_state = match &session {
+                Some(name) => session::open(session_db.as_deref())?
+                    .get(name)?
+                    .secret_state(&*secret_bytes(&share.secret_share))?,
+                None => cli::secret_state_or_prompt(secret_state)?,
+            };
+            let lookup_decimals = token_mint.is_some() && token_decimals.is_none();
+            let rpc_client =
+                connect_to_read(&net, &rpc_options, all || !lookup_table.is_empty() || lookup_decimals).await?;
+            let amount = if all { Some(sweep_amount(&rpc_client, &share.group_key).await?) } else { amount };
+            let lookup_tables = fetch_lookup_tables(&rpc_client, &lookup_table).await?;
+            // Only a transfer is built out of the amount and recipient, `--unsigned-tx` conflicts with them.
+            let token = match amount.zip(to) {
+                Some((amount, to)) => {
+                    lookup_token(&rpc_client, token_mint, token_decimals, token_2022, token_fee, amount, &to, &memo)
+                        .await?
+                }
+                None => None,
+            }
+            .map(|token| Token { fund_recipient, ..token });
+            let message = signing_message(unsigned_tx, amount, to, recent_block_hash, |amount, to| {
+                compile_message(
+                    &transfer_instructions(amount, token, &to, memo, nonce_account, &share.group_key),
+                    &share.group_key,
+                    &lookup_tables,
+                )
+            })?;
+            let policy = Policy::load(policy_file.as_deref())?;
+            let group_key = share.group_key;
+            let spent = policy.check(&message, &group_key, to.as_ref(), &net)?;
+            let session_id = secret_state.session_id;
+            let index = share.index;
+            let received: Vec<_> = first_messages.iter().map(|msg| audit::message(msg.index, msg)).collect();
+            let transaction = audit::transaction(&message);
+            let journal = NonceJournal::open(&format!("{}-{}", share.group_key, share.index))?;
+            journal.consume(&secret_state.public_nonces, force)?;
+            let sig = threshold::step_two(share, message, first_messages, secret_state)?;
+            policy.record(&group_key, &spent)?;
+            audit.record(
+                "threshold-agg-send-step-two",
+                Some(session_id),
+                json!({ "received": received, "transaction": transaction, "sent": audit::message(index, &sig) }),
+            )?;
+            if let Some(name) = session {
+                session::open(session_db.as_deref())?.delete(&name)?;
+            }
+            print_partial_signature(&sig, format, encoding);
+        }
+        Options::ThresholdAggregateSignaturesAndBroadcast {
+            signatures,
+            amount,
+            all,
+            token_mint,
+            token_decimals,
+            token_2022,
+            token_fee,
+            fund_recipient,
+            to,
+            memo,
+            recent_block_hash,
+            nonce_account,
+            lookup_table,
+            unsigned_tx,
+            net,
+            skip_preflight,
+            preflight_commitment,
+            simulate_only,
+            no_send,
+            sign_only,
+            signers,
+            group_key,
+            format,
+        } => {
+            let format = output.format(format);
+            let rpc_client = net.connect(&rpc_options).await?;
+            let recent_block_hash = match recent_block_hash {
+                Some(recent_block_hash) => Some(recent_block_hash),
+                None => nonce_value(&rpc_client, nonce_account).await?,
+            };
+            let amount = if all { Some(sweep_amount(&rpc_client, &group_key).await?) } else { amount };
+            let lookup_tables = fetch_lookup_tables(&rpc_client, &lookup_table).await?;
+            // Only a transfer is built out of the amount and recipient, `--unsigned-tx` conflicts with them.
+            let token = match amount.zip(to) {
+                Some((amount, to)) => {
+                    lookup_token(&rpc_client, token_mint, token_decimals, token_2022, token_fee, amount, &to, &memo)
+                        .await?
+                }
+                None => None,
+            }
+            .map(|token| Token { fund_recipient, ..token });
+            let message = signing_message(unsigned_tx, amount, to, recent_block_hash, |amount, to| {
+                compile_message(
+                    &transfer_instructions(amount, token, &to, memo, nonce_account, &group_key),
+                    &group_key,
+                    &lookup_tables,
+                )
+            })?;
+            send::check_block_hash(&rpc_client, &message).await?;
+            let received: Vec<_> = signatures.iter().map(|sig| audit::message(sig.sender, sig)).collect();
+            let transaction = audit::transaction(&message);
+            let session_id = signatures.first().ok_or(Error::NoPartialSignatures)?.session_id;
+            let mut tx = threshold::sign_and_broadcast(message, group_key, signatures)?;
+            let signature = audit::signature(&tx, group_key)?;
+            audit.record(
+                "threshold-aggregate-signatures-and-broadcast",
+                Some(session_id),
+                json!({ "received": received, "transaction": transaction, "signature": signature }),
+            )?;
+            offline::add_signers(&mut tx, &signers)?;
+            if sign_only {
+                offline::print_sign_only(&tx, format);
+            } else if no_send {
+                print_signed_tx(&tx, format);
+            } else if simulate_only {
+                offline::check_complete(&tx)?;
+                send::simulate(&rpc_client, &tx, true).await?.print(format);
+            } else {
+                offline::check_complete(&tx)?;
+                broadcast(
+                    &tx,
+                    &rpc_client,
+                    Preflight { skip: skip_preflight, commitment: preflight_commitment },
+                    format,
+                )
+                .await?;
+            }
+        }
+        Options::DkgStepOne { threshold, parties, index, save_secret_state, protect, encoding } => {
+            let (first_msg, secret) = dkg::step_one(threshold, parties, index)?;
+            let (secret_text, secret_json) = keep_secret(&secret, save_secret_state, protect, encoding)?;
+
+            output.line(
+                format!("Message 1: {} (send to all other parties)", first_msg.serialize_encoded(encoding).as_str()),
+                "message_1",
+                first_msg.serialize_json(encoding),
+            );
+            output.line(
+                format!(
+                    "Secret state: {} (keep this a secret, and pass it back to `dkg-step-two` and `dkg-step-three`)",
+                    secret_text
+                ),
+                "secret_state",
+                secret_json,
+            );
+        }
+        Options::DkgStepTwo { secret_state, first_messages, encoding } => {
+            let secret_state = cli::secret_state_or_prompt(secret_state)?;
+            output.list("messages_2");
+            for msg in dkg::step_two(&secret_state, &first_messages)? {
+                print_second_message(output, &msg, encoding);
+            }
+        }
+        Options::DkgStepThree { secret_state, first_messages, second_messages, save_share, encoding } => {
+            let secret_state = cli::secret_state_or_prompt(secret_state)?;
+            let share = dkg::step_three(secret_state, first_messages, second_messages)?;
+            let (share_text, share_json) = keep_secret(&share, save_share, false, encoding)?;
+            let group_key = share.group_key.to_string();
+            output.line(format!("The Aggregated Public Key: {}", group_key), "aggregated_key", group_key);
+            output.line(
+                format!("Share: {} (keep this a secret, and pass it to `threshold-agg-send-step-one`)", share_text),
+                "share",
+                share_json,
+            );
+        }
+        Options::ReshareStepOne { share, dealers, threshold, parties, encoding } => {
+            let threshold = threshold.unwrap_or(share.threshold);
+            let (first_msg, second_msgs) = dkg::reshare_step_one(&share, &dealers, threshold, parties)?;
+
+            output.line(
+                format!("Message 1: {} (send to all the new parties)", first_msg.serialize_encoded(encoding).as_str()),
+                "message_1",
+                first_msg.serialize_json(encoding),
+            );
+            output.list("messages_2");
+            for msg in second_msgs {
+                print_second_message(output, &msg, encoding);
+            }
+        }
+        Options::ReshareStepTwo { group_key, index, first_messages, second_messages, save_share, encoding } => {
+            let share = dkg::reshare_step_two(group_key, index, first_messages, second_messages)?;
+            let (share_text, share_json) = keep_secret(&share, save_share, false, encoding)?;
+            output.line(
+                format!(
+                    "Share: {} (keep this a secret and delete the old one, pass it to `threshold-agg-send-step-one`)",
+                    share_text
+                ),
+                "share",
+                share_json,
+            );
+        }
+        Options::AggNoncePool { secret_key, keypair, keypair_file, keypair_stdin, count } => {
+            let keypair =
+                cli::required_keypair(secret_key, keypair, keypair_file, keypair_stdin, config.keypair.as_deref())?;
+            let pooled = NoncePool::open(&keypair)?.fill(count)?;
+            output.line(
+                format!("Pooled nonces: {} (each signs one session with `agg-send-step-two --from-pool`)", pooled),
+                "pooled_nonces",
+                pooled,
+            );
+        }
+        Options::NonceCreate { wallet, seed, nonce_authority, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let authority = nonce_authority.unwrap_or(wallet);
+            let (nonce_account, instructions) =
+                nonce::create_instructions(&rpc_client, &wallet, &seed, &authority).await?;
+            output.line(format!("Nonce account: {}", nonce_account), "nonce_account", nonce_account.to_string());
+            print_unsigned_tx(output, &rpc_client, &instructions, &wallet).await?;
+        }
+        Options::NonceAdvance { nonce_account, keypair, keypair_file, keypair_stdin, net } => {
+            let keypair = cli::optional_keypair(keypair, keypair_file, keypair_stdin)?;
+            let rpc_client = net.connect(&rpc_options).await?;
+            let authority = nonce::fetch(&rpc_client, &nonce_account).await?.authority;
+            let instruction = system_instruction::advance_nonce_account(&nonce_account, &authority);
+            send_as_authority(output, &rpc_client, &[instruction], &authority, keypair).await?;
+        }
+        Options::NonceWithdraw { nonce_account, amount, all: _, to, keypair, keypair_file, keypair_stdin, net } => {
+            let keypair = cli::optional_keypair(keypair, keypair_file, keypair_stdin)?;
+            let rpc_client = net.connect(&rpc_options).await?;
+            let authority = nonce::fetch(&rpc_client, &nonce_account).await?.authority;
+            // clap requires either an amount or `--all`.
+            let lamports = match amount {
+                Some(amount) => native_token::sol_to_lamports(amount),
+                None => rpc_client.get_balance(&nonce_account).await.map_err(Error::BalaceFailed)?,
+            };
+            let to = to.unwrap_or(authority);
+            let instruction = system_instruction::withdraw_nonce_account(&nonce_account, &authority, &to, lamports);
+            send_as_authority(output, &rpc_client, &[instruction], &authority, keypair).await?;
+        }
+        Options::NonceShow { nonce_account, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let data = nonce::fetch(&rpc_client, &nonce_account).await?;
+            let fee = data.get_lamports_per_signature();
+            output.line(format!("Nonce: {}", data.blockhash()), "nonce", data.blockhash().to_string());
+            output.line(format!("Authority: {}", data.authority), "authority", data.authority.to_string());
+            output.line(
+                format!("Fee per signature: {} SOL", native_token::lamports_to_sol(fee)),
+                "lamports_per_signature",
+                fee,
+            );
+        }
+        Options::StakeCreate { wallet, amount, seed, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let lamports = native_token::sol_to_lamports(amount);
+            let (stake_account, instructions) = stake::create_instructions(&wallet, &seed, lamports)?;
+            output.line(format!("Stake account: {}", stake_account), "stake_account", stake_account.to_string());
+            print_unsigned_tx(output, &rpc_client, &instructions, &wallet).await?;
+        }
+        Options::StakeDelegate { stake_account, wallet, vote_account, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let instruction = stake_instruction::delegate_stake(&stake_account, &wallet, &vote_account);
+            print_unsigned_tx(output, &rpc_client, &[instruction], &wallet).await?;
+        }
+        Options::StakeDeactivate { stake_account, wallet, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let instruction = stake_instruction::deactivate_stake(&stake_account, &wallet);
+            print_unsigned_tx(output, &rpc_client, &[instruction], &wallet).await?;
+        }
+        Options::StakeWithdraw { stake_account, wallet, amount, to, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let lamports = native_token::sol_to_lamports(amount);
+            let to = to.unwrap_or(wallet);
+            let instruction = stake_instruction::withdraw(&stake_account, &wallet, &to, lamports, None);
+            print_unsigned_tx(output, &rpc_client, &[instruction], &wallet).await?;
+        }
+        Options::StakeAuthorize { stake_account, wallet, authority, new_authority, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let instruction = stake_instruction::authorize(&stake_account, &wallet, &new_authority, authority, None);
+            print_unsigned_tx(output, &rpc_client, &[instruction], &wallet).await?;
+        }
+        Options::VoteWithdraw { vote_account, wallet, amount, to, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let lamports = native_token::sol_to_lamports(amount);
+            let to = to.unwrap_or(wallet);
+            let instruction = vote_instruction::withdraw(&vote_account, &wallet, lamports, &to);
+            print_unsigned_tx(output, &rpc_client, &[instruction], &wallet).await?;
+        }
+        Options::VoteAuthorizeWithdrawer { vote_account, wallet, new_authority, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            // Not the checked variant, the new authority would have to sign too.
+            let instruction =
+                vote_instruction::authorize(&vote_account, &wallet, &new_authority, VoteAuthorize::Withdrawer);
+            print_unsigned_tx(output, &rpc_client, &[instruction], &wallet).await?;
+        }
+        Options::ProgramUpgrade { program: program_id, buffer, wallet, spill, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            program::check_upgrade_authority(&rpc_client, &program_id, &wallet).await?;
+            program::check_buffer(&rpc_client, &buffer, &wallet).await?;
+            let spill = spill.unwrap_or(wallet);
+            let instruction = bpf_loader_upgradeable::upgrade(&program_id, &buffer, &wallet, &spill);
+            print_unsigned_tx(output, &rpc_client, &[instruction], &wallet).await?;
+        }
+        Options::ProgramSetAuthority { program: program_id, wallet, new_authority, make_final, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            program::check_upgrade_authority(&rpc_client, &program_id, &wallet).await?;
+            // Not the checked variant, the new authority would have to sign too.
+            let new_authority = if make_final { None } else { new_authority.as_ref() };
+            let instruction = bpf_loader_upgradeable::set_upgrade_authority(&program_id, &wallet, new_authority);
+            print_unsigned_tx(output, &rpc_client, &[instruction], &wallet).await?;
+        }
+        Options::TokenMintTo { mint, wallet, amount, to, fund_recipient, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let token = Token { fund_recipient, ..Token::fetch(&rpc_client, mint, amount).await? };
+            let to = to.unwrap_or(wallet);
+            let instructions: Vec<_> = token
+                .create_recipient_instruction(&wallet, &to)
+                .into_iter()
+                .chain([token.mint_to_instruction(amount, &wallet, &to)])
+                .collect();
+            print_unsigned_tx(output, &rpc_client, &instructions, &wallet).await?;
+        }
+        Options::TokenSetAuthority { mint, wallet, authority, new_authority, disable, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let token = Token::fetch(&rpc_client, mint, 0.0).await?;
+            let new_authority = if disable { None } else { new_authority.as_ref() };
+            let instruction = token.set_authority_instruction(authority, &wallet, new_authority);
+            print_unsigned_tx(output, &rpc_client, &[instruction], &wallet).await?;
+        }
+        Options::TokenFreeze { account, mint, wallet, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let token = Token::fetch(&rpc_client, mint, 0.0).await?;
+            print_unsigned_tx(output, &rpc_client, &[token.freeze_instruction(true, &account, &wallet)], &wallet)
+                .await?;
+        }
+        Options::TokenThaw { account, mint, wallet, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let token = Token::fetch(&rpc_client, mint, 0.0).await?;
+            print_unsigned_tx(output, &rpc_client, &[token.freeze_instruction(false, &account, &wallet)], &wallet)
+                .await?;
+        }
+        Options::WrapSol { amount, wallet, keypair, keypair_file, keypair_stdin, net } => {
+            let keypair = cli::optional_keypair(keypair, keypair_file, keypair_stdin)?;
+            let rpc_client = net.connect(&rpc_options).await?;
+            // clap requires either the wallet or a keypair.
+            let owner = wallet.or_else(|| keypair.as_ref().map(|k| k.pubkey())).unwrap();
+            let instructions = token::wrap_sol_instructions(&owner, native_token::sol_to_lamports(amount));
+            send_as_authority(output, &rpc_client, &instructions, &owner, keypair).await?;
+        }
+        Options::UnwrapSol { wallet, keypair, keypair_file, keypair_stdin, net } => {
+            let keypair = cli::optional_keypair(keypair, keypair_file, keypair_stdin)?;
+            let rpc_client = net.connect(&rpc_options).await?;
+            let owner = wallet.or_else(|| keypair.as_ref().map(|k| k.pubkey())).unwrap();
+            send_as_authority(output, &rpc_client, &[token::unwrap_sol_instruction(&owner)], &owner, keypair).await?;
+        }
+        Options::CloseAccounts { accounts, empty_token_accounts, wallet, burn, to, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let to = to.unwrap_or(wallet);
+            let mut closing = close::fetch_accounts(&rpc_client, &accounts).await?;
+            if empty_token_accounts {
+                let empty = close::empty_token_accounts(&rpc_client, &wallet).await?;
+                closing.extend(empty.into_iter().filter(|(key, _)| !accounts.contains(key)));
+            }
+            let groups = closing
+                .iter()
+                .map(|(key, account)| close::close_instructions(key, account, &wallet, &to, burn))
+                .collect::<Result<_, _>>()?;
+            let batches = close::batch(groups, &wallet);
+            let closed: Vec<_> = closing.iter().map(|(key, _)| key.to_string()).collect();
+            output.line(
+                format!("Closing {} accounts in {} transactions", closing.len(), batches.len()),
+                "closing",
+                closed,
+            );
+            output.list("unsigned_transactions");
+            for instructions in batches {
+                let tx = unsigned_tx(&rpc_client, &instructions, &wallet).await?;
+                output.item(unsigned_tx_line(&tx), "unsigned_transactions", tx);
+            }
+        }
+        Options::SeedAddress { wallet, seed, owner } => {
+            let address = seed::address(&wallet, &seed, &owner)?;
+            output.line(format!("Address: {}", address), "address", address.to_string());
+        }
+        Options::SeedCreate { wallet, seed, amount, space, owner, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let lamports = native_token::sol_to_lamports(amount);
+            let (account, instruction) = seed::create_instruction(&wallet, &seed, lamports, space, &owner)?;
+            output.line(format!("Address: {}", account), "address", account.to_string());
+            print_unsigned_tx(output, &rpc_client, &[instruction], &wallet).await?;
+        }
+        Options::SeedTransfer { wallet, seed, amount, to, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let lamports = native_token::sol_to_lamports(amount);
+            let instruction = seed::transfer_instruction(&wallet, &seed, lamports, &to)?;
+            print_unsigned_tx(output, &rpc_client, &[instruction], &wallet).await?;
+        }
+        Options::NftTransfer { mint, wallet, to, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let nft = Nft::fetch(&rpc_client, mint).await?;
+            print_unsigned_tx(output, &rpc_client, &nft.transfer_instructions(&wallet, &to), &wallet).await?;
+        }
+        Options::GovernancePropose {
+            realm,
+            governance: governance_account,
+            governing_token_mint,
+            wallet,
+            name,
+            description,
+            program_id,
+            net,
+        } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let seed = Keypair::generate(&mut rand07::thread_rng()).pubkey();
+            let (proposal, instructions) = governance::propose_instructions(
+                &program_id,
+                &realm,
+                &governance_account,
+                &governing_token_mint,
+                &wallet,
+                name,
+                description,
+                seed,
+            );
+            output.line(format!("Proposal: {}", proposal), "proposal", proposal.to_string());
+            print_unsigned_tx(output, &rpc_client, &instructions, &wallet).await?;
+        }
+        Options::GovernanceVote { proposal, wallet, vote, program_id, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let instruction = governance::vote_instruction(&rpc_client, &program_id, &proposal, &wallet, vote).await?;
+            print_unsigned_tx(output, &rpc_client, &[instruction], &wallet).await?;
+        }
+        Options::GovernanceExecute { proposal_transaction, wallet, program_id, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let instruction = governance::execute_instruction(&rpc_client, &program_id, &proposal_transaction).await?;
+            print_unsigned_tx(output, &rpc_client, &[instruction], &wallet).await?;
+        }
+        Options::LookupTableCreate { wallet, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let (table, instruction) = lookup_table::create_instruction(&rpc_client, &wallet).await?;
+            output.line(format!("Lookup table: {}", table), "lookup_table", table.to_string());
+            print_unsigned_tx(output, &rpc_client, &[instruction], &wallet).await?;
+        }
+        Options::LookupTableExtend { lookup_table, addresses, net } => {
+            let rpc_client = net.connect(&rpc_options).await?;
+            let authority = lookup_table::authority(&rpc_client, &lookup_table).await?;
+            let instruction = lookup_table::extend_instruction(&lookup_table, &authority, addresses);
+            print_unsigned_tx(output, &rpc_client, &[instruction], &authority).await?;
+        }
+        Options::Simulate { unsigned_tx, net, format } => {
+            let format = output.format(format);
+            let rpc_client = net.connect(&rpc_options).await?;
+            let tx = VersionedTransaction {
+                signatures: vec![Signature::default(); unsigned_tx.header().num_required_signatures as usize],
+                message: unsigned_tx,
+            };
+            send::simulate(&rpc_client, &tx, false).await?.print(format);
+        }
+        Options::Fee {
+            amount,
+            token_mint,
+            token_decimals,
+            token_2022,
+            token_fee,
+            fund_recipient,
+            to,
+            memo,
+            nonce_account,
+            lookup_table,
+            unsigned_tx,
+            keys,
+            group_key,
+            net,
+            format,
+        } => {
+            let format = output.format(format);
+            let rpc_client = net.connect(&rpc_options).await?;
+            let lookup_tables = fetch_lookup_tables(&rpc_client, &lookup_table).await?;
+            // The fee is only quoted for a known block hash, but doesn't depend on which one.
+            let recent_hash = rpc_client.get_latest_blockhash().await.map_err(Error::RecentHashFailed)?;
+            // Only a transfer is built out of the amount and recipient, `--unsigned-tx` conflicts with them.
+            let token = match amount.zip(to) {
+                Some((amount, to)) => {
+                    lookup_token(&rpc_client, token_mint, token_decimals, token_2022, token_fee, amount, &to, &memo)
+                        .await?
+                }
+                None => None,
+            }
+            .map(|token| Token { fund_recipient, ..token });
+            let message = signing_message(unsigned_tx, amount, to, Some(recent_hash), |amount, to| {
+                // clap requires the keys or the group key without an unsigned transaction.
+                let wallet = match group_key {
+                    Some(group_key) => group_key,
+                    None => tss::aggregate_pubkey(keys)?,
+                };
+                compile_message(
+                    &transfer_instructions(amount, token, &to, memo, nonce_account, &wallet),
+                    &wallet,
+                    &lookup_tables,
+                )
+            })?;
+            let fee = match &message {
+                VersionedMessage::Legacy(message) => rpc_client.get_fee_for_message(message).await,
+                VersionedMessage::V0(message) => rpc_client.get_fee_for_message(message).await,
+            }
+            .map_err(Error::FeeFailed)?;
+            match format {
+                Format::Text => println!("Fee: {} lamports ({} SOL)", fee, native_token::lamports_to_sol(fee)),
+                Format::Json => println!("{}", json!({ "fee": fee })),
+            }
+        }
+        Options::Broadcast { signed_tx, key, net, skip_preflight, preflight_commitment, simulate_only, format } => {
+            let format = output.format(format);
+            if let Some(key) = key {
+                send::verify_signer(&signed_tx, &key)?;
+            }
+            let rpc_client = net.connect(&rpc_options).await?;
+            if simulate_only {
+                send::simulate(&rpc_client, &signed_tx, true).await?.print(format);
+            } else {
+                broadcast(
+                    &signed_tx,
+                    &rpc_client,
+                    Preflight { skip: skip_preflight, commitment: preflight_commitment },
+                    format,
+                )
+                .await?;
+            }
+        }
+        Options::History { address, limit, before, net, format } => {
+            let format = output.format(format);
+            let rpc_client = net.connect(&rpc_options).await?;
+            let entries = history::fetch(&rpc_client, &address, limit, before).await?;
+            history::print(&entries, format);
+            // A full page means there may be older transactions.
+            if let (Format::Text, true, Some(last)) = (format, entries.len() == limit, entries.last()) {
+                println!("Older transactions: `--before {}`", last.signature);
+            }
+        }
+        Options::Watch { address, net } => {
+            if rpc_options.proxy.is_some() {
+                return Err(Error::WatchProxy);
+            }
+            let rpc_client = net.connect(&rpc_options).await?;
+            let balance = rpc_client.get_balance(&address).await.map_err(Error::BalaceFailed)?;
+            watch::watch(&net.get_websocket_url(&rpc_options)?, &address, rpc_options.commitment, balance).await?;
+        }
+        Options::Confirm { signature, net, format } => {
+            let format = output.format(format);
+            let rpc_client = net.connect(&rpc_options).await?;
+            let lookup =
+                history::lookup(&rpc_client, &signature).await?.ok_or(Error::TransactionNotFound(signature))?;
+            history::print_lookup(&lookup, format);
+        }
+    }
+    Ok(())
+}
+
+/// Log to the standard error, more with each `-v`, unless RUST_LOG says what to log
+fn init_logging(verbose: u8, format: Format) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        EnvFilter::new(match verbose {
+            0 => "info",
+            1 => "debug",
+            _ => "trace",
+        })
+    });
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter).with_writer(std::io::stderr).without_time();
+    match format {
+        Format::Text => subscriber.with_target(false).init(),
+        Format::Json => subscriber.json().init(),
+    }
+}
+
+fn print_partial_signature(sig: &PartialSignature, format: Format, encoding: Encoding) {
+    match format {
+        Format::Text => println!("Partial signature: {}", sig.serialize_encoded(encoding).as_str()),
+        Format::Json => println!("{}", json!({ "partial_signature": sig.serialize_json(encoding) })),
+    }
+}
+
+fn print_sealed_partial_signature(sealed: &str, format: Format) {
+    match format {
+        Format::Text => println!("Partial signature: {}", sealed),
+        Format::Json => println!("{}", json!({ "partial_signature": sealed })),
+    }
+}
+
+/// Print a transaction of `instructions` paid for by `payer`, for all the parties to sign with `--unsigned-tx`
+async fn print_unsigned_tx(
+    output: &mut Output,
+    rpc_client: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Pubkey,
+) -> Result<(), Error> {
+    let tx = unsigned_tx(rpc_client, instructions, payer).await?;
+    output.line(unsigned_tx_line(&tx), "unsigned_transaction", tx);
+    Ok(())
+}
+
+/// The Base64 of the transaction of `instructions` paid by `payer`, with a recent block hash
+async fn unsigned_tx(rpc_client: &RpcClient, instructions: &[Instruction], payer: &Pubkey) -> Result<String, Error> {
+    let recent_hash = rpc_client.get_latest_blockhash().await.map_err(Error::RecentHashFailed)?;
+    let message = Message::new_with_blockhash(instructions, Some(payer), &recent_hash);
+    let tx = bincode::serialize(&Transaction::new_unsigned(message)).expect("serializing to memory can't fail");
+    Ok(base64::encode(tx))
+}
+
+fn unsigned_tx_line(tx: &str) -> String {
+    format!("Unsigned transaction: {} (pass it to `--unsigned-tx` of all parties' step two)", tx)
+}
+
+/// Sign with `keypair` and send right away if given, it must be the `authority` which also pays the fee.
+/// Otherwise the authority is the wallet, so print the transaction for all the parties to sign.
+async fn send_as_authority(
+    output: &mut Output,
+    rpc_client: &RpcClient,
+    instructions: &[Instruction],
+    authority: &Pubkey,
+    keypair: Option<Keypair>,
+) -> Result<(), Error> {
+    let keypair = match keypair {
+        Some(keypair) => keypair,
+        None => return print_unsigned_tx(output, rpc_client, instructions, authority).await,
+    };
+    if keypair.pubkey() != *authority {
+        return Err(Error::WrongAuthority { expected: *authority, found: keypair.pubkey() });
+    }
+    let recent_hash = rpc_client.get_latest_blockhash().await.map_err(Error::RecentHashFailed)?;
+    let tx: VersionedTransaction =
+        Transaction::new_signed_with_payer(instructions, Some(authority), &[&keypair], recent_hash).into();
+    let sig = send::send_transaction(rpc_client, &tx, Preflight::default()).await?;
+    output.line(format!("Transaction ID: {}", sig), "transaction_id", sig.to_string());
+    send::confirm(rpc_client, &sig, &recent_hash, Some(&tx)).await?;
+    Ok(())
+}
+
+/// The nonce stored in `nonce_account`, if signing with one
+async fn nonce_value(rpc_client: &RpcClient, nonce_account: Option<Pubkey>) -> Result<Option<Hash>, Error> {
+    let nonce_account = match nonce_account {
+        Some(nonce_account) => nonce_account,
+        None => return Ok(None),
+    };
+    Ok(Some(nonce::fetch(rpc_client, &nonce_account).await?.blockhash()))
+}
+
+/// All the SOL `wallet` can send, which is its balance minus the fee. Accounts holding data keep their rent exemption.
+async fn sweep_amount(rpc_client: &RpcClient, wallet: &Pubkey) -> Result<f64, Error> {
+    let account = rpc_client.get_account(wallet).await.map_err(Error::BalaceFailed)?;
+    let reserve = match account.data.len() {
+        0 => 0,
+        len => rpc_client.get_minimum_balance_for_rent_exemption(len).await.map_err(Error::RentFailed)?,
+    };
+    // The fee only depends on the number of signatures, which is always one.
+    let recent_hash = rpc_client.get_latest_blockhash().await.map_err(Error::RecentHashFailed)?;
+    let message =
+        Message::new_with_blockhash(&[system_instruction::transfer(wallet, wallet, 0)], Some(wallet), &recent_hash);
+    let fee = rpc_client.get_fee_for_message(&message).await.map_err(Error::FeeFailed)?;
+    let needed = fee + reserve;
+    let lamports = account.lamports.checked_sub(needed).filter(|&lamports| lamports > 0);
+    let lamports = lamports.ok_or(Error::InsufficientFunds { balance: account.lamports, needed })?;
+    let amount = lamports_to_exact_sol(lamports);
+    info!("Sending all: {} SOL", amount);
+    Ok(amount)
+}
+
+/// The amount of SOL that converts back to exactly `lamports`, as a plain division can round a lamport away
+fn lamports_to_exact_sol(lamports: u64) -> f64 {
+    let mut sol = native_token::lamports_to_sol(lamports);
+    while native_token::sol_to_lamports(sol) < lamports {
+        // The next float up, they're all positive.
+        sol = f64::from_bits(sol.to_bits() + 1);
+    }
+    sol
+}
+
+/// The RPC client of a step that signs without sending, checked to be on the right cluster when `needed` to read the
+/// chain. Otherwise the node isn't contacted at all, so the step can run air-gapped.
+async fn connect_to_read(net: &Network, rpc_options: &RpcOptions, needed: bool) -> Result<RpcClient, Error> {
+    if needed {
+        net.connect(rpc_options).await
+    } else {
+        net.client(rpc_options)
+    }
+}
+
+/// The addresses of the lookup tables to build a v0 transaction with, if any
+async fn fetch_lookup_tables(
+    rpc_client: &RpcClient,
+    tables: &[Pubkey],
+) -> Result<Vec<AddressLookupTableAccount>, Error> {
+    if tables.is_empty() {
+        return Ok(Vec::new());
+    }
+    lookup_table::fetch(rpc_client, tables).await
+}
+
+/// The path `generate --mnemonic` and `recover` derive the keys at unless given another one
+fn default_derivation_path() -> DerivationPath {
+    DerivationPath::from_absolute_path_str(mnemonic::DEFAULT_DERIVATION_PATH).expect("the default path is valid")
+}
+
+/// Store `secret` in the keychain when given an entry, before anything is printed. Returns what to print in its place,
+/// as text and as JSON: the entry, which the next step accepts instead of the secret, or otherwise the secret itself.
+/// With `protect` the secret is encrypted with a passphrase prompted for first, wherever it ends up.
+fn keep_secret<T: Serialize>(
+    secret: &T,
+    entry: Option<KeychainEntry>,
+    protect: bool,
+    encoding: Encoding,
+) -> Result<(String, serde_json::Value), Error> {
+    let protected = if protect { Some(keystore::protect(secret, &cli::prompt_new_passphrase()?)) } else { None };
+    match (entry, protected) {
+        (Some(entry), protected) => {
+            let encoded = secret.serialize_encoded(encoding);
+            entry.store(protected.as_deref().unwrap_or(&encoded))?;
+            Ok((entry.to_string(), json!(entry.to_string())))
+        }
+        (None, Some(protected)) => Ok((protected.clone(), json!(protected))),
+        (None, None) => Ok((secret.serialize_encoded(encoding).to_string(), secret.serialize_json(encoding))),
+    }
+}
+
+fn print_public_share(output: &mut Output, keypair: &Keypair) {
+    output.line(format!("public share: {}", keypair.pubkey()), "public_share", keypair.pubkey().to_string());
+}
+
+/// A kept session as listed, without its secret state
+fn session_json(session: &Session) -> serde_json::Value {
+    json!({
+        "name": session.name,
+        "session_id": session.session_id,
+        "party": session.party,
+        "created": session.created,
+        "first_message": session.first_message,
+    })
+}
+
+fn print_second_message(output: &mut Output, msg: &DkgMessage2, encoding: Encoding) {
+    output.item(
+        format!(
+            "Message 2 for party {}: {} (send privately to party {})",
+            msg.receiver,
+            msg.serialize_encoded(encoding).as_str(),
+            msg.receiver
+        ),
+        "messages_2",
+        json!({ "receiver": msg.receiver, "message_2": msg.serialize_json(encoding) }),
+    );
+}
+
+/// Print the secret key, or write it to `outfile` as a JSON keypair file when given one
+fn print_secret_share(output: &mut Output, keypair: &Keypair, outfile: Option<PathBuf>) -> Result<(), Error> {
+    match outfile {
+        Some(outfile) => {
+            // Never overwrite, the file may hold the only copy of another key.
+            if outfile.exists() {
+                return Err(Error::OutfileExists(outfile));
+            }
+            write_keypair_file(keypair, &outfile)
+                .map_err(|e| Error::KeypairFileFailed { path: outfile.display().to_string(), error: e.to_string() })?;
+            output.line(format!("secret share: written to {}", outfile.display()), "secret_share_file", outfile);
+        }
+        None => {
+            let secret = keypair.to_base58_string();
+            output.line(format!("secret share: {}", secret), "secret_share", secret);
+        }
+    }
+    Ok(())
+}
+
+/// Join the peer-to-peer network of a signing session, or fall back to pasting the messages if it can't be joined
+async fn join_p2p(session_id: SessionId, peers: &[Multiaddr]) -> Option<P2p> {
+    match P2p::join(session_id, peers).await {
+        Ok(node) => Some(node),
+        Err(e) => {
+            warn!("{}, the messages of the other parties will be asked for instead", e);
+            None
+        }
+    }
+}
+
+/// Publish `own` message of `round` and wait for the messages of `senders` on the peer-to-peer network.
+/// Those that don't arrive are asked for, to be pasted like without `--p2p`.
+async fn exchange<T: Relayed>(
+    node: Option<&mut P2p>,
+    session_id: SessionId,
+    round: Round,
+    own: Option<String>,
+    senders: &[Pubkey],
+    keypair: Option<&Keypair>,
+) -> Result<Vec<T>, Error> {
+    let mut received = Vec::new();
+    if let Some(node) = node {
+        if let Some(own) = own {
+            node.send(round, &own).await?;
+        }
+        info!("Waiting for the other parties on the peer-to-peer network...");
+        received = node.recv(round, senders, keypair).await?;
+    }
+    transport::recv(&mut Manual::new(session_id).with(round, received), round, senders, keypair).await
+}
+
+/// A copy of `keypair`, for a step that also needs it after giving it away
+fn copy_keypair(keypair: &Keypair) -> Keypair {
+    Keypair::from_bytes(&keypair.to_bytes()).expect("the keypair is valid")
+}
+
+/// Prompt for a line of the standard input on the standard error, without the line break
+fn read_line(prompt: &str) -> Result<String, Error> {
+    eprint!("{}", prompt);
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line).map_err(Error::StdinFailed)?;
+    Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+/// Print `tx` base64 encoded, the signatures of the signers it's still missing are zeroes
+fn print_signed_tx(tx: &VersionedTransaction, format: Format) {
+    let encoded = base64::encode(bincode::serialize(tx).expect("serializing to memory can't fail"));
+    let absent = offline::absent_signers(tx);
+    match format {
+        Format::Text if absent.is_empty() => {
+            println!("Transaction ID: {}", tx.signatures[0]);
+            println!("Signed transaction: {}", encoded);
+        }
+        Format::Text => {
+            let absent: Vec<_> = absent.iter().map(ToString::to_string).collect();
+            println!("Partially signed transaction: {} (missing the signatures of {})", encoded, absent.join(", "));
+        }
+        Format::Json => {
+            // The transaction ID is the fee payer's signature, unknown until they sign.
+            let id = Some(tx.signatures[0]).filter(|sig| *sig != Signature::default()).map(|sig| sig.to_string());
+            let absent: Vec<_> = absent.iter().map(ToString::to_string).collect();
+            let json = json!({ "signed_transaction": encoded, "transaction_id": id, "absent_signers": absent });
+            println!("{}", json)
+        }
+    }
+}
+
+async fn broadcast(
+    tx: &VersionedTransaction,
+    rpc_client: &RpcClient,
+    preflight: Preflight,
+    format: Format,
+) -> Result<(), Error> {
+    let sig = match send::send_transaction(rpc_client, tx, preflight).await {
+        // Sent already, e.g. by another party that aggregated the same signatures.
+        Err(Error::PreflightFailed { error: TransactionError::AlreadyProcessed, .. }) => tx.signatures[0],
+        result => result?,
+    };
+    match format {
+        Format::Text => println!("Transaction ID: {}", sig),
+        Format::Json => println!("{}", json!({ "transaction_id": sig.to_string() })),
+    }
+    // A durable nonce is never a valid block hash, so wait on the latest one instead.
+    let recent_block_hash = if tx.uses_durable_nonce() {
+        rpc_client.get_latest_blockhash().await.map_err(Error::RecentHashFailed)?
+    } else {
+        *tx.message.recent_blockhash()
+    };
+    send::confirm(rpc_client, &sig, &recent_block_hash, Some(tx)).await?;
+    Ok(())
+}
+
+/// The message to sign: either the `--unsigned-tx` after showing what it does, or the transfer `build_transfer` makes
+/// out of the amount and recipient. clap requires those, and the recent block hash, when there's no unsigned transaction.
+fn signing_message(
+    unsigned_tx: Option<VersionedMessage>,
+    amount: Option<f64>,
+    to: Option<Pubkey>,
+    recent_block_hash: Option<Hash>,
+    build_transfer: impl FnOnce(f64, Pubkey) -> Result<VersionedMessage, Error>,
+) -> Result<VersionedMessage, Error> {
+    let from_unsigned_tx = unsigned_tx.is_some();
+    let mut message = match unsigned_tx {
+        Some(message) => message,
+        None => build_transfer(amount.expect("required by clap"), to.expect("required by clap"))?,
+    };
+    if let Some(recent_block_hash) = recent_block_hash {
+        message.set_recent_blockhash(recent_block_hash);
+    }
+    if from_unsigned_tx {
+        print_instructions(&message);
+    }
+    Ok(message)
+}
+
+/// Decode the instructions of a message that wasn't built here to stderr, so the parties can see what they're signing
+fn print_instructions(message: &VersionedMessage) {
+    for line in describe_instructions(message) {
+        eprintln!("{}", line);
+    }
+}
+
+/// The fee payer, block hash and decoded instructions of a message, a line each
+fn describe_instructions(message: &VersionedMessage) -> Vec<String> {
+    let mut lines = Vec::new();
+    let static_keys = message.static_account_keys();
+    // The accounts after the static ones are loaded from the lookup tables, first the writable ones then the readonly ones.
+    let lookups = message.address_table_lookups().unwrap_or_default();
+    let loaded: Vec<_> = lookups
+        .iter()
+        .flat_map(|lookup| lookup.writable_indexes.iter().map(move |&i| (lookup.account_key, i)))
+        .chain(lookups.iter().flat_map(|lookup| lookup.readonly_indexes.iter().map(move |&i| (lookup.account_key, i))))
+        .collect();
+    let account_name = |index: usize| match static_keys.get(index) {
+        Some(key) => key.to_string(),
+        None => match loaded.get(index - static_keys.len()) {
+            Some((table, i)) => format!("#{} of lookup table {}", i, table),
+            None => format!("#{} (missing)", index),
+        },
+    };
+    lines.push(format!("Fee payer: {}", static_keys[0]));
+    lines.push(format!("Recent block hash: {}", message.recent_blockhash()));
+    for (i, ins) in message.instructions().iter().enumerate() {
+        // Program IDs can't come from a lookup table.
+        let program_id = static_keys[usize::from(ins.program_id_index)];
+        lines.push(format!("Instruction {}: program {}", i, program_id));
+        for &account in &ins.accounts {
+            let account = usize::from(account);
+            let signer = if message.is_signer(account) { " (signer)" } else { "" };
+            let writable = if message.is_maybe_writable(account) { " (writable)" } else { "" };
+            lines.push(format!("  account {}{}{}", account_name(account), signer, writable));
+        }
+        if program_id == system_program::id() {
+            match limited_deserialize::<SystemInstruction>(&ins.data) {
+                Ok(SystemInstruction::Transfer { lamports }) => {
+                    lines.push(format!("  transfer {} SOL", native_token::lamports_to_sol(lamports)))
+                }
+                Ok(system_ins) => lines.push(format!("  {:?}", system_ins)),
+                Err(_) => lines.push(format!("  data {}", hex::encode(&ins.data))),
+            }
+        } else if program_id == solana_sdk::stake::program::id() {
+            match limited_deserialize::<StakeInstruction>(&ins.data) {
+                Ok(stake_ins) => lines.push(format!("  {:?}", stake_ins)),
+                Err(_) => lines.push(format!("  data {}", hex::encode(&ins.data))),
+            }
+        } else if program_id == solana_sdk::vote::program::id() {
+            match limited_deserialize::<VoteInstruction>(&ins.data) {
+                Ok(vote_ins) => lines.push(format!("  {:?}", vote_ins)),
+                Err(_) => lines.push(format!("  data {}", hex::encode(&ins.data))),
+            }
+        } else if program_id == bpf_loader_upgradeable::id() {
+            match limited_deserialize::<UpgradeableLoaderInstruction>(&ins.data) {
+                Ok(loader_ins) => lines.push(format!("  {:?}", loader_ins)),
+                Err(_) => lines.push(format!("  data {}", hex::encode(&ins.data))),
+            }
+        } else if program_id == spl_memo::id() || program_id == spl_memo::v1::id() {
+            lines.push(format!("  memo {:?}", String::from_utf8_lossy(&ins.data)));
+        } else if program_id == spl_token::id() || program_id == spl_token_2022::id() {
+            match spl_token_2022::instruction::TokenInstruction::unpack(&ins.data) {
+                Ok(token_ins) => lines.push(format!("  {:?}", token_ins)),
+                Err(_) => lines.push(format!("  data {}", hex::encode(&ins.data))),
+            }
+        } else {
+            lines.push(format!("  data {}", hex::encode(&ins.data)));
+        }
+    }
+    lines
+}
+
+/// The token to send if `mint` is given. Unless its `decimals` are given the rest is looked up on `net` too,
+/// and the recipient's token account is checked for a memo requirement.
+#[allow(clippy::too_many_arguments)]
+async fn lookup_token(
+    rpc_client: &RpcClient,
+    mint: Option<Pubkey>,
+    decimals: Option<u8>,
+    token_2022: bool,
+    fee: Option<f64>,
+    amount: f64,
+    to: &Pubkey,
+    memo: &Option<String>,
+) -> Result<Option<Token>, Error> {
+    let mint = match mint {
+        Some(mint) => mint,
+        None => return Ok(None),
+    };
+    if let Some(decimals) = decimals {
+        return Ok(Some(Token::new(mint, decimals, token_2022, fee)));
+    }
+    let token = Token::fetch(rpc_client, mint, amount).await?;
+    token.check_recipient(rpc_client, to, memo.is_some()).await?;
+    Ok(Some(token))
+}
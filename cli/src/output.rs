@@ -0,0 +1,114 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use solana_tss::serialization::Format;
+use solana_tss::Error;
+
+/// The results of a command on the standard output: lines of text as they come, or with `--output json` a single JSON
+/// object of them all once the command is done, for scripts to read instead of the text
+pub struct Output {
+    format: Format,
+    object: Map<String, Value>,
+}
+
+impl Output {
+    pub fn new(format: Format) -> Self {
+        Self { format, object: Map::new() }
+    }
+
+    /// The format of a command's own `--format`, which `--output json` makes JSON
+    pub fn format(&self, format: Format) -> Format {
+        match self.format {
+            Format::Json => Format::Json,
+            Format::Text => format,
+        }
+    }
+
+    /// A line of the result, which is `value` under `key` in JSON
+    pub fn line(&mut self, text: impl Display, key: &str, value: impl Serialize) {
+        match self.format {
+            Format::Text => println!("{}", text),
+            Format::Json => {
+                self.object.insert(key.to_string(), json!(value));
+            }
+        }
+    }
+
+    /// A line only printed as text, which JSON has no use for
+    pub fn text(&mut self, text: impl Display) {
+        if let Format::Text = self.format {
+            println!("{}", text);
+        }
+    }
+
+    /// An empty list under `key` in JSON for the items to go in, so it's there even without any
+    pub fn list(&mut self, key: &str) {
+        if let Format::Json = self.format {
+            self.object.entry(key).or_insert_with(|| Value::Array(Vec::new()));
+        }
+    }
+
+    /// A line of a list, which is `value` in the array under `key` in JSON, however many there are
+    pub fn item(&mut self, text: impl Display, key: &str, value: impl Serialize) {
+        match self.format {
+            Format::Text => println!("{}", text),
+            Format::Json => {
+                let items = self.object.entry(key).or_insert_with(|| Value::Array(Vec::new()));
+                if let Value::Array(items) = items {
+                    items.push(json!(value));
+                }
+            }
+        }
+    }
+
+    /// Print the JSON object of the lines, if the command had any
+    pub fn finish(self) {
+        if let (Format::Json, false) = (self.format, self.object.is_empty()) {
+            println!("{}", Value::Object(self.object));
+        }
+    }
+
+    /// Print the failure on the standard error, in JSON with its code and context with `--output json`, and exit with
+    /// the code of its category
+    pub fn fail(&self, e: &Error) -> ! {
+        match self.format {
+            Format::Text => eprintln!("Error {}: {}", e.code(), e),
+            Format::Json => {
+                let context: Map<_, _> =
+                    e.context().into_iter().map(|(key, value)| (key.to_string(), Value::String(value))).collect();
+                let error = json!({
+                    "error": e.to_string(),
+                    "code": e.code(),
+                    "category": e.category().name(),
+                    "context": context,
+                });
+                eprintln!("{}", error);
+            }
+        }
+        std::process::exit(e.category().exit_code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use solana_tss::serialization::Format;
+
+    use crate::output::Output;
+
+    #[test]
+    fn test_output_json() {
+        let mut output = Output::new(Format::Json);
+        assert!(matches!(output.format(Format::Text), Format::Json));
+        output.line("public share: a", "public_share", "a");
+        output.item("Share 1: b", "shares", json!({ "index": 1, "share": "b" }));
+        output.item("Share 2: c", "shares", json!({ "index": 2, "share": "c" }));
+        assert_eq!(
+            serde_json::Value::Object(output.object),
+            json!({ "public_share": "a", "shares": [{ "index": 1, "share": "b" }, { "index": 2, "share": "c" }] })
+        );
+        assert!(matches!(Output::new(Format::Text).format(Format::Json), Format::Json));
+    }
+}
@@ -0,0 +1,552 @@
+use std::io::{self, Stdout};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
+use ratatui::{Frame, Terminal};
+use serde_json::{json, Value};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::transaction::VersionedTransaction;
+
+use solana_tss::audit::{self, AuditLog};
+use solana_tss::journal::NonceJournal;
+use solana_tss::network::Network;
+use solana_tss::policy::{Policy, Spent};
+use solana_tss::send::{self, Preflight};
+use solana_tss::serialization::{AggMessage1, Encoding, PartialSignature, Serialize, SessionId};
+use solana_tss::signing::{Round1, Round3, Session};
+use solana_tss::transaction::parse_unsigned_tx;
+use solana_tss::transport::{self, Round, Transport};
+use solana_tss::{tss, Error};
+
+/// How often the transport is asked for the messages of the other parties
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Where a ceremony is at
+enum Stage {
+    /// The transaction to sign is awaited, to be pasted
+    Transaction,
+    /// The transaction is shown, for the party to approve signing it
+    Review(VersionedMessage),
+    /// Message 1 is out, those of the other parties are awaited
+    FirstMessages(Session<Round1>),
+    /// The partial signature is out, those of the other parties are awaited
+    PartialSignatures(Session<Round3>),
+    /// The transaction is signed by all the parties, for the party to approve sending it
+    Broadcast(VersionedTransaction),
+    /// The transaction was sent and confirmed
+    Sent(Signature),
+}
+
+/// What the party is asked for at a stage
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prompt {
+    /// A message or transaction, pasted and entered
+    Paste,
+    /// y to sign the transaction, n to decline
+    Approve,
+    /// y to send the signed transaction, n to keep it
+    Send,
+    /// Nothing left to do
+    Quit,
+}
+
+/// How the ceremony ended for the party
+pub enum Outcome {
+    /// Left before the transaction was signed by all the parties
+    Unfinished,
+    /// Signed by all the parties but not sent
+    Signed(VersionedTransaction),
+    Sent(Signature),
+}
+
+/// The steps of signing a transaction as one of the parties of an aggregated key, on the same messages as
+/// `agg-send-step-one`, `agg-send-step-two` and `aggregate-signatures-and-broadcast`. The messages of the other parties
+/// are pasted or received over a transport, and the party's own are put in an outbox to send.
+pub struct Wizard {
+    keypair: Keypair,
+    keys: Vec<Pubkey>,
+    others: Vec<Pubkey>,
+    aggpubkey: Pubkey,
+    session_id: SessionId,
+    net: Network,
+    policy: Policy,
+    audit: AuditLog,
+    encoding: Encoding,
+    stage: Stage,
+    /// The lamports the transaction transfers, counted toward the daily limit of the policy once signed
+    spent: Spent,
+    /// The party's own message of the current round, as sent
+    own_message: String,
+    /// The messages of the current round received so far, as they were sent
+    posted: Vec<String>,
+    outbox: Vec<(Round, String)>,
+    received: Vec<Value>,
+}
+
+impl Wizard {
+    /// Sign as `keypair`, one of `keys`, in the session `session_id`, the transaction `message` if it's known already
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        keypair: Keypair,
+        keys: Vec<Pubkey>,
+        session_id: SessionId,
+        message: Option<VersionedMessage>,
+        net: Network,
+        policy: Policy,
+        audit: AuditLog,
+        encoding: Encoding,
+    ) -> Result<Self, Error> {
+        if !keys.contains(&keypair.pubkey()) {
+            return Err(Error::KeyPairIsNotInKeys);
+        }
+        let others = keys.iter().filter(|key| **key != keypair.pubkey()).copied().collect();
+        let aggpubkey = tss::aggregate_pubkey(keys.clone())?;
+        let mut wizard = Self {
+            keypair,
+            keys,
+            others,
+            aggpubkey,
+            session_id,
+            net,
+            policy,
+            audit,
+            encoding,
+            stage: Stage::Transaction,
+            spent: Spent::default(),
+            own_message: String::new(),
+            posted: Vec::new(),
+            outbox: Vec::new(),
+            received: Vec::new(),
+        };
+        if let Some(message) = message {
+            wizard.review(message)?;
+        }
+        Ok(wizard)
+    }
+
+    /// Show `message` for approval, unless the signing policy refuses it
+    fn review(&mut self, message: VersionedMessage) -> Result<(), Error> {
+        self.spent = self.policy.check(&message, &self.aggpubkey, None, &self.net)?;
+        self.stage = Stage::Review(message);
+        Ok(())
+    }
+
+    pub fn prompt(&self) -> Prompt {
+        match self.stage {
+            Stage::Transaction | Stage::FirstMessages(_) | Stage::PartialSignatures(_) => Prompt::Paste,
+            Stage::Review(_) => Prompt::Approve,
+            Stage::Broadcast(_) => Prompt::Send,
+            Stage::Sent(_) => Prompt::Quit,
+        }
+    }
+
+    /// The round whose messages are awaited
+    pub fn round(&self) -> Option<Round> {
+        match self.stage {
+            Stage::FirstMessages(_) => Some(Round::FirstMessages),
+            Stage::PartialSignatures(_) => Some(Round::PartialSignatures),
+            _ => None,
+        }
+    }
+
+    /// Take what was pasted: the transaction, or a message of another party of this session for the current round
+    pub fn paste(&mut self, text: &str) -> Result<(), Error> {
+        let text = text.trim();
+        let round = match self.round() {
+            Some(round) => round,
+            None if matches!(self.stage, Stage::Transaction) => return self.review(parse_unsigned_tx(text)?),
+            None => return Ok(()),
+        };
+        let (sender, session_id) = transport::origin(text, round, Some(&self.keypair))?;
+        if session_id != self.session_id {
+            return Err(Error::SessionMismatch);
+        }
+        if !self.others.contains(&sender) {
+            return Err(Error::NotAnotherParty(sender));
+        }
+        self.receive(vec![text.to_string()]);
+        Ok(())
+    }
+
+    /// Take the messages `posted` to the transport, the ones that aren't of the current round are left out later
+    pub fn receive(&mut self, posted: Vec<String>) {
+        for message in posted {
+            if !self.posted.contains(&message) {
+                self.posted.push(message);
+            }
+        }
+    }
+
+    /// The other parties whose message of the current round is still missing
+    pub fn missing(&self) -> Result<Vec<Pubkey>, Error> {
+        match self.round() {
+            Some(round) => transport::missing(&self.posted, self.session_id, round, &self.others, Some(&self.keypair)),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Approve signing the transaction under review: Message 1 goes out
+    pub fn approve(&mut self) -> Result<(), Error> {
+        if let Stage::Review(message) = &self.stage {
+            let keypair = Keypair::from_bytes(&self.keypair.to_bytes()).expect("the keypair is valid");
+            let session = Session::new(keypair, self.keys.clone(), message.clone(), self.session_id)?;
+            self.send(Round::FirstMessages, session.first_message().serialize_encoded(self.encoding).to_string());
+            self.stage = Stage::FirstMessages(session);
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, round: Round, message: String) {
+        self.own_message = message.clone();
+        self.outbox.push((round, message));
+        self.posted.clear();
+    }
+
+    /// The messages of the party to send to the others, since the last time
+    pub fn take_outbox(&mut self) -> Vec<(Round, String)> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// Move on once the messages of all the other parties are in: sign after the first messages, combine the partial
+    /// signatures after those. A failure here leaves the session unusable, so the ceremony can only be started over.
+    pub fn advance(&mut self) -> Result<(), Error> {
+        if self.round().is_none() || !self.missing()?.is_empty() {
+            return Ok(());
+        }
+        let posted = std::mem::take(&mut self.posted);
+        // The session is taken out to move it to its next round, a failure ends the ceremony anyway.
+        match std::mem::replace(&mut self.stage, Stage::Transaction) {
+            Stage::FirstMessages(session) => {
+                let first_messages: Vec<AggMessage1> =
+                    transport::accept(posted, self.session_id, &self.others, Some(&self.keypair))?;
+                self.received = first_messages.iter().map(|msg| audit::message(msg.sender, msg)).collect();
+                let session = session.receive(first_messages)?;
+                NonceJournal::open(&self.keypair.pubkey().to_string())?
+                    .consume(&session.first_message().public_nonces, false)?;
+                let session = session.sign()?;
+                self.policy.record(&self.aggpubkey, &self.spent)?;
+                self.send(
+                    Round::PartialSignatures,
+                    session.partial_signature().serialize_encoded(self.encoding).to_string(),
+                );
+                self.stage = Stage::PartialSignatures(session);
+            }
+            Stage::PartialSignatures(session) => {
+                let signatures: Vec<PartialSignature> =
+                    transport::accept(posted, self.session_id, &self.others, Some(&self.keypair))?;
+                let sent = audit::message(self.keypair.pubkey(), session.partial_signature());
+                let transaction = audit::transaction(session.message());
+                let tx = session.combine(signatures)?;
+                self.audit.record(
+                    "tui",
+                    Some(self.session_id),
+                    json!({
+                        "received": self.received,
+                        "transaction": transaction,
+                        "sent": sent,
+                        "signature": audit::signature(&tx, self.aggpubkey)?,
+                    }),
+                )?;
+                self.stage = Stage::Broadcast(tx);
+            }
+            stage => self.stage = stage,
+        }
+        Ok(())
+    }
+
+    /// The signed transaction, while it's awaiting approval to be sent
+    pub fn signed_tx(&self) -> Option<&VersionedTransaction> {
+        match &self.stage {
+            Stage::Broadcast(tx) => Some(tx),
+            _ => None,
+        }
+    }
+
+    pub fn sent(&mut self, sig: Signature) {
+        self.stage = Stage::Sent(sig);
+    }
+
+    pub fn outcome(self) -> Outcome {
+        match self.stage {
+            Stage::Broadcast(tx) => Outcome::Signed(tx),
+            Stage::Sent(sig) => Outcome::Sent(sig),
+            _ => Outcome::Unfinished,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self.stage {
+            Stage::Transaction => " 1. The transaction ",
+            Stage::Review(_) => " 1. Review the transaction ",
+            Stage::FirstMessages(_) => " 2. Message 1 ",
+            Stage::PartialSignatures(_) => " 3. Partial signatures ",
+            Stage::Broadcast(_) => " 4. Send the transaction ",
+            Stage::Sent(_) => " Done ",
+        }
+    }
+
+    fn header(&self) -> Vec<String> {
+        vec![
+            format!("Session ID: {}", self.session_id),
+            format!("Party:      {}", self.keypair.pubkey()),
+            format!("Wallet:     {} ({} parties)", self.aggpubkey, self.keys.len()),
+            format!("Network:    {}", self.net.get_cluster_url()),
+        ]
+    }
+
+    /// What there is to see at the current stage, a line each
+    fn body(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        match &self.stage {
+            Stage::Transaction => lines
+                .push("Paste the unsigned transaction (Base64) you were asked to sign, and press Enter.".to_string()),
+            Stage::Review(message) => {
+                lines.push("Check what the transaction does before signing it:".to_string());
+                lines.push(String::new());
+                lines.extend(crate::describe_instructions(message));
+                lines.push(String::new());
+                lines.push("Sign it? y to approve, n to decline.".to_string());
+            }
+            Stage::FirstMessages(_) | Stage::PartialSignatures(_) => {
+                let what = if self.round() == Some(Round::FirstMessages) { "Message 1" } else { "partial signature" };
+                lines.push(format!("Your {}, send it to all the other parties:", what));
+                lines.push(self.own_message.clone());
+                lines.push(String::new());
+                lines.push(format!("The {} of the other parties:", what));
+                let missing = self.missing().unwrap_or_else(|_| self.others.clone());
+                for party in &self.others {
+                    let status = if missing.contains(party) { "waiting " } else { "received" };
+                    lines.push(format!("  {} {}", status, party));
+                }
+            }
+            Stage::Broadcast(tx) => {
+                lines.push("All the parties signed.".to_string());
+                lines.push(format!("Transaction ID: {}", tx.signatures[0]));
+                lines.push(String::new());
+                lines.push(format!("Send it to {}? y to send, n to keep it and quit.", self.net.get_cluster_url()));
+            }
+            Stage::Sent(sig) => {
+                lines.push(format!("The transaction was sent and confirmed, its ID is {}", sig));
+                lines.push(String::new());
+                lines.push("Press Enter to quit.".to_string());
+            }
+        }
+        lines
+    }
+}
+
+/// The terminal in raw mode on the alternate screen, restored when dropped, also when the ceremony fails
+struct Screen {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl Screen {
+    fn start() -> Result<Self, Error> {
+        enable_raw_mode().map_err(Error::TerminalFailed)?;
+        execute!(io::stdout(), EnterAlternateScreen, EnableBracketedPaste).map_err(Error::TerminalFailed)?;
+        let terminal = Terminal::new(CrosstermBackend::new(io::stdout())).map_err(Error::TerminalFailed)?;
+        Ok(Self { terminal })
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(self.terminal.backend_mut(), DisableBracketedPaste, LeaveAlternateScreen);
+        let _ = self.terminal.show_cursor();
+    }
+}
+
+/// Walk the party through `wizard` until it's done or they quit, exchanging the messages over `transport` too if
+/// given, and sending the transaction with `rpc_client` once approved
+pub async fn run(
+    mut wizard: Wizard,
+    mut transport: Option<Box<dyn Transport>>,
+    rpc_client: &RpcClient,
+) -> Result<Outcome, Error> {
+    let mut screen = Screen::start()?;
+    let mut input = String::new();
+    let mut notice = match transport {
+        Some(_) => "The messages of the other parties are received over the relay, or can be pasted.".to_string(),
+        None => "Pass your messages on to the other parties, and paste theirs as they come.".to_string(),
+    };
+    let mut polled: Option<Instant> = None;
+    loop {
+        if let Some(transport) = &mut transport {
+            for (round, message) in wizard.take_outbox() {
+                if let Err(e) = transport.send(round, &message).await {
+                    notice = format!("{}, pass your message on by hand", e);
+                }
+            }
+            if let Some(round) = wizard.round().filter(|_| polled.map_or(true, |at| at.elapsed() >= POLL_INTERVAL)) {
+                match transport.list(round).await {
+                    Ok(posted) => wizard.receive(posted),
+                    Err(e) => notice = e.to_string(),
+                }
+                polled = Some(Instant::now());
+            }
+        }
+        wizard.advance()?;
+        screen.terminal.draw(|frame| draw(frame, &wizard, &input, &notice)).map_err(Error::TerminalFailed)?;
+
+        if !event::poll(Duration::from_millis(200)).map_err(Error::TerminalFailed)? {
+            continue;
+        }
+        let key = match event::read().map_err(Error::TerminalFailed)? {
+            Event::Paste(text) if wizard.prompt() == Prompt::Paste => {
+                input.push_str(&text);
+                continue;
+            }
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        let quit = key.code == KeyCode::Esc
+            || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL));
+        match (wizard.prompt(), key.code) {
+            _ if quit => break,
+            (Prompt::Paste, KeyCode::Enter) => {
+                let pasted = std::mem::take(&mut input);
+                notice = match wizard.paste(&pasted) {
+                    Ok(()) => String::new(),
+                    Err(e) => e.to_string(),
+                };
+            }
+            (Prompt::Paste, KeyCode::Backspace) => {
+                input.pop();
+            }
+            (Prompt::Paste, KeyCode::Char(c)) => input.push(c),
+            (Prompt::Approve, KeyCode::Char('y')) => {
+                notice = match wizard.approve() {
+                    Ok(()) => String::new(),
+                    Err(e) => e.to_string(),
+                }
+            }
+            (Prompt::Send, KeyCode::Char('y')) => {
+                notice = "Sending the transaction...".to_string();
+                screen.terminal.draw(|frame| draw(frame, &wizard, &input, &notice)).map_err(Error::TerminalFailed)?;
+                let tx = wizard.signed_tx().expect("the transaction is signed").clone();
+                match broadcast(rpc_client, &tx).await {
+                    Ok(sig) => {
+                        wizard.sent(sig);
+                        notice = String::new();
+                    }
+                    // It can be tried again, or the signed transaction kept.
+                    Err(e) => notice = e.to_string(),
+                }
+            }
+            (Prompt::Approve, KeyCode::Char('n')) | (Prompt::Send, KeyCode::Char('n')) | (Prompt::Quit, _) => break,
+            _ => {}
+        }
+    }
+    Ok(wizard.outcome())
+}
+
+async fn broadcast(rpc_client: &RpcClient, tx: &VersionedTransaction) -> Result<Signature, Error> {
+    send::check_block_hash(rpc_client, &tx.message).await?;
+    let sig = send::send_transaction(rpc_client, tx, Preflight::default()).await?;
+    send::confirm(rpc_client, &sig, tx.message.recent_blockhash(), Some(tx)).await?;
+    Ok(sig)
+}
+
+fn draw(frame: &mut Frame, wizard: &Wizard, input: &str, notice: &str) {
+    let header = wizard.header();
+    let [header_area, body_area, notice_area, input_area] = Layout::vertical([
+        Constraint::Length(header.len() as u16 + 2),
+        Constraint::Min(3),
+        Constraint::Length(1),
+        Constraint::Length(3),
+    ])
+    .areas(frame.size());
+
+    let block = |title| Block::default().borders(Borders::ALL).title(title);
+    frame.render_widget(Paragraph::new(header.join("\n")).block(block(" Signing ceremony ")), header_area);
+    let body = Paragraph::new(wizard.body().join("\n")).block(block(wizard.title())).wrap(Wrap { trim: false });
+    frame.render_widget(body, body_area);
+    frame.render_widget(Paragraph::new(notice).style(Style::default().fg(Color::Yellow)), notice_area);
+
+    let (title, text) = match wizard.prompt() {
+        Prompt::Paste => (" Paste a message and press Enter, Esc to quit ", input),
+        Prompt::Approve | Prompt::Send => (" y / n ", ""),
+        Prompt::Quit => (" Enter to quit ", ""),
+    };
+    // Only the end of a long message fits, it's checked once entered.
+    let width = usize::from(input_area.width.saturating_sub(2));
+    let shown = &text[text.len().saturating_sub(width)..];
+    frame.render_widget(Paragraph::new(shown).block(block(title)), input_area);
+    if wizard.prompt() == Prompt::Paste {
+        frame.set_cursor(input_area.x + 1 + shown.len() as u16, input_area.y + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::{Message, VersionedMessage};
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::system_instruction;
+    use solana_sdk::transaction::Transaction;
+    use solana_tss::audit::AuditLog;
+    use solana_tss::network::Network;
+    use solana_tss::policy::Policy;
+    use solana_tss::serialization::{AggMessage1, Encoding, PartialSignature, Serialize, SessionId};
+    use solana_tss::signing::Session;
+    use solana_tss::transport::Round;
+    use solana_tss::{tss, Error};
+
+    use crate::tui::{Outcome, Prompt, Wizard};
+
+    #[test]
+    fn test_wizard() {
+        let (party, other) = (Keypair::new(), Keypair::new());
+        let keys = vec![party.pubkey(), other.pubkey()];
+        let aggpubkey = tss::aggregate_pubkey(keys.clone()).unwrap();
+        let instruction = system_instruction::transfer(&aggpubkey, &Keypair::new().pubkey(), 1);
+        let message = Message::new_with_blockhash(&[instruction], Some(&aggpubkey), &Hash::new_unique());
+        let unsigned_tx = base64::encode(bincode::serialize(&Transaction::new_unsigned(message.clone())).unwrap());
+        let message = VersionedMessage::Legacy(message);
+        let session_id = SessionId::random();
+        let (net, policy, audit) = (Network::Devnet, Policy::default(), AuditLog::new(None));
+        let mut wizard =
+            Wizard::new(party, keys.clone(), session_id, None, net, policy, audit, Encoding::Bs58).unwrap();
+
+        assert!(wizard.paste("not a transaction").is_err());
+        wizard.paste(&unsigned_tx).unwrap();
+        assert_eq!(wizard.prompt(), Prompt::Approve);
+        wizard.approve().unwrap();
+        let (round, first_msg) = wizard.take_outbox().remove(0);
+        assert_eq!(round, Round::FirstMessages);
+
+        let session = Session::new(other, keys, message, session_id).unwrap();
+        // Its own message, and one of another session.
+        assert!(matches!(wizard.paste(&first_msg), Err(Error::NotAnotherParty(_))));
+        let (elsewhere, _) = tss::step_one(&Keypair::new(), SessionId::random());
+        assert!(wizard.paste(&elsewhere.serialize_encoded(Encoding::Bs58)).is_err());
+        wizard.paste(&session.first_message().serialize_encoded(Encoding::Hex)).unwrap();
+        wizard.advance().unwrap();
+        assert_eq!(wizard.round(), Some(Round::PartialSignatures));
+
+        let session = session.receive(vec![AggMessage1::parse(&first_msg).unwrap()]).unwrap().sign().unwrap();
+        let (_, partial_signature) = wizard.take_outbox().remove(0);
+        wizard.receive(vec![session.partial_signature().serialize_encoded(Encoding::Bs58).to_string()]);
+        wizard.advance().unwrap();
+        assert_eq!(wizard.prompt(), Prompt::Send);
+        let tx = match wizard.outcome() {
+            Outcome::Signed(tx) => tx,
+            _ => unreachable!(),
+        };
+        assert!(tx.verify_with_results().iter().all(|valid| *valid));
+        // The other party combines the same transaction.
+        let signatures =
+            vec![session.partial_signature().clone(), PartialSignature::parse(&partial_signature).unwrap()];
+        assert_eq!(session.combine(signatures).unwrap().signatures, tx.signatures);
+    }
+}
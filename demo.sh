@@ -72,14 +72,15 @@ printf "$ solana-tss agg-send-step-one %s\n" "$(short_print "$secretkey1")"
 sleep 0.6s
 party1_raw=$( solana-tss agg-send-step-one "$secretkey1" )
 party1msg1=$(echo "$party1_raw" | head -1 | cut -d " " -f3)
+session_id=$(echo "$party1_raw" | sed -n 2p | cut -d " " -f3)
 party1state=$(echo "$party1_raw" | tail -1 | cut -d " " -f3)
-printf "Message 1: %s (send to all other parties)\nSecret state: %s (keep this a secret, and pass it back to \`agg-send-step-two\`)\n" "$(short_print "$party1msg1")" "$(short_print "$party1state")"
+printf "Message 1: %s (send to all other parties)\nSession ID: %s (pass it to \`--session-id\` of all other parties' step 1)\nSecret state: %s (keep this a secret, and pass it back to \`agg-send-step-two\`)\n" "$(short_print "$party1msg1")" "$(short_print "$session_id")" "$(short_print "$party1state")"
 sleep 0.3s
 
 party_2 "Generate message 1"
-printf "$ solana-tss agg-send-step-one %s\n" "$(short_print "$secretkey2")"
+printf "$ solana-tss agg-send-step-one %s --session-id %s\n" "$(short_print "$secretkey2")" "$(short_print "$session_id")"
 sleep 0.6s
-party2_raw=$( solana-tss agg-send-step-one "$secretkey2" )
+party2_raw=$( solana-tss agg-send-step-one "$secretkey2" --session-id "$session_id" )
 party2msg1=$(echo "$party2_raw" | head -1 | cut -d " " -f3)
 party2state=$(echo "$party2_raw" | tail -1 | cut -d " " -f3)
 printf "Message 1: %s (send to all other parties)\nSecret state: %s (keep this a secret, and pass it back to \`agg-send-step-two\`)\n\n" "$(short_print "$party2msg1")" "$(short_print "$party2state")"
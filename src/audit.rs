@@ -0,0 +1,135 @@
+use std::fmt::Display;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::VersionedTransaction;
+
+use crate::serialization::{Encoding, Serialize, SessionId};
+use crate::{tss, Error};
+
+/// The `prev_hash` of the first entry, which has no entry before it
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// An append-only log of the messages sent and received in signing sessions, with the transactions they signed,
+/// for reviewing a session after the fact. Every line is a JSON entry holding the SHA-256 of the line before it,
+/// so an entry that's changed or removed later breaks the chain, which `verify` finds.
+/// Without a path nothing is recorded.
+pub struct AuditLog {
+    path: Option<PathBuf>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Entry {
+    /// Seconds since the Unix epoch
+    timestamp: u64,
+    command: String,
+    session_id: Option<String>,
+    event: Value,
+    prev_hash: String,
+}
+
+impl AuditLog {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self { path }
+    }
+
+    /// Append an entry of `command` in the session `session_id`, before its messages are printed to be sent
+    pub fn record(&self, command: &str, session_id: Option<SessionId>, event: Value) -> Result<(), Error> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let prev_hash = match fs::read_to_string(path) {
+            Ok(log) => log.lines().last().map_or_else(|| GENESIS_HASH.to_string(), hash),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => GENESIS_HASH.to_string(),
+            Err(e) => return Err(Error::AuditLogFailed(e)),
+        };
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+        let entry = Entry {
+            timestamp,
+            command: command.to_string(),
+            session_id: session_id.map(|id| id.to_string()),
+            event,
+            prev_hash,
+        };
+        let line = serde_json::to_string(&entry).expect("the entry is always valid JSON");
+        let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(Error::AuditLogFailed)?;
+        writeln!(file, "{}", line).map_err(Error::AuditLogFailed)
+    }
+}
+
+/// Check the hash chain of the audit log at `path`, returns the number of entries
+pub fn verify(path: &Path) -> Result<usize, Error> {
+    let log = fs::read_to_string(path).map_err(Error::AuditLogFailed)?;
+    let mut prev_hash = GENESIS_HASH.to_string();
+    for (i, line) in log.lines().enumerate() {
+        let entry: Entry = serde_json::from_str(line).map_err(|_| Error::AuditLogBroken(i + 1))?;
+        if entry.prev_hash != prev_hash {
+            return Err(Error::AuditLogBroken(i + 1));
+        }
+        prev_hash = hash(line);
+    }
+    Ok(log.lines().count())
+}
+
+/// A message sent or received, with the party that sent it: its public key, or its index in a threshold wallet
+pub fn message<T: Serialize>(sender: impl Display, message: &T) -> Value {
+    json!({ "sender": sender.to_string(), "message": message.serialize_json(Encoding::Bs58) })
+}
+
+/// The transaction a session signs, as the message that's signed, its digest and its block hash
+pub fn transaction(message: &VersionedMessage) -> Value {
+    json!({
+        "message": base64::encode(message.serialize()),
+        "digest": tss::transaction_digest(message).to_string(),
+        "recent_block_hash": message.recent_blockhash().to_string(),
+    })
+}
+
+/// The signature `signer` put on `tx`, which isn't the first one when another key pays the fee
+pub fn signature(tx: &VersionedTransaction, signer: Pubkey) -> Result<String, Error> {
+    let position = tss::check_signer(&tx.message, signer)?;
+    Ok(tx.signatures[position].to_string())
+}
+
+fn hash(line: &str) -> String {
+    hex::encode(Sha256::digest(line.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::audit::{verify, AuditLog};
+    use crate::serialization::SessionId;
+    use crate::Error;
+
+    #[test]
+    fn test_hash_chain() {
+        let path = std::env::temp_dir().join(format!("solana-tss-audit-{}.jsonl", SessionId::random()));
+        let log = AuditLog::new(Some(path.clone()));
+        log.record("agg-send-step-one", Some(SessionId::random()), json!({ "sent": "aggmsg1_..." })).unwrap();
+        log.record("agg-send-step-two", None, json!({ "received": ["aggmsg1_..."] })).unwrap();
+        log.record("aggregate-signatures-and-broadcast", None, json!({})).unwrap();
+        assert_eq!(verify(&path).unwrap(), 3);
+
+        // Changing an earlier entry breaks the hash of the one after it.
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, contents.replacen("agg-send-step-two", "agg-send-step-one", 1)).unwrap();
+        assert!(matches!(verify(&path), Err(Error::AuditLogBroken(3))));
+        // So does removing one.
+        let without_first: Vec<_> = contents.lines().skip(1).collect();
+        std::fs::write(&path, without_first.join("\n")).unwrap();
+        assert!(matches!(verify(&path), Err(Error::AuditLogBroken(1))));
+        std::fs::remove_file(&path).unwrap();
+
+        // Nothing is written without a path.
+        AuditLog::new(None).record("agg-send-step-one", None, json!({})).unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+use bip39::Language;
+use sha2::{Digest, Sha256};
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::keypair::keypair_from_seed;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::Error;
+
+const BACKUP_VERSION: u8 = 1;
+const SECRET_LEN: usize = 32;
+const CHECKSUM_LEN: usize = 3;
+/// The version, the identifier, the threshold, the index and the share of the secret, followed by the checksum
+const SHARE_LEN: usize = 1 + 2 + 1 + 1 + SECRET_LEN + CHECKSUM_LEN;
+const BITS_PER_WORD: usize = 11;
+const WORD_COUNT: usize = (SHARE_LEN * 8).div_ceil(BITS_PER_WORD);
+
+/// One share of a backup of a secret key split with Shamir's secret sharing over GF(256), like SLIP-0039.
+/// Any `threshold` of the shares of a backup bring the key back, fewer tell nothing about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupShare {
+    /// Random, the same for all the shares of a backup so shares of different backups aren't mixed up
+    pub identifier: u16,
+    pub threshold: u8,
+    /// Between 1 and the number of shares, the point of the polynomials the share is the value at
+    pub index: u8,
+    value: [u8; SECRET_LEN],
+}
+
+/// Split the secret key of `keypair` into `shares` shares, any `threshold` of which restore it
+pub fn split(keypair: &Keypair, threshold: u8, shares: u8) -> Result<Vec<BackupShare>, Error> {
+    if threshold == 0 || threshold > shares {
+        return Err(Error::WrongBackupThreshold { threshold, shares });
+    }
+    let secret = Zeroizing::new(keypair.secret().to_bytes());
+    let identifier = rand07::random();
+    let mut backup: Vec<_> =
+        (1..=shares).map(|index| BackupShare { identifier, threshold, index, value: [0; SECRET_LEN] }).collect();
+    for (i, &byte) in secret.iter().enumerate() {
+        // A random polynomial of degree threshold - 1 per byte, whose value at 0 is the byte of the secret.
+        let mut coefficients = Zeroizing::new(vec![byte]);
+        coefficients.extend((1..threshold).map(|_| rand07::random::<u8>()));
+        for share in &mut backup {
+            share.value[i] = evaluate(&coefficients, share.index);
+        }
+    }
+    Ok(backup)
+}
+
+/// Restore the secret key from at least `threshold` shares of the same backup
+pub fn restore(shares: &[BackupShare]) -> Result<Keypair, Error> {
+    let first = shares.first().ok_or(Error::NotEnoughBackupShares { needed: 1, given: 0 })?;
+    if shares.iter().any(|share| share.identifier != first.identifier || share.threshold != first.threshold) {
+        return Err(Error::MixedBackupShares);
+    }
+    let mut indices: Vec<_> = shares.iter().map(|share| share.index).collect();
+    indices.sort_unstable();
+    indices.dedup();
+    if indices.len() != shares.len() {
+        return Err(Error::DuplicateBackupShare);
+    }
+    if shares.len() < usize::from(first.threshold) {
+        return Err(Error::NotEnoughBackupShares { needed: first.threshold, given: shares.len() });
+    }
+    let shares = &shares[..usize::from(first.threshold)];
+    let mut secret = Zeroizing::new([0u8; SECRET_LEN]);
+    for (i, byte) in secret.iter_mut().enumerate() {
+        // Lagrange interpolation at 0, subtraction is xor in GF(256).
+        for share in shares {
+            let mut basis = 1;
+            for other in shares.iter().filter(|other| other.index != share.index) {
+                basis = mul(basis, mul(other.index, inverse(other.index ^ share.index)));
+            }
+            *byte ^= mul(share.value[i], basis);
+        }
+    }
+    keypair_from_seed(&*secret).map_err(|e| Error::DerivationFailed(e.to_string()))
+}
+
+impl Drop for BackupShare {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+impl BackupShare {
+    /// The share as English words, with a checksum so a mistyped word is noticed
+    pub fn to_words(&self) -> String {
+        let mut bytes = Zeroizing::new(vec![BACKUP_VERSION]);
+        bytes.extend(self.identifier.to_be_bytes());
+        bytes.extend([self.threshold, self.index]);
+        bytes.extend(self.value);
+        bytes.extend(checksum(&bytes));
+
+        // All the words, a prefix matches them all.
+        let words = Language::English.wordlist().get_words_by_prefix("");
+        let bits: Vec<bool> =
+            bytes.iter().flat_map(|byte| (0..8).rev().map(move |bit| (byte >> bit) & 1 == 1)).collect();
+        bits.chunks(BITS_PER_WORD)
+            .map(|chunk| {
+                // The last word is padded with zeroes.
+                let index =
+                    (0..BITS_PER_WORD).fold(0, |index, i| (index << 1) | usize::from(chunk.get(i) == Some(&true)));
+                words[index]
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Parse a share written with `to_words`, spacing and case don't matter
+    pub fn from_words(s: &str) -> Result<Self, Error> {
+        let wordlist = Language::English.wordlist().get_words_by_prefix("");
+        let words: Vec<_> = s.split_whitespace().map(str::to_lowercase).collect();
+        if words.len() != WORD_COUNT {
+            return Err(Error::InvalidBackupShare(format!("it has {} words instead of {}", words.len(), WORD_COUNT)));
+        }
+        let mut bits = Vec::with_capacity(WORD_COUNT * BITS_PER_WORD);
+        for word in &words {
+            let index = wordlist
+                .binary_search(&word.as_str())
+                .map_err(|_| Error::InvalidBackupShare(format!("{} isn't one of the words", word)))?;
+            bits.extend((0..BITS_PER_WORD).rev().map(|bit| (index >> bit) & 1 == 1));
+        }
+        let bytes: Vec<u8> = bits
+            .chunks(8)
+            .take(SHARE_LEN)
+            .map(|byte| byte.iter().fold(0, |acc, &bit| (acc << 1) | u8::from(bit)))
+            .collect();
+        let (payload, sum) = bytes.split_at(SHARE_LEN - CHECKSUM_LEN);
+        if checksum(payload) != sum {
+            return Err(Error::InvalidBackupShare("the checksum doesn't match, check the words".to_string()));
+        }
+        if payload[0] != BACKUP_VERSION {
+            return Err(Error::InvalidBackupShare(format!("unknown version {}", payload[0])));
+        }
+        let mut value = [0; SECRET_LEN];
+        value.copy_from_slice(&payload[5..]);
+        Ok(Self {
+            identifier: u16::from_be_bytes([payload[1], payload[2]]),
+            threshold: payload[3],
+            index: payload[4],
+            value,
+        })
+    }
+}
+
+fn checksum(bytes: &[u8]) -> Vec<u8> {
+    Sha256::digest(bytes)[..CHECKSUM_LEN].to_vec()
+}
+
+/// The value at `x` of the polynomial with `coefficients`, lowest degree first
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    coefficients.iter().rev().fold(0, |acc, &coefficient| mul(acc, x) ^ coefficient)
+}
+
+/// Multiplication in GF(256) with the AES polynomial x^8 + x^4 + x^3 + x + 1
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// The multiplicative inverse in GF(256), a^254 as a^255 = 1
+fn inverse(a: u8) -> u8 {
+    (0..254).fold(1, |acc, _| mul(acc, a))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::backup::{inverse, mul, restore, split, BackupShare, WORD_COUNT};
+    use crate::Error;
+
+    #[test]
+    fn test_field() {
+        for a in 1..=255 {
+            assert_eq!(mul(a, inverse(a)), 1);
+        }
+        assert_eq!(mul(0x57, 0x83), 0xc1);
+    }
+
+    #[test]
+    fn test_split_restore() {
+        let keypair = Keypair::new();
+        let shares = split(&keypair, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+        // Any 3 shares, in any order.
+        for subset in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let subset: Vec<_> = subset.iter().map(|&i| shares[i].clone()).collect();
+            assert_eq!(restore(&subset).unwrap().pubkey(), keypair.pubkey());
+        }
+        assert_ne!(restore(&shares[..3]).unwrap().pubkey(), Keypair::new().pubkey());
+        assert!(matches!(restore(&shares[..2]), Err(Error::NotEnoughBackupShares { needed: 3, given: 2 })));
+        let twice = [shares[0].clone(), shares[0].clone(), shares[1].clone()];
+        assert!(matches!(restore(&twice), Err(Error::DuplicateBackupShare)));
+        let other = split(&Keypair::new(), 3, 5).unwrap();
+        let mixed = [shares[0].clone(), shares[1].clone(), other[2].clone()];
+        assert!(matches!(restore(&mixed), Err(Error::MixedBackupShares)));
+
+        assert!(matches!(split(&keypair, 4, 3), Err(Error::WrongBackupThreshold { .. })));
+        assert!(matches!(split(&keypair, 0, 3), Err(Error::WrongBackupThreshold { .. })));
+        // 1-of-1 is a plain copy of the key.
+        assert_eq!(restore(&split(&keypair, 1, 1).unwrap()).unwrap().pubkey(), keypair.pubkey());
+    }
+
+    #[test]
+    fn test_words() {
+        let shares = split(&Keypair::new(), 2, 3).unwrap();
+        let words = shares[1].to_words();
+        assert_eq!(words.split(' ').count(), WORD_COUNT);
+        assert_eq!(BackupShare::from_words(&words).unwrap(), shares[1]);
+        assert_eq!(BackupShare::from_words(&format!("  {}\n", words.to_uppercase())).unwrap(), shares[1]);
+
+        // Swapping two words breaks the checksum.
+        let mut swapped: Vec<_> = words.split(' ').collect();
+        swapped.swap(3, 4);
+        if swapped[3] != swapped[4] {
+            assert!(matches!(BackupShare::from_words(&swapped.join(" ")), Err(Error::InvalidBackupShare(_))));
+        }
+        assert!(matches!(BackupShare::from_words("abandon ability"), Err(Error::InvalidBackupShare(_))));
+        let unknown = words.replacen(words.split(' ').next().unwrap(), "solana", 1);
+        assert!(matches!(BackupShare::from_words(&unknown), Err(Error::InvalidBackupShare(_))));
+    }
+}
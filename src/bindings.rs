@@ -0,0 +1,95 @@
+//! The protocol over strings, which the JavaScript of [`wasm`](crate::wasm) and the Python of
+//! [`python`](crate::python) both expose. Keys, messages and transactions are the strings the command line prints and
+//! parses: Base58 keys, encoded messages and Base64 transactions.
+
+use std::str::FromStr;
+
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::serialization::{
+    AggMessage1, Encoding, FieldError, PartialSignature, SecretAggStepOne, Serialize, SessionId,
+};
+use crate::transaction::parse_unsigned_tx;
+use crate::{tss, Error};
+
+/// A new keypair, as the Base58 of its 64 bytes
+pub(crate) fn generate_keypair() -> String {
+    Keypair::new().to_base58_string()
+}
+
+/// The public key of a keypair given as the Base58 of its 64 bytes
+pub(crate) fn public_key(keypair: &str) -> Result<String, Error> {
+    Ok(parse_keypair(keypair)?.pubkey().to_string())
+}
+
+/// The aggregated public key of `keys`, the address of the wallet
+pub(crate) fn aggregate_keys(keys: &[String]) -> Result<String, Error> {
+    Ok(tss::aggregate_pubkey(parse_keys(keys)?)?.to_string())
+}
+
+/// A new random session ID, for all the parties of a signing session to use
+pub(crate) fn new_session_id() -> String {
+    SessionId::random().to_string()
+}
+
+/// The first message of step one, to send to the other parties, and the secret state to keep for step two
+pub(crate) fn step_one(keypair: &str, session_id: &str) -> Result<(String, String), Error> {
+    let (first_message, secret_state) = tss::step_one(&parse_keypair(keypair)?, parse_session_id(session_id)?);
+    Ok((
+        first_message.serialize_encoded(Encoding::Bs58).to_string(),
+        secret_state.serialize_encoded(Encoding::Bs58).to_string(),
+    ))
+}
+
+/// The partial signature of the Base64 `unsigned_tx`, with the first messages of the other parties.
+/// The secret state must be thrown away after, whether this succeeds or not.
+pub(crate) fn step_two(
+    keypair: &str,
+    unsigned_tx: &str,
+    keys: &[String],
+    first_messages: &[String],
+    secret_state: &str,
+) -> Result<String, Error> {
+    let sig = tss::step_two(
+        parse_keypair(keypair)?,
+        parse_unsigned_tx(unsigned_tx)?,
+        parse_keys(keys)?,
+        Vec::new(),
+        parse_all::<AggMessage1>(first_messages)?,
+        SecretAggStepOne::parse(secret_state).with_field("secret_state")?,
+    )?;
+    Ok(sig.serialize_encoded(Encoding::Bs58).to_string())
+}
+
+/// The signed transaction, in Base64, out of the partial signatures of all the parties
+pub(crate) fn aggregate_signatures(
+    unsigned_tx: &str,
+    keys: &[String],
+    first_messages: &[String],
+    signatures: &[String],
+) -> Result<String, Error> {
+    let tx = tss::sign_and_broadcast(
+        parse_unsigned_tx(unsigned_tx)?,
+        parse_keys(keys)?,
+        parse_all::<AggMessage1>(first_messages)?,
+        parse_all::<PartialSignature>(signatures)?,
+    )?;
+    Ok(base64::encode(bincode::serialize(&tx).expect("serializing to memory can't fail")))
+}
+
+fn parse_keypair(keypair: &str) -> Result<Keypair, Error> {
+    Ok(Keypair::from_bytes(&bs58::decode(keypair.trim()).into_vec()?)?)
+}
+
+fn parse_keys(keys: &[String]) -> Result<Vec<Pubkey>, Error> {
+    keys.iter().map(|key| Pubkey::from_str(key).map_err(|_| Error::InvalidKey(key.clone()))).collect()
+}
+
+fn parse_session_id(session_id: &str) -> Result<SessionId, Error> {
+    SessionId::from_str(session_id).map_err(|error| Error::DeserializationFailed { error, field_name: "session_id" })
+}
+
+fn parse_all<T: Serialize>(messages: &[String]) -> Result<Vec<T>, Error> {
+    messages.iter().map(|msg| T::parse(msg).with_field("messages")).collect()
+}
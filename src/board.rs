@@ -0,0 +1,149 @@
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::{Transaction, VersionedTransaction};
+use spl_memo::build_memo;
+use tracing::info;
+
+use crate::history;
+use crate::send::{self, Preflight};
+use crate::serialization::SessionId;
+use crate::transport::{missing, Round, Transport};
+use crate::Error;
+
+const ADDRESS_DOMAIN: &[u8] = b"solana-tss bulletin board";
+/// Memos of the board start with it, followed by `<round>/<message>`
+const MEMO_PREFIX: &str = "solana-tss/";
+/// How many of the latest transactions of the board are read, far more than any session needs
+const HISTORY_LIMIT: usize = 1000;
+/// How often a party waiting on the board reads it again, about a dozen blocks
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const WAIT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// The address the messages of `session_id` are posted to. It's a hash, so no one holds its key, it's only there for
+/// the transactions of the session to be found by it.
+pub fn address(session_id: SessionId) -> Pubkey {
+    Pubkey::new_from_array(Sha256::new().chain(ADDRESS_DOMAIN).chain(session_id.0).finalize().into())
+}
+
+/// Exchanges the messages of a signing session as memo transactions sent to its address
+pub struct Board {
+    rpc_client: RpcClient,
+    session_id: SessionId,
+    /// Pays for the transactions, only parties sending messages need one
+    payer: Option<Keypair>,
+}
+
+impl Board {
+    pub fn new(rpc_client: RpcClient, session_id: SessionId, payer: Option<Keypair>) -> Self {
+        Self { rpc_client, session_id, payer }
+    }
+
+    /// Post a message of `round` as the memo of a transaction paid for by the payer, that also sends nothing to the
+    /// address of the session so it's found by it
+    pub async fn post(&self, round: Round, message: &str) -> Result<Signature, Error> {
+        let payer = self.payer.as_ref().expect("only a party sends messages");
+        let memo = format!("{}{}/{}", MEMO_PREFIX, round.path(), message);
+        let instructions = [
+            system_instruction::transfer(&payer.pubkey(), &address(self.session_id), 0),
+            build_memo(memo.as_bytes(), &[]),
+        ];
+        let recent_hash = self.rpc_client.get_latest_blockhash().await.map_err(Error::RecentHashFailed)?;
+        let tx: VersionedTransaction =
+            Transaction::new_signed_with_payer(&instructions, Some(&payer.pubkey()), &[payer], recent_hash).into();
+        let sig = send::send_transaction(&self.rpc_client, &tx, Preflight::default()).await?;
+        send::confirm(&self.rpc_client, &sig, &recent_hash, Some(&tx)).await?;
+        Ok(sig)
+    }
+
+    /// The messages of `round` posted so far, oldest first. Failed transactions don't count.
+    pub async fn fetch(&self, round: Round) -> Result<Vec<String>, Error> {
+        let prefix = format!("{}{}/", MEMO_PREFIX, round.path());
+        let entries = history::fetch(&self.rpc_client, &address(self.session_id), HISTORY_LIMIT, None).await?;
+        Ok(entries
+            .into_iter()
+            .rev()
+            .filter(|entry| entry.error.is_none())
+            .flat_map(|entry| entry.memos)
+            .filter_map(|memo| memo.strip_prefix(&prefix).map(String::from))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl Transport for Board {
+    fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+
+    async fn send(&mut self, round: Round, message: &str) -> Result<(), Error> {
+        let sig = self.post(round, message).await?;
+        info!("Posted the message to the board {} in {}", address(self.session_id), sig);
+        Ok(())
+    }
+
+    async fn list(&mut self, round: Round) -> Result<Vec<String>, Error> {
+        self.fetch(round).await
+    }
+
+    async fn recv(
+        &mut self,
+        round: Round,
+        senders: &[Pubkey],
+        keypair: Option<&Keypair>,
+    ) -> Result<Vec<String>, Error> {
+        let start = Instant::now();
+        loop {
+            let messages = self.fetch(round).await?;
+            if missing(&messages, self.session_id, round, senders, keypair)?.is_empty() {
+                return Ok(messages);
+            }
+            if start.elapsed() > WAIT_TIMEOUT {
+                return Err(Error::BoardTimeout(address(self.session_id)));
+            }
+            info!("Waiting for the other parties on the board {}...", address(self.session_id));
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::native_token::sol_to_lamports;
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_test_validator::TestValidatorGenesis;
+
+    use crate::board::{address, Board};
+    use crate::serialization::{AggMessage1, Encoding, Serialize, SessionId};
+    use crate::transport::{recv, Round, Transport};
+    use crate::tss;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_board() {
+        let (validator, payer) = TestValidatorGenesis::default().start_async().await;
+        let rpc_client = validator.get_async_rpc_client();
+        let session_id = SessionId::random();
+        assert_ne!(address(session_id), address(SessionId::random()));
+
+        let parties: Vec<_> = (0..2).map(|_| Keypair::new()).collect();
+        for party in &parties {
+            let (first_msg, _) = tss::step_one(party, session_id);
+            let sig = rpc_client.request_airdrop(&party.pubkey(), sol_to_lamports(1.0)).await.unwrap();
+            rpc_client.poll_for_signature(&sig).await.unwrap();
+            let party = Keypair::from_bytes(&party.to_bytes()).unwrap();
+            Board::new(validator.get_async_rpc_client(), session_id, Some(party))
+                .send(Round::FirstMessages, &first_msg.serialize_encoded(Encoding::Bs58))
+                .await
+                .unwrap();
+        }
+        let senders: Vec<_> = parties.iter().map(|party| party.pubkey()).collect();
+        let mut board = Board::new(rpc_client, session_id, None);
+        let messages: Vec<AggMessage1> = recv(&mut board, Round::FirstMessages, &senders, Some(&payer)).await.unwrap();
+        assert_eq!(messages.len(), 2);
+    }
+}
@@ -0,0 +1,202 @@
+use std::str::FromStr;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_request::{TokenAccountsFilter, MAX_MULTIPLE_ACCOUNTS};
+use solana_sdk::account::Account;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::nonce::state::{State as NonceState, Versions as NonceVersions};
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::stake::instruction as stake_instruction;
+use solana_sdk::stake::state::StakeState;
+use solana_sdk::transaction::Transaction;
+use solana_sdk::{system_instruction, system_program};
+use spl_token_2022::extension::StateWithExtensions;
+use spl_token_2022::state::Account as TokenAccount;
+
+use crate::Error;
+
+/// Fetch `keys`, failing if any of them doesn't exist
+pub async fn fetch_accounts(rpc_client: &RpcClient, keys: &[Pubkey]) -> Result<Vec<(Pubkey, Account)>, Error> {
+    let mut accounts = Vec::with_capacity(keys.len());
+    for chunk in keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let fetched = rpc_client.get_multiple_accounts(chunk).await.map_err(Error::AccountLookupFailed)?;
+        for (&key, account) in chunk.iter().zip(fetched) {
+            accounts.push((key, account.ok_or(Error::AccountNotFound(key))?));
+        }
+    }
+    Ok(accounts)
+}
+
+/// All the token accounts of `wallet` holding no tokens, of both token programs
+pub async fn empty_token_accounts(rpc_client: &RpcClient, wallet: &Pubkey) -> Result<Vec<(Pubkey, Account)>, Error> {
+    let mut keys = Vec::new();
+    for program_id in [spl_token::id(), spl_token_2022::id()] {
+        let accounts = rpc_client
+            .get_token_accounts_by_owner(wallet, TokenAccountsFilter::ProgramId(program_id))
+            .await
+            .map_err(Error::AccountLookupFailed)?;
+        keys.extend(accounts.iter().filter_map(|account| Pubkey::from_str(&account.pubkey).ok()));
+    }
+    let accounts = fetch_accounts(rpc_client, &keys).await?;
+    // Closing a wrapped SOL account unwraps it, which `unwrap-sol` is for.
+    Ok(accounts
+        .into_iter()
+        .filter(|(_, account)| {
+            StateWithExtensions::<TokenAccount>::unpack(&account.data)
+                .map_or(false, |state| state.base.amount == 0 && !state.base.is_native())
+        })
+        .collect())
+}
+
+/// Close `account`, sending its lamports to `destination`. `wallet` must be allowed to close it, and with `burn`
+/// any tokens left in a token account are burnt first.
+pub fn close_instructions(
+    key: &Pubkey,
+    account: &Account,
+    wallet: &Pubkey,
+    destination: &Pubkey,
+    burn: bool,
+) -> Result<Vec<Instruction>, Error> {
+    let check_authority = |authority: Pubkey| {
+        if authority == *wallet {
+            Ok(())
+        } else {
+            Err(Error::NotTheAuthority { account: *key, authority })
+        }
+    };
+
+    if account.owner == spl_token::id() || account.owner == spl_token_2022::id() {
+        let state = StateWithExtensions::<TokenAccount>::unpack(&account.data).map_err(|_| Error::NotClosable(*key))?;
+        let token_account = state.base;
+        check_authority(token_account.close_authority.unwrap_or(token_account.owner))?;
+        let mut instructions = Vec::new();
+        // Wrapped SOL is unwrapped by closing the account, anything else has to be gone before.
+        if token_account.amount != 0 && !token_account.is_native() {
+            if !burn {
+                return Err(Error::TokenAccountNotEmpty(*key));
+            }
+            check_authority(token_account.owner)?;
+            instructions.push(
+                spl_token_2022::instruction::burn(
+                    &account.owner,
+                    key,
+                    &token_account.mint,
+                    wallet,
+                    &[],
+                    token_account.amount,
+                )
+                .expect("the token program ID is always right"),
+            );
+        }
+        instructions.push(
+            spl_token_2022::instruction::close_account(&account.owner, key, destination, wallet, &[])
+                .expect("the token program ID is always right"),
+        );
+        Ok(instructions)
+    } else if account.owner == system_program::id() && account.data.len() == NonceState::size() {
+        match bincode::deserialize::<NonceVersions>(&account.data).map(|versions| versions.state().clone()) {
+            Ok(NonceState::Initialized(data)) => {
+                check_authority(data.authority)?;
+                Ok(vec![system_instruction::withdraw_nonce_account(key, wallet, destination, account.lamports)])
+            }
+            _ => Err(Error::NotClosable(*key)),
+        }
+    } else if account.owner == solana_sdk::stake::program::id() {
+        // The stake has to be deactivated first, or the withdrawal fails.
+        match bincode::deserialize::<StakeState>(&account.data).ok().and_then(|state| state.authorized()) {
+            Some(authorized) => {
+                check_authority(authorized.withdrawer)?;
+                Ok(vec![stake_instruction::withdraw(key, wallet, destination, account.lamports, None)])
+            }
+            None => Err(Error::NotClosable(*key)),
+        }
+    } else {
+        Err(Error::NotClosable(*key))
+    }
+}
+
+/// Split the closing instructions of every account into as few transactions paid by `payer` as fit.
+/// The instructions of an account always stay together.
+pub fn batch(groups: Vec<Vec<Instruction>>, payer: &Pubkey) -> Vec<Vec<Instruction>> {
+    let fits = |instructions: &[Instruction]| {
+        let tx = Transaction::new_unsigned(Message::new(instructions, Some(payer)));
+        bincode::serialized_size(&tx).map_or(false, |size| size <= PACKET_DATA_SIZE as u64)
+    };
+    let mut batches: Vec<Vec<Instruction>> = Vec::new();
+    for group in groups {
+        match batches.last_mut() {
+            Some(last) if fits(&[&last[..], &group[..]].concat()) => last.extend(group),
+            _ => batches.push(group),
+        }
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::account::Account;
+    use solana_sdk::program_option::COption;
+    use solana_sdk::program_pack::Pack;
+    use solana_sdk::pubkey::Pubkey;
+    use spl_token::instruction::TokenInstruction;
+    use spl_token::state::{Account as TokenAccount, AccountState};
+
+    use crate::close::{batch, close_instructions};
+    use crate::Error;
+
+    fn token_account(owner: Pubkey, amount: u64) -> Account {
+        let state = TokenAccount {
+            mint: Pubkey::new_unique(),
+            owner,
+            amount,
+            delegate: COption::None,
+            state: AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        };
+        let mut data = vec![0; TokenAccount::LEN];
+        state.pack_into_slice(&mut data);
+        Account { lamports: 2_039_280, data, owner: spl_token::id(), executable: false, rent_epoch: 0 }
+    }
+
+    #[test]
+    fn test_close_token_accounts() {
+        let wallet = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let instructions = close_instructions(&key, &token_account(wallet, 0), &wallet, &wallet, false).unwrap();
+        assert_eq!(instructions.len(), 1);
+        assert!(matches!(TokenInstruction::unpack(&instructions[0].data), Ok(TokenInstruction::CloseAccount)));
+
+        let dust = token_account(wallet, 7);
+        assert!(matches!(
+            close_instructions(&key, &dust, &wallet, &wallet, false),
+            Err(Error::TokenAccountNotEmpty(account)) if account == key
+        ));
+        let instructions = close_instructions(&key, &dust, &wallet, &wallet, true).unwrap();
+        assert!(matches!(TokenInstruction::unpack(&instructions[0].data), Ok(TokenInstruction::Burn { amount: 7 })));
+
+        let other = Pubkey::new_unique();
+        assert!(matches!(
+            close_instructions(&key, &token_account(other, 0), &wallet, &wallet, false),
+            Err(Error::NotTheAuthority { authority, .. }) if authority == other
+        ));
+    }
+
+    #[test]
+    fn test_batch_fits_transactions() {
+        let wallet = Pubkey::new_unique();
+        let groups: Vec<_> = (0..100)
+            .map(|_| {
+                close_instructions(&Pubkey::new_unique(), &token_account(wallet, 1), &wallet, &wallet, true).unwrap()
+            })
+            .collect();
+        let batches = batch(groups, &wallet);
+        assert!(batches.len() > 1);
+        assert_eq!(batches.iter().map(Vec::len).sum::<usize>(), 200);
+        // Every burn stays with its close.
+        assert!(batches.iter().all(|batch| batch.len() % 2 == 0));
+    }
+}
@@ -0,0 +1,481 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::VersionedTransaction;
+
+use crate::audit::{self, AuditLog};
+use crate::journal::NonceJournal;
+use crate::network::Network;
+use crate::policy::Policy;
+use crate::seal::parse_sealable;
+use crate::serialization::{AggMessage1, PartialSignature, SecretAggStepOne, SessionId};
+use crate::{tss, Error};
+
+/// How long the daemon keeps a session without any request for it, unless told otherwise
+pub const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+/// So the callers can't fill the daemon up with secret states
+const MAX_SESSIONS: usize = 1000;
+
+/// Where a session hosted by the daemon is at
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    /// Some of the other parties' first messages weren't submitted yet
+    Collecting,
+    /// All the first messages are in, the partial signature can be fetched
+    Ready,
+    /// The partial signature was made, the nonces of the session are used up
+    Signed,
+}
+
+/// What went wrong with a request to the daemon, for its services to answer with the status their protocol has for it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The request itself is wrong, sending it again won't help
+    InvalidRequest,
+    NotFound,
+    /// It goes against what was submitted to the session before
+    Conflict,
+    /// The signing policy refuses it
+    Refused,
+    /// The session isn't there yet, or the nonces were used already
+    NotReady,
+    /// The daemon holds too many sessions for now
+    Full,
+    Internal,
+}
+
+impl Failure {
+    pub fn of(e: &Error) -> Self {
+        match e {
+            Error::NoSuchDaemonSession(_) => Self::NotFound,
+            Error::DaemonSessionExists(_) | Error::FirstMessageConflict(_) => Self::Conflict,
+            Error::PolicyViolation(_) => Self::Refused,
+            Error::MissingFirstMessages(_) | Error::NonceAlreadyUsed | Error::NonceAborted => Self::NotReady,
+            Error::TooManyDaemonSessions => Self::Full,
+            Error::BadBase58(_)
+            | Error::DeserializationFailed { .. }
+            | Error::InvalidUnsignedTransaction
+            | Error::WrongTransactionSigner(_)
+            | Error::KeyPairIsNotInKeys
+            | Error::NotAnotherParty(_)
+            | Error::SessionMismatch
+            | Error::MissingDecryptionKey
+            | Error::MismatchMessages
+            | Error::InvalidSignature
+            | Error::InvalidPartialSignature(_)
+            | Error::MissingFirstMessage(_) => Self::InvalidRequest,
+            _ => Self::Internal,
+        }
+    }
+}
+
+/// What the callers are told about a session
+pub struct Status {
+    pub session_id: SessionId,
+    pub state: State,
+    /// All the parties of the session, the daemon's own included
+    pub keys: Vec<Pubkey>,
+    /// How long until the session expires with its secret state, unless a request comes for it meanwhile
+    pub expires_in: Duration,
+    /// The first message of the daemon's party, to pass on to the other parties
+    pub first_message: AggMessage1,
+    /// The other parties whose first message was submitted
+    pub received: Vec<Pubkey>,
+    /// The other parties whose first message is still missing
+    pub missing: Vec<Pubkey>,
+}
+
+struct HostedSession {
+    created: Instant,
+    /// When the session was last created, submitted to, signed or aggregated with, it expires some time after
+    active: Instant,
+    keys: Vec<Pubkey>,
+    message: VersionedMessage,
+    /// The owner of the token account the transaction sends to, for the policy to check
+    to: Option<Pubkey>,
+    first_message: AggMessage1,
+    /// Dropped once signed, so its nonces can't sign anything else
+    secret_state: Option<SecretAggStepOne>,
+    first_messages: Vec<AggMessage1>,
+    partial_signature: Option<PartialSignature>,
+}
+
+impl HostedSession {
+    fn status(&self, session_id: SessionId, timeout: Duration) -> Status {
+        let received: Vec<_> = self.first_messages.iter().map(|msg| msg.sender).collect();
+        let missing: Vec<_> = self
+            .keys
+            .iter()
+            .filter(|key| **key != self.first_message.sender && !received.contains(key))
+            .copied()
+            .collect();
+        let state = match (&self.partial_signature, missing.is_empty()) {
+            (Some(_), _) => State::Signed,
+            (None, true) => State::Ready,
+            (None, false) => State::Collecting,
+        };
+        Status {
+            session_id,
+            state,
+            keys: self.keys.clone(),
+            expires_in: timeout.saturating_sub(self.active.elapsed()),
+            first_message: self.first_message.clone(),
+            received,
+            missing,
+        }
+    }
+}
+
+/// The signing sessions of one party, held in memory for the services of `daemon` to drive: a session is created for
+/// a transaction, the first messages of the other parties are submitted to it, and its partial signature is fetched
+/// once they're all in. The transaction is checked against the policy when the session is created and again when it's
+/// signed, whoever calls the daemon is expected to have approved it before fetching the partial signature.
+///
+/// Any number of sessions run side by side, each with its own parties and round. A session nobody made a request for
+/// in the session timeout expires, dropping its secret state.
+pub struct Daemon {
+    keypair: Keypair,
+    net: Network,
+    policy: Policy,
+    audit: AuditLog,
+    session_timeout: Duration,
+    journal_dir: Option<PathBuf>,
+    sessions: Mutex<HashMap<SessionId, HostedSession>>,
+}
+
+impl Daemon {
+    pub fn new(keypair: Keypair, net: Network, policy: Policy, audit: AuditLog) -> Self {
+        Self {
+            keypair,
+            net,
+            policy,
+            audit,
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+            journal_dir: None,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Expire the sessions after `timeout` without any request instead of [`DEFAULT_SESSION_TIMEOUT`]
+    pub fn with_session_timeout(mut self, timeout: Duration) -> Self {
+        self.session_timeout = timeout;
+        self
+    }
+
+    /// Keep the nonce journal of the party in `dir` instead of `~/.solana-tss/nonces`
+    pub fn with_journal_dir(mut self, dir: PathBuf) -> Self {
+        self.journal_dir = Some(dir);
+        self
+    }
+
+    /// The party the daemon signs for
+    pub fn party(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+
+    /// Start a session signing `message` with the aggregated key of `keys`, with a new session ID unless the parties
+    /// agreed on one already, and make the first message of the daemon's party. `to` is the recipient the transaction
+    /// was made for, which the policy checks too.
+    pub fn create_session(
+        &self,
+        session_id: Option<SessionId>,
+        keys: Vec<Pubkey>,
+        message: VersionedMessage,
+        to: Option<Pubkey>,
+    ) -> Result<Status, Error> {
+        if !keys.contains(&self.party()) {
+            return Err(Error::KeyPairIsNotInKeys);
+        }
+        let aggpubkey = tss::aggregate_pubkey(keys.clone())?;
+        tss::check_signer(&message, aggpubkey)?;
+        // Refused before any nonce is made, so none is wasted.
+        self.policy.check(&message, &aggpubkey, to.as_ref(), &self.net)?;
+        let session_id = session_id.unwrap_or_else(SessionId::random);
+        let mut sessions = self.sessions.lock().unwrap();
+        self.expire(&mut sessions)?;
+        if sessions.contains_key(&session_id) {
+            return Err(Error::DaemonSessionExists(session_id));
+        }
+        if sessions.len() >= MAX_SESSIONS {
+            return Err(Error::TooManyDaemonSessions);
+        }
+        let (first_message, secret_state) = tss::step_one(&self.keypair, session_id);
+        self.audit.record(
+            "daemon-create-session",
+            Some(session_id),
+            json!({
+                "transaction": audit::transaction(&message),
+                "sent": audit::message(self.party(), &first_message),
+            }),
+        )?;
+        let session = HostedSession {
+            created: Instant::now(),
+            active: Instant::now(),
+            keys,
+            message,
+            to,
+            first_message,
+            secret_state: Some(secret_state),
+            first_messages: Vec::new(),
+            partial_signature: None,
+        };
+        let status = session.status(session_id, self.session_timeout);
+        sessions.insert(session_id, session);
+        Ok(status)
+    }
+
+    /// Add the first message of another party to its session, as printed, sealed to the daemon's party or not.
+    /// Submitting the same message again changes nothing.
+    pub fn submit_message(&self, session_id: SessionId, message: &str) -> Result<Status, Error> {
+        let message: AggMessage1 = parse_sealable(message)?.open(Some(&self.keypair))?;
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = self.session(&mut sessions, session_id)?;
+        if message.session_id != session_id {
+            return Err(Error::SessionMismatch);
+        }
+        if message.sender == self.party() || !session.keys.contains(&message.sender) {
+            return Err(Error::NotAnotherParty(message.sender));
+        }
+        // The nonces of a party are fixed once submitted, or the partial signature could be made over other ones.
+        match session.first_messages.iter().find(|msg| msg.sender == message.sender) {
+            Some(submitted) if *submitted != message => return Err(Error::FirstMessageConflict(message.sender)),
+            Some(_) => {}
+            None => session.first_messages.push(message),
+        }
+        session.active = Instant::now();
+        Ok(session.status(session_id, self.session_timeout))
+    }
+
+    pub fn status(&self, session_id: SessionId) -> Result<Status, Error> {
+        let mut sessions = self.sessions.lock().unwrap();
+        Ok(self.session(&mut sessions, session_id)?.status(session_id, self.session_timeout))
+    }
+
+    /// Where all the sessions that didn't expire are at, the oldest first
+    pub fn sessions(&self) -> Result<Vec<Status>, Error> {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.expire(&mut sessions)?;
+        let mut hosted: Vec<_> = sessions.iter().collect();
+        hosted.sort_by_key(|(_, session)| session.created);
+        Ok(hosted.into_iter().map(|(session_id, session)| session.status(*session_id, self.session_timeout)).collect())
+    }
+
+    /// Drop the sessions that expired with their secret states, as they're otherwise only dropped when another session
+    /// is created or the sessions are listed. Returns how many there were.
+    pub fn expire_sessions(&self) -> Result<usize, Error> {
+        self.expire(&mut self.sessions.lock().unwrap())
+    }
+
+    /// The partial signature of the session, made the first time it's asked for once all the first messages are in
+    pub fn partial_signature(&self, session_id: SessionId) -> Result<PartialSignature, Error> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = self.session(&mut sessions, session_id)?;
+        session.active = Instant::now();
+        if let Some(sig) = &session.partial_signature {
+            return Ok(sig.clone());
+        }
+        let missing = session.status(session_id, self.session_timeout).missing;
+        if !missing.is_empty() {
+            return Err(Error::MissingFirstMessages(missing));
+        }
+        let aggpubkey = tss::aggregate_pubkey(session.keys.clone())?;
+        // The daily limit may have been spent meanwhile by other sessions of the wallet.
+        let spent = self.policy.check(&session.message, &aggpubkey, session.to.as_ref(), &self.net)?;
+        // Kept in the session until signing succeeded, so a failure leaves it as it was and the journal refuses a retry.
+        let secret_state = session.secret_state.clone().ok_or(Error::NonceAlreadyUsed)?;
+        self.journal()?.consume(&secret_state.public_nonces, false)?;
+        let keypair = Keypair::from_bytes(&self.keypair.to_bytes()).expect("the keypair is valid");
+        let sig = tss::step_two(
+            keypair,
+            session.message.clone(),
+            session.keys.clone(),
+            Vec::new(),
+            session.first_messages.clone(),
+            secret_state,
+        )?;
+        self.policy.record(&aggpubkey, &spent)?;
+        let received: Vec<_> = session.first_messages.iter().map(|msg| audit::message(msg.sender, msg)).collect();
+        self.audit.record(
+            "daemon-partial-signature",
+            Some(session_id),
+            json!({
+                "received": received,
+                "transaction": audit::transaction(&session.message),
+                "sent": audit::message(sig.sender, &sig),
+            }),
+        )?;
+        session.secret_state = None;
+        session.partial_signature = Some(sig.clone());
+        Ok(sig)
+    }
+
+    /// Combine the partial `signatures` of the other parties with the daemon's own, once it signed, into the signed
+    /// transaction of the session
+    pub fn aggregate(&self, session_id: SessionId, signatures: &[String]) -> Result<VersionedTransaction, Error> {
+        let mut signatures = signatures
+            .iter()
+            .map(|sig| parse_sealable::<PartialSignature>(sig)?.open(Some(&self.keypair)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = self.session(&mut sessions, session_id)?;
+        session.active = Instant::now();
+        if let Some(own) = &session.partial_signature {
+            if !signatures.iter().any(|sig| sig.sender == own.sender) {
+                signatures.push(own.clone());
+            }
+        }
+        let received: Vec<_> = signatures.iter().map(|sig| audit::message(sig.sender, sig)).collect();
+        let first_messages = session.first_messages.iter().chain([&session.first_message]).cloned().collect();
+        let aggpubkey = tss::aggregate_pubkey(session.keys.clone())?;
+        let tx = tss::sign_and_broadcast(session.message.clone(), session.keys.clone(), first_messages, signatures)?;
+        self.audit.record(
+            "daemon-aggregate",
+            Some(session_id),
+            json!({
+                "received": received,
+                "transaction": audit::transaction(&session.message),
+                "signature": audit::signature(&tx, aggpubkey)?,
+            }),
+        )?;
+        Ok(tx)
+    }
+
+    fn session<'a>(
+        &self,
+        sessions: &'a mut HashMap<SessionId, HostedSession>,
+        session_id: SessionId,
+    ) -> Result<&'a mut HostedSession, Error> {
+        sessions
+            .get_mut(&session_id)
+            .filter(|session| session.active.elapsed() < self.session_timeout)
+            .ok_or(Error::NoSuchDaemonSession(session_id))
+    }
+
+    fn journal(&self) -> Result<NonceJournal, Error> {
+        let name = self.party().to_string();
+        match &self.journal_dir {
+            Some(dir) => NonceJournal::open_in(dir, &name),
+            None => NonceJournal::open(&name),
+        }
+    }
+
+    fn expire(&self, sessions: &mut HashMap<SessionId, HostedSession>) -> Result<usize, Error> {
+        let expired: Vec<_> = sessions
+            .iter()
+            .filter(|(_, session)| session.active.elapsed() >= self.session_timeout)
+            .map(|(session_id, _)| *session_id)
+            .collect();
+        for session_id in &expired {
+            let session = sessions.remove(session_id).expect("the session is there");
+            let state = session.status(*session_id, self.session_timeout).state;
+            self.audit.record(
+                "daemon-expire-session",
+                Some(*session_id),
+                json!({ "state": format!("{:?}", state) }),
+            )?;
+        }
+        Ok(expired.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::{Message, VersionedMessage};
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::system_instruction;
+
+    use crate::audit::AuditLog;
+    use crate::daemon::{Daemon, State};
+    use crate::journal::NonceJournal;
+    use crate::network::Network;
+    use crate::policy::Policy;
+    use crate::serialization::{Encoding, Serialize, SessionId};
+    use crate::{tss, Error};
+
+    #[test]
+    fn test_daemon() {
+        let journal_dir = std::env::temp_dir().join(format!("solana-tss-daemon-{}", SessionId::random()));
+        let new_daemon = || {
+            Daemon::new(Keypair::new(), Network::Localnet, Policy::default(), AuditLog::new(None))
+                .with_journal_dir(journal_dir.clone())
+        };
+        let daemons: Vec<_> = (0..2).map(|_| new_daemon()).collect();
+        let keys: Vec<_> = daemons.iter().map(Daemon::party).collect();
+        let aggpubkey = tss::aggregate_pubkey(keys.clone()).unwrap();
+        let instruction = system_instruction::transfer(&aggpubkey, &Keypair::new().pubkey(), 1);
+        let message = VersionedMessage::Legacy(Message::new_with_blockhash(
+            &[instruction],
+            Some(&aggpubkey),
+            &Hash::new_unique(),
+        ));
+
+        let first = daemons[0].create_session(None, keys.clone(), message.clone(), None).unwrap();
+        let session_id = first.session_id;
+        assert_eq!(first.state, State::Collecting);
+        assert_eq!(first.missing, vec![keys[1]]);
+        let second = daemons[1].create_session(Some(session_id), keys.clone(), message.clone(), None).unwrap();
+        assert!(matches!(
+            daemons[1].create_session(Some(session_id), keys.clone(), message.clone(), None),
+            Err(Error::DaemonSessionExists(_))
+        ));
+        assert!(matches!(daemons[0].partial_signature(session_id), Err(Error::MissingFirstMessages(_))));
+        // Another session of the same party goes on side by side.
+        let other = daemons[0].create_session(None, keys.clone(), message.clone(), None).unwrap();
+        let listed: Vec<_> = daemons[0].sessions().unwrap().iter().map(|status| status.session_id).collect();
+        assert_eq!(listed, vec![session_id, other.session_id]);
+        assert_eq!(other.keys, keys);
+
+        let status =
+            daemons[0].submit_message(session_id, &second.first_message.serialize_encoded(Encoding::Base64)).unwrap();
+        assert_eq!(status.state, State::Ready);
+        // Its own message isn't one of the others'.
+        assert!(daemons[1].submit_message(session_id, &second.first_message.serialize_encoded(Encoding::Hex)).is_err());
+        daemons[1].submit_message(session_id, &first.first_message.serialize_encoded(Encoding::Bs58)).unwrap();
+
+        let signatures: Vec<_> = daemons.iter().map(|daemon| daemon.partial_signature(session_id).unwrap()).collect();
+        // Fetched again, it's the same partial signature, not one made with the same nonces again.
+        assert!(daemons[0].partial_signature(session_id).unwrap() == signatures[0]);
+        assert_eq!(daemons[0].status(session_id).unwrap().state, State::Signed);
+        let first_messages = vec![first.first_message, second.first_message];
+        let tx = tss::sign_and_broadcast(message, keys, first_messages, signatures).unwrap();
+        assert!(tx.verify_with_results().iter().all(|valid| *valid));
+        assert_eq!(daemons[0].status(other.session_id).unwrap().state, State::Collecting);
+
+        // Signing that fails keeps the session's secret state, and asking again fails the same way rather than panicking.
+        let started = daemons[0].create_session(None, keys.clone(), message.clone(), None).unwrap();
+        let retried = daemons[1].create_session(Some(started.session_id), keys.clone(), message.clone(), None).unwrap();
+        daemons[1]
+            .submit_message(retried.session_id, &started.first_message.serialize_encoded(Encoding::Bs58))
+            .unwrap();
+        let nonces = {
+            let sessions = daemons[1].sessions.lock().unwrap();
+            sessions[&retried.session_id].secret_state.as_ref().unwrap().public_nonces.clone()
+        };
+        NonceJournal::open_in(&journal_dir, &keys[1].to_string()).unwrap().consume(&nonces, false).unwrap();
+        for _ in 0..2 {
+            assert!(matches!(daemons[1].partial_signature(retried.session_id), Err(Error::NonceAlreadyUsed)));
+        }
+        assert_eq!(daemons[1].status(retried.session_id).unwrap().state, State::Ready);
+
+        let daemon = new_daemon().with_session_timeout(Duration::ZERO);
+        let keys = vec![daemon.party(), keys[1]];
+        let aggpubkey = tss::aggregate_pubkey(keys.clone()).unwrap();
+        let instruction = system_instruction::transfer(&aggpubkey, &Keypair::new().pubkey(), 1);
+        let message = VersionedMessage::Legacy(Message::new(&[instruction], Some(&aggpubkey)));
+        let session_id = daemon.create_session(None, keys, message, None).unwrap().session_id;
+        assert!(matches!(daemon.status(session_id), Err(Error::NoSuchDaemonSession(_))));
+        assert_eq!(daemon.expire_sessions().unwrap(), 1);
+        assert!(daemon.sessions().unwrap().is_empty());
+
+        std::fs::remove_dir_all(journal_dir).unwrap();
+    }
+}
@@ -0,0 +1,344 @@
+use curv::elliptic::curves::{Ed25519, Point, Scalar};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Signature, Signer};
+
+use crate::serialization::{sender_signed_bytes, DkgMessage1, DkgMessage2, DkgSecretStepOne, ThresholdKeyShare};
+use crate::threshold::{evaluate_polynomial, hash_to_scalar, lagrange_coefficient, ScalarSigner};
+use crate::Error;
+
+/// Pick a random polynomial and commit to it, the commitments go to all the other parties.
+pub fn step_one(threshold: u16, parties: u16, index: u16) -> Result<(DkgMessage1, DkgSecretStepOne), Error> {
+    if threshold < 2 || threshold > parties {
+        return Err(Error::InvalidThreshold { threshold, parties });
+    }
+    if index == 0 || index > parties {
+        return Err(Error::InvalidPartyIndex { index, parties });
+    }
+    let coefficients: Vec<Scalar<Ed25519>> = (0..threshold).map(|_| Scalar::random()).collect();
+    Ok((commit(index, &coefficients), DkgSecretStepOne { index, parties, coefficients }))
+}
+
+/// Verify the other parties' commitments, and evaluate our polynomial for each one of them.
+pub fn step_two(secret_state: &DkgSecretStepOne, first_messages: &[DkgMessage1]) -> Result<Vec<DkgMessage2>, Error> {
+    verify_first_messages(secret_state, first_messages)?;
+
+    Ok((1..=secret_state.parties)
+        .filter(|&receiver| receiver != secret_state.index)
+        .map(|receiver| deal(secret_state.index, receiver, &secret_state.coefficients))
+        .collect())
+}
+
+/// Verify the shares we received against the senders' commitments and add them up into our key share.
+pub fn step_three(
+    secret_state: DkgSecretStepOne,
+    first_messages: Vec<DkgMessage1>,
+    second_messages: Vec<DkgMessage2>,
+) -> Result<ThresholdKeyShare, Error> {
+    verify_first_messages(&secret_state, &first_messages)?;
+    let index = secret_state.index;
+
+    let mut secret_share = evaluate_polynomial(&secret_state.coefficients, index);
+    let mut group_point = Point::generator() * &secret_state.coefficients[0];
+    for msg1 in &first_messages {
+        let msg2 = second_messages
+            .iter()
+            .find(|msg2| msg2.sender == msg1.index)
+            .ok_or(Error::MissingPartyMessage(msg1.index))?;
+        // Only the party that committed to the polynomial can deal shares of it.
+        if msg2.sender_key != msg1.sender() {
+            return Err(Error::ForgedSender(msg2.sender_key));
+        }
+        if msg2.receiver != index {
+            return Err(Error::WrongRecipient { expected: index, found: msg2.receiver });
+        }
+        if Point::generator() * &msg2.share != evaluate_commitments(&msg1.commitments, index) {
+            return Err(Error::InvalidShare(msg1.index));
+        }
+        secret_share = secret_share + &msg2.share;
+        group_point = group_point + &msg1.commitments[0];
+    }
+
+    Ok(ThresholdKeyShare {
+        index,
+        threshold: secret_state.coefficients.len() as u16,
+        secret_share,
+        group_key: Pubkey::new(&*group_point.to_bytes(true)),
+    })
+}
+
+fn verify_first_messages(secret_state: &DkgSecretStepOne, first_messages: &[DkgMessage1]) -> Result<(), Error> {
+    let mut indices: Vec<_> = first_messages.iter().map(|msg1| msg1.index).collect();
+    indices.push(secret_state.index);
+    indices.sort_unstable();
+    if let Some(w) = indices.windows(2).find(|w| w[0] == w[1]) {
+        return Err(Error::DuplicateSignerIndex(w[0]));
+    }
+    // Every party must take part, or some shares won't ever be dealt.
+    if let Some(missing) = (1..=secret_state.parties).find(|index| indices.binary_search(index).is_err()) {
+        return Err(Error::MissingPartyMessage(missing));
+    }
+    if indices.len() != usize::from(secret_state.parties) {
+        return Err(Error::MismatchMessages);
+    }
+
+    for msg1 in first_messages {
+        if msg1.commitments.len() != secret_state.coefficients.len() {
+            return Err(Error::MismatchMessages);
+        }
+        verify_proof(msg1)?;
+    }
+    Ok(())
+}
+
+/// Re-deal an existing threshold key to a new set of `parties`, the aggregated public key stays the same.
+/// At least `threshold` of the current share holders (the `dealers`) need to run this,
+/// refreshing the shares of a wallet is resharing it to the same parties.
+pub fn reshare_step_one(
+    share: &ThresholdKeyShare,
+    dealers: &[u16],
+    threshold: u16,
+    parties: u16,
+) -> Result<(DkgMessage1, Vec<DkgMessage2>), Error> {
+    if threshold < 2 || threshold > parties {
+        return Err(Error::InvalidThreshold { threshold, parties });
+    }
+    let mut dealers = dealers.to_vec();
+    dealers.sort_unstable();
+    if let Some(w) = dealers.windows(2).find(|w| w[0] == w[1]) {
+        return Err(Error::DuplicateSignerIndex(w[0]));
+    }
+    if dealers.binary_search(&share.index).is_err() {
+        return Err(Error::NotADealer(share.index));
+    }
+    if dealers.len() < usize::from(share.threshold) {
+        return Err(Error::NotEnoughSigners { threshold: share.threshold, found: dealers.len() });
+    }
+
+    // The dealers' constant terms interpolate to the original secret, so the new shares do too.
+    let mut coefficients: Vec<Scalar<Ed25519>> = (0..threshold).map(|_| Scalar::random()).collect();
+    coefficients[0] = lagrange_coefficient(share.index, dealers.iter().copied()) * &share.secret_share;
+
+    let second_messages = (1..=parties).map(|receiver| deal(share.index, receiver, &coefficients)).collect();
+    Ok((commit(share.index, &coefficients), second_messages))
+}
+
+/// Verify the shares dealt to party `index` and add them up into its new key share for `group_key`.
+pub fn reshare_step_two(
+    group_key: Pubkey,
+    index: u16,
+    first_messages: Vec<DkgMessage1>,
+    second_messages: Vec<DkgMessage2>,
+) -> Result<ThresholdKeyShare, Error> {
+    if index == 0 {
+        return Err(Error::InvalidPartyIndex { index, parties: u16::MAX });
+    }
+    let mut dealers: Vec<_> = first_messages.iter().map(|msg1| msg1.index).collect();
+    dealers.sort_unstable();
+    if let Some(w) = dealers.windows(2).find(|w| w[0] == w[1]) {
+        return Err(Error::DuplicateSignerIndex(w[0]));
+    }
+    let threshold = first_messages.first().map_or(0, |msg1| msg1.commitments.len());
+
+    let mut secret_share = Scalar::zero();
+    let mut group_point = Point::zero();
+    for msg1 in &first_messages {
+        if msg1.commitments.len() != threshold {
+            return Err(Error::MismatchMessages);
+        }
+        verify_proof(msg1)?;
+        let msg2 = second_messages
+            .iter()
+            .find(|msg2| msg2.sender == msg1.index)
+            .ok_or(Error::MissingPartyMessage(msg1.index))?;
+        // Only the party that committed to the polynomial can deal shares of it.
+        if msg2.sender_key != msg1.sender() {
+            return Err(Error::ForgedSender(msg2.sender_key));
+        }
+        if msg2.receiver != index {
+            return Err(Error::WrongRecipient { expected: index, found: msg2.receiver });
+        }
+        if Point::generator() * &msg2.share != evaluate_commitments(&msg1.commitments, index) {
+            return Err(Error::InvalidShare(msg1.index));
+        }
+        secret_share = secret_share + &msg2.share;
+        group_point = group_point + &msg1.commitments[0];
+    }
+    // A dealer that dealt anything but its part of the original secret changes the key.
+    if Pubkey::new(&*group_point.to_bytes(true)) != group_key {
+        return Err(Error::GroupKeyMismatch);
+    }
+
+    Ok(ThresholdKeyShare { index, threshold: threshold as u16, secret_share, group_key })
+}
+
+/// Commit to the polynomial, and prove knowledge of its constant term,
+/// so no party can pick its commitment based on the others' (rogue key attack).
+fn commit(index: u16, coefficients: &[Scalar<Ed25519>]) -> DkgMessage1 {
+    let commitments: Vec<_> = coefficients.iter().map(|coefficient| Point::generator() * coefficient).collect();
+
+    let nonce = Scalar::random();
+    let proof_nonce = Point::generator() * &nonce;
+    let challenge = proof_challenge(index, &commitments[0], &proof_nonce);
+    let proof_response = nonce + challenge * &coefficients[0];
+    let mut msg1 =
+        DkgMessage1 { index, commitments, proof_nonce, proof_response, sender_signature: Signature::default() };
+    msg1.sender_signature = ScalarSigner(&coefficients[0]).sign_message(&sender_signed_bytes(&msg1));
+    msg1
+}
+
+/// Evaluate the polynomial for `receiver`, signed with its constant term like the commitments to it
+fn deal(sender: u16, receiver: u16, coefficients: &[Scalar<Ed25519>]) -> DkgMessage2 {
+    let signer = ScalarSigner(&coefficients[0]);
+    let mut msg2 = DkgMessage2 {
+        sender,
+        receiver,
+        share: evaluate_polynomial(coefficients, receiver),
+        sender_key: signer.pubkey(),
+        sender_signature: Signature::default(),
+    };
+    msg2.sender_signature = signer.sign_message(&sender_signed_bytes(&msg2));
+    msg2
+}
+
+fn verify_proof(msg1: &DkgMessage1) -> Result<(), Error> {
+    let challenge = proof_challenge(msg1.index, &msg1.commitments[0], &msg1.proof_nonce);
+    if Point::generator() * &msg1.proof_response != &msg1.proof_nonce + &msg1.commitments[0] * challenge {
+        return Err(Error::InvalidProofOfKnowledge(msg1.index));
+    }
+    Ok(())
+}
+
+fn proof_challenge(index: u16, commitment: &Point<Ed25519>, proof_nonce: &Point<Ed25519>) -> Scalar<Ed25519> {
+    hash_to_scalar(&[
+        b"solana-tss dkg proof of knowledge",
+        &index.to_le_bytes(),
+        &*commitment.to_bytes(true),
+        &*proof_nonce.to_bytes(true),
+    ])
+}
+
+/// Evaluate the polynomial "in the exponent", giving the public counterpart of `evaluate_polynomial`
+fn evaluate_commitments(commitments: &[Point<Ed25519>], x: u16) -> Point<Ed25519> {
+    let x = Scalar::from(x);
+    commitments.iter().rev().fold(Point::zero(), |acc, commitment| acc * &x + commitment)
+}
+
+#[cfg(test)]
+mod tests {
+    use curv::elliptic::curves::Scalar;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::VersionedMessage;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::dkg::{reshare_step_one, reshare_step_two, step_one, step_three, step_two};
+    use crate::serialization::{DkgMessage1, Serialize, SessionId, ThresholdKeyShare};
+    use crate::transaction::create_unsigned_transaction;
+    use crate::{threshold, Error};
+
+    fn clone_serialize<T: Serialize>(t: &T) -> T {
+        let mut v = vec![0u8; t.size_hint()];
+        t.serialize(&mut v);
+        T::deserialize(&v).unwrap()
+    }
+
+    fn others(msgs: &[DkgMessage1], index: u16) -> Vec<DkgMessage1> {
+        msgs.iter().filter(|msg| msg.index != index).map(clone_serialize).collect()
+    }
+
+    fn run_dkg(threshold: u16, parties: u16) -> Vec<ThresholdKeyShare> {
+        let (first_msgs, secrets): (Vec<_>, Vec<_>) =
+            (1..=parties).map(|index| step_one(threshold, parties, index).unwrap()).unzip();
+        let second_msgs: Vec<_> =
+            secrets.iter().flat_map(|secret| step_two(secret, &others(&first_msgs, secret.index)).unwrap()).collect();
+        secrets
+            .into_iter()
+            .map(|secret| {
+                let index = secret.index;
+                let received = second_msgs.iter().filter(|msg| msg.receiver == index).map(clone_serialize).collect();
+                step_three(secret, others(&first_msgs, index), received).unwrap()
+            })
+            .collect()
+    }
+
+    fn reshare(shares: &[ThresholdKeyShare], dealers: &[u16], threshold: u16, parties: u16) -> Vec<ThresholdKeyShare> {
+        let dealing: Vec<_> = shares
+            .iter()
+            .filter(|share| dealers.contains(&share.index))
+            .map(|share| reshare_step_one(share, dealers, threshold, parties).unwrap())
+            .collect();
+        (1..=parties)
+            .map(|index| {
+                let first_msgs = dealing.iter().map(|(msg1, _)| clone_serialize(msg1)).collect();
+                let received = dealing
+                    .iter()
+                    .flat_map(|(_, msgs2)| msgs2.iter().filter(|msg| msg.receiver == index).map(clone_serialize))
+                    .collect();
+                reshare_step_two(shares[0].group_key, index, first_msgs, received).unwrap()
+            })
+            .collect()
+    }
+
+    fn sign(signing: &[&ThresholdKeyShare]) {
+        let to = Keypair::generate(&mut rand07::thread_rng()).pubkey();
+        let mut message = create_unsigned_transaction(0.1, None, &to, None, None, &signing[0].group_key).message;
+        message.recent_blockhash = Hash::new_unique();
+        let message = VersionedMessage::Legacy(message);
+        let session_id = SessionId::random();
+        let (first_msgs, secrets): (Vec<_>, Vec<_>) =
+            signing.iter().map(|share| threshold::step_one(share, session_id)).unzip();
+        let partial_sigs = signing
+            .iter()
+            .zip(secrets)
+            .enumerate()
+            .map(|(i, (share, secret))| {
+                let mut first_msgs: Vec<_> = first_msgs.iter().map(clone_serialize).collect();
+                first_msgs.remove(i);
+                threshold::step_two(clone_serialize(*share), message.clone(), first_msgs, secret).unwrap()
+            })
+            .collect();
+        threshold::sign_and_broadcast(message, signing[0].group_key, partial_sigs).unwrap();
+    }
+
+    #[test]
+    fn test_dkg_then_sign() {
+        let shares = run_dkg(2, 3);
+        assert!(shares.iter().all(|share| share.group_key == shares[0].group_key));
+        sign(&[&shares[0], &shares[2]]);
+    }
+
+    #[test]
+    fn test_refresh_and_reshare() {
+        let shares = run_dkg(2, 3);
+        let refreshed = reshare(&shares, &[1, 2, 3], 2, 3);
+        assert_ne!(refreshed[0].secret_share, shares[0].secret_share);
+        sign(&[&refreshed[0], &refreshed[1]]);
+
+        let reshared = reshare(&refreshed, &[1, 3], 3, 5);
+        assert!(reshared.iter().all(|share| share.group_key == shares[0].group_key && share.threshold == 3));
+        sign(&[&reshared[4], &reshared[0], &reshared[2]]);
+    }
+
+    #[test]
+    fn test_reshare_wrong_secret() {
+        let shares = run_dkg(2, 3);
+        let mut evil = clone_serialize(&shares[1]);
+        evil.secret_share = Scalar::random();
+        let (msg1, msgs2) = reshare_step_one(&shares[0], &[1, 2], 2, 2).unwrap();
+        let (evil_msg1, evil_msgs2) = reshare_step_one(&evil, &[1, 2], 2, 2).unwrap();
+        let received = vec![clone_serialize(&msgs2[0]), clone_serialize(&evil_msgs2[0])];
+        let res = reshare_step_two(shares[0].group_key, 1, vec![msg1, evil_msg1], received);
+        assert!(matches!(res, Err(Error::GroupKeyMismatch)));
+    }
+
+    #[test]
+    fn test_dkg_bad_share() {
+        let (first_msgs, secrets): (Vec<_>, Vec<_>) = (1..=3).map(|index| step_one(2, 3, index).unwrap()).unzip();
+        let mut second_msgs = step_two(&secrets[1], &others(&first_msgs, 2)).unwrap();
+        second_msgs.extend(step_two(&secrets[2], &others(&first_msgs, 3)).unwrap());
+        let mut received: Vec<_> = second_msgs.into_iter().filter(|msg| msg.receiver == 1).collect();
+        received[1].share = Scalar::random();
+
+        let res = step_three(clone_serialize(&secrets[0]), others(&first_msgs, 1), received);
+        assert!(matches!(res, Err(Error::InvalidShare(3))));
+    }
+}
@@ -1,43 +1,968 @@
 use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
 
 use bs58::decode::Error as Bs58Error;
+#[cfg(feature = "client")]
 use solana_client::client_error::ClientError;
+#[cfg(feature = "client")]
+use solana_client::nonblocking::pubsub_client::PubsubClientError;
+#[cfg(feature = "client")]
+use solana_client::nonce_utils::Error as NonceError;
+#[cfg(feature = "client")]
+use solana_remote_wallet::remote_wallet::RemoteWalletError;
+use solana_sdk::hash::Hash;
+use solana_sdk::message::CompileError;
+use solana_sdk::pubkey::{Pubkey, PubkeyError};
+use solana_sdk::signature::Signature;
+use solana_sdk::signer::SignerError;
+use solana_sdk::transaction::TransactionError;
 
-use crate::serialization::Error as DeserializationError;
+use crate::serialization::{Error as DeserializationError, SessionId};
 
+/// Every failure of the library and the command line. Each has a stable [`code`](Error::code) and a
+/// [`Category`] telling callers how to react, which the command line exits with.
 #[derive(Debug)]
 pub enum Error {
     WrongNetwork(String),
+    WrongFormat(String),
+    WrongCommitment(String),
+    WrongProxy(String),
+    #[cfg(feature = "client")]
+    HttpClientFailed(reqwest::Error),
+    #[cfg(feature = "client")]
+    GenesisHashFailed(ClientError),
+    WrongCluster {
+        expected: &'static str,
+        url: String,
+        genesis_hash: Hash,
+    },
     BadBase58(Bs58Error),
     WrongKeyPair(ed25519_dalek::SignatureError),
+    #[cfg(feature = "client")]
     AirdropFailed(ClientError),
+    #[cfg(feature = "client")]
     RecentHashFailed(ClientError),
+    #[cfg(feature = "client")]
+    BlockHashCheckFailed(ClientError),
+    BlockHashExpired(Hash),
+    #[cfg(feature = "client")]
     ConfirmingTransactionFailed(ClientError),
+    TransactionFailed(TransactionError),
+    TransactionDropped(Signature),
+    #[cfg(feature = "client")]
     BalaceFailed(ClientError),
+    #[cfg(feature = "client")]
     SendTransactionFailed(ClientError),
-    DeserializationFailed { error: DeserializationError, field_name: &'static str },
+    #[cfg(feature = "client")]
+    PreflightFailed {
+        error: TransactionError,
+        logs: Vec<String>,
+    },
+    #[cfg(feature = "client")]
+    SimulationFailed(ClientError),
+    DeserializationFailed {
+        error: DeserializationError,
+        field_name: &'static str,
+    },
     MismatchMessages,
     InvalidSignature,
     KeyPairIsNotInKeys,
+    InvalidThreshold {
+        threshold: u16,
+        parties: u16,
+    },
+    NotEnoughSigners {
+        threshold: u16,
+        found: usize,
+    },
+    DuplicateSignerIndex(u16),
+    InvalidPartyIndex {
+        index: u16,
+        parties: u16,
+    },
+    MissingPartyMessage(u16),
+    WrongRecipient {
+        expected: u16,
+        found: u16,
+    },
+    InvalidShare(u16),
+    InvalidProofOfKnowledge(u16),
+    NotADealer(u16),
+    GroupKeyMismatch,
+    MissingProofOfPossession(Pubkey),
+    InvalidProofOfPossession(Pubkey),
+    NoHomeDir,
+    JournalFailed(std::io::Error),
+    NonceAlreadyUsed,
+    NonceAborted,
+    AuditLogFailed(std::io::Error),
+    AuditLogBroken(usize),
+    SessionStoreFailed(std::io::Error),
+    WrongSessionName(String),
+    SessionExists(String),
+    NoSuchSession(String),
+    CorruptSession(String),
+    SessionKeyMismatch(String),
+    SessionMismatch,
+    BlockHashMismatch {
+        party: Pubkey,
+        signed: Hash,
+        expected: Hash,
+    },
+    TransactionDigestMismatch {
+        party: Pubkey,
+        signed: Hash,
+        expected: Hash,
+    },
+    MissingFirstMessage(Pubkey),
+    InvalidPartialSignature(Pubkey),
+    InvalidOwnPartialSignature,
+    PolicyFailed(String),
+    PolicyViolation(String),
+    #[cfg(feature = "client")]
+    RelayFailed(reqwest::Error),
+    RelayProtocol,
+    RelayTimeout,
+    #[cfg(feature = "client")]
+    RelayServerFailed(hyper::Error),
+    P2pFailed(String),
+    MissingSessionId,
+    UnexpectedSender {
+        expected: Pubkey,
+        found: Pubkey,
+    },
+    MessageFileFailed(String),
+    UnexpectedMessageFile {
+        path: PathBuf,
+        reason: String,
+    },
+    MissingMessageFile {
+        sender: Pubkey,
+        dir: PathBuf,
+    },
+    QrFailed(String),
+    BoardTimeout(Pubkey),
+    TransportTimeout,
+    NoSuchDaemonSession(SessionId),
+    DaemonSessionExists(SessionId),
+    TooManyDaemonSessions,
+    NotAnotherParty(Pubkey),
+    FirstMessageConflict(Pubkey),
+    MissingFirstMessages(Vec<Pubkey>),
+    DaemonFailed(String),
+    #[cfg(feature = "client")]
+    DaemonUnreachable(reqwest::Error),
+    InvalidQrFrame(String),
+    NoncePoolFailed(std::io::Error),
+    CorruptNoncePool,
+    NotInNoncePool(SessionId),
+    #[cfg(feature = "client")]
+    MintLookupFailed(ClientError),
+    NotATokenMint(Pubkey),
+    TransferFeeOverflow,
+    MemoRequired(Pubkey),
+    InvalidUnsignedTransaction,
+    InvalidSignedTransaction,
+    WrongTransactionSigner(Pubkey),
+    #[cfg(feature = "client")]
+    NonceAccountFailed(NonceError),
+    InvalidSeed(PubkeyError),
+    #[cfg(feature = "client")]
+    RentFailed(ClientError),
+    #[cfg(feature = "client")]
+    LookupTableFailed(ClientError),
+    NotALookupTable(Pubkey),
+    CompileFailed(CompileError),
+    #[cfg(feature = "client")]
+    FeeFailed(ClientError),
+    InsufficientFunds {
+        balance: u64,
+        needed: u64,
+    },
+    WrongStakeAuthority(String),
+    WrongTokenAuthority(String),
+    WrongAuthority {
+        expected: Pubkey,
+        found: Pubkey,
+    },
+    #[cfg(feature = "client")]
+    AccountLookupFailed(ClientError),
+    #[cfg(feature = "client")]
+    HistoryFailed(ClientError),
+    CorruptHistory(String),
+    TransactionNotFound(Signature),
+    WrongSigner(String),
+    NotASigner(Pubkey),
+    InvalidSignerSignature(Pubkey),
+    MissingSignatures(Vec<Pubkey>),
+    #[cfg(feature = "client")]
+    WatchFailed(PubsubClientError),
+    WrongWordCount(usize),
+    WrongDerivationPath(String),
+    InvalidSeedPhrase,
+    DerivationFailed(String),
+    StdinFailed(std::io::Error),
+    KeypairFileFailed {
+        path: String,
+        error: String,
+    },
+    OutfileExists(PathBuf),
+    MissingKeypair,
+    MissingSecretState,
+    InvalidKeypairJson,
+    InvalidKeystore(String),
+    WrongPassphrase,
+    PassphraseMismatch,
+    EmptyPassphrase,
+    MissingPassphrase,
+    InvalidProtectedSecret(String),
+    InvalidSealedMessage(String),
+    NotSealedToUs,
+    MissingDecryptionKey,
+    InvalidSealRecipient(Pubkey),
+    TooManyRecipients(usize),
+    WrongKeySource(String),
+    #[cfg(feature = "client")]
+    KeychainFailed(String, keyring::Error),
+    NotInKeychain(String),
+    KeychainEntryExists(String),
+    WrongLedgerUri(String),
+    NoLedger,
+    #[cfg(feature = "client")]
+    LedgerFailed(RemoteWalletError),
+    LedgerSignFailed(SignerError),
+    WrongVanityPrefix(String),
+    WrongBackupThreshold {
+        threshold: u8,
+        shares: u8,
+    },
+    InvalidBackupShare(String),
+    NotEnoughBackupShares {
+        needed: u8,
+        given: usize,
+    },
+    MixedBackupShares,
+    DuplicateBackupShare,
+    WatchClosed,
+    WatchProxy,
+    AccountNotFound(Pubkey),
+    NotClosable(Pubkey),
+    NotTheAuthority {
+        account: Pubkey,
+        authority: Pubkey,
+    },
+    TokenAccountNotEmpty(Pubkey),
+    NotAnNft(Pubkey),
+    NotAGovernanceAccount(Pubkey),
+    WrongVote(String),
+    #[cfg(feature = "client")]
+    ProgramAccountFailed(ClientError),
+    NotAProgramBuffer(Pubkey),
+    NotAnUpgradeableProgram(Pubkey),
+    WrongProgramAuthority {
+        account: Pubkey,
+        expected: Pubkey,
+        found: Option<Pubkey>,
+    },
+    ConfigFailed(String),
+    TerminalFailed(std::io::Error),
+    UnknownAlias(String),
+    WrongAlias(String),
+    AliasExists(String),
+    AddressBookFailed(String),
+    InvalidKey(String),
+    NoKeys,
+    NoPartialSignatures,
+    UnattributablePartialSignature,
+}
+
+/// What a failure is about, and so what can be done about it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    /// An argument, key or passphrase given is wrong: fix it and run again
+    Input,
+    /// A message, transaction or file can't be decoded, it was likely copied wrong
+    Serialization,
+    /// The other parties' messages don't add up, or the nonces can't be used: start the session over
+    Protocol,
+    /// The signing policy refuses it, or can't be read
+    Policy,
+    /// The RPC node couldn't be reached or didn't answer, trying again may help
+    Rpc,
+    /// The chain isn't as needed: an account is missing or different, or the transaction failed or expired
+    Chain,
+    /// The messages couldn't be exchanged over a relay, board, daemon or the peer-to-peer network
+    Transport,
+    /// A local file, the keychain or the standard input couldn't be used
+    Storage,
+    /// The Ledger isn't there or refused
+    Device,
+}
+
+impl Category {
+    /// The process exit code of the command line for failures of this category, 1 is left for the unexpected
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Input => 2,
+            Self::Serialization => 3,
+            Self::Protocol => 4,
+            Self::Policy => 5,
+            Self::Rpc => 6,
+            Self::Chain => 7,
+            Self::Transport => 8,
+            Self::Storage => 9,
+            Self::Device => 10,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Input => "input",
+            Self::Serialization => "serialization",
+            Self::Protocol => "protocol",
+            Self::Policy => "policy",
+            Self::Rpc => "rpc",
+            Self::Chain => "chain",
+            Self::Transport => "transport",
+            Self::Storage => "storage",
+            Self::Device => "device",
+        }
+    }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::WrongNetwork(net) => write!(f, "Unrecognized network: {}, please select Mainnet/Testnet/Devnet", net),
+            Self::WrongNetwork(net) => write!(f, "Unrecognized network: {}, please select Mainnet/Testnet/Devnet/Localnet or an http(s) URL", net),
+            Self::WrongProxy(proxy) => {
+                write!(f, "Invalid proxy: {}, please give a URL like socks5h://127.0.0.1:9050 or http://proxy:3128", proxy)
+            }
+            #[cfg(feature = "client")]
+            Self::HttpClientFailed(e) => write!(f, "Failed setting up the HTTP client: {}", e),
+            #[cfg(feature = "client")]
+            Self::GenesisHashFailed(e) => write!(f, "Failed reading the genesis hash of the cluster: {}", e),
+            Self::WrongCluster { expected, url, genesis_hash } => {
+                write!(f, "The RPC node at {} isn't on {}, its genesis hash is {}", url, expected, genesis_hash)
+            }
+            Self::WrongCommitment(commitment) => {
+                write!(f, "Unrecognized commitment: {}, please select processed/confirmed/finalized", commitment)
+            }
+            Self::WrongFormat(format) => write!(f, "Unrecognized format: {}, please select text/json", format),
             Self::BadBase58(e) => write!(f, "Based58 Error: {}", e),
             Self::WrongKeyPair(e) => write!(f, "Failed deserializing keypair: {}", e),
+            #[cfg(feature = "client")]
             Self::AirdropFailed(e) => write!(f, "Failed asking for an airdrop: {}", e),
+            #[cfg(feature = "client")]
             Self::RecentHashFailed(e) => write!(f, "Failed recieving the latest hash: {}", e),
+            #[cfg(feature = "client")]
+            Self::BlockHashCheckFailed(e) => write!(f, "Failed checking the block hash: {}", e),
+            Self::BlockHashExpired(hash) => {
+                write!(f, "The block hash {} expired, all parties must sign again with a recent one", hash)
+            }
+            #[cfg(feature = "client")]
             Self::ConfirmingTransactionFailed(e) => write!(f, "Failed confirming transaction: {}", e),
+            Self::TransactionFailed(e) => write!(f, "The transaction failed: {}", e),
+            Self::TransactionDropped(sig) => {
+                write!(f, "The transaction {} was dropped: its block hash expired before it landed", sig)
+            }
+            #[cfg(feature = "client")]
             Self::BalaceFailed(e) => write!(f, "Failed checking balance: {}", e),
+            #[cfg(feature = "client")]
             Self::SendTransactionFailed(e) => write!(f, "Failed sending transaction: {}", e),
+            #[cfg(feature = "client")]
+            Self::SimulationFailed(e) => write!(f, "Failed simulating transaction: {}", e),
+            #[cfg(feature = "client")]
+            Self::PreflightFailed { error, logs } => {
+                write!(f, "The transaction failed simulation: {}\n{}", error, crate::send::format_logs(logs))
+            }
             Self::DeserializationFailed { error, field_name } => {
                 write!(f, "Failed deserializing {}: {}", field_name, error)
             }
             Self::MismatchMessages => write!(f, "There is a mismatch between first_messages and second_messages"),
             Self::InvalidSignature => write!(f, "The resulting signature doesn't match the transaction"),
             Self::KeyPairIsNotInKeys => write!(f, "The provided keypair is not in the list of pubkeys"),
+            Self::InvalidThreshold { threshold, parties } => {
+                write!(
+                    f,
+                    "Invalid threshold: {}-of-{}, the threshold must be between 2 and the number of parties",
+                    threshold, parties
+                )
+            }
+            Self::NotEnoughSigners { threshold, found } => {
+                write!(f, "Not enough signers, the threshold is {} but only {} are signing", threshold, found)
+            }
+            Self::DuplicateSignerIndex(index) => write!(f, "Party {} appears more than once in the signing set", index),
+            Self::InvalidPartyIndex { index, parties } => {
+                write!(f, "Invalid party index: {}, it must be between 1 and {}", index, parties)
+            }
+            Self::MissingPartyMessage(index) => write!(f, "Missing the message from party {}", index),
+            Self::WrongRecipient { expected, found } => {
+                write!(f, "Received a message meant for party {}, but this is party {}", found, expected)
+            }
+            Self::InvalidShare(index) => write!(f, "The share sent by party {} doesn't match its commitments", index),
+            Self::InvalidProofOfKnowledge(index) => write!(f, "Party {} sent an invalid proof of knowledge", index),
+            Self::NotADealer(index) => write!(f, "Party {} is not in the list of dealers", index),
+            Self::GroupKeyMismatch => write!(f, "The dealt shares don't add up to the aggregated public key"),
+            Self::MissingProofOfPossession(key) => write!(f, "Missing a proof of possession for {}", key),
+            Self::InvalidProofOfPossession(key) => write!(f, "Invalid proof of possession for {}", key),
+            Self::NoHomeDir => write!(f, "Couldn't find the home directory"),
+            Self::JournalFailed(e) => write!(f, "Failed accessing the nonce journal: {}", e),
+            Self::NonceAlreadyUsed => write!(
+                f,
+                "The secret state was already used for signing, signing a different transaction with it would leak the key. \
+                 Start over from step one, or pass `--force` if this is the exact same transaction"
+            ),
+            Self::NonceAborted => write!(
+                f,
+                "The signing session of the secret state was aborted with `agg-abort`, it can't be used anymore. \
+                 Start over from step one"
+            ),
+            Self::AuditLogFailed(e) => write!(f, "Failed accessing the audit log: {}", e),
+            Self::AuditLogBroken(line) => {
+                write!(f, "The hash chain of the audit log is broken at line {}, it was changed after the fact", line)
+            }
+            Self::SessionStoreFailed(e) => write!(f, "Failed accessing the session store: {}", e),
+            Self::WrongSessionName(name) => {
+                write!(f, "{} isn't a valid session name, use only letters, digits, `-` and `_`", name)
+            }
+            Self::SessionExists(name) => write!(f, "There's already a session named {}", name),
+            Self::NoSuchSession(name) => write!(f, "There's no session named {}, `session-list` lists them", name),
+            Self::CorruptSession(name) => write!(f, "The session {} is corrupted", name),
+            Self::SessionKeyMismatch(name) => {
+                write!(f, "The session {} was started with a different key, or is corrupted", name)
+            }
+            Self::SessionMismatch => {
+                write!(f, "The messages are from different signing sessions, all parties must use the same `--session-id`")
+            }
+            Self::BlockHashMismatch { party, signed, expected } => write!(
+                f,
+                "{} signed the block hash {}, but the transaction has {}, all parties must sign the same block hash",
+                party, signed, expected
+            ),
+            Self::TransactionDigestMismatch { party, signed, expected } => write!(
+                f,
+                "{} signed a transaction with digest {}, but this one has {}, check all parties used the same amount, \
+                 recipient, memo and keys",
+                party, signed, expected
+            ),
+            Self::MissingFirstMessage(key) => write!(f, "Missing the first message of {}", key),
+            Self::InvalidPartialSignature(key) => write!(f, "The partial signature of {} is invalid", key),
+            Self::PolicyFailed(e) => write!(f, "Failed reading the signing policy: {}", e),
+            Self::PolicyViolation(rule) => write!(f, "Refusing to sign, the signing policy says {}", rule),
+            #[cfg(feature = "client")]
+            Self::RelayFailed(e) => write!(f, "Failed reaching the relay: {}", e),
+            Self::RelayProtocol => write!(f, "The relay answered with something that isn't a list of messages"),
+            Self::RelayTimeout => write!(f, "Timed out waiting for the messages of the other parties on the relay"),
+            #[cfg(feature = "client")]
+            Self::RelayServerFailed(e) => write!(f, "The relay server failed: {}", e),
+            Self::P2pFailed(e) => write!(f, "Failed joining the peer-to-peer network: {}", e),
+            Self::MissingSessionId => write!(f, "Pass the session ID of step 1 with `--session-id`"),
+            Self::UnexpectedSender { expected, found } => {
+                write!(f, "Expected the message of {}, but it was sent by {}", expected, found)
+            }
+            Self::MessageFileFailed(e) => write!(f, "Failed exchanging the message files: {}", e),
+            Self::UnexpectedMessageFile { path, reason } => {
+                write!(f, "Unexpected message file {}: {}, remove it if it doesn't belong there", path.display(), reason)
+            }
+            Self::MissingMessageFile { sender, dir } => {
+                write!(f, "The message of {} isn't in {} yet", sender, dir.display())
+            }
+            Self::QrFailed(e) => write!(f, "Failed drawing the QR code: {}", e),
+            Self::TransportTimeout => write!(f, "Timed out waiting for the messages of the other parties"),
+            Self::BoardTimeout(address) => {
+                write!(f, "Timed out waiting for the messages of the other parties on the board {}", address)
+            }
+            Self::InvalidQrFrame(frame) => write!(f, "Invalid QR code frame: {}", frame),
+            Self::NoSuchDaemonSession(session_id) => {
+                write!(f, "The daemon has no session {}, it was never created or it expired", session_id)
+            }
+            Self::DaemonSessionExists(session_id) => write!(f, "The daemon already has a session {}", session_id),
+            Self::TooManyDaemonSessions => write!(f, "The daemon holds too many sessions, try again once some expire"),
+            Self::NotAnotherParty(key) => write!(f, "{} isn't one of the other parties of the session", key),
+            Self::FirstMessageConflict(key) => {
+                write!(f, "A different first message of {} was submitted to the session already", key)
+            }
+            Self::MissingFirstMessages(keys) => {
+                let keys: Vec<_> = keys.iter().map(ToString::to_string).collect();
+                write!(f, "The first messages of {} weren't submitted yet", keys.join(", "))
+            }
+            Self::DaemonFailed(e) => write!(f, "The daemon failed: {}", e),
+            #[cfg(feature = "client")]
+            Self::DaemonUnreachable(e) => write!(f, "Failed reaching the daemon: {}", e),
+            Self::InvalidOwnPartialSignature => {
+                write!(f, "The partial signature came out invalid so it wasn't sent, the machine signing may be faulty")
+            }
+            Self::NoncePoolFailed(e) => write!(f, "Failed accessing the nonce pool: {}", e),
+            Self::CorruptNoncePool => write!(f, "The nonce pool is corrupted, or was made with a different key"),
+            Self::NotInNoncePool(session_id) => write!(f, "The nonce pool has no nonces left for session {}", session_id),
+            #[cfg(feature = "client")]
+            Self::MintLookupFailed(e) => write!(f, "Failed looking up the token mint: {}", e),
+            Self::NotATokenMint(mint) => write!(f, "{} is not an SPL token mint", mint),
+            Self::TransferFeeOverflow => write!(f, "The transfer fee of the token overflows for this amount"),
+            Self::MemoRequired(to) => {
+                write!(f, "The token account of {} only accepts transfers with a memo, pass one with `--memo`", to)
+            }
+            Self::InvalidUnsignedTransaction => write!(f, "The unsigned transaction isn't a base64 transaction or message"),
+            Self::InvalidSignedTransaction => write!(f, "The signed transaction isn't a fully signed base64 transaction"),
+            Self::WrongTransactionSigner(key) => write!(f, "The transaction must have {} as one of its signers", key),
+            #[cfg(feature = "client")]
+            Self::NonceAccountFailed(e) => write!(f, "Failed reading the nonce account: {}", e),
+            Self::InvalidSeed(e) => write!(f, "Invalid seed for the derived address: {}", e),
+            #[cfg(feature = "client")]
+            Self::RentFailed(e) => write!(f, "Failed looking up the rent exemption: {}", e),
+            #[cfg(feature = "client")]
+            Self::LookupTableFailed(e) => write!(f, "Failed reading the address lookup table: {}", e),
+            Self::NotALookupTable(key) => write!(f, "{} is not an address lookup table with an authority", key),
+            Self::CompileFailed(e) => write!(f, "Failed compiling the transaction: {}", e),
+            #[cfg(feature = "client")]
+            Self::FeeFailed(e) => write!(f, "Failed looking up the transaction fee: {}", e),
+            Self::WrongStakeAuthority(authority) => {
+                write!(f, "Unrecognized authority: {}, please select staker/withdrawer", authority)
+            }
+            Self::WrongTokenAuthority(authority) => {
+                write!(f, "Unrecognized authority: {}, please select mint/freeze", authority)
+            }
+            Self::WrongAuthority { expected, found } => {
+                write!(f, "The authority is {}, the given keypair is {}", expected, found)
+            }
+            #[cfg(feature = "client")]
+            Self::AccountLookupFailed(e) => write!(f, "Failed reading the accounts: {}", e),
+            #[cfg(feature = "client")]
+            Self::HistoryFailed(e) => write!(f, "Failed reading the transaction history: {}", e),
+            Self::CorruptHistory(sig) => write!(f, "The RPC node returned the transaction {} corrupted", sig),
+            Self::TransactionNotFound(sig) => write!(
+                f,
+                "The transaction {} wasn't found, it didn't land, was dropped or is older than the RPC node's history",
+                sig
+            ),
+            Self::WrongSigner(s) => write!(f, "Invalid signer: {}, please give it as PUBKEY=SIGNATURE", s),
+            Self::NotASigner(key) => write!(f, "{} isn't a signer of the transaction", key),
+            Self::InvalidSignerSignature(key) => write!(f, "The signature of {} doesn't match the transaction", key),
+            Self::MissingSignatures(keys) => {
+                let keys: Vec<_> = keys.iter().map(ToString::to_string).collect();
+                write!(
+                    f,
+                    "The transaction is missing the signatures of {}, pass them with `--signer` or use `--sign-only`",
+                    keys.join(", ")
+                )
+            }
+            #[cfg(feature = "client")]
+            Self::WatchFailed(e) => write!(f, "Failed subscribing to the RPC node's websocket: {}", e),
+            Self::WrongWordCount(n) => write!(f, "A seed phrase can't have {} words, only 12/15/18/21/24", n),
+            Self::WrongDerivationPath(path) => {
+                write!(f, "Invalid derivation path: {}, please give one like m/44'/501'/0'/0'", path)
+            }
+            Self::InvalidSeedPhrase => write!(f, "The seed phrase isn't a valid English BIP39 phrase, check its words"),
+            Self::DerivationFailed(e) => write!(f, "Failed deriving the key from the seed: {}", e),
+            Self::StdinFailed(e) => write!(f, "Failed reading the standard input: {}", e),
+            Self::KeypairFileFailed { path, error } => write!(f, "Failed reading the keypair file {}: {}", path, error),
+            Self::MissingKeypair => write!(
+                f,
+                "No secret key given and no terminal to prompt for it, pass one with `--keypair-file`, `--keypair-stdin` or the SOLANA_TSS_KEYPAIR environment variable"
+            ),
+            Self::MissingSecretState => {
+                write!(f, "No secret state given and no terminal to prompt for it, pass it with `--secret-state`")
+            }
+            Self::InvalidKeypairJson => write!(f, "The keypair isn't a JSON array of 64 bytes like solana-keygen's id.json"),
+            Self::InvalidKeystore(e) => write!(f, "The keystore is invalid: {}", e),
+            Self::WrongPassphrase => write!(f, "Wrong passphrase, the keystore or secret state can't be decrypted with it"),
+            Self::PassphraseMismatch => write!(f, "The passphrases don't match"),
+            Self::EmptyPassphrase => write!(f, "The passphrase can't be empty, anyone could decrypt the keystore"),
+            Self::MissingPassphrase => write!(f, "No terminal to prompt for the passphrase"),
+            Self::InvalidProtectedSecret(e) => write!(f, "The protected secret state is invalid: {}", e),
+            Self::InvalidSealedMessage(e) => write!(f, "The sealed message is invalid: {}", e),
+            Self::NotSealedToUs => write!(f, "The message is sealed, but not to this key"),
+            Self::MissingDecryptionKey => {
+                write!(f, "The message is sealed, pass the keypair of one of its recipients with `--decryption-keypair`")
+            }
+            Self::InvalidSealRecipient(key) => write!(f, "Can't seal a message to {}, it isn't a valid ed25519 public key", key),
+            Self::TooManyRecipients(n) => write!(f, "Can't seal a message to {} recipients, the most is 255", n),
+            Self::WrongKeySource(s) => write!(f, "Invalid key source: {}, please give one like keychain:<name>", s),
+            #[cfg(feature = "client")]
+            Self::KeychainFailed(name, e) => write!(f, "Failed accessing keychain:{} in the OS keychain: {}", name, e),
+            Self::NotInKeychain(name) => write!(f, "There's no keychain:{} in the OS keychain", name),
+            Self::KeychainEntryExists(name) => {
+                write!(f, "keychain:{} already exists, refusing to overwrite a secret that may not be backed up", name)
+            }
+            Self::WrongLedgerUri(uri) => {
+                write!(f, "Invalid Ledger key: {}, please give one like usb://ledger?key=0", uri)
+            }
+            Self::NoLedger => write!(f, "No Ledger found, connect it and open the Solana app"),
+            #[cfg(feature = "client")]
+            Self::LedgerFailed(e) => write!(f, "Failed talking to the Ledger: {}", e),
+            Self::LedgerSignFailed(e) => write!(f, "The Ledger didn't sign the transaction: {}", e),
+            Self::WrongVanityPrefix(prefix) => {
+                write!(f, "No address starts with {}, Base58 has no 0, O, I and l", prefix)
+            }
+            Self::WrongBackupThreshold { threshold, shares } => {
+                write!(f, "Can't split a key into {} shares needing {} of them to restore it", shares, threshold)
+            }
+            Self::InvalidBackupShare(e) => write!(f, "Invalid backup share: {}", e),
+            Self::NotEnoughBackupShares { needed, given } => {
+                write!(f, "Restoring the key needs {} backup shares, only {} were given", needed, given)
+            }
+            Self::MixedBackupShares => write!(f, "The backup shares come from different backups"),
+            Self::DuplicateBackupShare => write!(f, "The same backup share was given twice"),
+            Self::OutfileExists(path) => {
+                write!(f, "{} already exists, refusing to overwrite a secret key that may not be backed up", path.display())
+            }
+            Self::WatchClosed => write!(f, "The RPC node closed the websocket"),
+            Self::WatchProxy => {
+                write!(f, "The websocket can't go through the proxy, watching would reveal your IP address to the RPC node")
+            }
+            Self::AccountNotFound(key) => write!(f, "The account {} doesn't exist", key),
+            Self::NotClosable(key) => write!(f, "{} is not a token, nonce or stake account", key),
+            Self::NotTheAuthority { account, authority } => {
+                write!(f, "The authority of {} is {}, not the wallet", account, authority)
+            }
+            Self::TokenAccountNotEmpty(key) => {
+                write!(f, "The token account {} isn't empty, pass `--burn` to burn the tokens left", key)
+            }
+            Self::NotAnNft(key) => write!(f, "{} is not the mint of an NFT with Token Metadata", key),
+            Self::NotAGovernanceAccount(key) => write!(f, "{} is not the expected SPL Governance account", key),
+            Self::WrongVote(vote) => write!(f, "Unrecognized vote: {}, please select yes/no/abstain/veto", vote),
+            Self::InsufficientFunds { balance, needed } => {
+                write!(f, "The balance of {} lamports doesn't cover the {} lamports of fees", balance, needed)
+            }
+            #[cfg(feature = "client")]
+            Self::ProgramAccountFailed(e) => write!(f, "Failed reading the program account: {}", e),
+            Self::NotAProgramBuffer(key) => write!(f, "{} is not a program buffer", key),
+            Self::NotAnUpgradeableProgram(key) => write!(f, "{} is not an upgradeable program", key),
+            Self::WrongProgramAuthority { account, expected, found: Some(found) } => {
+                write!(f, "The authority of {} is {}, not {}", account, found, expected)
+            }
+            Self::WrongProgramAuthority { account, expected, found: None } => {
+                write!(f, "{} is immutable, {} can't use it", account, expected)
+            }
+            Self::ConfigFailed(e) => write!(f, "Failed reading the configuration: {}", e),
+            Self::TerminalFailed(e) => write!(f, "Failed using the terminal: {}", e),
+            Self::UnknownAlias(alias) => write!(f, "{} is neither an address nor an alias of the address book", alias),
+            Self::WrongAlias(alias) => {
+                write!(f, "Invalid alias: {}, it can't be empty, have spaces or be an address itself", alias)
+            }
+            Self::AliasExists(alias) => write!(f, "The address book already has {}, remove it first", alias),
+            Self::AddressBookFailed(e) => write!(f, "Failed reading or writing the address book: {}", e),
+            Self::InvalidKey(key) => write!(f, "Invalid key {}", key),
+            Self::NoKeys => write!(f, "No keys were given, the aggregated key needs at least one"),
+            Self::NoPartialSignatures => write!(f, "No partial signatures were given, there's nothing to aggregate"),
+            Self::UnattributablePartialSignature => write!(
+                f,
+                "The combined signature is invalid, one of the parties of a nested key signed wrong but which can't be told, start the session over"
+            ),
+        }
+    }
+}
+
+impl Error {
+    /// The number identifying the failure, its thousands the category. A code is never reused or renumbered, the
+    /// wording of the message may change.
+    pub fn code(&self) -> u16 {
+        match self {
+            // Input
+            Self::WrongNetwork(..) => 1001,
+            Self::WrongFormat(..) => 1002,
+            Self::WrongCommitment(..) => 1003,
+            Self::WrongProxy(..) => 1004,
+            Self::WrongKeyPair(..) => 1005,
+            Self::KeyPairIsNotInKeys => 1006,
+            Self::InvalidThreshold { .. } => 1007,
+            Self::InvalidPartyIndex { .. } => 1008,
+            Self::NotEnoughSigners { .. } => 1009,
+            Self::DuplicateSignerIndex(..) => 1010,
+            Self::WrongSessionName(..) => 1011,
+            Self::MissingSessionId => 1012,
+            Self::WrongTransactionSigner(..) => 1013,
+            Self::InvalidSeed(..) => 1014,
+            Self::WrongStakeAuthority(..) => 1015,
+            Self::WrongTokenAuthority(..) => 1016,
+            Self::WrongAuthority { .. } => 1017,
+            Self::WrongSigner(..) => 1018,
+            Self::NotASigner(..) => 1019,
+            Self::InvalidSignerSignature(..) => 1020,
+            Self::MissingSignatures(..) => 1021,
+            Self::WrongWordCount(..) => 1022,
+            Self::WrongDerivationPath(..) => 1023,
+            Self::InvalidSeedPhrase => 1024,
+            Self::DerivationFailed(..) => 1025,
+            Self::MissingKeypair => 1026,
+            Self::MissingSecretState => 1027,
+            Self::WrongPassphrase => 1028,
+            Self::PassphraseMismatch => 1029,
+            Self::EmptyPassphrase => 1030,
+            Self::MissingPassphrase => 1031,
+            Self::MissingDecryptionKey => 1032,
+            Self::InvalidSealRecipient(..) => 1033,
+            Self::TooManyRecipients(..) => 1034,
+            Self::NotSealedToUs => 1035,
+            Self::WrongKeySource(..) => 1036,
+            Self::WrongLedgerUri(..) => 1037,
+            Self::WrongVanityPrefix(..) => 1038,
+            Self::WrongBackupThreshold { .. } => 1039,
+            Self::InvalidBackupShare(..) => 1040,
+            Self::NotEnoughBackupShares { .. } => 1041,
+            Self::MixedBackupShares => 1042,
+            Self::DuplicateBackupShare => 1043,
+            Self::OutfileExists(..) => 1044,
+            Self::WrongVote(..) => 1045,
+            Self::MemoRequired(..) => 1046,
+            Self::TransferFeeOverflow => 1047,
+            Self::WatchProxy => 1048,
+            Self::ConfigFailed(..) => 1049,
+            Self::UnknownAlias(..) => 1050,
+            Self::WrongAlias(..) => 1051,
+            Self::InvalidKey(..) => 1052,
+            Self::NoKeys => 1053,
+            // Serialization
+            Self::BadBase58(..) => 2001,
+            Self::DeserializationFailed { .. } => 2002,
+            Self::InvalidUnsignedTransaction => 2003,
+            Self::InvalidSignedTransaction => 2004,
+            Self::InvalidKeypairJson => 2005,
+            Self::InvalidKeystore(..) => 2006,
+            Self::InvalidProtectedSecret(..) => 2007,
+            Self::InvalidSealedMessage(..) => 2008,
+            Self::InvalidQrFrame(..) => 2009,
+            Self::QrFailed(..) => 2010,
+            Self::CompileFailed(..) => 2011,
+            // Protocol
+            Self::MismatchMessages => 3001,
+            Self::InvalidSignature => 3002,
+            Self::MissingPartyMessage(..) => 3003,
+            Self::WrongRecipient { .. } => 3004,
+            Self::InvalidShare(..) => 3005,
+            Self::InvalidProofOfKnowledge(..) => 3006,
+            Self::NotADealer(..) => 3007,
+            Self::GroupKeyMismatch => 3008,
+            Self::MissingProofOfPossession(..) => 3009,
+            Self::InvalidProofOfPossession(..) => 3010,
+            Self::NonceAlreadyUsed => 3011,
+            Self::NonceAborted => 3012,
+            Self::SessionMismatch => 3013,
+            Self::BlockHashMismatch { .. } => 3014,
+            Self::TransactionDigestMismatch { .. } => 3015,
+            Self::MissingFirstMessage(..) => 3016,
+            Self::InvalidPartialSignature(..) => 3017,
+            Self::InvalidOwnPartialSignature => 3018,
+            Self::UnexpectedSender { .. } => 3019,
+            Self::NotAnotherParty(..) => 3020,
+            Self::FirstMessageConflict(..) => 3021,
+            Self::MissingFirstMessages(..) => 3022,
+            Self::NotInNoncePool(..) => 3023,
+            Self::NoPartialSignatures => 3024,
+            Self::UnattributablePartialSignature => 3025,
+            // Policy
+            Self::PolicyFailed(..) => 4001,
+            Self::PolicyViolation(..) => 4002,
+            // Rpc
+            #[cfg(feature = "client")]
+            Self::HttpClientFailed(..) => 5001,
+            #[cfg(feature = "client")]
+            Self::GenesisHashFailed(..) => 5002,
+            Self::WrongCluster { .. } => 5003,
+            #[cfg(feature = "client")]
+            Self::AirdropFailed(..) => 5004,
+            #[cfg(feature = "client")]
+            Self::RecentHashFailed(..) => 5005,
+            #[cfg(feature = "client")]
+            Self::BlockHashCheckFailed(..) => 5006,
+            #[cfg(feature = "client")]
+            Self::ConfirmingTransactionFailed(..) => 5007,
+            #[cfg(feature = "client")]
+            Self::BalaceFailed(..) => 5008,
+            #[cfg(feature = "client")]
+            Self::SendTransactionFailed(..) => 5009,
+            #[cfg(feature = "client")]
+            Self::SimulationFailed(..) => 5010,
+            #[cfg(feature = "client")]
+            Self::MintLookupFailed(..) => 5011,
+            #[cfg(feature = "client")]
+            Self::NonceAccountFailed(..) => 5012,
+            #[cfg(feature = "client")]
+            Self::RentFailed(..) => 5013,
+            #[cfg(feature = "client")]
+            Self::LookupTableFailed(..) => 5014,
+            #[cfg(feature = "client")]
+            Self::FeeFailed(..) => 5015,
+            #[cfg(feature = "client")]
+            Self::AccountLookupFailed(..) => 5016,
+            #[cfg(feature = "client")]
+            Self::HistoryFailed(..) => 5017,
+            Self::CorruptHistory(..) => 5018,
+            #[cfg(feature = "client")]
+            Self::WatchFailed(..) => 5019,
+            Self::WatchClosed => 5020,
+            #[cfg(feature = "client")]
+            Self::ProgramAccountFailed(..) => 5021,
+            // Chain
+            Self::BlockHashExpired(..) => 6001,
+            Self::TransactionFailed(..) => 6002,
+            Self::TransactionDropped(..) => 6003,
+            #[cfg(feature = "client")]
+            Self::PreflightFailed { .. } => 6004,
+            Self::TransactionNotFound(..) => 6005,
+            Self::InsufficientFunds { .. } => 6006,
+            Self::NotATokenMint(..) => 6007,
+            Self::NotALookupTable(..) => 6008,
+            Self::AccountNotFound(..) => 6009,
+            Self::NotClosable(..) => 6010,
+            Self::NotTheAuthority { .. } => 6011,
+            Self::TokenAccountNotEmpty(..) => 6012,
+            Self::NotAnNft(..) => 6013,
+            Self::NotAGovernanceAccount(..) => 6014,
+            Self::NotAProgramBuffer(..) => 6015,
+            Self::NotAnUpgradeableProgram(..) => 6016,
+            Self::WrongProgramAuthority { .. } => 6017,
+            // Transport
+            #[cfg(feature = "client")]
+            Self::RelayFailed(..) => 7001,
+            Self::RelayProtocol => 7002,
+            Self::RelayTimeout => 7003,
+            #[cfg(feature = "client")]
+            Self::RelayServerFailed(..) => 7004,
+            Self::P2pFailed(..) => 7005,
+            Self::MessageFileFailed(..) => 7006,
+            Self::UnexpectedMessageFile { .. } => 7007,
+            Self::MissingMessageFile { .. } => 7008,
+            Self::BoardTimeout(..) => 7009,
+            Self::TransportTimeout => 7010,
+            Self::NoSuchDaemonSession(..) => 7011,
+            Self::DaemonSessionExists(..) => 7012,
+            Self::TooManyDaemonSessions => 7013,
+            Self::DaemonFailed(..) => 7014,
+            #[cfg(feature = "client")]
+            Self::DaemonUnreachable(..) => 7015,
+            // Storage
+            Self::NoHomeDir => 8001,
+            Self::JournalFailed(..) => 8002,
+            Self::AuditLogFailed(..) => 8003,
+            Self::AuditLogBroken(..) => 8004,
+            Self::SessionStoreFailed(..) => 8005,
+            Self::SessionExists(..) => 8006,
+            Self::NoSuchSession(..) => 8007,
+            Self::CorruptSession(..) => 8008,
+            Self::SessionKeyMismatch(..) => 8009,
+            Self::NoncePoolFailed(..) => 8010,
+            Self::CorruptNoncePool => 8011,
+            Self::StdinFailed(..) => 8012,
+            Self::KeypairFileFailed { .. } => 8013,
+            #[cfg(feature = "client")]
+            Self::KeychainFailed(..) => 8014,
+            Self::NotInKeychain(..) => 8015,
+            Self::KeychainEntryExists(..) => 8016,
+            Self::TerminalFailed(..) => 8017,
+            Self::AliasExists(..) => 8018,
+            Self::AddressBookFailed(..) => 8019,
+            // Device
+            Self::NoLedger => 9001,
+            #[cfg(feature = "client")]
+            Self::LedgerFailed(..) => 9002,
+            Self::LedgerSignFailed(..) => 9003,
+        }
+    }
+
+    pub fn category(&self) -> Category {
+        match self.code() / 1000 {
+            1 => Category::Input,
+            2 => Category::Serialization,
+            3 => Category::Protocol,
+            4 => Category::Policy,
+            5 => Category::Rpc,
+            6 => Category::Chain,
+            7 => Category::Transport,
+            8 => Category::Storage,
+            _ => Category::Device,
+        }
+    }
+
+    /// The details of the failure a program may act on: the field, party, account, session or RPC request involved
+    pub fn context(&self) -> Vec<(&'static str, String)> {
+        let rpc_request = |request: &str| vec![("rpc_request", request.to_string())];
+        match self {
+            Self::DeserializationFailed { field_name, .. } => vec![("field", field_name.to_string())],
+            #[cfg(feature = "client")]
+            Self::GenesisHashFailed(_) => rpc_request("genesis_hash"),
+            Self::WrongCluster { url, .. } => vec![("url", url.clone())],
+            #[cfg(feature = "client")]
+            Self::AirdropFailed(_) => rpc_request("airdrop"),
+            #[cfg(feature = "client")]
+            Self::RecentHashFailed(_) => rpc_request("latest_blockhash"),
+            #[cfg(feature = "client")]
+            Self::BlockHashCheckFailed(_) => rpc_request("blockhash_validity"),
+            #[cfg(feature = "client")]
+            Self::ConfirmingTransactionFailed(_) => rpc_request("signature_statuses"),
+            #[cfg(feature = "client")]
+            Self::BalaceFailed(_) => rpc_request("balance"),
+            #[cfg(feature = "client")]
+            Self::SendTransactionFailed(_) | Self::PreflightFailed { .. } => rpc_request("send_transaction"),
+            #[cfg(feature = "client")]
+            Self::SimulationFailed(_) => rpc_request("simulate_transaction"),
+            #[cfg(feature = "client")]
+            Self::MintLookupFailed(_) => rpc_request("mint"),
+            #[cfg(feature = "client")]
+            Self::NonceAccountFailed(_) => rpc_request("nonce_account"),
+            #[cfg(feature = "client")]
+            Self::RentFailed(_) => rpc_request("rent"),
+            #[cfg(feature = "client")]
+            Self::LookupTableFailed(_) => rpc_request("lookup_table"),
+            #[cfg(feature = "client")]
+            Self::FeeFailed(_) => rpc_request("fee"),
+            #[cfg(feature = "client")]
+            Self::AccountLookupFailed(_) => rpc_request("accounts"),
+            #[cfg(feature = "client")]
+            Self::HistoryFailed(_) => rpc_request("history"),
+            #[cfg(feature = "client")]
+            Self::WatchFailed(_) => rpc_request("subscribe"),
+            #[cfg(feature = "client")]
+            Self::ProgramAccountFailed(_) => rpc_request("program_account"),
+            Self::MissingPartyMessage(index)
+            | Self::InvalidShare(index)
+            | Self::InvalidProofOfKnowledge(index)
+            | Self::NotADealer(index)
+            | Self::DuplicateSignerIndex(index) => vec![("party_index", index.to_string())],
+            Self::MissingProofOfPossession(party)
+            | Self::InvalidProofOfPossession(party)
+            | Self::BlockHashMismatch { party, .. }
+            | Self::TransactionDigestMismatch { party, .. }
+            | Self::MissingFirstMessage(party)
+            | Self::InvalidPartialSignature(party)
+            | Self::UnexpectedSender { found: party, .. }
+            | Self::MissingMessageFile { sender: party, .. }
+            | Self::NotAnotherParty(party)
+            | Self::FirstMessageConflict(party) => vec![("party", party.to_string())],
+            Self::MissingFirstMessages(parties) => parties.iter().map(|party| ("party", party.to_string())).collect(),
+            Self::MissingSignatures(signers) => signers.iter().map(|signer| ("signer", signer.to_string())).collect(),
+            Self::NotASigner(signer) | Self::InvalidSignerSignature(signer) => vec![("signer", signer.to_string())],
+            Self::NoSuchDaemonSession(session_id)
+            | Self::DaemonSessionExists(session_id)
+            | Self::NotInNoncePool(session_id) => vec![("session_id", session_id.to_string())],
+            Self::SessionExists(name)
+            | Self::NoSuchSession(name)
+            | Self::CorruptSession(name)
+            | Self::SessionKeyMismatch(name)
+            | Self::WrongSessionName(name) => vec![("session", name.clone())],
+            Self::TransactionDropped(sig) | Self::TransactionNotFound(sig) => vec![("signature", sig.to_string())],
+            Self::AccountNotFound(account)
+            | Self::NotClosable(account)
+            | Self::NotTheAuthority { account, .. }
+            | Self::TokenAccountNotEmpty(account)
+            | Self::NotAnNft(account)
+            | Self::NotATokenMint(account)
+            | Self::NotALookupTable(account)
+            | Self::NotAGovernanceAccount(account)
+            | Self::NotAProgramBuffer(account)
+            | Self::NotAnUpgradeableProgram(account)
+            | Self::WrongProgramAuthority { account, .. } => vec![("account", account.to_string())],
+            Self::KeypairFileFailed { path, .. } => vec![("path", path.clone())],
+            Self::OutfileExists(path) | Self::UnexpectedMessageFile { path, .. } => {
+                vec![("path", path.display().to_string())]
+            }
+            #[cfg(feature = "client")]
+            Self::KeychainFailed(name, _) => vec![("keychain", name.clone())],
+            Self::NotInKeychain(name) | Self::KeychainEntryExists(name) => vec![("keychain", name.clone())],
+            Self::UnknownAlias(alias) | Self::WrongAlias(alias) | Self::AliasExists(alias) => {
+                vec![("alias", alias.clone())]
+            }
+            _ => Vec::new(),
         }
     }
 }
@@ -55,3 +980,37 @@ impl From<ed25519_dalek::SignatureError> for Error {
 }
 
 impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::hash::Hash;
+    use solana_sdk::pubkey::Pubkey;
+
+    use crate::error::Category;
+    use crate::serialization::SessionId;
+    use crate::Error;
+
+    #[test]
+    fn test_error_codes() {
+        let party = Pubkey::new_unique();
+        let errors = [
+            (Error::WrongNetwork("moon".to_string()), 1001, Category::Input),
+            (Error::InvalidUnsignedTransaction, 2003, Category::Serialization),
+            (Error::MissingFirstMessage(party), 3016, Category::Protocol),
+            (Error::PolicyViolation("no".to_string()), 4002, Category::Policy),
+            (Error::BlockHashExpired(Hash::new_unique()), 6001, Category::Chain),
+            (Error::NoSuchDaemonSession(SessionId::random()), 7011, Category::Transport),
+            (Error::NoHomeDir, 8001, Category::Storage),
+            (Error::NoLedger, 9001, Category::Device),
+        ];
+        for (error, code, category) in &errors {
+            // The codes are relied on by scripts, changing one breaks them.
+            assert_eq!(error.code(), *code, "{}", error);
+            assert_eq!(error.category(), *category);
+        }
+        assert_eq!(errors[2].0.context(), vec![("party", party.to_string())]);
+        let mut exit_codes: Vec<_> = errors.iter().map(|(error, _, _)| error.category().exit_code()).collect();
+        exit_codes.dedup();
+        assert_eq!(exit_codes.len(), errors.len());
+    }
+}
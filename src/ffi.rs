@@ -0,0 +1,397 @@
+//! C bindings of [`signing`](crate::signing), for custody platforms in C, C++ or Go to run the signing in-process.
+//! Building with the `ffi` feature writes their header to `solana_tss.h` in the build script's `OUT_DIR`.
+//!
+//! Every function returns `SOLANA_TSS_STATUS_OK` or the status of the failure, whose message is then in
+//! `solana_tss_last_error` until the next failure on the same thread. Keys are 32 bytes, keypairs 64 bytes and session
+//! IDs 16 bytes, first messages and partial signatures are the bytes the command line encodes, and transactions are
+//! bincode, as for `--unsigned-tx`. Buffers returned by the library are freed with `solana_tss_buffer_free`, sessions
+//! with `solana_tss_session_free`.
+//!
+//! All the pointers passed must be valid for the lengths passed with them, and a session must only be used by one
+//! thread at a time. A session whose step fails can't be used anymore, its nonces are gone: start a new one.
+#![allow(clippy::missing_safety_doc)]
+
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+
+use crate::serialization::{AggMessage1, FieldError, PartialSignature, Serialize, SessionId};
+use crate::signing::{Round1, Round2, Round3, Session};
+use crate::transaction::deserialize_unsigned_tx;
+use crate::{tss, Error};
+
+const KEY_LEN: usize = 32;
+const KEYPAIR_LEN: usize = 64;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolanaTssStatus {
+    Ok = 0,
+    /// A pointer that can't be null is
+    NullArgument = 1,
+    /// A key, message or transaction passed can't be parsed
+    InvalidArgument = 2,
+    /// The session isn't at the round of the step, or failed before
+    WrongRound = 3,
+    /// The step itself failed, e.g. on a message of another session or an invalid partial signature
+    Failed = 4,
+}
+
+/// Bytes passed to the library, which only reads them during the call
+#[repr(C)]
+pub struct SolanaTssBytes {
+    pub data: *const u8,
+    pub len: usize,
+}
+
+/// Bytes returned by the library, to free with `solana_tss_buffer_free`
+#[repr(C)]
+pub struct SolanaTssBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl SolanaTssBuffer {
+    fn new(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        Self { data, len }
+    }
+}
+
+/// An opaque signing session
+pub struct SolanaTssSession(Option<Round>);
+
+enum Round {
+    One(Session<Round1>),
+    Two(Session<Round2>),
+    Three(Session<Round3>),
+}
+
+impl Round {
+    fn first_message(&self) -> &AggMessage1 {
+        match self {
+            Round::One(session) => session.first_message(),
+            Round::Two(session) => session.first_message(),
+            Round::Three(session) => session.first_message(),
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// The message of the last failure on this thread, or null if there was none. It's owned by the library.
+#[no_mangle]
+pub extern "C" fn solana_tss_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn solana_tss_buffer_free(buffer: SolanaTssBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)));
+    }
+}
+
+/// Write the aggregated public key of the `count` keys at `keys` to the 32 bytes at `out`
+#[no_mangle]
+pub unsafe extern "C" fn solana_tss_aggregate_keys(keys: *const u8, count: usize, out: *mut u8) -> SolanaTssStatus {
+    run(|| {
+        let aggpubkey = tss::aggregate_pubkey(read_keys(keys, count)?).map_err(invalid)?;
+        write(out, aggpubkey.as_ref())
+    })
+}
+
+/// Write a new random session ID to the 16 bytes at `out`, for all the parties of a signing session to use
+#[no_mangle]
+pub unsafe extern "C" fn solana_tss_session_id_new(out: *mut u8) -> SolanaTssStatus {
+    run(|| write(out, &SessionId::random().0))
+}
+
+/// Start signing the transaction at `message` as the 64 bytes `keypair`, one of the `count` keys at `keys`, and store
+/// the new session in `out`
+#[no_mangle]
+pub unsafe extern "C" fn solana_tss_session_new(
+    keypair: *const u8,
+    keys: *const u8,
+    count: usize,
+    message: *const u8,
+    message_len: usize,
+    session_id: *const u8,
+    out: *mut *mut SolanaTssSession,
+) -> SolanaTssStatus {
+    run(|| {
+        let keypair = Keypair::from_bytes(read(keypair, KEYPAIR_LEN)?).map_err(|e| invalid(e.into()))?;
+        let keys = read_keys(keys, count)?;
+        let message = deserialize_unsigned_tx(read(message, message_len)?).map_err(invalid)?;
+        let mut id = [0u8; SessionId::LEN];
+        id.copy_from_slice(read(session_id, SessionId::LEN)?);
+        let session = Session::new(keypair, keys, message, SessionId(id)).map_err(failed)?;
+        let out = out.as_mut().ok_or_else(null)?;
+        *out = Box::into_raw(Box::new(SolanaTssSession(Some(Round::One(session)))));
+        Ok(())
+    })
+}
+
+/// The first message of the party in the session, to send to all the others
+#[no_mangle]
+pub unsafe extern "C" fn solana_tss_session_first_message(
+    session: *const SolanaTssSession,
+    out: *mut SolanaTssBuffer,
+) -> SolanaTssStatus {
+    run(|| {
+        let round = session.as_ref().ok_or_else(null)?.0.as_ref().ok_or_else(|| wrong_round(&None))?;
+        output(out, round.first_message().serialize_versioned())
+    })
+}
+
+/// Take the first messages of all the other parties
+#[no_mangle]
+pub unsafe extern "C" fn solana_tss_session_receive(
+    session: *mut SolanaTssSession,
+    messages: *const SolanaTssBytes,
+    count: usize,
+) -> SolanaTssStatus {
+    run(|| {
+        let messages = read_messages::<AggMessage1>(messages, count)?;
+        let session = session.as_mut().ok_or_else(null)?;
+        match session.0.take() {
+            Some(Round::One(one)) => {
+                session.0 = Some(Round::Two(one.receive(messages).map_err(failed)?));
+                Ok(())
+            }
+            other => {
+                session.0 = other;
+                Err(wrong_round(&session.0))
+            }
+        }
+    })
+}
+
+/// Sign, and return the partial signature of the party in `out` to send to whoever combines them
+#[no_mangle]
+pub unsafe extern "C" fn solana_tss_session_sign(
+    session: *mut SolanaTssSession,
+    out: *mut SolanaTssBuffer,
+) -> SolanaTssStatus {
+    run(|| {
+        let session = session.as_mut().ok_or_else(null)?;
+        match session.0.take() {
+            Some(Round::Two(two)) => {
+                let three = two.sign().map_err(failed)?;
+                let partial_signature = three.partial_signature().serialize_versioned();
+                session.0 = Some(Round::Three(three));
+                output(out, partial_signature)
+            }
+            other => {
+                session.0 = other;
+                Err(wrong_round(&session.0))
+            }
+        }
+    })
+}
+
+/// Combine the partial signatures of the other parties with the party's own, and return the signed transaction
+/// in `out`. The session is done after.
+#[no_mangle]
+pub unsafe extern "C" fn solana_tss_session_combine(
+    session: *mut SolanaTssSession,
+    signatures: *const SolanaTssBytes,
+    count: usize,
+    out: *mut SolanaTssBuffer,
+) -> SolanaTssStatus {
+    run(|| {
+        let signatures = read_messages::<PartialSignature>(signatures, count)?;
+        let session = session.as_mut().ok_or_else(null)?;
+        match session.0.take() {
+            Some(Round::Three(three)) => {
+                let tx = three.combine(signatures).map_err(failed)?;
+                output(out, bincode::serialize(&tx).expect("serializing to memory can't fail"))
+            }
+            other => {
+                session.0 = other;
+                Err(wrong_round(&session.0))
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn solana_tss_session_free(session: *mut SolanaTssSession) {
+    if !session.is_null() {
+        drop(Box::from_raw(session));
+    }
+}
+
+/// Run a step, turning a panic into a failure rather than unwinding into the caller
+fn run(step: impl FnOnce() -> Result<(), SolanaTssStatus>) -> SolanaTssStatus {
+    match panic::catch_unwind(AssertUnwindSafe(step)) {
+        Ok(Ok(())) => SolanaTssStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => fail(SolanaTssStatus::Failed, "The library panicked"),
+    }
+}
+
+fn fail(status: SolanaTssStatus, message: impl ToString) -> SolanaTssStatus {
+    let message = CString::new(message.to_string().replace('\0', "")).expect("there's no NUL left");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    status
+}
+
+fn null() -> SolanaTssStatus {
+    fail(SolanaTssStatus::NullArgument, "A pointer passed is null")
+}
+
+fn invalid(e: Error) -> SolanaTssStatus {
+    fail(SolanaTssStatus::InvalidArgument, e)
+}
+
+fn failed(e: Error) -> SolanaTssStatus {
+    fail(SolanaTssStatus::Failed, e)
+}
+
+fn wrong_round(round: &Option<Round>) -> SolanaTssStatus {
+    match round {
+        Some(_) => fail(SolanaTssStatus::WrongRound, "The session isn't at the round of this step"),
+        None => fail(SolanaTssStatus::WrongRound, "The session failed or is done, start a new one"),
+    }
+}
+
+unsafe fn read<'a>(data: *const u8, len: usize) -> Result<&'a [u8], SolanaTssStatus> {
+    if data.is_null() {
+        return if len == 0 { Ok(&[]) } else { Err(null()) };
+    }
+    Ok(slice::from_raw_parts(data, len))
+}
+
+unsafe fn read_keys(keys: *const u8, count: usize) -> Result<Vec<Pubkey>, SolanaTssStatus> {
+    Ok(read(keys, count * KEY_LEN)?.chunks(KEY_LEN).map(Pubkey::new).collect())
+}
+
+unsafe fn read_messages<T: Serialize>(
+    messages: *const SolanaTssBytes,
+    count: usize,
+) -> Result<Vec<T>, SolanaTssStatus> {
+    let messages = if messages.is_null() {
+        if count != 0 {
+            return Err(null());
+        }
+        &[]
+    } else {
+        slice::from_raw_parts(messages, count)
+    };
+    messages
+        .iter()
+        .map(|message| {
+            T::deserialize_versioned(read(message.data, message.len)?).with_field("messages").map_err(invalid)
+        })
+        .collect()
+}
+
+unsafe fn write(out: *mut u8, bytes: &[u8]) -> Result<(), SolanaTssStatus> {
+    if out.is_null() {
+        return Err(null());
+    }
+    ptr::copy_nonoverlapping(bytes.as_ptr(), out, bytes.len());
+    Ok(())
+}
+
+unsafe fn output(out: *mut SolanaTssBuffer, bytes: Vec<u8>) -> Result<(), SolanaTssStatus> {
+    *out.as_mut().ok_or_else(null)? = SolanaTssBuffer::new(bytes);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+    use std::ptr;
+
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::{Message, VersionedMessage};
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::system_instruction;
+    use solana_sdk::transaction::VersionedTransaction;
+
+    use crate::ffi::*;
+
+    #[test]
+    fn test_ffi() {
+        let parties = [Keypair::new(), Keypair::new()];
+        let keys: Vec<u8> = parties.iter().flat_map(|party| party.pubkey().to_bytes()).collect();
+        let mut aggpubkey = [0u8; 32];
+        let mut session_id = [0u8; 16];
+        unsafe {
+            assert_eq!(solana_tss_aggregate_keys(keys.as_ptr(), 2, aggpubkey.as_mut_ptr()), SolanaTssStatus::Ok);
+            assert_eq!(solana_tss_session_id_new(session_id.as_mut_ptr()), SolanaTssStatus::Ok);
+        }
+        let aggpubkey = Pubkey::new(&aggpubkey);
+        let instruction = system_instruction::transfer(&aggpubkey, &Pubkey::new_unique(), 1);
+        let message = Message::new_with_blockhash(&[instruction], Some(&aggpubkey), &Hash::new_unique());
+        let message = bincode::serialize(&VersionedMessage::Legacy(message)).unwrap();
+
+        let sessions: Vec<_> = parties
+            .iter()
+            .map(|party| unsafe {
+                let mut session = ptr::null_mut();
+                let status = solana_tss_session_new(
+                    party.to_bytes().as_ptr(),
+                    keys.as_ptr(),
+                    2,
+                    message.as_ptr(),
+                    message.len(),
+                    session_id.as_ptr(),
+                    &mut session,
+                );
+                assert_eq!(status, SolanaTssStatus::Ok);
+                session
+            })
+            .collect();
+        let first_messages: Vec<_> = sessions
+            .iter()
+            .map(|session| unsafe {
+                let mut first_message = SolanaTssBuffer { data: ptr::null_mut(), len: 0 };
+                assert_eq!(solana_tss_session_first_message(*session, &mut first_message), SolanaTssStatus::Ok);
+                first_message
+            })
+            .collect();
+        let mut signatures = Vec::new();
+        for (i, session) in sessions.iter().enumerate() {
+            let other = &first_messages[1 - i];
+            let other = SolanaTssBytes { data: other.data, len: other.len };
+            let mut signature = SolanaTssBuffer { data: ptr::null_mut(), len: 0 };
+            unsafe {
+                // Signing comes after receiving the first messages.
+                assert_eq!(solana_tss_session_sign(*session, &mut signature), SolanaTssStatus::WrongRound);
+                assert!(!CStr::from_ptr(solana_tss_last_error()).to_bytes().is_empty());
+                assert_eq!(solana_tss_session_receive(*session, &other, 1), SolanaTssStatus::Ok);
+                assert_eq!(solana_tss_session_sign(*session, &mut signature), SolanaTssStatus::Ok);
+            }
+            signatures.push(signature);
+        }
+
+        let other = SolanaTssBytes { data: signatures[1].data, len: signatures[1].len };
+        let mut tx = SolanaTssBuffer { data: ptr::null_mut(), len: 0 };
+        unsafe {
+            assert_eq!(solana_tss_session_combine(sessions[0], &other, 1, &mut tx), SolanaTssStatus::Ok);
+            let signed: VersionedTransaction =
+                bincode::deserialize(std::slice::from_raw_parts(tx.data, tx.len)).unwrap();
+            assert!(signed.verify_with_results().iter().all(|valid| *valid));
+            // Done with.
+            assert_eq!(solana_tss_session_combine(sessions[0], &other, 1, &mut tx), SolanaTssStatus::WrongRound);
+            for buffer in first_messages.into_iter().chain(signatures).chain([tx]) {
+                solana_tss_buffer_free(buffer);
+            }
+            for session in sessions {
+                solana_tss_session_free(session);
+            }
+        }
+    }
+}
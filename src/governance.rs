@@ -0,0 +1,354 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{pubkey, system_program};
+
+use crate::Error;
+
+/// The SPL Governance deployment most DAOs use, DAOs running their own pass its ID instead
+pub const GOVERNANCE_PROGRAM_ID: Pubkey = pubkey!("GovER5Lthms3bLBqWub97yVrMmEogzX7xNjdXpPPCVZw");
+
+// The indices of the instructions in `GovernanceInstruction`.
+const CREATE_PROPOSAL: u8 = 6;
+const SIGN_OFF_PROPOSAL: u8 = 12;
+const CAST_VOTE: u8 = 13;
+const EXECUTE_TRANSACTION: u8 = 16;
+
+// The `GovernanceAccountType`s this reads.
+const REALMS: &[u8] = &[1, 16];
+const PROPOSALS: &[u8] = &[5, 14];
+const PROPOSAL_TRANSACTION_V2: u8 = 13;
+
+/// A vote on a proposal, `Veto` is cast with the other governing token than the proposal's
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vote {
+    Yes,
+    No,
+    Abstain,
+    Veto,
+}
+
+#[derive(BorshSerialize)]
+enum VoteArgs {
+    Approve(Vec<VoteChoice>),
+    Deny,
+    Abstain,
+    Veto,
+}
+
+#[derive(BorshSerialize)]
+struct VoteChoice {
+    rank: u8,
+    weight_percentage: u8,
+}
+
+#[derive(BorshSerialize)]
+struct CreateProposalArgs {
+    name: String,
+    description_link: String,
+    /// `VoteType::SingleChoice`
+    vote_type: u8,
+    options: Vec<String>,
+    use_deny_option: bool,
+    proposal_seed: Pubkey,
+}
+
+/// The start of a realm account, up to its council mint
+#[derive(BorshDeserialize)]
+struct Realm {
+    _account_type: u8,
+    community_mint: Pubkey,
+    _config_reserved: [u8; 8],
+    _min_community_weight_to_create_governance: u64,
+    _community_mint_max_voter_weight_source: (u8, u64),
+    council_mint: Option<Pubkey>,
+}
+
+/// The start of any kind of governance account, they all begin with their realm
+#[derive(BorshDeserialize)]
+struct Governance {
+    _account_type: u8,
+    realm: Pubkey,
+}
+
+/// The start of a proposal account, up to its owner
+#[derive(BorshDeserialize)]
+struct Proposal {
+    _account_type: u8,
+    governance: Pubkey,
+    governing_token_mint: Pubkey,
+    _state: u8,
+    token_owner_record: Pubkey,
+}
+
+#[derive(BorshDeserialize)]
+struct ProposalTransaction {
+    _account_type: u8,
+    proposal: Pubkey,
+    _option_index: u8,
+    _transaction_index: u16,
+    _hold_up_time: u32,
+    instructions: Vec<InstructionData>,
+}
+
+#[derive(BorshDeserialize)]
+struct InstructionData {
+    program_id: Pubkey,
+    accounts: Vec<AccountMetaData>,
+    _data: Vec<u8>,
+}
+
+#[derive(BorshDeserialize)]
+struct AccountMetaData {
+    pubkey: Pubkey,
+    _is_signer: bool,
+    is_writable: bool,
+}
+
+/// Create a yes/no proposal in `governance` owned by `wallet`, and sign it off right away so voting starts.
+/// `governing_token_mint` is the mint of the wallet's deposited tokens, council or community.
+#[allow(clippy::too_many_arguments)]
+pub fn propose_instructions(
+    program_id: &Pubkey,
+    realm: &Pubkey,
+    governance: &Pubkey,
+    governing_token_mint: &Pubkey,
+    wallet: &Pubkey,
+    name: String,
+    description_link: String,
+    proposal_seed: Pubkey,
+) -> (Pubkey, Vec<Instruction>) {
+    let owner_record = token_owner_record_address(program_id, realm, governing_token_mint, wallet);
+    let proposal = find_address(
+        program_id,
+        &[b"governance", governance.as_ref(), governing_token_mint.as_ref(), proposal_seed.as_ref()],
+    );
+    let args = CreateProposalArgs {
+        name,
+        description_link,
+        vote_type: 0,
+        options: vec!["Approve".to_string()],
+        use_deny_option: true,
+        proposal_seed,
+    };
+    let create = Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*realm, false),
+            AccountMeta::new(proposal, false),
+            AccountMeta::new(*governance, false),
+            AccountMeta::new(owner_record, false),
+            AccountMeta::new_readonly(*governing_token_mint, false),
+            AccountMeta::new_readonly(*wallet, true),
+            AccountMeta::new(*wallet, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(realm_config_address(program_id, realm), false),
+            AccountMeta::new(
+                find_address(program_id, &[b"proposal-deposit", proposal.as_ref(), wallet.as_ref()]),
+                false,
+            ),
+        ],
+        data: instruction_data(CREATE_PROPOSAL, &args),
+    };
+    // Without any signatories the owner signs off the proposal.
+    let sign_off = Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*realm, false),
+            AccountMeta::new(*governance, false),
+            AccountMeta::new(proposal, false),
+            AccountMeta::new_readonly(*wallet, true),
+            AccountMeta::new_readonly(owner_record, false),
+        ],
+        data: vec![SIGN_OFF_PROPOSAL],
+    };
+    (proposal, vec![create, sign_off])
+}
+
+/// Cast the vote of `wallet` on `proposal`, with the tokens it deposited in the realm
+pub async fn vote_instruction(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    proposal: &Pubkey,
+    wallet: &Pubkey,
+    vote: Vote,
+) -> Result<Instruction, Error> {
+    let proposal_state: Proposal = fetch(rpc_client, program_id, proposal, PROPOSALS).await?;
+    let governance: Governance = fetch(rpc_client, program_id, &proposal_state.governance, &[]).await?;
+    let realm: Realm = fetch(rpc_client, program_id, &governance.realm, REALMS).await?;
+    let voting_mint = match vote {
+        // Vetoing takes the other side's tokens, the council vetoes community proposals and the other way around.
+        Vote::Veto if proposal_state.governing_token_mint == realm.community_mint => {
+            realm.council_mint.ok_or(Error::NotAGovernanceAccount(governance.realm))?
+        }
+        Vote::Veto => realm.community_mint,
+        _ => proposal_state.governing_token_mint,
+    };
+    let voter_record = token_owner_record_address(program_id, &governance.realm, &voting_mint, wallet);
+    let args = match vote {
+        Vote::Yes => VoteArgs::Approve(vec![VoteChoice { rank: 0, weight_percentage: 100 }]),
+        Vote::No => VoteArgs::Deny,
+        Vote::Abstain => VoteArgs::Abstain,
+        Vote::Veto => VoteArgs::Veto,
+    };
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(governance.realm, false),
+            AccountMeta::new(proposal_state.governance, false),
+            AccountMeta::new(*proposal, false),
+            AccountMeta::new(proposal_state.token_owner_record, false),
+            AccountMeta::new(voter_record, false),
+            AccountMeta::new_readonly(*wallet, true),
+            AccountMeta::new(
+                find_address(program_id, &[b"governance", proposal.as_ref(), voter_record.as_ref()]),
+                false,
+            ),
+            AccountMeta::new_readonly(voting_mint, false),
+            AccountMeta::new(*wallet, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(realm_config_address(program_id, &governance.realm), false),
+        ],
+        data: instruction_data(CAST_VOTE, &args),
+    })
+}
+
+/// Execute a transaction of an approved proposal. Anyone can, the governance signs for its own instructions.
+pub async fn execute_instruction(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    proposal_transaction: &Pubkey,
+) -> Result<Instruction, Error> {
+    let transaction: ProposalTransaction =
+        fetch(rpc_client, program_id, proposal_transaction, &[PROPOSAL_TRANSACTION_V2]).await?;
+    let proposal: Proposal = fetch(rpc_client, program_id, &transaction.proposal, PROPOSALS).await?;
+    Ok(execute_transaction(program_id, &proposal.governance, proposal_transaction, &transaction))
+}
+
+fn execute_transaction(
+    program_id: &Pubkey,
+    governance: &Pubkey,
+    proposal_transaction: &Pubkey,
+    transaction: &ProposalTransaction,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*governance, false),
+        AccountMeta::new(transaction.proposal, false),
+        AccountMeta::new(*proposal_transaction, false),
+    ];
+    // The accounts the instructions sign with are the governance's own, so they don't sign the transaction.
+    for instruction in &transaction.instructions {
+        accounts.push(AccountMeta::new_readonly(instruction.program_id, false));
+        accounts.extend(instruction.accounts.iter().map(|account| {
+            if account.is_writable {
+                AccountMeta::new(account.pubkey, false)
+            } else {
+                AccountMeta::new_readonly(account.pubkey, false)
+            }
+        }));
+    }
+    Instruction { program_id: *program_id, accounts, data: vec![EXECUTE_TRANSACTION] }
+}
+
+async fn fetch<T: BorshDeserialize>(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    key: &Pubkey,
+    account_types: &[u8],
+) -> Result<T, Error> {
+    let account = rpc_client.get_account(key).await.map_err(Error::AccountLookupFailed)?;
+    let type_matches = account_types.is_empty() || account.data.first().map_or(false, |t| account_types.contains(t));
+    if account.owner != *program_id || !type_matches {
+        return Err(Error::NotAGovernanceAccount(*key));
+    }
+    // The accounts are longer than the parts read here.
+    T::deserialize(&mut &account.data[..]).map_err(|_| Error::NotAGovernanceAccount(*key))
+}
+
+fn instruction_data(index: u8, args: &impl BorshSerialize) -> Vec<u8> {
+    let mut data = vec![index];
+    args.serialize(&mut data).expect("serializing to memory can't fail");
+    data
+}
+
+fn find_address(program_id: &Pubkey, seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, program_id).0
+}
+
+fn token_owner_record_address(program_id: &Pubkey, realm: &Pubkey, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+    find_address(program_id, &[b"governance", realm.as_ref(), mint.as_ref(), owner.as_ref()])
+}
+
+fn realm_config_address(program_id: &Pubkey, realm: &Pubkey) -> Pubkey {
+    find_address(program_id, &[b"realm-config", realm.as_ref()])
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshDeserialize;
+    use solana_sdk::message::Message;
+    use solana_sdk::pubkey::Pubkey;
+
+    use crate::governance::{
+        execute_transaction, propose_instructions, ProposalTransaction, GOVERNANCE_PROGRAM_ID, PROPOSAL_TRANSACTION_V2,
+    };
+
+    #[test]
+    fn test_propose() {
+        let (realm, governance, mint, wallet) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let seed = Pubkey::new_unique();
+        let (proposal, instructions) = propose_instructions(
+            &GOVERNANCE_PROGRAM_ID,
+            &realm,
+            &governance,
+            &mint,
+            &wallet,
+            "Fund the grants".to_string(),
+            String::new(),
+            seed,
+        );
+        let message = Message::new(&instructions, Some(&wallet));
+        assert_eq!(message.header.num_required_signatures, 1);
+        assert_eq!(instructions[0].accounts[1].pubkey, proposal);
+        // The instruction index, then the name.
+        assert_eq!(&instructions[0].data[..5], &[6, 15, 0, 0, 0]);
+        assert!(instructions[0].data.ends_with(seed.as_ref()));
+        assert_eq!(instructions[1].data, [12]);
+    }
+
+    #[test]
+    fn test_execute_transaction() {
+        let (proposal, target, governance) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = vec![PROPOSAL_TRANSACTION_V2];
+        data.extend(proposal.as_ref());
+        data.extend([0, 0, 0, 0, 0, 0, 0]);
+        // One system transfer out of the governance's treasury
+        data.extend([1, 0, 0, 0]);
+        data.extend(solana_sdk::system_program::id().as_ref());
+        data.extend([2, 0, 0, 0]);
+        data.extend(governance.as_ref());
+        data.extend([1, 1]);
+        data.extend(target.as_ref());
+        data.extend([0, 1]);
+        data.extend([12, 0, 0, 0, 2, 0, 0, 0, 64, 66, 15, 0, 0, 0, 0, 0]);
+        data.extend([0; 16]);
+        let transaction = ProposalTransaction::deserialize(&mut &data[..]).unwrap();
+
+        let key = Pubkey::new_unique();
+        let instruction = execute_transaction(&GOVERNANCE_PROGRAM_ID, &governance, &key, &transaction);
+        let accounts: Vec<_> = instruction.accounts.iter().map(|a| (a.pubkey, a.is_signer, a.is_writable)).collect();
+        assert_eq!(
+            accounts,
+            [
+                (governance, false, false),
+                (proposal, false, true),
+                (key, false, true),
+                (solana_sdk::system_program::id(), false, false),
+                (governance, false, true),
+                (target, false, true)
+            ]
+        );
+    }
+}
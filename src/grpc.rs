@@ -0,0 +1,178 @@
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use solana_sdk::pubkey::Pubkey;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+use crate::daemon::{self, Daemon, Failure};
+use crate::serialization::{Encoding, Serialize, SessionId};
+use crate::transaction::parse_unsigned_tx;
+use crate::Error;
+
+/// The messages and the service of `proto/solana_tss.proto`
+pub mod proto {
+    tonic::include_proto!("solana_tss");
+}
+
+use proto::signing_service_server::{SigningService, SigningServiceServer};
+use tracing::info;
+
+/// Serve the sessions of `daemon` as the gRPC `SigningService` on `addr` until stopped, with the messages of the
+/// party in `encoding`
+pub async fn serve(addr: SocketAddr, daemon: Arc<Daemon>, encoding: Encoding) -> Result<(), Error> {
+    info!("Serving the signing sessions of {} over gRPC on {}", daemon.party(), addr);
+    Server::builder()
+        .add_service(SigningServiceServer::new(Service { daemon, encoding }))
+        .serve(addr)
+        .await
+        .map_err(|e| Error::DaemonFailed(e.to_string()))
+}
+
+struct Service {
+    daemon: Arc<Daemon>,
+    encoding: Encoding,
+}
+
+impl Service {
+    fn session(&self, status: daemon::Status) -> proto::Session {
+        let state = match status.state {
+            daemon::State::Collecting => proto::session::State::Collecting,
+            daemon::State::Ready => proto::session::State::Ready,
+            daemon::State::Signed => proto::session::State::Signed,
+        };
+        proto::Session {
+            session_id: status.session_id.to_string(),
+            state: state.into(),
+            first_message: status.first_message.serialize_encoded(self.encoding).to_string(),
+            received: status.received.iter().map(ToString::to_string).collect(),
+            missing: status.missing.iter().map(ToString::to_string).collect(),
+            keys: status.keys.iter().map(ToString::to_string).collect(),
+            expires_in_secs: status.expires_in.as_secs(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl SigningService for Service {
+    async fn create_session(
+        &self,
+        request: Request<proto::CreateSessionRequest>,
+    ) -> Result<Response<proto::Session>, Status> {
+        let request = request.into_inner();
+        let session_id = match request.session_id.as_str() {
+            "" => None,
+            session_id => Some(parse_session_id(session_id)?),
+        };
+        let keys = request
+            .keys
+            .iter()
+            .map(|key| Pubkey::from_str(key).map_err(|_| Status::invalid_argument(format!("Invalid key {}", key))))
+            .collect::<Result<_, _>>()?;
+        let message = parse_unsigned_tx(&request.unsigned_tx).map_err(status)?;
+        let to = match request.to.as_str() {
+            "" => None,
+            to => Some(Pubkey::from_str(to).map_err(|_| Status::invalid_argument(format!("Invalid to {}", to)))?),
+        };
+        Ok(Response::new(self.session(self.daemon.create_session(session_id, keys, message, to).map_err(status)?)))
+    }
+
+    async fn submit_message(
+        &self,
+        request: Request<proto::SubmitMessageRequest>,
+    ) -> Result<Response<proto::Session>, Status> {
+        let request = request.into_inner();
+        let session_id = parse_session_id(&request.session_id)?;
+        Ok(Response::new(self.session(self.daemon.submit_message(session_id, &request.message).map_err(status)?)))
+    }
+
+    async fn get_session(
+        &self,
+        request: Request<proto::GetSessionRequest>,
+    ) -> Result<Response<proto::Session>, Status> {
+        let session_id = parse_session_id(&request.into_inner().session_id)?;
+        Ok(Response::new(self.session(self.daemon.status(session_id).map_err(status)?)))
+    }
+
+    async fn list_sessions(
+        &self,
+        _request: Request<proto::ListSessionsRequest>,
+    ) -> Result<Response<proto::SessionList>, Status> {
+        let sessions = self.daemon.sessions().map_err(status)?.into_iter().map(|status| self.session(status));
+        Ok(Response::new(proto::SessionList { sessions: sessions.collect() }))
+    }
+
+    async fn get_partial_signature(
+        &self,
+        request: Request<proto::GetPartialSignatureRequest>,
+    ) -> Result<Response<proto::PartialSignature>, Status> {
+        let session_id = parse_session_id(&request.into_inner().session_id)?;
+        let sig = self.daemon.partial_signature(session_id).map_err(status)?;
+        Ok(Response::new(proto::PartialSignature {
+            partial_signature: sig.serialize_encoded(self.encoding).to_string(),
+        }))
+    }
+}
+
+fn parse_session_id(s: &str) -> Result<SessionId, Status> {
+    SessionId::from_str(s).map_err(|error| status(Error::DeserializationFailed { error, field_name: "session_id" }))
+}
+
+/// The gRPC status of a request the daemon failed, so callers can tell a request to fix from one to retry
+fn status(e: Error) -> Status {
+    let message = e.to_string();
+    match Failure::of(&e) {
+        Failure::InvalidRequest => Status::invalid_argument(message),
+        Failure::NotFound => Status::not_found(message),
+        Failure::Conflict => Status::already_exists(message),
+        Failure::Refused => Status::permission_denied(message),
+        Failure::NotReady => Status::failed_precondition(message),
+        Failure::Full => Status::resource_exhausted(message),
+        Failure::Internal => Status::internal(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use solana_sdk::signature::{Keypair, Signer};
+    use tonic::{Code, Request};
+
+    use crate::audit::AuditLog;
+    use crate::daemon::Daemon;
+    use crate::grpc::proto::signing_service_server::SigningService;
+    use crate::grpc::proto::{
+        CreateSessionRequest, GetPartialSignatureRequest, GetSessionRequest, ListSessionsRequest,
+    };
+    use crate::grpc::Service;
+    use crate::network::Network;
+    use crate::policy::Policy;
+    use crate::serialization::{Encoding, SessionId};
+
+    #[tokio::test]
+    async fn test_grpc_status() {
+        let daemon = Daemon::new(Keypair::new(), Network::Localnet, Policy::default(), AuditLog::new(None));
+        let keys = vec![daemon.party().to_string(), Keypair::new().pubkey().to_string()];
+        let service = Service { daemon: Arc::new(daemon), encoding: Encoding::Bs58 };
+
+        let request = CreateSessionRequest {
+            session_id: String::new(),
+            keys,
+            unsigned_tx: "not base64".to_string(),
+            to: String::new(),
+        };
+        let error = service.create_session(Request::new(request)).await.unwrap_err();
+        assert_eq!(error.code(), Code::InvalidArgument);
+        let request = GetSessionRequest { session_id: SessionId::random().to_string() };
+        assert_eq!(service.get_session(Request::new(request)).await.unwrap_err().code(), Code::NotFound);
+        let sessions = service.list_sessions(Request::new(ListSessionsRequest {})).await.unwrap();
+        assert!(sessions.into_inner().sessions.is_empty());
+        let request = GetPartialSignatureRequest { session_id: "not a session".to_string() };
+        assert_eq!(
+            service.get_partial_signature(Request::new(request)).await.unwrap_err().code(),
+            Code::InvalidArgument
+        );
+    }
+}
@@ -0,0 +1,456 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use serde_json::json;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::native_token::lamports_to_sol;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::{TransactionError, VersionedTransaction};
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{
+    TransactionConfirmationStatus, TransactionStatus, UiTransactionEncoding, UiTransactionStatusMeta,
+};
+
+use crate::serialization::Format;
+use crate::Error;
+
+/// A transaction involving an address, with what it did to the address' balances
+#[derive(Debug)]
+pub struct Entry {
+    pub signature: Signature,
+    pub slot: u64,
+    /// Seconds since the Unix epoch, unknown for very old blocks
+    pub block_time: Option<i64>,
+    pub error: Option<TransactionError>,
+    pub fee: u64,
+    /// The change of the address' SOL balance in lamports, the fee included
+    pub lamports: i128,
+    /// The change of the address' balance of every token mint, in its smallest unit, with the mint's decimals
+    pub tokens: Vec<(Pubkey, i128, u8)>,
+    pub memos: Vec<String>,
+}
+
+/// What a transaction did to the balances of all the accounts it changed
+#[derive(Debug)]
+pub struct Changes {
+    pub error: Option<TransactionError>,
+    pub fee: u64,
+    /// The accounts whose SOL balance changed, by how many lamports, fees included
+    pub lamports: Vec<(Pubkey, i128)>,
+    /// The changes of token balances in their smallest unit, by owner and mint, with the mint's decimals
+    pub tokens: Vec<(Pubkey, Pubkey, i128, u8)>,
+    pub memos: Vec<String>,
+}
+
+/// A transaction as found by its signature
+#[derive(Debug)]
+pub struct Lookup {
+    pub status: TransactionStatus,
+    /// Seconds since the Unix epoch, unknown for very old blocks and unconfirmed transactions
+    pub block_time: Option<i64>,
+    /// Only known once the transaction is confirmed
+    pub changes: Option<Changes>,
+}
+
+/// The last `limit` transactions of `address` before the transaction `before`, or the latest ones. Newest first.
+pub async fn fetch(
+    rpc_client: &RpcClient,
+    address: &Pubkey,
+    limit: usize,
+    before: Option<Signature>,
+) -> Result<Vec<Entry>, Error> {
+    // Only confirmed transactions can be looked up.
+    let commitment = match rpc_client.commitment() {
+        commitment if commitment.is_finalized() => commitment,
+        _ => CommitmentConfig::confirmed(),
+    };
+    let config = GetConfirmedSignaturesForAddress2Config {
+        before,
+        until: None,
+        limit: Some(limit),
+        commitment: Some(commitment),
+    };
+    let signatures =
+        rpc_client.get_signatures_for_address_with_config(address, config).await.map_err(Error::HistoryFailed)?;
+    let mut entries = Vec::with_capacity(signatures.len());
+    for status in signatures {
+        let signature =
+            Signature::from_str(&status.signature).map_err(|_| Error::CorruptHistory(status.signature.clone()))?;
+        let (slot, block_time, tx, meta) = get_transaction(rpc_client, &signature, commitment).await?;
+        entries.push(decode(address, signature, slot, block_time, &tx, meta));
+    }
+    Ok(entries)
+}
+
+/// The status of the transaction `signature`, and what it did once it's confirmed. None if the RPC node doesn't know it.
+pub async fn lookup(rpc_client: &RpcClient, signature: &Signature) -> Result<Option<Lookup>, Error> {
+    let statuses =
+        rpc_client.get_signature_statuses_with_history(&[*signature]).await.map_err(Error::HistoryFailed)?.value;
+    let status = match statuses.into_iter().next().flatten() {
+        Some(status) => status,
+        None => return Ok(None),
+    };
+    // Processed transactions can't be read yet.
+    if status.confirmation_status() == TransactionConfirmationStatus::Processed {
+        return Ok(Some(Lookup { status, block_time: None, changes: None }));
+    }
+    let (_, block_time, tx, meta) = get_transaction(rpc_client, signature, CommitmentConfig::confirmed()).await?;
+    Ok(Some(Lookup { status, block_time, changes: Some(changes(&tx, meta)) }))
+}
+
+/// The slot, block time, transaction and status of the confirmed transaction `signature`
+async fn get_transaction(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+    commitment: CommitmentConfig,
+) -> Result<(u64, Option<i64>, VersionedTransaction, UiTransactionStatusMeta), Error> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(commitment),
+        max_supported_transaction_version: Some(0),
+    };
+    let corrupt = || Error::CorruptHistory(signature.to_string());
+    let confirmed = rpc_client.get_transaction_with_config(signature, config).await.map_err(Error::HistoryFailed)?;
+    let tx = confirmed.transaction.transaction.decode().ok_or_else(corrupt)?;
+    let meta = confirmed.transaction.meta.ok_or_else(corrupt)?;
+    Ok((confirmed.slot, confirmed.block_time, tx, meta))
+}
+
+/// What the transaction `tx` with the status `meta` did to `address`
+fn decode(
+    address: &Pubkey,
+    signature: Signature,
+    slot: u64,
+    block_time: Option<i64>,
+    tx: &VersionedTransaction,
+    meta: UiTransactionStatusMeta,
+) -> Entry {
+    let changes = changes(tx, meta);
+    Entry {
+        signature,
+        slot,
+        block_time,
+        error: changes.error,
+        fee: changes.fee,
+        lamports: changes.lamports.iter().find(|(key, _)| key == address).map_or(0, |(_, change)| *change),
+        tokens: changes
+            .tokens
+            .into_iter()
+            .filter(|(owner, ..)| owner == address)
+            .map(|(_, mint, change, decimals)| (mint, change, decimals))
+            .collect(),
+        memos: changes.memos,
+    }
+}
+
+/// What the transaction `tx` with the status `meta` did to all the accounts
+fn changes(tx: &VersionedTransaction, meta: UiTransactionStatusMeta) -> Changes {
+    // The balances are of the static keys followed by the ones loaded from lookup tables.
+    let mut keys = tx.message.static_account_keys().to_vec();
+    if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+        keys.extend(loaded.writable.iter().chain(&loaded.readonly).filter_map(|key| Pubkey::from_str(key).ok()));
+    }
+    let lamports = keys
+        .iter()
+        .zip(meta.pre_balances.iter().zip(&meta.post_balances))
+        .map(|(key, (pre, post))| (*key, i128::from(*post) - i128::from(*pre)))
+        .filter(|(_, change)| *change != 0)
+        .collect();
+
+    // Token balances are of the token accounts, they count for their owners.
+    let mut tokens = BTreeMap::new();
+    let balances = |balances: OptionSerializer<_>, sign| {
+        Option::<Vec<_>>::from(balances).unwrap_or_default().into_iter().map(move |balance| (balance, sign))
+    };
+    let pre = balances(meta.pre_token_balances, -1);
+    let post = balances(meta.post_token_balances, 1);
+    for (balance, sign) in pre.chain(post) {
+        let owner = match &balance.owner {
+            OptionSerializer::Some(owner) => Pubkey::from_str(owner),
+            _ => continue,
+        };
+        let mint = Pubkey::from_str(&balance.mint);
+        let (owner, mint, amount) = match (owner, mint, balance.ui_token_amount.amount.parse::<u64>()) {
+            (Ok(owner), Ok(mint), Ok(amount)) => (owner, mint, amount),
+            _ => continue,
+        };
+        let (change, _) = tokens.entry((owner, mint)).or_insert((0i128, balance.ui_token_amount.decimals));
+        *change += sign * i128::from(amount);
+    }
+
+    let memos = tx
+        .message
+        .instructions()
+        .iter()
+        .filter(|ins| {
+            let program_id = keys.get(usize::from(ins.program_id_index));
+            program_id.map_or(false, |id| *id == spl_memo::id() || *id == spl_memo::v1::id())
+        })
+        .map(|ins| String::from_utf8_lossy(&ins.data).into_owned())
+        .collect();
+
+    Changes {
+        error: meta.err,
+        fee: meta.fee,
+        lamports,
+        tokens: tokens
+            .into_iter()
+            .filter(|(_, (change, _))| *change != 0)
+            .map(|((owner, mint), (change, decimals))| (owner, mint, change, decimals))
+            .collect(),
+        memos,
+    }
+}
+
+pub fn print(entries: &[Entry], format: Format) {
+    match format {
+        Format::Text => {
+            for entry in entries {
+                match entry.block_time {
+                    Some(time) => println!("{} (slot {}, {})", entry.signature, entry.slot, format_time(time)),
+                    None => println!("{} (slot {})", entry.signature, entry.slot),
+                }
+                if let Some(error) = &entry.error {
+                    println!("  Failed: {}", error);
+                }
+                println!("  SOL: {} (fee {})", format_amount(entry.lamports, 9), lamports_to_sol(entry.fee));
+                for (mint, change, decimals) in &entry.tokens {
+                    println!("  {}: {}", mint, format_amount(*change, *decimals));
+                }
+                for memo in &entry.memos {
+                    println!("  Memo: {}", memo);
+                }
+            }
+        }
+        Format::Json => {
+            let entries: Vec<_> = entries
+                .iter()
+                .map(|entry| {
+                    let tokens: Vec<_> = entry
+                        .tokens
+                        .iter()
+                        .map(|(mint, change, decimals)| {
+                            json!({ "mint": mint.to_string(), "change": change.to_string(), "decimals": decimals })
+                        })
+                        .collect();
+                    json!({
+                        "signature": entry.signature.to_string(),
+                        "slot": entry.slot,
+                        "block_time": entry.block_time,
+                        "error": entry.error.as_ref().map(ToString::to_string),
+                        "fee": entry.fee,
+                        "lamports": entry.lamports.to_string(),
+                        "tokens": tokens,
+                        "memos": entry.memos,
+                    })
+                })
+                .collect();
+            println!("{}", json!(entries));
+        }
+    }
+}
+
+pub fn print_lookup(lookup: &Lookup, format: Format) {
+    let status = format!("{:?}", lookup.status.confirmation_status()).to_lowercase();
+    match format {
+        Format::Text => {
+            match lookup.block_time {
+                Some(time) => println!("Status: {} in slot {}, {}", status, lookup.status.slot, format_time(time)),
+                None => println!("Status: {} in slot {}", status, lookup.status.slot),
+            }
+            if let Some(error) = &lookup.status.err {
+                println!("Failed: {}", error);
+            }
+            let changes = match &lookup.changes {
+                Some(changes) => changes,
+                None => return println!("The transaction isn't confirmed yet, its balance changes aren't known"),
+            };
+            println!("Fee: {} SOL", lamports_to_sol(changes.fee));
+            for (key, change) in &changes.lamports {
+                println!("{}: {} SOL", key, format_amount(*change, 9));
+            }
+            for (owner, mint, change, decimals) in &changes.tokens {
+                println!("{}: {} of {}", owner, format_amount(*change, *decimals), mint);
+            }
+            for memo in &changes.memos {
+                println!("Memo: {}", memo);
+            }
+        }
+        Format::Json => {
+            let changes = lookup.changes.as_ref().map(|changes| {
+                let lamports: Vec<_> = changes
+                    .lamports
+                    .iter()
+                    .map(|(key, change)| json!({ "address": key.to_string(), "change": change.to_string() }))
+                    .collect();
+                let tokens: Vec<_> = changes
+                    .tokens
+                    .iter()
+                    .map(|(owner, mint, change, decimals)| {
+                        json!({
+                            "owner": owner.to_string(),
+                            "mint": mint.to_string(),
+                            "change": change.to_string(),
+                            "decimals": decimals,
+                        })
+                    })
+                    .collect();
+                json!({ "fee": changes.fee, "lamports": lamports, "tokens": tokens, "memos": changes.memos })
+            });
+            let json = json!({
+                "status": status,
+                "slot": lookup.status.slot,
+                "block_time": lookup.block_time,
+                "error": lookup.status.err.as_ref().map(ToString::to_string),
+                "changes": changes,
+            });
+            println!("{}", json);
+        }
+    }
+}
+
+/// An amount given in the smallest unit as a signed decimal, e.g. -0.000005 for -5000 lamports
+pub fn format_amount(amount: i128, decimals: u8) -> String {
+    let sign = if amount < 0 {
+        "-"
+    } else if amount > 0 {
+        "+"
+    } else {
+        ""
+    };
+    let unit = 10u128.pow(u32::from(decimals));
+    let (whole, fraction) = (amount.unsigned_abs() / unit, amount.unsigned_abs() % unit);
+    let fraction = format!("{:0width$}", fraction, width = usize::from(decimals));
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        format!("{}{}", sign, whole)
+    } else {
+        format!("{}{}.{}", sign, whole, fraction)
+    }
+}
+
+/// A Unix timestamp as a UTC date and time
+fn format_time(time: i64) -> String {
+    let (days, seconds) = (time.div_euclid(86400), time.rem_euclid(86400));
+    // The civil date of a day number, by Howard Hinnant's `civil_from_days`.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+    use solana_client::nonblocking::rpc_client::RpcClient;
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::Message;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Signature;
+    use solana_sdk::system_instruction;
+    use solana_sdk::transaction::{Transaction, VersionedTransaction};
+    use spl_memo::build_memo;
+
+    use crate::history::{decode, format_amount, format_time, lookup};
+
+    #[test]
+    fn test_decode() {
+        let (wallet, to, mint) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let instructions = [system_instruction::transfer(&wallet, &to, 1_000_000), build_memo(b"invoice 42", &[])];
+        let message = Message::new_with_blockhash(&instructions, Some(&wallet), &Hash::new_unique());
+        let tx = VersionedTransaction::from(Transaction::new_unsigned(message));
+        let token_balance = |amount: &str| {
+            json!({
+                "accountIndex": 3,
+                "mint": mint.to_string(),
+                "owner": wallet.to_string(),
+                "uiTokenAmount": { "uiAmount": null, "decimals": 6, "amount": amount, "uiAmountString": "" },
+            })
+        };
+        let meta = serde_json::from_value(json!({
+            "err": null,
+            "status": { "Ok": null },
+            "fee": 5000,
+            "preBalances": [10_000_000, 0, 1, 1],
+            "postBalances": [8_995_000, 1_000_000, 1, 1],
+            "preTokenBalances": [token_balance("2500000")],
+            "postTokenBalances": [token_balance("1000000")],
+        }))
+        .unwrap();
+        let entry = decode(&wallet, Signature::default(), 7, None, &tx, meta);
+        assert_eq!(entry.lamports, -1_005_000);
+        assert_eq!(entry.fee, 5000);
+        assert_eq!(entry.tokens, [(mint, -1_500_000, 6)]);
+        assert_eq!(entry.memos, ["invoice 42"]);
+        assert!(entry.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lookup() {
+        let (wallet, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let instructions = [system_instruction::transfer(&wallet, &to, 1_000_000), build_memo(b"payroll", &[])];
+        let message = Message::new_with_blockhash(&instructions, Some(&wallet), &Hash::new_unique());
+        let tx = bincode::serialize(&Transaction::new_unsigned(message)).unwrap();
+        let transaction = json!({
+            "slot": 7,
+            "blockTime": 1_700_000_000,
+            "transaction": [base64::encode(tx), "base64"],
+            "meta": {
+                "err": null,
+                "status": { "Ok": null },
+                "fee": 5000,
+                "preBalances": [10_000_000, 0, 1, 1],
+                "postBalances": [8_995_000, 1_000_000, 1, 1],
+            },
+        });
+        // The mock node has every transaction it knows finalized in slot 1.
+        let mocks = HashMap::from([(RpcRequest::GetTransaction, transaction)]);
+        let rpc_client = RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks);
+        let found = lookup(&rpc_client, &Signature::new_unique()).await.unwrap().unwrap();
+        assert_eq!(found.block_time, Some(1_700_000_000));
+        let changes = found.changes.unwrap();
+        assert_eq!(changes.fee, 5000);
+        assert_eq!(changes.lamports, [(wallet, -1_005_000), (to, 1_000_000)]);
+        assert_eq!(changes.memos, ["payroll"]);
+
+        let rpc_client = RpcClient::new_mock("sig_not_found".to_string());
+        assert!(lookup(&rpc_client, &Signature::new_unique()).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(-1_005_000, 9), "-0.001005");
+        assert_eq!(format_amount(2_500_000, 6), "+2.5");
+        assert_eq!(format_amount(3_000_000_000, 9), "+3");
+        assert_eq!(format_amount(0, 9), "0");
+        assert_eq!(format_amount(7, 0), "+7");
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_time(1_709_251_199), "2024-02-29 23:59:59 UTC");
+        assert_eq!(format_time(1_700_000_000), "2023-11-14 22:13:20 UTC");
+    }
+}
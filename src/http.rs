@@ -0,0 +1,114 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, Proxy};
+use serde::Deserialize;
+use solana_client::client_error::Result as ClientResult;
+use solana_client::rpc_custom_error::{
+    JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY, JSON_RPC_SERVER_ERROR_SEND_TRANSACTION_PREFLIGHT_FAILURE,
+};
+use solana_client::rpc_request::{RpcError, RpcRequest, RpcResponseErrorData};
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+
+use crate::Error;
+
+/// Sends RPC requests over HTTP like the client's own sender, but through `proxy` if given.
+/// Without one, the `HTTPS_PROXY`, `HTTP_PROXY` and `ALL_PROXY` environment variables are honored.
+pub struct HttpSender {
+    client: Client,
+    url: String,
+    request_id: AtomicU64,
+}
+
+#[derive(Deserialize)]
+struct RpcErrorObject {
+    code: i64,
+    message: String,
+}
+
+impl HttpSender {
+    pub fn new(url: String, timeout: Duration, proxy: Option<Proxy>) -> Result<Self, Error> {
+        let mut builder = Client::builder().timeout(timeout).pool_idle_timeout(timeout);
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(proxy);
+        }
+        let client = builder.build().map_err(Error::HttpClientFailed)?;
+        Ok(Self { client, url, request_id: AtomicU64::new(0) })
+    }
+}
+
+#[async_trait]
+impl RpcSender for HttpSender {
+    async fn send(&self, request: RpcRequest, params: serde_json::Value) -> ClientResult<serde_json::Value> {
+        let request_id = self.request_id.fetch_add(1, Ordering::Relaxed);
+        let body = request.build_request_json(request_id, params).to_string();
+        let response = self.client.post(&self.url).header(CONTENT_TYPE, "application/json").body(body).send().await?;
+        let mut json: serde_json::Value = response.error_for_status()?.json().await?;
+        if !json["error"].is_object() {
+            return Ok(json["result"].take());
+        }
+        let error: RpcErrorObject = serde_json::from_value(json["error"].clone()).map_err(|e| {
+            RpcError::RpcRequestError(format!("Failed to deserialize RPC error response: {} [{}]", json["error"], e))
+        })?;
+        Err(RpcError::RpcResponseError {
+            code: error.code,
+            message: error.message,
+            data: error_data(error.code, &json),
+        }
+        .into())
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        // Only used for the client's own metrics, which aren't reported here.
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        self.url.clone()
+    }
+}
+
+/// The details the node gives about an error, for the errors the client understands
+fn error_data(code: i64, json: &serde_json::Value) -> RpcResponseErrorData {
+    let data = json["error"]["data"].clone();
+    match code {
+        JSON_RPC_SERVER_ERROR_SEND_TRANSACTION_PREFLIGHT_FAILURE => serde_json::from_value(data)
+            .map_or(RpcResponseErrorData::Empty, RpcResponseErrorData::SendTransactionPreflightFailure),
+        JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY => {
+            RpcResponseErrorData::NodeUnhealthy { num_slots_behind: data["numSlotsBehind"].as_u64() }
+        }
+        _ => RpcResponseErrorData::Empty,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use solana_client::rpc_request::RpcResponseErrorData;
+
+    use crate::http::error_data;
+
+    #[test]
+    fn test_error_data() {
+        let preflight = json!({ "error": { "code": -32002, "message": "Transaction simulation failed", "data": {
+            "err": { "InstructionError": [0, { "Custom": 1 }] },
+            "logs": ["Program 11111111111111111111111111111111 invoke [1]"],
+            "accounts": null,
+            "unitsConsumed": 150,
+            "returnData": null,
+        } } });
+        assert!(matches!(
+            error_data(-32002, &preflight),
+            RpcResponseErrorData::SendTransactionPreflightFailure(result) if result.logs.as_ref().map_or(0, Vec::len) == 1
+        ));
+        let unhealthy =
+            json!({ "error": { "code": -32005, "message": "Node is behind", "data": { "numSlotsBehind": 42 } } });
+        assert!(matches!(
+            error_data(-32005, &unhealthy),
+            RpcResponseErrorData::NodeUnhealthy { num_slots_behind: Some(42) }
+        ));
+        assert!(matches!(error_data(-32601, &json!({ "error": {} })), RpcResponseErrorData::Empty));
+    }
+}
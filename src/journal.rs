@@ -0,0 +1,112 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use multi_party_eddsa::protocols::musig2::PublicPartialNonces;
+
+use crate::Error;
+
+/// What follows the nonce on the line of a session that was aborted
+const ABORTED: &str = "aborted";
+
+/// The directory solana-tss keeps its local state in, `~/.solana-tss`
+pub fn data_dir() -> Result<PathBuf, Error> {
+    let home = dirs_next::home_dir().ok_or(Error::NoHomeDir)?;
+    Ok(home.join(".solana-tss"))
+}
+
+/// A local record of the nonces a key already signed with, signing two different transactions with the same nonce leaks the key.
+pub struct NonceJournal {
+    path: PathBuf,
+}
+
+impl NonceJournal {
+    /// Open the journal of `name`, every key (or key share) has a journal of its own
+    pub fn open(name: &str) -> Result<Self, Error> {
+        Self::open_in(&data_dir()?.join("nonces"), name)
+    }
+
+    /// Open the journal of `name` kept in `dir` instead of `~/.solana-tss/nonces`
+    pub fn open_in(dir: &Path, name: &str) -> Result<Self, Error> {
+        fs::create_dir_all(dir).map_err(Error::JournalFailed)?;
+        Ok(Self { path: dir.join(name) })
+    }
+
+    /// Record the nonces as used, fails if they were already used before unless `force` is set.
+    /// Nonces of an aborted session are refused even with `force`.
+    pub fn consume(&self, nonces: &PublicPartialNonces, force: bool) -> Result<(), Error> {
+        let entry = Self::entry(nonces);
+        let lines = self.read()?;
+        let used: Vec<_> = lines.iter().filter(|line| line.split(' ').next() == Some(entry.as_str())).collect();
+        if used.iter().any(|line| line.ends_with(ABORTED)) {
+            return Err(Error::NonceAborted);
+        }
+        if !used.is_empty() {
+            return if force { Ok(()) } else { Err(Error::NonceAlreadyUsed) };
+        }
+        // Record the nonce before signing, so a crash can't leave a signature that isn't in the journal.
+        self.append(&entry)
+    }
+
+    /// Burn the nonces of an abandoned session, so its secret state is refused from then on, whether it was used or not.
+    pub fn abort(&self, nonces: &PublicPartialNonces) -> Result<(), Error> {
+        self.append(&format!("{} {}", Self::entry(nonces), ABORTED))
+    }
+
+    fn entry(nonces: &PublicPartialNonces) -> String {
+        bs58::encode(&*nonces.R[0].to_bytes(true)).into_string()
+    }
+
+    fn read(&self) -> Result<Vec<String>, Error> {
+        match fs::read_to_string(&self.path) {
+            Ok(journal) => Ok(journal.lines().map(str::to_string).collect()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(Error::JournalFailed(e)),
+        }
+    }
+
+    fn append(&self, line: &str) -> Result<(), Error> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path).map_err(Error::JournalFailed)?;
+        writeln!(file, "{}", line).map_err(Error::JournalFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::Keypair;
+
+    use crate::journal::NonceJournal;
+    use crate::serialization::SessionId;
+    use crate::{tss, Error};
+
+    #[test]
+    fn test_nonce_journal() {
+        let dir = std::env::temp_dir().join(format!("solana-tss-journal-{}", SessionId::random()));
+        let keypair = Keypair::new();
+        let (_, used) = tss::step_one(&keypair, SessionId::random());
+        let (_, aborted) = tss::step_one(&keypair, SessionId::random());
+        let (_, fresh) = tss::step_one(&keypair, SessionId::random());
+
+        let journal = NonceJournal::open_in(&dir, "party").unwrap();
+        journal.consume(&used.public_nonces, false).unwrap();
+        assert!(matches!(journal.consume(&used.public_nonces, false), Err(Error::NonceAlreadyUsed)));
+        // Signing the exact same transaction again is allowed when forced.
+        journal.consume(&used.public_nonces, true).unwrap();
+
+        journal.abort(&aborted.public_nonces).unwrap();
+        assert!(matches!(journal.consume(&aborted.public_nonces, false), Err(Error::NonceAborted)));
+        assert!(matches!(journal.consume(&aborted.public_nonces, true), Err(Error::NonceAborted)));
+        // Aborting after signing burns the nonces all the same.
+        journal.abort(&used.public_nonces).unwrap();
+        assert!(matches!(journal.consume(&used.public_nonces, true), Err(Error::NonceAborted)));
+
+        // Kept across openings, and every name has a journal of its own.
+        let journal = NonceJournal::open_in(&dir, "party").unwrap();
+        assert!(matches!(journal.consume(&used.public_nonces, false), Err(Error::NonceAborted)));
+        assert!(matches!(journal.consume(&aborted.public_nonces, false), Err(Error::NonceAborted)));
+        journal.consume(&fresh.public_nonces, false).unwrap();
+        NonceJournal::open_in(&dir, "other-party").unwrap().consume(&fresh.public_nonces, false).unwrap();
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use keyring::Entry;
+
+use crate::Error;
+
+const SERVICE: &str = "solana-tss";
+const PREFIX: &str = "keychain:";
+
+/// A secret kept in the OS keychain: the macOS Keychain, the Windows Credential Manager or the Linux Secret Service.
+/// Given as `keychain:<name>`, so keys and secret states don't go through the clipboard or plain files between rounds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeychainEntry(String);
+
+impl KeychainEntry {
+    /// Store `secret` under this entry, an existing entry is never overwritten as it may hold the only copy of a key.
+    pub fn store(&self, secret: &str) -> Result<(), Error> {
+        let entry = self.entry()?;
+        match entry.get_password() {
+            Ok(_) => return Err(Error::KeychainEntryExists(self.0.clone())),
+            Err(keyring::Error::NoEntry) => (),
+            Err(e) => return Err(Error::KeychainFailed(self.0.clone(), e)),
+        }
+        entry.set_password(secret).map_err(|e| Error::KeychainFailed(self.0.clone(), e))
+    }
+
+    /// The secret stored under this entry
+    pub fn load(&self) -> Result<String, Error> {
+        self.entry()?.get_password().map_err(|e| self.error(e))
+    }
+
+    /// Remove the entry from the keychain, once its secret state is used up or its key moved elsewhere
+    pub fn delete(&self) -> Result<(), Error> {
+        self.entry()?.delete_password().map_err(|e| self.error(e))
+    }
+
+    fn entry(&self) -> Result<Entry, Error> {
+        Entry::new(SERVICE, &self.0).map_err(|e| Error::KeychainFailed(self.0.clone(), e))
+    }
+
+    fn error(&self, e: keyring::Error) -> Error {
+        match e {
+            keyring::Error::NoEntry => Error::NotInKeychain(self.0.clone()),
+            e => Error::KeychainFailed(self.0.clone(), e),
+        }
+    }
+}
+
+impl FromStr for KeychainEntry {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(PREFIX) {
+            Some(name) if !name.is_empty() => Ok(Self(name.to_string())),
+            _ => Err(Error::WrongKeySource(s.to_string())),
+        }
+    }
+}
+
+impl Display for KeychainEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", PREFIX, self.0)
+    }
+}
+
+/// Whether `s` refers to a keychain entry rather than being a secret or a path itself
+pub fn is_entry(s: &str) -> bool {
+    s.starts_with(PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::keychain::{is_entry, KeychainEntry};
+    use crate::Error;
+
+    #[test]
+    fn test_parse_entry() {
+        let entry: KeychainEntry = "keychain:alice".parse().unwrap();
+        assert_eq!(entry.to_string(), "keychain:alice");
+        assert!(is_entry("keychain:alice"));
+        assert!(!is_entry("/home/alice/keychain:alice"));
+        assert!(matches!("keychain:".parse::<KeychainEntry>(), Err(Error::WrongKeySource(_))));
+        assert!(matches!("alice".parse::<KeychainEntry>(), Err(Error::WrongKeySource(_))));
+    }
+}
@@ -0,0 +1,254 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::{Keypair, Signer};
+use zeroize::Zeroizing;
+
+use crate::serialization::{FieldError, Serialize as Message};
+use crate::Error;
+
+const KEYSTORE_VERSION: u8 = 1;
+const PROTECTED_VERSION: u8 = 1;
+/// What a protected secret state starts with, it's never the prefix of a message so the two aren't mixed up
+const PROTECTED_PREFIX: &str = "protected_";
+const KDF: &str = "argon2id";
+const CIPHER: &str = "xchacha20poly1305";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// A secret key encrypted with a passphrase, kept as a JSON file so it can be backed up and moved around like a keypair file.
+/// The encryption key is derived from the passphrase with argon2id, the secret key is encrypted with XChaCha20-Poly1305.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Keystore {
+    version: u8,
+    /// The public key of the encrypted secret key, so the keystore can be told apart without its passphrase
+    pub pubkey: String,
+    kdf: KdfParams,
+    cipher: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KdfParams {
+    name: String,
+    salt: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl Default for KdfParams {
+    /// 64 MiB and 3 passes, about a second on a laptop, so guessing passphrases is slow.
+    fn default() -> Self {
+        let salt: [u8; SALT_LEN] = rand07::random();
+        Self { name: KDF.to_string(), salt: hex::encode(salt), memory_kib: 64 * 1024, iterations: 3, parallelism: 1 }
+    }
+}
+
+/// The cipher keyed with argon2id of `passphrase`
+fn derive_cipher(passphrase: &str, salt: &[u8], params: Params) -> Result<XChaCha20Poly1305, Error> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut *key)
+        .map_err(|e| Error::InvalidKeystore(e.to_string()))?;
+    Ok(XChaCha20Poly1305::new(Key::from_slice(&*key)))
+}
+
+impl KdfParams {
+    fn cipher(&self, passphrase: &str) -> Result<XChaCha20Poly1305, Error> {
+        if self.name != KDF {
+            return Err(Error::InvalidKeystore(format!("unknown key derivation function {}", self.name)));
+        }
+        let salt = hex::decode(&self.salt).map_err(|_| Error::InvalidKeystore("the salt isn't hex".to_string()))?;
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| Error::InvalidKeystore(e.to_string()))?;
+        derive_cipher(passphrase, &salt, params)
+    }
+}
+
+impl Keystore {
+    /// Encrypt `keypair` with `passphrase`
+    pub fn encrypt(keypair: &Keypair, passphrase: &str) -> Result<Self, Error> {
+        Self::encrypt_with(keypair, passphrase, KdfParams::default())
+    }
+
+    fn encrypt_with(keypair: &Keypair, passphrase: &str, kdf: KdfParams) -> Result<Self, Error> {
+        let nonce: [u8; NONCE_LEN] = rand07::random();
+        let ciphertext = kdf
+            .cipher(passphrase)?
+            .encrypt(XNonce::from_slice(&nonce), &Zeroizing::new(keypair.to_bytes())[..])
+            .expect("encryption can't fail");
+        Ok(Self {
+            version: KEYSTORE_VERSION,
+            pubkey: keypair.pubkey().to_string(),
+            kdf,
+            cipher: CIPHER.to_string(),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    /// The secret key, fails with `WrongPassphrase` when `passphrase` isn't the one it was encrypted with.
+    pub fn decrypt(&self, passphrase: &str) -> Result<Keypair, Error> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(Error::InvalidKeystore(format!("unknown version {}", self.version)));
+        }
+        if self.cipher != CIPHER {
+            return Err(Error::InvalidKeystore(format!("unknown cipher {}", self.cipher)));
+        }
+        let nonce = hex::decode(&self.nonce).ok().filter(|nonce| nonce.len() == NONCE_LEN);
+        let nonce = nonce.ok_or_else(|| Error::InvalidKeystore("the nonce isn't 24 bytes of hex".to_string()))?;
+        let ciphertext = hex::decode(&self.ciphertext)
+            .map_err(|_| Error::InvalidKeystore("the ciphertext isn't hex".to_string()))?;
+        let plaintext = self
+            .kdf
+            .cipher(passphrase)?
+            .decrypt(XNonce::from_slice(&nonce), &*ciphertext)
+            .map(Zeroizing::new)
+            .map_err(|_| Error::WrongPassphrase)?;
+        let keypair = Keypair::from_bytes(&plaintext)?;
+        if keypair.pubkey().to_string() != self.pubkey {
+            return Err(Error::InvalidKeystore("the secret key isn't the one of its public key".to_string()));
+        }
+        Ok(keypair)
+    }
+
+    /// Read the keystore file at `path`
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| Error::KeypairFileFailed { path: path.display().to_string(), error: e.to_string() })?;
+        Self::parse(&contents)
+    }
+
+    /// Parse the contents of a keystore file
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        serde_json::from_str(s).map_err(|e| Error::InvalidKeystore(e.to_string()))
+    }
+
+    /// Write the keystore to a new file at `path`, readable only by its owner. Never overwrites an existing file.
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let error = |e: std::io::Error| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => Error::OutfileExists(path.to_path_buf()),
+            _ => Error::KeypairFileFailed { path: path.display().to_string(), error: e.to_string() },
+        };
+        let mut file = options.open(path).map_err(error)?;
+        let json = serde_json::to_string_pretty(self).expect("serializing to memory can't fail");
+        writeln!(file, "{}", json).map_err(error)
+    }
+}
+
+/// Encrypt a secret state with `passphrase`, for passing it between the steps of a signing session without it being
+/// readable in the shell history or wherever it's kept meanwhile. Printed as `protected_<Base58>`, with the same
+/// key derivation and cipher as keystores.
+pub fn protect<T: Message>(secret: &T, passphrase: &str) -> String {
+    protect_with(secret, passphrase, protected_params())
+}
+
+fn protect_with<T: Message>(secret: &T, passphrase: &str, params: Params) -> String {
+    let salt: [u8; SALT_LEN] = rand07::random();
+    let nonce: [u8; NONCE_LEN] = rand07::random();
+    let ciphertext = derive_cipher(passphrase, &salt, params)
+        .expect("the parameters are valid")
+        .encrypt(XNonce::from_slice(&nonce), &**Zeroizing::new(secret.serialize_versioned()))
+        .expect("encryption can't fail");
+    let mut blob = vec![PROTECTED_VERSION];
+    blob.extend(salt);
+    blob.extend(nonce);
+    blob.extend(ciphertext);
+    format!("{}{}", PROTECTED_PREFIX, bs58::encode(blob).into_string())
+}
+
+/// Decrypt a secret state encrypted with `protect`, fails with `WrongPassphrase` when it was another passphrase.
+pub fn unprotect<T: Message>(s: &str, passphrase: &str) -> Result<T, Error> {
+    unprotect_with(s, passphrase, protected_params())
+}
+
+fn unprotect_with<T: Message>(s: &str, passphrase: &str, params: Params) -> Result<T, Error> {
+    let invalid = |e: &str| Error::InvalidProtectedSecret(e.to_string());
+    let encoded = s.trim().strip_prefix(PROTECTED_PREFIX).ok_or_else(|| invalid("it isn't protected"))?;
+    let blob = bs58::decode(encoded).into_vec().map_err(|_| invalid("it isn't Base58"))?;
+    if blob.len() < 1 + SALT_LEN + NONCE_LEN {
+        return Err(invalid("it's too short"));
+    }
+    if blob[0] != PROTECTED_VERSION {
+        return Err(Error::InvalidProtectedSecret(format!("unknown version {}", blob[0])));
+    }
+    let (salt, rest) = blob[1..].split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let plaintext = derive_cipher(passphrase, salt, params)?
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map(Zeroizing::new)
+        .map_err(|_| Error::WrongPassphrase)?;
+    T::deserialize_versioned(&plaintext).with_field("secret state")
+}
+
+/// Whether `s` is a secret state encrypted with `protect`, rather than a plain one
+pub fn is_protected(s: &str) -> bool {
+    s.trim_start().starts_with(PROTECTED_PREFIX)
+}
+
+/// The same as the default parameters of keystores, fixed by the version of protected secret states.
+fn protected_params() -> Params {
+    let kdf = KdfParams::default();
+    Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32)).expect("the parameters are valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::keystore::{is_protected, protect_with, unprotect_with, KdfParams, Keystore};
+    use crate::serialization::{Encoding, SecretAggStepOne, Serialize, SessionId};
+    use crate::{tss, Error};
+
+    #[test]
+    fn test_keystore() {
+        let keypair = Keypair::new();
+        // Cheap parameters, the default ones are slow on purpose.
+        let kdf = KdfParams { memory_kib: 64, iterations: 1, ..KdfParams::default() };
+        let keystore = Keystore::encrypt_with(&keypair, "correct horse", kdf).unwrap();
+        assert_eq!(keystore.pubkey, keypair.pubkey().to_string());
+
+        let parsed = Keystore::parse(&serde_json::to_string(&keystore).unwrap()).unwrap();
+        assert_eq!(parsed.decrypt("correct horse").unwrap().pubkey(), keypair.pubkey());
+        assert!(matches!(parsed.decrypt("battery staple"), Err(Error::WrongPassphrase)));
+        assert!(!serde_json::to_string(&keystore).unwrap().contains(&hex::encode(keypair.secret().to_bytes())));
+
+        let path = std::env::temp_dir().join(format!("solana-tss-keystore-{}.json", keypair.pubkey()));
+        keystore.write(&path).unwrap();
+        let written = Keystore::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written.decrypt("correct horse").unwrap().pubkey(), keypair.pubkey());
+        // Never overwritten.
+        assert!(matches!(keystore.write(&path), Err(Error::OutfileExists(_))));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(Keystore::parse("[1, 2, 3]"), Err(Error::InvalidKeystore(_))));
+    }
+
+    #[test]
+    fn test_protect() {
+        let (_, secret) = tss::step_one(&Keypair::new(), SessionId::random());
+        let params = || argon2::Params::new(64, 1, 1, Some(32)).unwrap();
+        let protected = protect_with(&secret, "correct horse", params());
+        assert!(is_protected(&protected));
+        assert!(!is_protected(&secret.serialize_encoded(Encoding::Bs58)));
+        assert!(!protected.contains(&secret.serialize_encoded(Encoding::Bs58)["secret1_".len()..]));
+
+        let unprotected: SecretAggStepOne = unprotect_with(&protected, "correct horse", params()).unwrap();
+        assert_eq!(unprotected.serialize_versioned(), secret.serialize_versioned());
+        let wrong = unprotect_with::<SecretAggStepOne>(&protected, "battery staple", params());
+        assert!(matches!(wrong, Err(Error::WrongPassphrase)));
+        let truncated = unprotect_with::<SecretAggStepOne>(&protected[..20], "correct horse", params());
+        assert!(matches!(truncated, Err(Error::InvalidProtectedSecret(_))));
+    }
+}
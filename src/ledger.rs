@@ -0,0 +1,88 @@
+use std::str::FromStr;
+
+use solana_remote_wallet::locator::Locator;
+use solana_remote_wallet::remote_keypair::{generate_remote_keypair, RemoteKeypair};
+use solana_remote_wallet::remote_wallet::maybe_wallet_manager;
+use solana_sdk::derivation_path::DerivationPath;
+use solana_sdk::signature::Signer;
+use solana_sdk::transaction::VersionedTransaction;
+
+use crate::Error;
+
+/// A key held by a Ledger running the Solana app, given like the Solana CLI does: `usb://ledger?key=<account>[/<change>]`,
+/// with the device's public key after `usb://ledger/` to pick one of several devices.
+///
+/// A Ledger only signs whole transactions, which it shows for approval, it can't compute the partial signatures
+/// of aggregate signing. Its key can't be one of the aggregated keys, it co-signs transactions next to them instead.
+#[derive(Debug, Clone)]
+pub struct LedgerKey {
+    uri: String,
+    locator: Locator,
+    derivation_path: DerivationPath,
+}
+
+impl LedgerKey {
+    /// Connect to the device, which derives the key and hands out its public key.
+    /// With `confirm` the device shows the address, to check it against the one printed.
+    pub fn connect(&self, confirm: bool) -> Result<RemoteKeypair, Error> {
+        let manager = maybe_wallet_manager().map_err(Error::LedgerFailed)?.ok_or(Error::NoLedger)?;
+        generate_remote_keypair(self.locator.clone(), self.derivation_path.clone(), &manager, confirm, &self.uri)
+            .map_err(Error::LedgerFailed)
+    }
+}
+
+impl FromStr for LedgerKey {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let wrong = || Error::WrongLedgerUri(s.to_string());
+        let (base, query) = match s.split_once('?') {
+            Some((base, query)) => (base, Some(query)),
+            None => (s, None),
+        };
+        let locator = Locator::new_from_path(base).map_err(|_| wrong())?;
+        let derivation_path = match query.map(|query| query.strip_prefix("key=").ok_or_else(wrong)).transpose()? {
+            None => DerivationPath::default(),
+            Some(key) => {
+                let mut indices = key.splitn(2, '/').map(u32::from_str);
+                let account = indices.next().transpose().map_err(|_| wrong())?;
+                let change = indices.next().transpose().map_err(|_| wrong())?;
+                DerivationPath::new_bip44(account, change)
+            }
+        };
+        Ok(Self { uri: s.to_string(), locator, derivation_path })
+    }
+}
+
+/// Have the Ledger sign `tx`, whose message it shows for approval, and fill in its signature.
+/// The key must be one of the signers the transaction is still missing.
+pub fn sign(key: &RemoteKeypair, tx: &mut VersionedTransaction) -> Result<(), Error> {
+    let pubkey = key.pubkey();
+    let mut signer_keys = tx.message.static_account_keys().iter().take(tx.signatures.len());
+    let position = signer_keys.position(|signer| *signer == pubkey).ok_or(Error::NotASigner(pubkey))?;
+    tx.signatures[position] = key.try_sign_message(&tx.message.serialize()).map_err(Error::LedgerSignFailed)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::derivation_path::DerivationPath;
+
+    use crate::ledger::LedgerKey;
+    use crate::Error;
+
+    #[test]
+    fn test_parse_ledger_key() {
+        let key: LedgerKey = "usb://ledger?key=0".parse().unwrap();
+        assert_eq!(key.derivation_path, DerivationPath::new_bip44(Some(0), None));
+        let key: LedgerKey = "usb://ledger?key=1/2".parse().unwrap();
+        assert_eq!(key.derivation_path, DerivationPath::new_bip44(Some(1), Some(2)));
+        let key: LedgerKey = "usb://ledger".parse().unwrap();
+        assert_eq!(key.derivation_path, DerivationPath::default());
+        let key: LedgerKey = "usb://ledger/BsNsvfXqQTtJnagwFWdBS7FBXgnsK8VZ5CmuznN85swK?key=0".parse().unwrap();
+        assert!(key.locator.pubkey.is_some());
+
+        assert!(matches!("usb://ledger?key=zero".parse::<LedgerKey>(), Err(Error::WrongLedgerUri(_))));
+        assert!(matches!("usb://ledger?account=0".parse::<LedgerKey>(), Err(Error::WrongLedgerUri(_))));
+        assert!(matches!("usb://trezor?key=0".parse::<LedgerKey>(), Err(Error::WrongLedgerUri(_))));
+    }
+}
@@ -0,0 +1,126 @@
+//! Threshold and n-of-n aggregated signatures for Solana wallets, as used by the `solana-tss` command line.
+//!
+//! The signing protocol is in [`tss`]: [`tss::aggregate_pubkey`] makes the address of the wallet out of the keys of
+//! its parties, then every signing takes three steps. In [`tss::step_one`] every party makes nonces and sends the
+//! first message to the others, in [`tss::step_two`] every party signs the transaction with the others' first
+//! messages, and [`tss::sign_and_broadcast`] combines the partial signatures into the signed transaction. The
+//! messages are the types of [`serialization`], printed and parsed with [`serialization::Serialize`], and
+//! [`transaction`] builds the transactions to sign.
+//!
+//! ```
+//! use solana_sdk::hash::Hash;
+//! use solana_sdk::message::{Message, VersionedMessage};
+//! use solana_sdk::pubkey::Pubkey;
+//! use solana_sdk::signature::{Keypair, Signer};
+//! use solana_tss::serialization::SessionId;
+//! use solana_tss::{transaction, tss};
+//!
+//! let parties = [Keypair::new(), Keypair::new()];
+//! let keys: Vec<_> = parties.iter().map(|party| party.pubkey()).collect();
+//! let wallet = tss::aggregate_pubkey(keys.clone())?;
+//! let instructions = transaction::transfer_instructions(0.1, None, &Pubkey::new_unique(), None, None, &wallet);
+//! let message = Message::new_with_blockhash(&instructions, Some(&wallet), &Hash::new_unique());
+//! let message = VersionedMessage::Legacy(message);
+//!
+//! // Step one, a new session ID for every transaction.
+//! let session_id = SessionId::random();
+//! let (first_messages, secret_states): (Vec<_>, Vec<_>) =
+//!     parties.iter().map(|party| tss::step_one(party, session_id)).unzip();
+//! // Step two, every party with the first messages of the others.
+//! let mut signatures = Vec::new();
+//! for (party, secret_state) in parties.iter().zip(secret_states) {
+//!     let others = first_messages.iter().filter(|msg| msg.sender != party.pubkey()).cloned().collect();
+//!     let keypair = Keypair::from_bytes(&party.to_bytes())?;
+//!     signatures.push(tss::step_two(keypair, message.clone(), keys.clone(), Vec::new(), others, secret_state)?);
+//! }
+//! // Step three, by anyone.
+//! let tx = tss::sign_and_broadcast(message, keys, first_messages, signatures)?;
+//! assert!(tx.verify_with_results().iter().all(|valid| *valid));
+//! # Ok::<(), solana_tss::Error>(())
+//! ```
+//!
+//! A secret state must never be used for two transactions, or the secret key leaks: the command line records the
+//! nonces of every signing in a [`journal::NonceJournal`] to refuse that, programs embedding the protocol should too.
+//! [`signing::Session`] runs the same steps with the order of the protocol and the single use of the nonces checked at
+//! compile time.
+
+pub mod audit;
+pub mod backup;
+#[cfg(any(feature = "wasm", feature = "python"))]
+mod bindings;
+#[cfg(feature = "client")]
+pub mod board;
+#[cfg(feature = "client")]
+pub mod close;
+#[cfg(feature = "client")]
+pub mod daemon;
+pub mod dkg;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "client")]
+pub mod governance;
+#[cfg(feature = "client")]
+pub mod grpc;
+#[cfg(feature = "client")]
+pub mod history;
+#[cfg(feature = "client")]
+mod http;
+pub mod journal;
+#[cfg(feature = "client")]
+pub mod keychain;
+pub mod keystore;
+#[cfg(feature = "client")]
+pub mod ledger;
+#[cfg(feature = "client")]
+pub mod lookup_table;
+#[cfg(feature = "client")]
+pub mod message_dir;
+pub mod mnemonic;
+#[cfg(feature = "client")]
+pub mod network;
+#[cfg(feature = "client")]
+pub mod nft;
+#[cfg(feature = "client")]
+pub mod nonce;
+pub mod offline;
+#[cfg(feature = "client")]
+pub mod p2p;
+#[cfg(feature = "client")]
+pub mod policy;
+pub mod pool;
+#[cfg(feature = "client")]
+pub mod program;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod qr;
+#[cfg(feature = "client")]
+pub mod relay;
+#[cfg(feature = "client")]
+pub mod rest;
+#[cfg(feature = "client")]
+mod retry;
+#[cfg(feature = "client")]
+pub mod rpc;
+pub mod seal;
+pub mod seed;
+#[cfg(feature = "client")]
+pub mod send;
+pub mod serialization;
+#[cfg(feature = "client")]
+pub mod session;
+pub mod signing;
+pub mod stake;
+pub mod threshold;
+pub mod token;
+pub mod transaction;
+#[cfg(feature = "client")]
+pub mod transport;
+pub mod tss;
+pub mod vanity;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "client")]
+pub mod watch;
+
+pub use error::Error;
@@ -0,0 +1,97 @@
+use solana_address_lookup_table_program::instruction::{create_lookup_table, extend_lookup_table};
+use solana_address_lookup_table_program::state::AddressLookupTable;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::address_lookup_table_account::AddressLookupTableAccount;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::Error;
+
+/// Read the addresses of `tables`, a v0 transaction refers to its accounts by their index in these.
+/// All parties must read the same addresses, so only tables that aren't extended anymore should be used while signing.
+pub async fn fetch(rpc_client: &RpcClient, tables: &[Pubkey]) -> Result<Vec<AddressLookupTableAccount>, Error> {
+    let mut accounts = Vec::with_capacity(tables.len());
+    for &key in tables {
+        let account = rpc_client.get_account(&key).await.map_err(Error::LookupTableFailed)?;
+        let table = AddressLookupTable::deserialize(&account.data).map_err(|_| Error::NotALookupTable(key))?;
+        accounts.push(AddressLookupTableAccount { key, addresses: table.addresses.into_owned() });
+    }
+    Ok(accounts)
+}
+
+/// The authority of `table`, the only one allowed to extend it
+pub async fn authority(rpc_client: &RpcClient, table: &Pubkey) -> Result<Pubkey, Error> {
+    let account = rpc_client.get_account(table).await.map_err(Error::LookupTableFailed)?;
+    let table_state = AddressLookupTable::deserialize(&account.data).map_err(|_| Error::NotALookupTable(*table))?;
+    table_state.meta.authority.ok_or(Error::NotALookupTable(*table))
+}
+
+/// Create a lookup table owned and paid for by `wallet`.
+/// Its address is derived from a recent slot, so the transaction must be sent within a few minutes.
+pub async fn create_instruction(rpc_client: &RpcClient, wallet: &Pubkey) -> Result<(Pubkey, Instruction), Error> {
+    let slot =
+        rpc_client.get_slot_with_commitment(CommitmentConfig::finalized()).await.map_err(Error::LookupTableFailed)?;
+    let (instruction, table) = create_lookup_table(*wallet, *wallet, slot);
+    Ok((table, instruction))
+}
+
+/// Add `addresses` to `table`, with its `authority` paying for the extra space
+pub fn extend_instruction(table: &Pubkey, authority: &Pubkey, addresses: Vec<Pubkey>) -> Instruction {
+    extend_lookup_table(*table, *authority, Some(*authority), addresses)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::address_lookup_table_account::AddressLookupTableAccount;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::VersionedMessage;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::serialization::{Serialize, SessionId};
+    use crate::transaction::{compile_message, transfer_instructions};
+    use crate::tss;
+
+    fn clone_serialize<T: Serialize>(t: &T) -> T {
+        let mut v = vec![0u8; t.size_hint()];
+        t.serialize(&mut v);
+        T::deserialize(&v).unwrap()
+    }
+
+    #[test]
+    fn test_sign_v0_message() {
+        let keys: Vec<_> = (0..2).map(|_| Keypair::generate(&mut rand07::thread_rng())).collect();
+        let pubkeys: Vec<_> = keys.iter().map(|k| k.pubkey()).collect();
+        let aggpubkey = tss::aggregate_pubkey(pubkeys.clone()).unwrap();
+        let to = Pubkey::new_unique();
+        let table = AddressLookupTableAccount { key: Pubkey::new_unique(), addresses: vec![Pubkey::new_unique(), to] };
+
+        let instructions = transfer_instructions(0.1, None, &to, None, None, &aggpubkey);
+        let mut message = compile_message(&instructions, &aggpubkey, std::slice::from_ref(&table)).unwrap();
+        message.set_recent_blockhash(Hash::new_unique());
+        // The recipient is loaded from the table instead of being part of the message.
+        let lookups = match &message {
+            VersionedMessage::V0(message) => &message.address_table_lookups,
+            VersionedMessage::Legacy(_) => panic!("expected a v0 message"),
+        };
+        assert_eq!(lookups.len(), 1);
+        assert_eq!((lookups[0].account_key, &lookups[0].writable_indexes[..]), (table.key, &[1][..]));
+        assert!(!message.static_account_keys().contains(&to));
+
+        let session_id = SessionId::random();
+        let (first_msgs, secrets): (Vec<_>, Vec<_>) = keys.iter().map(|k| tss::step_one(k, session_id)).unzip();
+        let partial_sigs: Vec<_> = keys
+            .iter()
+            .zip(secrets)
+            .enumerate()
+            .map(|(i, (key, secret))| {
+                let other = clone_serialize(&first_msgs[1 - i]);
+                let key = Keypair::from_bytes(&key.to_bytes()).unwrap();
+                tss::step_two(key, message.clone(), pubkeys.clone(), vec![], vec![other], secret).unwrap()
+            })
+            .collect();
+        let tx = tss::sign_and_broadcast(message, pubkeys, first_msgs, partial_sigs).unwrap();
+        assert!(tx.verify_with_results().into_iter().all(|valid| valid));
+    }
+}
@@ -0,0 +1,208 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use tracing::{info, warn};
+
+use crate::serialization::SessionId;
+use crate::transport::{origin, Round, Transport};
+use crate::Error;
+
+const PREFIX: &str = "solana-tss-";
+const EXTENSION: &str = ".txt";
+
+/// The name of the file of the message of `sender` in `round`, like `solana-tss-<session ID>-1-<sender>.txt`.
+/// There's one file per party and round, so writing a message again replaces the one before.
+fn file_name(session_id: SessionId, round: Round, sender: &Pubkey) -> String {
+    format!("{}{}-{}-{}{}", PREFIX, session_id, round.path(), sender, EXTENSION)
+}
+
+/// The session, round and sender of a message file name, `None` when it isn't one
+fn parse_file_name(name: &str) -> Option<(SessionId, String, Pubkey)> {
+    let mut parts = name.strip_prefix(PREFIX)?.strip_suffix(EXTENSION)?.splitn(3, '-');
+    let session_id = parts.next()?.parse().ok()?;
+    let round = parts.next()?.to_string();
+    let sender = Pubkey::from_str(parts.next()?).ok()?;
+    Some((session_id, round, sender))
+}
+
+/// Write the message of `sender` in `round` to `dir`, e.g. a folder shared over Dropbox or NFS, or a USB stick carried
+/// to the other parties. It's written to a temporary file first, so a half written message is never read.
+pub fn write(
+    dir: &Path,
+    session_id: SessionId,
+    round: Round,
+    sender: &Pubkey,
+    message: &str,
+) -> Result<PathBuf, Error> {
+    let failed = |e: std::io::Error| Error::MessageFileFailed(format!("{}: {}", dir.display(), e));
+    fs::create_dir_all(dir).map_err(failed)?;
+    let name = file_name(session_id, round, sender);
+    let path = dir.join(&name);
+    let temporary = dir.join(format!(".{}.tmp", name));
+    fs::write(&temporary, format!("{}\n", message)).map_err(failed)?;
+    fs::rename(&temporary, &path).map_err(failed)?;
+    Ok(path)
+}
+
+/// The message files of `round` in `dir` with the sender they're named after, but those of `skip`.
+/// The files of other sessions and rounds and hidden files are skipped too, other files are warned about.
+fn message_files(
+    dir: &Path,
+    session_id: SessionId,
+    round: Round,
+    skip: Option<Pubkey>,
+) -> Result<Vec<(PathBuf, Pubkey)>, Error> {
+    let failed = |e: std::io::Error| Error::MessageFileFailed(format!("{}: {}", dir.display(), e));
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .map_err(failed)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()
+        .map_err(failed)?;
+    entries.sort();
+
+    let mut files = Vec::new();
+    for path in entries {
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        if name.starts_with('.') || !path.is_file() {
+            continue;
+        }
+        match parse_file_name(&name) {
+            Some((file_session_id, file_round, sender))
+                if file_session_id == session_id && file_round == round.path() && Some(sender) != skip =>
+            {
+                files.push((path, sender))
+            }
+            Some(_) => {}
+            None => warn!("Ignoring {}, it isn't a message file", path.display()),
+        }
+    }
+    Ok(files)
+}
+
+/// Exchanges the messages of a signing session as files in a directory, one per party and round
+pub struct MessageDir {
+    dir: PathBuf,
+    session_id: SessionId,
+    /// The party writing to the directory, its own messages aren't read back
+    party: Option<Pubkey>,
+}
+
+impl MessageDir {
+    pub fn new(dir: PathBuf, session_id: SessionId, party: Option<Pubkey>) -> Self {
+        Self { dir, session_id, party }
+    }
+}
+
+#[async_trait]
+impl Transport for MessageDir {
+    fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+
+    async fn send(&mut self, round: Round, message: &str) -> Result<(), Error> {
+        let party = self.party.expect("only a party sends messages");
+        let path = write(&self.dir, self.session_id, round, &party, message)?;
+        info!("Wrote the message to {}", path.display());
+        Ok(())
+    }
+
+    async fn list(&mut self, round: Round) -> Result<Vec<String>, Error> {
+        let mut messages = Vec::new();
+        for (path, _) in message_files(&self.dir, self.session_id, round, self.party)? {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| Error::MessageFileFailed(format!("{}: {}", path.display(), e)))?;
+            messages.push(contents.trim().to_string());
+        }
+        Ok(messages)
+    }
+
+    /// Read the messages of all of `senders` in `round`, opening the sealed ones with `keypair`. The files aren't waited
+    /// for. A message file that isn't valid, isn't from one of `senders` or isn't from the party it's named after fails
+    /// the reading, since someone put it there.
+    async fn recv(
+        &mut self,
+        round: Round,
+        senders: &[Pubkey],
+        keypair: Option<&Keypair>,
+    ) -> Result<Vec<String>, Error> {
+        let mut messages = Vec::new();
+        let mut found = Vec::new();
+        for (path, sender) in message_files(&self.dir, self.session_id, round, self.party)? {
+            let unexpected = |reason: String| Error::UnexpectedMessageFile { path: path.clone(), reason };
+            if !senders.contains(&sender) {
+                return Err(unexpected(format!("{} isn't one of the keys", sender)));
+            }
+            let contents = fs::read_to_string(&path).map_err(|e| unexpected(e.to_string()))?;
+            let (message_sender, session_id) =
+                origin(&contents, round, keypair).map_err(|e| unexpected(e.to_string()))?;
+            if message_sender != sender {
+                return Err(unexpected(format!("it was sent by {}", message_sender)));
+            }
+            if session_id != self.session_id {
+                return Err(unexpected(format!("it's a message of session {}", session_id)));
+            }
+            messages.push(contents.trim().to_string());
+            found.push(sender);
+        }
+        match senders.iter().find(|sender| !found.contains(sender)) {
+            Some(missing) => Err(Error::MissingMessageFile { sender: *missing, dir: self.dir.clone() }),
+            None => Ok(messages),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::message_dir::{file_name, MessageDir};
+    use crate::serialization::{AggMessage1, Encoding, Serialize, SessionId};
+    use crate::transport::{recv, Round, Transport};
+    use crate::{tss, Error};
+
+    #[tokio::test]
+    async fn test_message_dir() {
+        let session_id = SessionId::random();
+        let dir = std::env::temp_dir().join(format!("solana-tss-messages-{}", session_id));
+        let parties: Vec<_> = (0..3).map(|_| Keypair::new()).collect();
+        for party in &parties {
+            let (first_msg, _) = tss::step_one(party, session_id);
+            MessageDir::new(dir.clone(), session_id, Some(party.pubkey()))
+                .send(Round::FirstMessages, &first_msg.serialize_encoded(Encoding::Bs58))
+                .await
+                .unwrap();
+        }
+        fs::write(dir.join("notes.txt"), "not a message").unwrap();
+
+        // The message of the party reading is skipped.
+        let mut transport = MessageDir::new(dir.clone(), session_id, Some(parties[0].pubkey()));
+        let others = [parties[1].pubkey(), parties[2].pubkey()];
+        let keypair = Some(&parties[0]);
+        let messages: Vec<AggMessage1> = recv(&mut transport, Round::FirstMessages, &others, keypair).await.unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(transport.list(Round::FirstMessages).await.unwrap().len(), 2);
+        assert!(matches!(
+            recv::<AggMessage1>(&mut transport, Round::FirstMessages, &others[..1], keypair).await,
+            Err(Error::UnexpectedMessageFile { .. })
+        ));
+
+        // A message renamed after another party is refused.
+        let impostor = dir.join(file_name(session_id, Round::FirstMessages, &parties[2].pubkey()));
+        fs::copy(dir.join(file_name(session_id, Round::FirstMessages, &parties[1].pubkey())), impostor).unwrap();
+        assert!(matches!(
+            recv::<AggMessage1>(&mut transport, Round::FirstMessages, &others, keypair).await,
+            Err(Error::UnexpectedMessageFile { .. })
+        ));
+        assert!(matches!(
+            recv::<AggMessage1>(&mut transport, Round::PartialSignatures, &others, keypair).await,
+            Err(Error::MissingMessageFile { .. })
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
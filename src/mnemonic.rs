@@ -0,0 +1,62 @@
+use bip39::{Language, Mnemonic, MnemonicType, Seed};
+use solana_sdk::derivation_path::DerivationPath;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::keypair::{keypair_from_seed, keypair_from_seed_and_derivation_path};
+
+use crate::Error;
+
+/// The path of the first account of Phantom and Solflare, and of `solana-keygen` with `?key=0/0`
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/501'/0'/0'";
+
+/// A new random English seed phrase of `word_count` words
+pub fn generate(word_count: usize) -> Result<String, Error> {
+    let mnemonic_type = MnemonicType::for_word_count(word_count).map_err(|_| Error::WrongWordCount(word_count))?;
+    Ok(Mnemonic::new(mnemonic_type, Language::English).into_phrase())
+}
+
+/// The keypair of the seed phrase `phrase` and its optional `passphrase` at `derivation_path`.
+/// Without a path the seed is the secret key itself, as with `solana-keygen new` and `recover` when not given one.
+pub fn keypair(phrase: &str, passphrase: &str, derivation_path: Option<DerivationPath>) -> Result<Keypair, Error> {
+    let phrase = phrase.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    let mnemonic = Mnemonic::from_phrase(&phrase, Language::English).map_err(|_| Error::InvalidSeedPhrase)?;
+    let seed = Seed::new(&mnemonic, passphrase);
+    let keypair = match derivation_path {
+        Some(derivation_path) => keypair_from_seed_and_derivation_path(seed.as_bytes(), Some(derivation_path)),
+        None => keypair_from_seed(seed.as_bytes()),
+    };
+    keypair.map_err(|e| Error::DerivationFailed(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::derivation_path::DerivationPath;
+    use solana_sdk::signature::Signer;
+    use solana_sdk::signer::keypair::keypair_from_seed_phrase_and_passphrase;
+
+    use crate::mnemonic::{generate, keypair, DEFAULT_DERIVATION_PATH};
+    use crate::Error;
+
+    #[test]
+    fn test_keypair() {
+        let phrase = generate(12).unwrap();
+        assert_eq!(phrase.split(' ').count(), 12);
+        let path = |path: &str| Some(DerivationPath::from_absolute_path_str(path).unwrap());
+        let first = keypair(&phrase, "", path(DEFAULT_DERIVATION_PATH)).unwrap();
+        let second = keypair(&phrase, "", path("m/44'/501'/1'/0'")).unwrap();
+        assert_ne!(first.pubkey(), second.pubkey());
+        // Spacing and case don't matter, the passphrase does.
+        let shouted = phrase.to_uppercase().replace(' ', "  ");
+        assert_eq!(keypair(&shouted, "", path(DEFAULT_DERIVATION_PATH)).unwrap().pubkey(), first.pubkey());
+        assert_ne!(keypair(&phrase, "extra", path(DEFAULT_DERIVATION_PATH)).unwrap().pubkey(), first.pubkey());
+
+        // Without a path, the same key as `solana-keygen`.
+        let keygen = keypair_from_seed_phrase_and_passphrase(&phrase, "").unwrap();
+        assert_eq!(keypair(&phrase, "", None).unwrap().pubkey(), keygen.pubkey());
+
+        // The last word carries a checksum, "abandon" 12 times fails it.
+        let known = keypair(&format!("{}about", "abandon ".repeat(11)), "", path(DEFAULT_DERIVATION_PATH)).unwrap();
+        assert_eq!(known.pubkey().to_string(), "HAgk14JpMQLgt6rVgv7cBQFJWFto5Dqxi472uT3DKpqk");
+        assert!(matches!(keypair(&"abandon ".repeat(12), "", None), Err(Error::InvalidSeedPhrase)));
+        assert!(matches!(generate(13), Err(Error::WrongWordCount(13))));
+    }
+}
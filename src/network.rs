@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use reqwest::Proxy;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::RpcClientConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
+use tracing::{info, warn};
+
+use crate::retry::RetrySender;
+use crate::Error;
+
+/// The cluster to read and send transactions to, through an RPC node
+#[derive(Debug)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Devnet,
+    /// A `solana-test-validator` running on this machine
+    Localnet,
+    /// Any other RPC node, like a private RPC provider
+    Url(String),
+}
+
+impl Network {
+    pub fn get_cluster_url(&self) -> &str {
+        match self {
+            Self::Mainnet => "https://api.mainnet-beta.solana.com",
+            Self::Testnet => "https://api.testnet.solana.com",
+            Self::Devnet => "https://api.devnet.solana.com",
+            Self::Localnet => "http://127.0.0.1:8899",
+            Self::Url(url) => url,
+        }
+    }
+
+    /// The name of the public cluster, or of the local validator, `None` for a URL
+    pub fn cluster_name(&self) -> Option<&'static str> {
+        match self {
+            Self::Mainnet => Some("mainnet"),
+            Self::Testnet => Some("testnet"),
+            Self::Devnet => Some("devnet"),
+            Self::Localnet => Some("localnet"),
+            Self::Url(_) => None,
+        }
+    }
+
+    /// The URL of the RPC node to use: the one `options` has for the cluster, otherwise the cluster's public one
+    pub fn rpc_url<'a>(&'a self, options: &'a RpcOptions) -> &'a str {
+        match self.cluster_name().and_then(|name| options.rpc_urls.get(name)) {
+            Some(url) => url,
+            None => self.get_cluster_url(),
+        }
+    }
+
+    /// The URL of the RPC node's websocket, which by convention listens on the port after the RPC port
+    pub fn get_websocket_url(&self, options: &RpcOptions) -> Result<String, Error> {
+        let cluster_url = self.rpc_url(options);
+        let mut url = reqwest::Url::parse(cluster_url).map_err(|_| Error::WrongNetwork(cluster_url.to_string()))?;
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        let port = url.port().map(|port| port + 1);
+        if url.set_scheme(scheme).is_err() || url.set_port(port).is_err() {
+            return Err(Error::WrongNetwork(cluster_url.to_string()));
+        }
+        Ok(url.to_string())
+    }
+
+    /// A client of the network's RPC node, once it's checked to be on the right cluster
+    pub async fn connect(&self, options: &RpcOptions) -> Result<RpcClient, Error> {
+        let rpc_client = self.client(options)?;
+        let genesis_hash = rpc_client.get_genesis_hash().await.map_err(Error::GenesisHashFailed)?;
+        self.check_cluster(&genesis_hash)?;
+        Ok(rpc_client)
+    }
+
+    /// A client of the network's RPC node without contacting it, for a command that may not need the network at all.
+    /// Nothing checks that the node is on the right cluster, use [`connect`](Self::connect) before reading from it.
+    pub fn client(&self, options: &RpcOptions) -> Result<RpcClient, Error> {
+        let url = self.rpc_url(options).to_string();
+        let sender = RetrySender::new(url, options.timeout, options.retries, options.proxy.clone())?;
+        Ok(RpcClient::new_sender(sender, RpcClientConfig::with_commitment(options.commitment)))
+    }
+
+    /// Fail if the node of a public cluster has another cluster's genesis hash. A local validator starts a cluster of
+    /// its own and a URL can point to any cluster, so for a URL the cluster is only named, for the user to notice.
+    fn check_cluster(&self, genesis_hash: &Hash) -> Result<(), Error> {
+        let found =
+            GENESIS_HASHES.iter().find(|(_, hash)| *hash == genesis_hash.to_string()).map(|(cluster, _)| *cluster);
+        let expected = match self {
+            Self::Mainnet => "mainnet",
+            Self::Testnet => "testnet",
+            Self::Devnet => "devnet",
+            Self::Localnet => return Ok(()),
+            Self::Url(url) => {
+                match found {
+                    Some(cluster) => info!("{} is on {}", url, cluster),
+                    None => warn!("{} is on an unknown cluster, its genesis hash is {}", url, genesis_hash),
+                }
+                return Ok(());
+            }
+        };
+        if found == Some(expected) {
+            Ok(())
+        } else {
+            Err(Error::WrongCluster { expected, url: self.get_cluster_url().to_string(), genesis_hash: *genesis_hash })
+        }
+    }
+}
+
+/// The genesis hashes of the public clusters, which tell them apart
+const GENESIS_HASHES: [(&str, &str); 3] = [
+    ("mainnet", "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d"),
+    ("testnet", "4uhcVJyU9pJkvQyS88uRDiswHXSCkY3zQawwpjk2NsNY"),
+    ("devnet", "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG"),
+];
+
+/// How to talk to the RPC node, the same for every command
+#[derive(Debug, Clone, Default)]
+pub struct RpcOptions {
+    /// The commitment to read the chain and confirm transactions with
+    pub commitment: CommitmentConfig,
+    pub timeout: Duration,
+    pub retries: u32,
+    pub proxy: Option<Proxy>,
+    /// The RPC nodes to use instead of the public ones, by [cluster name](Network::cluster_name), e.g. a private
+    /// node for mainnet. The node is still checked to be on the cluster.
+    pub rpc_urls: HashMap<String, String>,
+}
+
+impl FromStr for Network {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" | "Mainnet" => Ok(Self::Mainnet),
+            "testnet" | "Testnet" => Ok(Self::Testnet),
+            "devnet" | "Devnet" => Ok(Self::Devnet),
+            "localnet" | "Localnet" => Ok(Self::Localnet),
+            _ if s.starts_with("http://") || s.starts_with("https://") => Ok(Self::Url(s.to_string())),
+            _ => Err(Error::WrongNetwork(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use solana_sdk::hash::Hash;
+
+    use crate::network::{Network, RpcOptions};
+    use crate::Error;
+
+    #[test]
+    fn test_check_cluster() {
+        let mainnet = Hash::from_str("5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d").unwrap();
+        let devnet = Hash::from_str("EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG").unwrap();
+        Network::Mainnet.check_cluster(&mainnet).unwrap();
+        Network::Devnet.check_cluster(&devnet).unwrap();
+        assert!(matches!(
+            Network::Testnet.check_cluster(&mainnet),
+            Err(Error::WrongCluster { expected: "testnet", genesis_hash, .. }) if genesis_hash == mainnet
+        ));
+        // A local validator has a genesis hash of its own, a URL can be on any cluster.
+        Network::Localnet.check_cluster(&Hash::new_unique()).unwrap();
+        Network::Url("https://rpc.example.com".to_string()).check_cluster(&mainnet).unwrap();
+    }
+
+    #[test]
+    fn test_get_websocket_url() {
+        let mut options = RpcOptions::default();
+        assert_eq!(Network::Devnet.get_websocket_url(&options).unwrap(), "wss://api.devnet.solana.com/");
+        assert_eq!(Network::Localnet.get_websocket_url(&options).unwrap(), "ws://127.0.0.1:8900/");
+        let url = Network::Url("https://rpc.example.com:8443/key?x=1".to_string());
+        assert_eq!(url.get_websocket_url(&options).unwrap(), "wss://rpc.example.com:8444/key?x=1");
+        // A private node of the cluster instead of the public one.
+        options.rpc_urls.insert("mainnet".to_string(), "https://mainnet.example.com:8443".to_string());
+        assert_eq!(Network::Mainnet.rpc_url(&options), "https://mainnet.example.com:8443");
+        assert_eq!(Network::Mainnet.get_websocket_url(&options).unwrap(), "wss://mainnet.example.com:8444/");
+        assert_eq!(Network::Devnet.rpc_url(&options), "https://api.devnet.solana.com");
+    }
+}
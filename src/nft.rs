@@ -0,0 +1,255 @@
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{pubkey, system_program, sysvar};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+use crate::Error;
+
+pub const TOKEN_METADATA_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+const AUTH_RULES_PROGRAM_ID: Pubkey = pubkey!("auth9SigNpDKz4sJJ1DfCTuZrZNSAgh9sFD3rboVmgg");
+
+/// `MetadataInstruction::Transfer`, followed by `TransferArgs::V1`
+const TRANSFER_V1: [u8; 2] = [49, 0];
+const PROGRAMMABLE_NON_FUNGIBLE: u8 = 4;
+const PROGRAMMABLE_NON_FUNGIBLE_EDITION: u8 = 5;
+/// Checking the rule set of a programmable NFT takes more than the default compute budget.
+const PROGRAMMABLE_COMPUTE_UNITS: u32 = 400_000;
+
+/// An NFT with Token Metadata, it is transferred through the Token Metadata program
+/// which also handles programmable NFTs, whose token accounts are frozen for plain token transfers.
+#[derive(Debug, Clone, Copy)]
+pub struct Nft {
+    pub mint: Pubkey,
+    /// The program owning the mint
+    pub token_program: Pubkey,
+    pub programmable: bool,
+    /// The authorization rules a programmable NFT's transfers are checked against
+    pub rule_set: Option<Pubkey>,
+}
+
+impl Nft {
+    /// Look up the token program of `mint` and its metadata
+    pub async fn fetch(rpc_client: &RpcClient, mint: Pubkey) -> Result<Self, Error> {
+        let token_program = rpc_client.get_account(&mint).await.map_err(Error::MintLookupFailed)?.owner;
+        let metadata = rpc_client.get_account(&metadata_address(&mint)).await.map_err(|_| Error::NotAnNft(mint))?;
+        let (token_standard, rule_set) = parse_metadata(&metadata.data).ok_or(Error::NotAnNft(mint))?;
+        let programmable =
+            matches!(token_standard, Some(PROGRAMMABLE_NON_FUNGIBLE) | Some(PROGRAMMABLE_NON_FUNGIBLE_EDITION));
+        Ok(Self { mint, token_program, programmable, rule_set })
+    }
+
+    /// Transfer the NFT from the associated token account of `from` to the one of `to`, creating it if needed.
+    /// `from` signs and pays for everything, so it is the only signer.
+    pub fn transfer_instructions(&self, from: &Pubkey, to: &Pubkey) -> Vec<Instruction> {
+        let token = get_associated_token_address_with_program_id(from, &self.mint, &self.token_program);
+        let destination = get_associated_token_address_with_program_id(to, &self.mint, &self.token_program);
+        // Token Metadata takes its own program ID in place of the optional accounts that are left out.
+        let optional = |key: Option<Pubkey>, writable: bool| match key {
+            Some(key) if writable => AccountMeta::new(key, false),
+            Some(key) => AccountMeta::new_readonly(key, false),
+            None => AccountMeta::new_readonly(TOKEN_METADATA_PROGRAM_ID, false),
+        };
+        let (token_record, destination_record) = if self.programmable {
+            (Some(token_record_address(&self.mint, &token)), Some(token_record_address(&self.mint, &destination)))
+        } else {
+            (None, None)
+        };
+        let accounts = vec![
+            AccountMeta::new(token, false),
+            AccountMeta::new_readonly(*from, false),
+            AccountMeta::new(destination, false),
+            AccountMeta::new_readonly(*to, false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new(metadata_address(&self.mint), false),
+            optional(Some(edition_address(&self.mint)), false),
+            optional(token_record, true),
+            optional(destination_record, true),
+            AccountMeta::new_readonly(*from, true),
+            AccountMeta::new(*from, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            AccountMeta::new_readonly(self.token_program, false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            optional(self.rule_set.map(|_| AUTH_RULES_PROGRAM_ID), false),
+            optional(self.rule_set, false),
+        ];
+        // Always one whole NFT, and no authorization data.
+        let mut data = TRANSFER_V1.to_vec();
+        data.extend(1u64.to_le_bytes());
+        data.push(0);
+
+        let mut instructions = Vec::new();
+        if self.programmable {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(PROGRAMMABLE_COMPUTE_UNITS));
+        }
+        instructions.push(Instruction { program_id: TOKEN_METADATA_PROGRAM_ID, accounts, data });
+        instructions
+    }
+}
+
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    let seeds: &[&[u8]] = &[b"metadata", TOKEN_METADATA_PROGRAM_ID.as_ref(), mint.as_ref()];
+    Pubkey::find_program_address(seeds, &TOKEN_METADATA_PROGRAM_ID).0
+}
+
+fn edition_address(mint: &Pubkey) -> Pubkey {
+    let seeds: &[&[u8]] = &[b"metadata", TOKEN_METADATA_PROGRAM_ID.as_ref(), mint.as_ref(), b"edition"];
+    Pubkey::find_program_address(seeds, &TOKEN_METADATA_PROGRAM_ID).0
+}
+
+fn token_record_address(mint: &Pubkey, token: &Pubkey) -> Pubkey {
+    let seeds: &[&[u8]] =
+        &[b"metadata", TOKEN_METADATA_PROGRAM_ID.as_ref(), mint.as_ref(), b"token_record", token.as_ref()];
+    Pubkey::find_program_address(seeds, &TOKEN_METADATA_PROGRAM_ID).0
+}
+
+/// The token standard and rule set out of a metadata account, skipping over everything before them
+fn parse_metadata(data: &[u8]) -> Option<(Option<u8>, Option<Pubkey>)> {
+    let mut reader = Reader(data);
+    // Key, update authority and mint
+    reader.skip(1 + 32 + 32)?;
+    // Name, symbol and URI
+    for _ in 0..3 {
+        let len = reader.length()?;
+        reader.skip(len)?;
+    }
+    // Seller fee basis points
+    reader.skip(2)?;
+    if reader.option()? {
+        let creators = reader.length()?;
+        reader.skip(creators.checked_mul(32 + 1 + 1)?)?;
+    }
+    // Primary sale happened and is mutable
+    reader.skip(2)?;
+    if reader.option()? {
+        reader.skip(1)?;
+    }
+    let token_standard = if reader.option()? { Some(reader.take(1)?[0]) } else { None };
+    // Collection, uses and collection details
+    if reader.option()? {
+        reader.skip(1 + 32)?;
+    }
+    if reader.option()? {
+        reader.skip(1 + 8 + 8)?;
+    }
+    if reader.option()? {
+        reader.skip(1 + 8)?;
+    }
+    // Older metadata accounts end before the programmable config.
+    let rule_set = match reader.option() {
+        Some(true) => {
+            reader.skip(1)?;
+            if reader.option()? {
+                Some(Pubkey::new(reader.take(32)?))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+    Some((token_standard, rule_set))
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.take(len).map(|_| ())
+    }
+
+    /// The length of a string or vector
+    fn length(&mut self) -> Option<usize> {
+        let bytes = self.take(4)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn option(&mut self) -> Option<bool> {
+        self.take(1).map(|tag| tag[0] != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::message::Message;
+    use solana_sdk::pubkey::Pubkey;
+
+    use crate::nft::{parse_metadata, token_record_address, Nft, TOKEN_METADATA_PROGRAM_ID};
+
+    fn metadata(token_standard: u8, rule_set: Option<Pubkey>) -> Vec<u8> {
+        let mut data = vec![4];
+        data.extend(Pubkey::new_unique().as_ref());
+        data.extend(Pubkey::new_unique().as_ref());
+        for field in ["Pixel #1", "PXL", "https://example.com/1.json"] {
+            data.extend((field.len() as u32).to_le_bytes());
+            data.extend(field.as_bytes());
+        }
+        data.extend(500u16.to_le_bytes());
+        // One verified creator with all the share
+        data.extend([1, 1, 0, 0, 0]);
+        data.extend(Pubkey::new_unique().as_ref());
+        data.extend([1, 100]);
+        data.extend([1, 1]);
+        data.extend([1, 255]);
+        data.extend([1, token_standard]);
+        data.extend([1, 1]);
+        data.extend(Pubkey::new_unique().as_ref());
+        data.extend([0, 0]);
+        match rule_set {
+            Some(rule_set) => {
+                data.extend([1, 0, 1]);
+                data.extend(rule_set.as_ref());
+            }
+            None => data.extend([1, 0, 0]),
+        }
+        // Metadata accounts are padded to a fixed size.
+        data.resize(679, 0);
+        data
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        let rule_set = Pubkey::new_unique();
+        assert_eq!(parse_metadata(&metadata(4, Some(rule_set))), Some((Some(4), Some(rule_set))));
+        assert_eq!(parse_metadata(&metadata(0, None)), Some((Some(0), None)));
+        assert_eq!(parse_metadata(&[4; 10]), None);
+    }
+
+    #[test]
+    fn test_programmable_transfer() {
+        let (wallet, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let rule_set = Pubkey::new_unique();
+        let nft = Nft {
+            mint: Pubkey::new_unique(),
+            token_program: spl_token::id(),
+            programmable: true,
+            rule_set: Some(rule_set),
+        };
+        let instructions = nft.transfer_instructions(&wallet, &to);
+        let message = Message::new(&instructions, Some(&wallet));
+        assert_eq!(message.header.num_required_signatures, 1);
+
+        let transfer = &instructions[1];
+        assert_eq!(transfer.program_id, TOKEN_METADATA_PROGRAM_ID);
+        let source = transfer.accounts[0].pubkey;
+        assert_eq!(transfer.accounts[7].pubkey, token_record_address(&nft.mint, &source));
+        assert!(transfer.accounts[7].is_writable);
+        assert_eq!(transfer.accounts[16].pubkey, rule_set);
+
+        // A plain NFT leaves out the token records and rules.
+        let nft = Nft { programmable: false, rule_set: None, ..nft };
+        let instructions = nft.transfer_instructions(&wallet, &to);
+        assert_eq!(instructions.len(), 1);
+        assert!([7, 8, 15, 16].iter().all(|&i| instructions[0].accounts[i].pubkey == TOKEN_METADATA_PROGRAM_ID));
+    }
+}
@@ -0,0 +1,49 @@
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::nonce_utils::nonblocking as nonce_utils;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::nonce::state::{Data, State};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{system_instruction, system_program};
+
+use crate::Error;
+
+/// The state of `nonce_account`, its nonce is signed in place of a recent block hash
+pub async fn fetch(rpc_client: &RpcClient, nonce_account: &Pubkey) -> Result<Data, Error> {
+    let account = nonce_utils::get_account(rpc_client, nonce_account).await.map_err(Error::NonceAccountFailed)?;
+    nonce_utils::data_from_account(&account).map_err(Error::NonceAccountFailed)
+}
+
+/// Create a nonce account controlled by `authority`, paid for by `wallet`.
+/// Its address is derived from `wallet` and `seed`, so the wallet is the only one signing.
+pub async fn create_instructions(
+    rpc_client: &RpcClient,
+    wallet: &Pubkey,
+    seed: &str,
+    authority: &Pubkey,
+) -> Result<(Pubkey, Vec<Instruction>), Error> {
+    let nonce_account = Pubkey::create_with_seed(wallet, seed, &system_program::id()).map_err(Error::InvalidSeed)?;
+    let rent = rpc_client.get_minimum_balance_for_rent_exemption(State::size()).await.map_err(Error::RentFailed)?;
+    let instructions =
+        system_instruction::create_nonce_account_with_seed(wallet, &nonce_account, wallet, seed, authority, rent);
+    Ok((nonce_account, instructions))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::system_instruction::SystemInstruction;
+
+    use crate::transaction::create_unsigned_transaction;
+
+    #[test]
+    fn test_advance_nonce() {
+        let (from, to, nonce_account) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let tx = create_unsigned_transaction(0.5, None, &to, None, Some(nonce_account), &from);
+        // The nonce has to be advanced by the first instruction, by the wallet that is already signing.
+        let ins = &tx.message.instructions[0];
+        assert_eq!(tx.message.account_keys[usize::from(ins.accounts[0])], nonce_account);
+        assert!(matches!(bincode::deserialize(&ins.data), Ok(SystemInstruction::AdvanceNonceAccount)));
+        assert!(solana_sdk::transaction::uses_durable_nonce(&tx).is_some());
+        assert_eq!(tx.message.header.num_required_signatures, 1);
+    }
+}
@@ -0,0 +1,107 @@
+use serde_json::json;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::VersionedTransaction;
+
+use crate::serialization::Format;
+use crate::Error;
+
+/// Add the signatures of the other signers of `tx`, given as `PUBKEY=SIGNATURE` like the Solana CLI's `--signer`
+pub fn add_signers(tx: &mut VersionedTransaction, signers: &[(Pubkey, Signature)]) -> Result<(), Error> {
+    let message = tx.message.serialize();
+    for (key, signature) in signers {
+        let mut signer_keys = tx.message.static_account_keys().iter().take(tx.signatures.len());
+        let position = signer_keys.position(|signer| signer == key).ok_or(Error::NotASigner(*key))?;
+        if !signature.verify(key.as_ref(), &message) {
+            return Err(Error::InvalidSignerSignature(*key));
+        }
+        tx.signatures[position] = *signature;
+    }
+    Ok(())
+}
+
+/// The signers of `tx` whose signatures are still missing
+pub fn absent_signers(tx: &VersionedTransaction) -> Vec<Pubkey> {
+    let signer_keys = tx.message.static_account_keys().iter().zip(&tx.signatures);
+    signer_keys.filter(|(_, signature)| **signature == Signature::default()).map(|(key, _)| *key).collect()
+}
+
+/// Fail unless every signer of `tx` signed it, a transaction can only be sent once it's complete
+pub fn check_complete(tx: &VersionedTransaction) -> Result<(), Error> {
+    let absent = absent_signers(tx);
+    if absent.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::MissingSignatures(absent))
+    }
+}
+
+/// Print the signatures of `tx` the way the Solana CLI's `--sign-only` does, for its `--signer`
+pub fn print_sign_only(tx: &VersionedTransaction, format: Format) {
+    let keys = tx.message.static_account_keys().iter().zip(&tx.signatures);
+    let signers: Vec<_> = keys
+        .filter(|(_, signature)| **signature != Signature::default())
+        .map(|(key, signature)| format!("{}={}", key, signature))
+        .collect();
+    let absent: Vec<_> = absent_signers(tx).iter().map(ToString::to_string).collect();
+    match format {
+        Format::Text => {
+            println!("Blockhash: {}", tx.message.recent_blockhash());
+            println!("Signers (Pubkey=Signature):");
+            for signer in &signers {
+                println!(" {}", signer);
+            }
+            if !absent.is_empty() {
+                println!("Absent Signers (Pubkey):");
+                for key in &absent {
+                    println!(" {}", key);
+                }
+            }
+        }
+        Format::Json => {
+            let json = json!({
+                "blockhash": tx.message.recent_blockhash().to_string(),
+                "signers": signers,
+                "absent": absent,
+            });
+            println!("{}", json);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::Message;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::{Keypair, Signature, Signer};
+    use solana_sdk::system_instruction;
+    use solana_sdk::transaction::{Transaction, VersionedTransaction};
+
+    use crate::offline::{absent_signers, add_signers, check_complete};
+    use crate::Error;
+
+    #[test]
+    fn test_add_signers() {
+        let (payer, wallet) = (Keypair::new(), Pubkey::new_unique());
+        let instructions = [system_instruction::transfer(&wallet, &Pubkey::new_unique(), 1)];
+        let message = Message::new_with_blockhash(&instructions, Some(&payer.pubkey()), &Hash::new_unique());
+        let mut tx = VersionedTransaction::from(Transaction::new_unsigned(message));
+        assert_eq!(absent_signers(&tx), [payer.pubkey(), wallet]);
+
+        let signature = payer.sign_message(&tx.message.serialize());
+        assert!(matches!(
+            add_signers(&mut tx, &[(wallet, signature)]),
+            Err(Error::InvalidSignerSignature(key)) if key == wallet
+        ));
+        let stranger = Pubkey::new_unique();
+        assert!(matches!(
+            add_signers(&mut tx, &[(stranger, Signature::default())]),
+            Err(Error::NotASigner(key)) if key == stranger
+        ));
+
+        add_signers(&mut tx, &[(payer.pubkey(), signature)]).unwrap();
+        assert_eq!(tx.signatures[0], signature);
+        assert!(matches!(check_complete(&tx), Err(Error::MissingSignatures(absent)) if absent == [wallet]));
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use libp2p::gossipsub::{self, IdentTopic, MessageAuthenticity, TopicHash, ValidationMode};
+use libp2p::multiaddr::Protocol;
+use libp2p::swarm::{NetworkBehaviour, SwarmEvent};
+use libp2p::{dcutr, identify, mdns, noise, relay, tcp, yamux, Multiaddr, Swarm, SwarmBuilder};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+
+use crate::serialization::SessionId;
+use crate::transport::{missing, Round, Transport};
+use crate::Error;
+
+/// How long to wait for the other parties before asking for their messages to be pasted instead
+const EXCHANGE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// How often the messages of this party are published again, for the parties that joined since
+const REPUBLISH_INTERVAL: Duration = Duration::from_secs(3);
+/// So peers on the topic can't fill up the memory
+const MAX_MESSAGES: usize = 256;
+
+#[derive(NetworkBehaviour)]
+struct Behaviour {
+    gossipsub: gossipsub::Behaviour,
+    mdns: mdns::tokio::Behaviour,
+    relay: relay::client::Behaviour,
+    dcutr: dcutr::Behaviour,
+    identify: identify::Behaviour,
+}
+
+/// A node of the peer-to-peer network of a signing session, the parties find each other on the local network with mDNS
+/// or through the peers they're given, and exchange their messages over gossipsub, without any server in between
+pub struct P2p {
+    swarm: Swarm<Behaviour>,
+    session_id: SessionId,
+    /// The messages of this party, with the topic of their round
+    published: Vec<(IdentTopic, String)>,
+    received: HashMap<TopicHash, Vec<String>>,
+}
+
+impl P2p {
+    /// Join the network of `session_id`. `peers` are dialed, those ending in `/p2p-circuit` are libp2p relays that are
+    /// listened on instead, for parties behind a NAT to be reached through them until they connect directly.
+    pub async fn join(session_id: SessionId, peers: &[Multiaddr]) -> Result<Self, Error> {
+        let failed = |e: &dyn std::fmt::Display| Error::P2pFailed(e.to_string());
+        let mut swarm = SwarmBuilder::with_new_identity()
+            .with_tokio()
+            .with_tcp(tcp::Config::default(), noise::Config::new, yamux::Config::default)
+            .map_err(|e| failed(&e))?
+            .with_relay_client(noise::Config::new, yamux::Config::default)
+            .map_err(|e| failed(&e))?
+            .with_behaviour(|key, relay| {
+                let peer_id = key.public().to_peer_id();
+                let config = gossipsub::ConfigBuilder::default()
+                    .heartbeat_interval(Duration::from_secs(1))
+                    .validation_mode(ValidationMode::Strict)
+                    .build()?;
+                Ok(Behaviour {
+                    gossipsub: gossipsub::Behaviour::new(MessageAuthenticity::Signed(key.clone()), config)?,
+                    mdns: mdns::tokio::Behaviour::new(mdns::Config::default(), peer_id)?,
+                    relay,
+                    dcutr: dcutr::Behaviour::new(peer_id),
+                    identify: identify::Behaviour::new(identify::Config::new("/solana-tss/1".into(), key.public())),
+                })
+            })
+            .map_err(|e| failed(&e))?
+            .with_swarm_config(|config| config.with_idle_connection_timeout(Duration::from_secs(60)))
+            .build();
+
+        swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse().expect("the address is valid")).map_err(|e| failed(&e))?;
+        for peer in peers {
+            if peer.iter().any(|protocol| protocol == Protocol::P2pCircuit) {
+                swarm.listen_on(peer.clone()).map_err(|e| failed(&e))?;
+            } else {
+                swarm.dial(peer.clone()).map_err(|e| failed(&e))?;
+            }
+        }
+        for round in [Round::FirstMessages, Round::PartialSignatures] {
+            swarm.behaviour_mut().gossipsub.subscribe(&topic(session_id, round)).map_err(|e| failed(&e))?;
+        }
+        Ok(Self { swarm, session_id, published: Vec::new(), received: HashMap::new() })
+    }
+
+    /// Publish a message of `round`, as printed. It's published again until this party is done, so the parties that
+    /// join later get it too.
+    pub fn publish(&mut self, round: Round, message: String) {
+        let topic = topic(self.session_id, round);
+        // Without peers yet it fails, it's published again once there are some.
+        let _ = self.swarm.behaviour_mut().gossipsub.publish(topic.clone(), message.as_bytes());
+        self.published.push((topic, message));
+    }
+
+    /// Keep publishing the messages of this party until `count` messages of `round` were seen, its own included,
+    /// so the other parties and whoever aggregates get it before this party leaves
+    pub async fn linger(&mut self, round: Round, count: usize) {
+        let deadline = tokio::time::sleep(EXCHANGE_TIMEOUT);
+        tokio::pin!(deadline);
+        let mut republish = tokio::time::interval(REPUBLISH_INTERVAL);
+        let topic = topic(self.session_id, round).hash();
+        while self.received.get(&topic).map_or(0, Vec::len) + 1 < count {
+            tokio::select! {
+                _ = &mut deadline => return,
+                _ = republish.tick() => self.republish(),
+                event = self.swarm.select_next_some() => self.handle(event),
+            }
+        }
+    }
+
+    /// The messages of `round` that arrived so far
+    fn received(&self, round: Round) -> Vec<String> {
+        self.received.get(&topic(self.session_id, round).hash()).cloned().unwrap_or_default()
+    }
+
+    fn republish(&mut self) {
+        for (topic, message) in &self.published {
+            let _ = self.swarm.behaviour_mut().gossipsub.publish(topic.clone(), message.as_bytes());
+        }
+    }
+
+    fn handle(&mut self, event: SwarmEvent<BehaviourEvent>) {
+        let gossipsub = &mut self.swarm.behaviour_mut().gossipsub;
+        match event {
+            SwarmEvent::Behaviour(BehaviourEvent::Mdns(mdns::Event::Discovered(peers))) => {
+                for (peer, _) in peers {
+                    gossipsub.add_explicit_peer(&peer);
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Mdns(mdns::Event::Expired(peers))) => {
+                for (peer, _) in peers {
+                    gossipsub.remove_explicit_peer(&peer);
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Gossipsub(gossipsub::Event::Message { message, .. })) => {
+                let messages = self.received.entry(message.topic).or_default();
+                if let Ok(text) = String::from_utf8(message.data) {
+                    if messages.len() < MAX_MESSAGES && !messages.contains(&text) {
+                        messages.push(text);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for P2p {
+    fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+
+    async fn send(&mut self, round: Round, message: &str) -> Result<(), Error> {
+        self.publish(round, message.to_string());
+        Ok(())
+    }
+
+    async fn list(&mut self, round: Round) -> Result<Vec<String>, Error> {
+        Ok(self.received(round))
+    }
+
+    /// Wait for the message of every one of `senders` in `round`, but give up after a while and return the messages
+    /// that arrived, for the missing ones to be asked for
+    async fn recv(
+        &mut self,
+        round: Round,
+        senders: &[Pubkey],
+        keypair: Option<&Keypair>,
+    ) -> Result<Vec<String>, Error> {
+        let deadline = tokio::time::sleep(EXCHANGE_TIMEOUT);
+        tokio::pin!(deadline);
+        let mut republish = tokio::time::interval(REPUBLISH_INTERVAL);
+        loop {
+            let messages = self.received(round);
+            if missing(&messages, self.session_id, round, senders, keypair)?.is_empty() {
+                return Ok(messages);
+            }
+            tokio::select! {
+                _ = &mut deadline => return Ok(messages),
+                _ = republish.tick() => self.republish(),
+                event = self.swarm.select_next_some() => self.handle(event),
+            }
+        }
+    }
+}
+
+fn topic(session_id: SessionId, round: Round) -> IdentTopic {
+    IdentTopic::new(format!("solana-tss/{}/{}", session_id, round.path()))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::p2p::{topic, P2p};
+    use crate::serialization::{AggMessage1, Encoding, Serialize, SessionId};
+    use crate::transport::{accept, recv, Round, Transport};
+    use crate::tss;
+
+    #[tokio::test]
+    async fn test_p2p() {
+        let session_id = SessionId::random();
+        let (alice, bob) = (Keypair::new(), Keypair::new());
+        let mut node = P2p::join(session_id, &[]).await.unwrap();
+        let (first_msg, _) = tss::step_one(&bob, session_id);
+        let (other_session, _) = tss::step_one(&bob, SessionId::random());
+        let hash = topic(session_id, Round::FirstMessages).hash();
+        node.received.insert(
+            hash,
+            vec![
+                other_session.serialize_encoded(Encoding::Bs58).to_string(),
+                first_msg.serialize_encoded(Encoding::Bs58).to_string(),
+            ],
+        );
+
+        let received: Vec<AggMessage1> = recv(&mut node, Round::FirstMessages, &[bob.pubkey()], None).await.unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].session_id, session_id);
+        let posted = node.list(Round::FirstMessages).await.unwrap();
+        let received: Vec<AggMessage1> = accept(posted, session_id, &[alice.pubkey()], None).unwrap();
+        assert!(received.is_empty());
+    }
+}
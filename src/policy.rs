@@ -0,0 +1,534 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use regex::Regex;
+use solana_sdk::loader_upgradeable_instruction::UpgradeableLoaderInstruction;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::native_token::{lamports_to_sol, sol_to_lamports};
+use solana_sdk::program_utils::limited_deserialize;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::stake::instruction::StakeInstruction;
+use solana_sdk::system_instruction::SystemInstruction;
+use solana_sdk::vote::instruction::VoteInstruction;
+use solana_sdk::{bpf_loader_upgradeable, compute_budget, system_program};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_token_2022::extension::transfer_fee::instruction::TransferFeeInstruction;
+use spl_token_2022::instruction::TokenInstruction;
+
+use crate::journal::data_dir;
+use crate::network::Network;
+use crate::Error;
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+/// The policy file as written, every rule is optional
+#[derive(Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    max_amount: Option<f64>,
+    max_daily_amount: Option<f64>,
+    allowed_recipients: Option<Vec<String>>,
+    #[serde(default)]
+    denied_recipients: Vec<String>,
+    memo_pattern: Option<String>,
+    networks: Option<Vec<String>>,
+    #[serde(default)]
+    token_limits: HashMap<String, TokenLimits>,
+}
+
+/// The limits of a token, in tokens rather than in the base units of its mint
+#[derive(Clone, Copy, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TokenLimits {
+    max_amount: Option<f64>,
+    max_daily_amount: Option<f64>,
+}
+
+/// What a transaction spends, counted toward the daily limits once it's signed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Spent {
+    pub lamports: u64,
+    /// In the base units of each token mint
+    pub tokens: BTreeMap<Pubkey, u64>,
+}
+
+/// What a signer agrees to sign, checked before signing anything. An empty policy allows everything.
+#[derive(Default)]
+pub struct Policy {
+    /// The most lamports a single transaction may transfer
+    max_amount: Option<u64>,
+    /// The most lamports a wallet may transfer in any 24 hours
+    max_daily_amount: Option<u64>,
+    allowed_recipients: Option<Vec<Pubkey>>,
+    denied_recipients: Vec<Pubkey>,
+    /// Every memo must match it, and there must be one
+    memo_pattern: Option<Regex>,
+    /// The cluster URLs of the networks that may be signed for
+    networks: Option<Vec<String>>,
+    /// The limits of the tokens that may be sent, by mint, any other token is refused when there's a limit
+    token_limits: HashMap<Pubkey, TokenLimits>,
+}
+
+impl Policy {
+    /// The policy in `path`, or in `~/.solana-tss/policy.toml` when it exists, or an empty policy
+    pub fn load(path: Option<&Path>) -> Result<Self, Error> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => (data_dir()?.join("policy.toml"), false),
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(Error::PolicyFailed(format!("{}: {}", path.display(), e))),
+        };
+        let file: PolicyFile =
+            toml::from_str(&contents).map_err(|e| Error::PolicyFailed(format!("{}: {}", path.display(), e)))?;
+        Self::from_file(file)
+    }
+
+    fn from_file(file: PolicyFile) -> Result<Self, Error> {
+        let invalid = |field: &str, value: &str| Error::PolicyFailed(format!("invalid {}: {}", field, value));
+        let parse_keys = |field: &str, keys: Vec<String>| -> Result<Vec<Pubkey>, Error> {
+            keys.iter().map(|key| Pubkey::from_str(key).map_err(|_| invalid(field, key))).collect()
+        };
+        Ok(Self {
+            max_amount: file.max_amount.map(sol_to_lamports),
+            max_daily_amount: file.max_daily_amount.map(sol_to_lamports),
+            allowed_recipients: file
+                .allowed_recipients
+                .map(|keys| parse_keys("allowed_recipients", keys))
+                .transpose()?,
+            denied_recipients: parse_keys("denied_recipients", file.denied_recipients)?,
+            memo_pattern: file
+                .memo_pattern
+                .map(|pattern| Regex::new(&pattern).map_err(|_| invalid("memo_pattern", &pattern)))
+                .transpose()?,
+            networks: file
+                .networks
+                .map(|networks| {
+                    networks
+                        .iter()
+                        .map(|net| {
+                            Network::from_str(net)
+                                .map(|net| net.get_cluster_url().to_string())
+                                .map_err(|_| invalid("networks", net))
+                        })
+                        .collect()
+                })
+                .transpose()?,
+            token_limits: file
+                .token_limits
+                .into_iter()
+                .map(|(mint, limits)| {
+                    Ok((Pubkey::from_str(&mint).map_err(|_| invalid("token_limits", &mint))?, limits))
+                })
+                .collect::<Result<_, Error>>()?,
+        })
+    }
+
+    /// Whether a rule needs the amounts sent
+    fn limits_amounts(&self) -> bool {
+        self.max_amount.is_some() || self.max_daily_amount.is_some() || !self.token_limits.is_empty()
+    }
+
+    /// Whether a rule needs the recipients
+    fn limits_recipients(&self) -> bool {
+        self.allowed_recipients.is_some() || !self.denied_recipients.is_empty()
+    }
+
+    /// Refuse a transaction of `wallet` on `net` that breaks a rule, returns what it spends. `to` is the owner of the
+    /// token account a token transfer goes to, when known, it's checked as a recipient too. When a rule limits the
+    /// amounts or the recipients, any instruction whose effect the policy can't tell is refused.
+    pub fn check(
+        &self,
+        message: &VersionedMessage,
+        wallet: &Pubkey,
+        to: Option<&Pubkey>,
+        net: &Network,
+    ) -> Result<Spent, Error> {
+        let violation = |rule: String| Err(Error::PolicyViolation(rule));
+        if let Some(networks) = &self.networks {
+            if !networks.iter().any(|url| url == net.get_cluster_url()) {
+                return violation(format!("signing for {} isn't allowed", net.get_cluster_url()));
+            }
+        }
+
+        let mut summary = summarize(message);
+        if self.limits_amounts() || self.limits_recipients() {
+            if let Some(unknown) = summary.unknown.first() {
+                return violation(format!("the policy can't tell what {} does", unknown));
+            }
+        }
+
+        summary.recipients.extend(to.map(|to| Recipient { account: Some(*to), token: None }));
+        for recipient in summary.recipients.iter().filter(|_| self.limits_recipients()) {
+            let account = match recipient.account {
+                Some(account) => account,
+                None => {
+                    return violation("a recipient is loaded from a lookup table, so it can't be checked".to_string())
+                }
+            };
+            if let Some(denied) = self.denied_recipients.iter().find(|key| recipient.is(key)) {
+                return violation(format!("{} is a denied recipient", denied));
+            }
+            if let Some(allowed) = &self.allowed_recipients {
+                if !allowed.iter().any(|key| recipient.is(key)) {
+                    return violation(format!("{} isn't an allowed recipient", account));
+                }
+            }
+        }
+
+        if let Some(pattern) = &self.memo_pattern {
+            if summary.memos.is_empty() {
+                return violation(format!("a memo matching {} is required", pattern));
+            }
+            if let Some(memo) = summary.memos.iter().find(|memo| !pattern.is_match(memo)) {
+                return violation(format!("the memo {:?} doesn't match {}", memo, pattern));
+            }
+        }
+
+        let amount = summary.lamports;
+        if let Some(max) = self.max_amount.filter(|&max| amount > max) {
+            return violation(format!(
+                "the transaction sends {} SOL, over the limit of {} SOL",
+                lamports_to_sol(amount),
+                lamports_to_sol(max)
+            ));
+        }
+        if let Some(max) = self.max_daily_amount {
+            let spent = spent_today(wallet, None)?;
+            if spent.saturating_add(amount) > max {
+                return violation(format!(
+                    "{} already sent {} SOL in the last 24 hours, {} SOL more is over the limit of {} SOL",
+                    wallet,
+                    lamports_to_sol(spent),
+                    lamports_to_sol(amount),
+                    lamports_to_sol(max)
+                ));
+            }
+        }
+
+        for (mint, &(amount, decimals)) in summary.tokens.iter().filter(|_| self.limits_amounts()) {
+            let limits = match self.token_limits.get(mint) {
+                Some(limits) => limits,
+                None => return violation(format!("the token {} has no limits, so it can't be sent", mint)),
+            };
+            let tokens = |amount| spl_token::amount_to_ui_amount(amount, decimals);
+            if let Some(max) = limits.max_amount.filter(|&max| amount > spl_token::ui_amount_to_amount(max, decimals)) {
+                return violation(format!(
+                    "the transaction sends {} of the token {}, over the limit of {}",
+                    tokens(amount),
+                    mint,
+                    max
+                ));
+            }
+            if let Some(max) = limits.max_daily_amount {
+                let spent = spent_today(wallet, Some(mint))?;
+                if spent.saturating_add(amount) > spl_token::ui_amount_to_amount(max, decimals) {
+                    return violation(format!(
+                        "{} already sent {} of the token {} in the last 24 hours, {} more is over the limit of {}",
+                        wallet,
+                        tokens(spent),
+                        mint,
+                        tokens(amount),
+                        max
+                    ));
+                }
+            }
+        }
+        let tokens = summary.tokens.into_iter().map(|(mint, (amount, _))| (mint, amount)).collect();
+        Ok(Spent { lamports: amount, tokens })
+    }
+
+    /// Count what `wallet` signed for toward its daily limits, once it's signed
+    pub fn record(&self, wallet: &Pubkey, spent: &Spent) -> Result<(), Error> {
+        let mut amounts = Vec::new();
+        if self.max_daily_amount.is_some() {
+            amounts.push((None, spent.lamports));
+        }
+        for (mint, amount) in &spent.tokens {
+            if self.token_limits.get(mint).map_or(false, |limits| limits.max_daily_amount.is_some()) {
+                amounts.push((Some(mint), *amount));
+            }
+        }
+        for (mint, amount) in amounts.into_iter().filter(|(_, amount)| *amount > 0) {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(spent_path(wallet, mint)?)
+                .map_err(|e| Error::PolicyFailed(e.to_string()))?;
+            writeln!(file, "{} {}", now(), amount).map_err(|e| Error::PolicyFailed(e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+/// An account a transaction sends to, `None` when loaded from a lookup table
+struct Recipient {
+    account: Option<Pubkey>,
+    /// The mint and token program of a token account, whose owner may be the recipient checked
+    token: Option<(Pubkey, Pubkey)>,
+}
+
+impl Recipient {
+    /// Whether it's `key`, or the associated token account of `key`
+    fn is(&self, key: &Pubkey) -> bool {
+        let account = match self.account {
+            Some(account) => account,
+            None => return false,
+        };
+        account == *key
+            || self.token.map_or(false, |(mint, program_id)| {
+                get_associated_token_address_with_program_id(key, &mint, &program_id) == account
+            })
+    }
+}
+
+/// What an instruction does, as far as the rules are concerned
+enum Effect {
+    Nothing,
+    /// Lamports sent, to a recipient unless it's an account the transaction creates
+    Send {
+        lamports: u64,
+        recipient: Option<Recipient>,
+    },
+    /// The base units of a token sent to one of its accounts
+    SendToken {
+        mint: Pubkey,
+        amount: u64,
+        decimals: u8,
+        recipient: Recipient,
+    },
+    Memo(String),
+    /// What the instruction is, when the policy can't tell what it does
+    Unknown(String),
+}
+
+/// What a message does, as far as the rules are concerned
+#[derive(Default)]
+struct Summary {
+    lamports: u64,
+    /// The base units sent and the decimals of each token mint
+    tokens: BTreeMap<Pubkey, (u64, u8)>,
+    recipients: Vec<Recipient>,
+    memos: Vec<String>,
+    unknown: Vec<String>,
+}
+
+fn summarize(message: &VersionedMessage) -> Summary {
+    let keys = message.static_account_keys();
+    let mut summary = Summary::default();
+    for ins in message.instructions() {
+        // Program IDs can't come from a lookup table.
+        let program_id = keys[usize::from(ins.program_id_index)];
+        let account = |i: usize| ins.accounts.get(i).and_then(|&index| keys.get(usize::from(index))).copied();
+        match effect(&program_id, &ins.data, account) {
+            Effect::Nothing => {}
+            Effect::Send { lamports, recipient } => {
+                summary.lamports = summary.lamports.saturating_add(lamports);
+                summary.recipients.extend(recipient);
+            }
+            // Wrapped SOL is SOL.
+            Effect::SendToken { mint, amount, recipient, .. }
+                if mint == spl_token::native_mint::id() || mint == spl_token_2022::native_mint::id() =>
+            {
+                summary.lamports = summary.lamports.saturating_add(amount);
+                summary.recipients.push(recipient);
+            }
+            Effect::SendToken { mint, amount, decimals, recipient } => {
+                let sent = summary.tokens.entry(mint).or_insert((0, decimals));
+                sent.0 = sent.0.saturating_add(amount);
+                summary.recipients.push(recipient);
+            }
+            Effect::Memo(memo) => summary.memos.push(memo),
+            Effect::Unknown(what) => summary.unknown.push(what),
+        }
+    }
+    summary
+}
+
+/// What an instruction of `program_id` does, `account` gives its accounts by position
+fn effect(program_id: &Pubkey, data: &[u8], account: impl Fn(usize) -> Option<Pubkey>) -> Effect {
+    let send = |lamports, i| Effect::Send { lamports, recipient: Some(Recipient { account: account(i), token: None }) };
+    let unknown = |what: &str| Effect::Unknown(what.to_string());
+    if *program_id == system_program::id() {
+        match limited_deserialize::<SystemInstruction>(data) {
+            Ok(SystemInstruction::Transfer { lamports }) => send(lamports, 1),
+            Ok(SystemInstruction::TransferWithSeed { lamports, .. }) => send(lamports, 2),
+            Ok(SystemInstruction::WithdrawNonceAccount(lamports)) => send(lamports, 1),
+            // The account created signs the transaction too, so it's one of the signers' own.
+            Ok(SystemInstruction::CreateAccount { lamports, .. })
+            | Ok(SystemInstruction::CreateAccountWithSeed { lamports, .. }) => {
+                Effect::Send { lamports, recipient: None }
+            }
+            Ok(SystemInstruction::AdvanceNonceAccount) => Effect::Nothing,
+            _ => unknown("a system instruction"),
+        }
+    } else if *program_id == solana_sdk::stake::program::id() {
+        match limited_deserialize::<StakeInstruction>(data) {
+            Ok(StakeInstruction::Withdraw(lamports)) => send(lamports, 1),
+            Ok(StakeInstruction::DelegateStake) | Ok(StakeInstruction::Deactivate) => Effect::Nothing,
+            _ => unknown("a stake instruction"),
+        }
+    } else if *program_id == solana_sdk::vote::program::id() {
+        match limited_deserialize::<VoteInstruction>(data) {
+            Ok(VoteInstruction::Withdraw(lamports)) => send(lamports, 1),
+            _ => unknown("a vote instruction"),
+        }
+    } else if *program_id == bpf_loader_upgradeable::id() {
+        match limited_deserialize::<UpgradeableLoaderInstruction>(data) {
+            // The lamports of the closed account go to the recipient.
+            Ok(UpgradeableLoaderInstruction::Close) => send(0, 1),
+            _ => unknown("a program loader instruction"),
+        }
+    } else if *program_id == spl_token::id() || *program_id == spl_token_2022::id() {
+        let (amount, decimals) = match TokenInstruction::unpack(data) {
+            Ok(TokenInstruction::TransferChecked { amount, decimals }) => (amount, decimals),
+            Ok(TokenInstruction::TransferFeeExtension) => match TransferFeeInstruction::unpack(&data[1..]) {
+                Ok(TransferFeeInstruction::TransferCheckedWithFee { amount, decimals, .. }) => (amount, decimals),
+                _ => return unknown("a token transfer fee instruction"),
+            },
+            // The rent of the closed account goes to the destination.
+            Ok(TokenInstruction::CloseAccount) => return send(0, 1),
+            Ok(TokenInstruction::SyncNative) => return Effect::Nothing,
+            // A plain transfer doesn't name its mint, so its amount can't be counted.
+            _ => return unknown("a token instruction"),
+        };
+        match account(1) {
+            Some(mint) => Effect::SendToken {
+                mint,
+                amount,
+                decimals,
+                recipient: Recipient { account: account(2), token: Some((mint, *program_id)) },
+            },
+            None => unknown("a token transfer whose mint is loaded from a lookup table"),
+        }
+    } else if *program_id == spl_memo::id() || *program_id == spl_memo::v1::id() {
+        Effect::Memo(String::from_utf8_lossy(data).into_owned())
+    } else if *program_id == spl_associated_token_account::id() || *program_id == compute_budget::id() {
+        // Creating a token account only pays its rent, the compute budget only raises the fee.
+        Effect::Nothing
+    } else {
+        Effect::Unknown(format!("an instruction of the program {}", program_id))
+    }
+}
+
+/// The lamports `wallet` signed for in the last 24 hours, or the base units of `mint`
+fn spent_today(wallet: &Pubkey, mint: Option<&Pubkey>) -> Result<u64, Error> {
+    let log = match fs::read_to_string(spent_path(wallet, mint)?) {
+        Ok(log) => log,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(Error::PolicyFailed(e.to_string())),
+    };
+    let since = now().saturating_sub(DAY_SECS);
+    Ok(log
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .filter_map(|(time, lamports)| Some((time.parse::<u64>().ok()?, lamports.parse::<u64>().ok()?)))
+        .filter(|&(time, _)| time > since)
+        .fold(0, |spent, (_, lamports)| spent.saturating_add(lamports)))
+}
+
+fn spent_path(wallet: &Pubkey, mint: Option<&Pubkey>) -> Result<PathBuf, Error> {
+    let dir = data_dir()?.join("spent");
+    fs::create_dir_all(&dir).map_err(|e| Error::PolicyFailed(e.to_string()))?;
+    Ok(match mint {
+        Some(mint) => dir.join(format!("{}-{}", wallet, mint)),
+        None => dir.join(wallet.to_string()),
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::hash::Hash;
+    use solana_sdk::instruction::Instruction;
+    use solana_sdk::message::{Message, VersionedMessage};
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::stake::instruction as stake_instruction;
+
+    use crate::network::Network;
+    use crate::policy::{Policy, PolicyFile};
+    use crate::token::Token;
+    use crate::transaction::create_unsigned_transaction;
+    use crate::Error;
+
+    fn message(amount: f64, to: &Pubkey, memo: Option<&str>, wallet: &Pubkey) -> VersionedMessage {
+        let tx = create_unsigned_transaction(amount, None, to, memo.map(String::from), None, wallet);
+        VersionedMessage::Legacy(Message { recent_blockhash: Hash::new_unique(), ..tx.message })
+    }
+
+    #[test]
+    fn test_policy() {
+        let (wallet, friend, stranger) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let file: PolicyFile = toml::from_str(&format!(
+            r#"
+                max_amount = 10.0
+                allowed_recipients = ["{}"]
+                memo_pattern = "^invoice-[0-9]+$"
+                networks = ["mainnet"]
+            "#,
+            friend
+        ))
+        .unwrap();
+        let policy = Policy::from_file(file).unwrap();
+        let check = |message: &VersionedMessage, net: &Network| policy.check(message, &wallet, None, net);
+
+        let ok = message(10.0, &friend, Some("invoice-42"), &wallet);
+        assert_eq!(check(&ok, &Network::Mainnet).unwrap(), 10_000_000_000);
+        // 1.0 typed as 100.0.
+        let too_much = message(100.0, &friend, Some("invoice-42"), &wallet);
+        assert!(matches!(check(&too_much, &Network::Mainnet), Err(Error::PolicyViolation(_))));
+        let stranger = message(1.0, &stranger, Some("invoice-42"), &wallet);
+        assert!(matches!(check(&stranger, &Network::Mainnet), Err(Error::PolicyViolation(_))));
+        let no_memo = message(1.0, &friend, None, &wallet);
+        assert!(matches!(check(&no_memo, &Network::Mainnet), Err(Error::PolicyViolation(_))));
+        let bad_memo = message(1.0, &friend, Some("lunch"), &wallet);
+        assert!(matches!(check(&bad_memo, &Network::Mainnet), Err(Error::PolicyViolation(_))));
+        assert!(matches!(check(&ok, &Network::Devnet), Err(Error::PolicyViolation(_))));
+
+        // No policy allows everything.
+        assert!(Policy::default().check(&too_much, &wallet, None, &Network::Devnet).is_ok());
+        // A typo in a rule is an error rather than a rule that's silently ignored.
+        assert!(toml::from_str::<PolicyFile>("max_amout = 1.0").is_err());
+        let file: PolicyFile = toml::from_str(r#"denied_recipients = ["not a key"]"#).unwrap();
+        assert!(matches!(Policy::from_file(file), Err(Error::PolicyFailed(_))));
+
+        // Tokens count toward the limits of their mint, and their recipient is the owner of the token account.
+        let (mint, other_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let file: PolicyFile = toml::from_str(&format!(
+            r#"
+                allowed_recipients = ["{}"]
+                [token_limits."{}"]
+                max_amount = 10.0
+            "#,
+            friend, mint
+        ))
+        .unwrap();
+        let policy = Policy::from_file(file).unwrap();
+        let check = |instructions: &[Instruction]| {
+            let message = VersionedMessage::Legacy(Message::new(instructions, Some(&wallet)));
+            policy.check(&message, &wallet, None, &Network::Mainnet)
+        };
+        let send_token = |mint, amount, to| Token::new(mint, 6, false, None).transfer_instruction(amount, &wallet, to);
+        let spent = check(&[send_token(mint, 5.0, &friend)]).unwrap();
+        assert_eq!(spent.tokens.get(&mint), Some(&5_000_000));
+        assert!(matches!(check(&[send_token(mint, 20.0, &friend)]), Err(Error::PolicyViolation(_))));
+        assert!(matches!(check(&[send_token(mint, 1.0, &stranger)]), Err(Error::PolicyViolation(_))));
+        assert!(matches!(check(&[send_token(other_mint, 1.0, &friend)]), Err(Error::PolicyViolation(_))));
+        let withdraw = stake_instruction::withdraw(&Pubkey::new_unique(), &wallet, &stranger, 1, None);
+        assert!(matches!(check(&[withdraw]), Err(Error::PolicyViolation(_))));
+        // What the policy can't tell the effect of is refused, unless there's no rule it would matter to.
+        let unknown = Instruction::new_with_bytes(Pubkey::new_unique(), &[1, 2, 3], Vec::new());
+        assert!(matches!(check(&[unknown.clone()]), Err(Error::PolicyViolation(_))));
+        let message = VersionedMessage::Legacy(Message::new(&[unknown], Some(&wallet)));
+        assert!(Policy::default().check(&message, &wallet, None, &Network::Mainnet).is_ok());
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use curv::elliptic::curves::{Point, Scalar};
+use multi_party_eddsa::protocols::musig2::{PrivatePartialNonces, PublicPartialNonces};
+use sha2::{Digest, Sha512};
+use solana_sdk::signature::{Keypair, Signer};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::journal::data_dir;
+use crate::serialization::{scalar_from_bytes, secret_bytes, SecretAggStepOne, SessionId};
+use crate::Error;
+
+const POOL_KEY_DOMAIN: &[u8] = b"solana-tss nonce pool";
+const NONCE_LEN: usize = 12;
+
+/// Nonces of step one generated ahead of time, which aren't tied to any signing session until step two takes one for its
+/// session, so a party that's only online at times can join whichever session is started without a step one of its own.
+/// They are kept on disk encrypted with a key derived from the party's secret key.
+pub struct NoncePool {
+    path: PathBuf,
+    cipher: ChaCha20Poly1305,
+}
+
+impl NoncePool {
+    /// Open the pool of `keypair`, every key has a pool of its own
+    pub fn open(keypair: &Keypair) -> Result<Self, Error> {
+        Self::open_in(&data_dir()?.join("pools"), keypair)
+    }
+
+    /// Open the pool of `keypair` kept in `dir` instead of `~/.solana-tss/pools`
+    pub fn open_in(dir: &Path, keypair: &Keypair) -> Result<Self, Error> {
+        fs::create_dir_all(dir).map_err(Error::NoncePoolFailed)?;
+        let secret_key = Zeroizing::new(keypair.secret().to_bytes());
+        let mut key = Sha512::new().chain(POOL_KEY_DOMAIN).chain(&*secret_key).finalize();
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&key[..32]));
+        key.as_mut_slice().zeroize();
+        Ok(Self { path: dir.join(keypair.pubkey().to_string()), cipher })
+    }
+
+    /// Add `count` new nonces to the pool, returns how many there are in it now.
+    pub fn fill(&self, count: usize) -> Result<usize, Error> {
+        let mut entries = self.read()?;
+        for _ in 0..count {
+            entries.push(self.encrypt(&PrivatePartialNonces { r: [Scalar::random(), Scalar::random()] }));
+        }
+        self.write(&entries)?;
+        Ok(entries.len())
+    }
+
+    /// Remove the oldest nonces from the pool and bind them to `session_id`, so they can only be taken once.
+    pub fn take(&self, session_id: SessionId) -> Result<SecretAggStepOne, Error> {
+        let mut entries = self.read()?;
+        if entries.is_empty() {
+            return Err(Error::NotInNoncePool(session_id));
+        }
+        let private_nonces = self.decrypt(&entries.remove(0))?;
+        // Gone from the pool before they're used, even if signing fails afterwards.
+        self.write(&entries)?;
+        let public_nonces = PublicPartialNonces {
+            R: [Point::generator() * &private_nonces.r[0], Point::generator() * &private_nonces.r[1]],
+        };
+        Ok(SecretAggStepOne { session_id, private_nonces, public_nonces })
+    }
+
+    fn encrypt(&self, nonces: &PrivatePartialNonces) -> String {
+        let nonce: [u8; NONCE_LEN] = rand07::random();
+        let plaintext = Zeroizing::new([&*secret_bytes(&nonces.r[0]), &*secret_bytes(&nonces.r[1])].concat());
+        let ciphertext = self.cipher.encrypt(Nonce::from_slice(&nonce), &*plaintext).expect("encryption can't fail");
+        let mut entry = nonce.to_vec();
+        entry.extend(ciphertext);
+        bs58::encode(entry).into_string()
+    }
+
+    fn decrypt(&self, entry: &str) -> Result<PrivatePartialNonces, Error> {
+        let entry = bs58::decode(entry).into_vec().map_err(|_| Error::CorruptNoncePool)?;
+        if entry.len() < NONCE_LEN {
+            return Err(Error::CorruptNoncePool);
+        }
+        let (nonce, ciphertext) = entry.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map(Zeroizing::new)
+            .map_err(|_| Error::CorruptNoncePool)?;
+        if plaintext.len() != 64 {
+            return Err(Error::CorruptNoncePool);
+        }
+        let scalar = |b: &[u8]| scalar_from_bytes(b).map_err(|_| Error::CorruptNoncePool);
+        Ok(PrivatePartialNonces { r: [scalar(&plaintext[..32])?, scalar(&plaintext[32..])?] })
+    }
+
+    fn read(&self) -> Result<Vec<String>, Error> {
+        match fs::read_to_string(&self.path) {
+            Ok(pool) => Ok(pool.lines().map(str::to_owned).collect()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(Error::NoncePoolFailed(e)),
+        }
+    }
+
+    /// Written to a temporary file first and renamed over the pool, so a crash halfway never loses the pool
+    /// nor leaves behind nonces that were already taken.
+    fn write(&self, entries: &[String]) -> Result<(), Error> {
+        let pool: String = entries.iter().map(|entry| format!("{}\n", entry)).collect();
+        let name = self.path.file_name().expect("the pool is named after its key").to_string_lossy();
+        let temporary = self.path.with_file_name(format!(".{}.tmp", name));
+        fs::write(&temporary, pool).map_err(Error::NoncePoolFailed)?;
+        fs::rename(&temporary, &self.path).map_err(Error::NoncePoolFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::pool::NoncePool;
+    use crate::serialization::SessionId;
+    use crate::Error;
+
+    #[test]
+    fn test_nonce_pool() {
+        let dir = std::env::temp_dir().join(format!("solana-tss-pool-{}", SessionId::random()));
+        let keypair = Keypair::new();
+
+        let pool = NoncePool::open_in(&dir, &keypair).unwrap();
+        assert_eq!(pool.fill(2).unwrap(), 2);
+        assert_eq!(pool.fill(1).unwrap(), 3);
+        let (first, second) = (SessionId::random(), SessionId::random());
+        let taken = pool.take(first).unwrap();
+        assert_eq!(taken.session_id, first);
+
+        // Kept across openings, without the nonces already taken.
+        let pool = NoncePool::open_in(&dir, &keypair).unwrap();
+        let next = pool.take(second).unwrap();
+        assert_eq!(next.session_id, second);
+        assert_ne!(next.public_nonces, taken.public_nonces);
+        pool.take(SessionId::random()).unwrap();
+        let session_id = SessionId::random();
+        assert!(matches!(pool.take(session_id), Err(Error::NotInNoncePool(id)) if id == session_id));
+
+        // Only the key the pool was filled with can open it.
+        pool.fill(1).unwrap();
+        let stranger = Keypair::new();
+        std::fs::copy(&pool.path, dir.join(stranger.pubkey().to_string())).unwrap();
+        assert!(matches!(NoncePool::open_in(&dir, &stranger).unwrap().take(first), Err(Error::CorruptNoncePool)));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,83 @@
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::Error;
+
+/// Check `buffer` is a program buffer whose authority is `authority`.
+/// The loader only upgrades from a buffer whose authority is also the program's upgrade authority.
+pub async fn check_buffer(rpc_client: &RpcClient, buffer: &Pubkey, authority: &Pubkey) -> Result<(), Error> {
+    let account = rpc_client.get_account(buffer).await.map_err(Error::ProgramAccountFailed)?;
+    check_authority(buffer, buffer_authority(buffer, &account.data)?, authority)
+}
+
+/// Check `authority` is the upgrade authority of `program`
+pub async fn check_upgrade_authority(
+    rpc_client: &RpcClient,
+    program: &Pubkey,
+    authority: &Pubkey,
+) -> Result<(), Error> {
+    let (programdata, _) = Pubkey::find_program_address(&[program.as_ref()], &bpf_loader_upgradeable::id());
+    let account = rpc_client.get_account(&programdata).await.map_err(Error::ProgramAccountFailed)?;
+    match bincode::deserialize(&account.data) {
+        Ok(UpgradeableLoaderState::ProgramData { upgrade_authority_address, .. }) => {
+            check_authority(program, upgrade_authority_address, authority)
+        }
+        _ => Err(Error::NotAnUpgradeableProgram(*program)),
+    }
+}
+
+fn buffer_authority(buffer: &Pubkey, data: &[u8]) -> Result<Option<Pubkey>, Error> {
+    // The program's code follows the state.
+    match bincode::deserialize(data) {
+        Ok(UpgradeableLoaderState::Buffer { authority_address }) => Ok(authority_address),
+        _ => Err(Error::NotAProgramBuffer(*buffer)),
+    }
+}
+
+fn check_authority(account: &Pubkey, found: Option<Pubkey>, expected: &Pubkey) -> Result<(), Error> {
+    match found {
+        Some(found) if found == *expected => Ok(()),
+        found => Err(Error::WrongProgramAuthority { account: *account, expected: *expected, found }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+    use solana_sdk::message::Message;
+    use solana_sdk::pubkey::Pubkey;
+
+    use crate::program::{buffer_authority, check_authority};
+    use crate::Error;
+
+    #[test]
+    fn test_program_buffer() {
+        let (buffer, wallet) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = bincode::serialize(&UpgradeableLoaderState::Buffer { authority_address: Some(wallet) }).unwrap();
+        data.extend([0xAB; 64]);
+        let authority = buffer_authority(&buffer, &data).unwrap();
+        check_authority(&buffer, authority, &wallet).unwrap();
+        assert!(matches!(
+            check_authority(&buffer, authority, &Pubkey::new_unique()),
+            Err(Error::WrongProgramAuthority { found: Some(found), .. }) if found == wallet
+        ));
+        // Immutable buffers can't be used for an upgrade.
+        assert!(matches!(
+            check_authority(&buffer, None, &wallet),
+            Err(Error::WrongProgramAuthority { found: None, .. })
+        ));
+
+        let data = bincode::serialize(&UpgradeableLoaderState::Program { programdata_address: buffer }).unwrap();
+        assert!(matches!(buffer_authority(&buffer, &data), Err(Error::NotAProgramBuffer(key)) if key == buffer));
+    }
+
+    #[test]
+    fn test_upgrade_signed_by_wallet() {
+        let (program, buffer, wallet) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let instruction = bpf_loader_upgradeable::upgrade(&program, &buffer, &wallet, &wallet);
+        let message = Message::new(&[instruction], Some(&wallet));
+        assert_eq!(message.header.num_required_signatures, 1);
+        assert_eq!(message.account_keys[0], wallet);
+    }
+}
@@ -0,0 +1,76 @@
+//! The `solana_tss` Python module, for scripts coordinating the parties. A failure raises `TssError` with the message
+//! of the command line.
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+use crate::{bindings, Error};
+
+create_exception!(solana_tss, TssError, PyException, "A failure of the protocol, with the message of the command line");
+
+impl From<Error> for PyErr {
+    fn from(e: Error) -> Self {
+        TssError::new_err(e.to_string())
+    }
+}
+
+#[pyfunction]
+fn generate_keypair() -> String {
+    bindings::generate_keypair()
+}
+
+#[pyfunction]
+fn public_key(keypair: &str) -> PyResult<String> {
+    Ok(bindings::public_key(keypair)?)
+}
+
+#[pyfunction]
+fn aggregate_keys(keys: Vec<String>) -> PyResult<String> {
+    Ok(bindings::aggregate_keys(&keys)?)
+}
+
+#[pyfunction]
+fn new_session_id() -> String {
+    bindings::new_session_id()
+}
+
+/// Returns the tuple `(first_message, secret_state)`
+#[pyfunction]
+fn step_one(keypair: &str, session_id: &str) -> PyResult<(String, String)> {
+    Ok(bindings::step_one(keypair, session_id)?)
+}
+
+#[pyfunction]
+fn step_two(
+    keypair: &str,
+    unsigned_tx: &str,
+    keys: Vec<String>,
+    first_messages: Vec<String>,
+    secret_state: &str,
+) -> PyResult<String> {
+    Ok(bindings::step_two(keypair, unsigned_tx, &keys, &first_messages, secret_state)?)
+}
+
+#[pyfunction]
+fn aggregate_signatures(
+    unsigned_tx: &str,
+    keys: Vec<String>,
+    first_messages: Vec<String>,
+    signatures: Vec<String>,
+) -> PyResult<String> {
+    Ok(bindings::aggregate_signatures(unsigned_tx, &keys, &first_messages, &signatures)?)
+}
+
+#[pymodule]
+fn solana_tss(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add("TssError", py.get_type::<TssError>())?;
+    m.add_function(wrap_pyfunction!(generate_keypair, m)?)?;
+    m.add_function(wrap_pyfunction!(public_key, m)?)?;
+    m.add_function(wrap_pyfunction!(aggregate_keys, m)?)?;
+    m.add_function(wrap_pyfunction!(new_session_id, m)?)?;
+    m.add_function(wrap_pyfunction!(step_one, m)?)?;
+    m.add_function(wrap_pyfunction!(step_two, m)?)?;
+    m.add_function(wrap_pyfunction!(aggregate_signatures, m)?)?;
+    Ok(())
+}
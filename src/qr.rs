@@ -0,0 +1,166 @@
+use std::io::BufRead;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::Error;
+
+/// Frames start with it, followed by `/<index>-<count>/<ID>/<part of the message>`
+const FRAME_PREFIX: &str = "STSS";
+/// The characters of the message in every frame, small enough for the QR codes to be scanned off a terminal
+const FRAME_LEN: usize = 300;
+/// More frames than that aren't from this tool
+const MAX_FRAMES: usize = 1000;
+/// How long every frame of a message split in several is shown
+const FRAME_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Split `message` into the texts of its QR codes. A message that fits in one is left as is, so any QR reader can
+/// scan it, longer ones are split into frames numbered and tagged with the ID of the message to be put back together.
+pub fn frames(message: &str) -> Vec<String> {
+    if message.len() <= FRAME_LEN {
+        return vec![message.to_string()];
+    }
+    let id = message_id(message);
+    // The messages are ASCII, Base58, Base64, hex or JSON of them.
+    let parts: Vec<_> = message.as_bytes().chunks(FRAME_LEN).map(String::from_utf8_lossy).collect();
+    parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| format!("{}/{}-{}/{}/{}", FRAME_PREFIX, i + 1, parts.len(), id, part))
+        .collect()
+}
+
+fn message_id(message: &str) -> String {
+    hex::encode(&Sha256::digest(message.as_bytes())[..4])
+}
+
+/// Draw `message` as QR codes on the standard error, a single one or its frames in a loop until Enter is pressed
+pub fn show(message: &str) -> Result<(), Error> {
+    let rendered = frames(message)
+        .iter()
+        .map(|frame| {
+            let code = QrCode::new(frame.as_bytes()).map_err(|e| Error::QrFailed(e.to_string()))?;
+            // Light on dark, as terminals mostly are.
+            Ok(code.render::<Dense1x2>().dark_color(Dense1x2::Light).light_color(Dense1x2::Dark).build())
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    if let [code] = &rendered[..] {
+        eprintln!("{}", code);
+        return Ok(());
+    }
+
+    let (done, pressed) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = std::io::stdin().read_line(&mut String::new());
+        let _ = done.send(());
+    });
+    for (i, code) in rendered.iter().enumerate().cycle() {
+        eprint!("\x1b[2J\x1b[H{}\nFrame {} of {}, press Enter once all are scanned", code, i + 1, rendered.len());
+        if pressed.recv_timeout(FRAME_INTERVAL).is_ok() {
+            break;
+        }
+    }
+    eprintln!();
+    Ok(())
+}
+
+/// Puts a message back together out of its frames, scanned in any order and any number of times
+#[derive(Default)]
+pub struct Assembler {
+    id: Option<String>,
+    parts: Vec<Option<String>>,
+}
+
+impl Assembler {
+    /// Add a scanned frame, returns the message once all its frames were added
+    pub fn add(&mut self, frame: &str) -> Result<Option<String>, Error> {
+        let frame = frame.trim();
+        let rest = match frame.strip_prefix(FRAME_PREFIX).and_then(|rest| rest.strip_prefix('/')) {
+            Some(rest) => rest,
+            // A message that fit in a single QR code.
+            None => return Ok(Some(frame.to_string())),
+        };
+        let invalid = || Error::InvalidQrFrame(frame.to_string());
+        let mut fields = rest.splitn(3, '/');
+        let (position, id, part) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(position), Some(id), Some(part)) => (position, id, part),
+            _ => return Err(invalid()),
+        };
+        let (index, count) = position.split_once('-').ok_or_else(invalid)?;
+        let (index, count): (usize, usize) =
+            (index.parse().map_err(|_| invalid())?, count.parse().map_err(|_| invalid())?);
+        if index == 0 || index > count || count > MAX_FRAMES {
+            return Err(invalid());
+        }
+        match &self.id {
+            // A frame of another message shown before, e.g. the last one's.
+            Some(current) if current != id => return Ok(None),
+            Some(_) if self.parts.len() != count => return Err(invalid()),
+            Some(_) => {}
+            None => {
+                self.id = Some(id.to_string());
+                self.parts = vec![None; count];
+            }
+        }
+        self.parts[index - 1] = Some(part.to_string());
+        if self.parts.iter().any(Option::is_none) {
+            return Ok(None);
+        }
+        let message: String = self.parts.iter().flatten().map(String::as_str).collect();
+        if message_id(&message) != id {
+            return Err(Error::InvalidQrFrame(format!("the frames of {} don't add up to it", id)));
+        }
+        Ok(Some(message))
+    }
+
+    /// How many frames were added and how many there are, once the first one was added
+    pub fn progress(&self) -> (usize, usize) {
+        (self.parts.iter().flatten().count(), self.parts.len())
+    }
+}
+
+/// Read the frames decoded from a camera, one per line like `zbarcam --raw` prints them, until the message is whole
+pub fn scan(input: impl BufRead) -> Result<String, Error> {
+    let mut assembler = Assembler::default();
+    for line in input.lines() {
+        let line = line.map_err(Error::StdinFailed)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(message) = assembler.add(&line)? {
+            return Ok(message);
+        }
+        let (scanned, count) = assembler.progress();
+        info!("Scanned {} of {} frames", scanned, count);
+    }
+    Err(Error::InvalidQrFrame("the input ended before all the frames were scanned".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::qr::{frames, scan, Assembler};
+
+    #[test]
+    fn test_qr_frames() {
+        let message = "psig_".to_string() + &"3x".repeat(400);
+        let frames = frames(&message);
+        assert_eq!(frames.len(), 3);
+
+        // Out of order, repeated, and with a frame of another message mixed in.
+        let other = crate::qr::frames(&"aggmsg1_".repeat(100));
+        let scanned =
+            [&frames[2], &frames[0], &other[1], &frames[2], &frames[1]].map(|frame| frame.as_str()).join("\n");
+        assert_eq!(scan(scanned.as_bytes()).unwrap(), message);
+
+        let mut assembler = Assembler::default();
+        assert_eq!(assembler.add("aggmsg1_short").unwrap(), Some("aggmsg1_short".to_string()));
+        let tampered = frames[0].replacen("3x", "4x", 1);
+        assert!(assembler.add(&tampered).unwrap().is_none());
+        assembler.add(&frames[1]).unwrap();
+        assert!(assembler.add(&frames[2]).is_err());
+    }
+}
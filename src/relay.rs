@@ -0,0 +1,519 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::Infallible;
+use std::fmt::{self, Display, Formatter};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures::{SinkExt, StreamExt};
+use hyper::body::HttpBody;
+use hyper::header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use reqwest::Client;
+use serde_json::json;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use tokio::sync::watch;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::WebSocketStream;
+use tracing::info;
+
+use crate::serialization::SessionId;
+use crate::transport::{missing, Round, Transport};
+use crate::Error;
+
+/// How long a relay keeps a session after its first message
+const SESSION_TTL: Duration = Duration::from_secs(60 * 60);
+/// Limits so a relay open to the internet can't be filled up
+const MAX_SESSIONS: usize = 1000;
+const MAX_MESSAGES: usize = 256;
+const MAX_PARTIES: usize = 256;
+const MAX_MESSAGE_LEN: usize = 4096;
+/// How often a party waiting on a relay asks for new messages
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long a party waits for the others on a relay before giving up
+const WAIT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// The header a party names itself with, for the others to see who's there. It isn't authenticated,
+/// the messages themselves are.
+const PARTY_HEADER: &str = "x-solana-tss-party";
+
+/// Posts and fetches the messages of a signing session on a relay started with `relay-serve`,
+/// so the parties don't have to pass them around by hand
+pub struct RelayClient {
+    client: Client,
+    url: String,
+    session_id: SessionId,
+    /// The party using the relay, shown to the others in the live view of the session
+    party: Option<Pubkey>,
+}
+
+impl RelayClient {
+    pub fn new(url: &str, session_id: SessionId, party: Option<Pubkey>) -> Result<Self, Error> {
+        let client = Client::builder().timeout(Duration::from_secs(30)).build().map_err(Error::HttpClientFailed)?;
+        Ok(Self { client, url: url.trim_end_matches('/').to_string(), session_id, party })
+    }
+
+    fn round_url(&self, round: Round) -> String {
+        format!("{}/sessions/{}/{}", self.url, self.session_id, round.path())
+    }
+
+    /// Post a message of `round`, as printed
+    pub async fn post(&self, round: Round, message: &str) -> Result<(), Error> {
+        let mut request = self.client.post(self.round_url(round)).body(message.to_string());
+        if let Some(party) = self.party {
+            request = request.header(PARTY_HEADER, party.to_string());
+        }
+        request.send().await.and_then(|response| response.error_for_status()).map_err(Error::RelayFailed)?;
+        Ok(())
+    }
+
+    /// All the messages of `round` posted so far
+    pub async fn fetch(&self, round: Round) -> Result<Vec<String>, Error> {
+        let response = self
+            .client
+            .get(self.round_url(round))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(Error::RelayFailed)?;
+        let json: serde_json::Value = response.json().await.map_err(Error::RelayFailed)?;
+        serde_json::from_value(json["messages"].clone()).map_err(|_| Error::RelayProtocol)
+    }
+
+    /// Fetch the messages of `round` until `accept` says there are enough of them, or `timeout` passed.
+    /// They're fetched again whenever the live view of the session changes, showing it meanwhile, or every few seconds
+    /// when the relay can't be followed live.
+    pub async fn wait_for<T>(
+        &self,
+        round: Round,
+        timeout: Duration,
+        mut accept: impl FnMut(Vec<String>) -> Result<Option<T>, Error>,
+    ) -> Result<T, Error> {
+        let start = Instant::now();
+        let mut live = self.live().await.ok();
+        loop {
+            if let Some(messages) = accept(self.fetch(round).await?)? {
+                return Ok(messages);
+            }
+            if start.elapsed() > timeout {
+                return Err(Error::RelayTimeout);
+            }
+            let ws = match &mut live {
+                Some(ws) => ws,
+                None => {
+                    info!("Waiting for the other parties on the relay...");
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                    continue;
+                }
+            };
+            match tokio::time::timeout(POLL_INTERVAL, ws.next()).await {
+                Ok(Some(Ok(WsMessage::Text(state)))) => match serde_json::from_str::<LiveState>(&state) {
+                    Ok(state) => info!("{}", state),
+                    Err(_) => live = None,
+                },
+                Ok(None | Some(Err(_))) => live = None,
+                Ok(Some(Ok(_))) | Err(_) => {}
+            }
+        }
+    }
+
+    /// Follow the live view of the session, which also shows this party as online to the others while it's followed
+    async fn live(&self) -> Result<WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>, Error> {
+        // The URL was given as http(s)://, the same host is ws(s)://.
+        let mut url = format!("ws{}/sessions/{}/live", self.url.trim_start_matches("http"), self.session_id);
+        if let Some(party) = self.party {
+            url = format!("{}?party={}", url, party);
+        }
+        let (ws, _) = tokio_tungstenite::connect_async(url).await.map_err(|_| Error::RelayProtocol)?;
+        Ok(ws)
+    }
+}
+
+#[async_trait]
+impl Transport for RelayClient {
+    fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+
+    async fn send(&mut self, round: Round, message: &str) -> Result<(), Error> {
+        self.post(round, message).await
+    }
+
+    async fn list(&mut self, round: Round) -> Result<Vec<String>, Error> {
+        self.fetch(round).await
+    }
+
+    async fn recv(
+        &mut self,
+        round: Round,
+        senders: &[Pubkey],
+        keypair: Option<&Keypair>,
+    ) -> Result<Vec<String>, Error> {
+        let session_id = self.session_id;
+        self.wait_for(round, WAIT_TIMEOUT, |posted| {
+            let complete = missing(&posted, session_id, round, senders, keypair)?.is_empty();
+            Ok(Some(posted).filter(|_| complete))
+        })
+        .await
+    }
+}
+
+/// The live view of a session: who's following it, which rounds every party posted in, and how many messages each
+/// round has
+#[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+struct LiveState {
+    parties: BTreeMap<String, PartyState>,
+    messages: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+struct PartyState {
+    online: bool,
+    rounds: BTreeSet<String>,
+}
+
+// Logged rather than printed, so the live view goes wherever the progress of the steps goes.
+impl Display for LiveState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let messages: Vec<_> =
+            self.messages.iter().map(|(round, count)| format!("{} in round {}", count, round)).collect();
+        write!(f, "Session: {} messages", if messages.is_empty() { "no".to_string() } else { messages.join(", ") })?;
+        for (party, state) in &self.parties {
+            let rounds: Vec<_> = state.rounds.iter().map(String::as_str).collect();
+            write!(
+                f,
+                "\n  {} {}, posted in round {}",
+                party,
+                if state.online { "online" } else { "offline" },
+                if rounds.is_empty() { "none yet".to_string() } else { rounds.join(", ") }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A signing session on the relay, the messages of every round in the order they were posted
+struct HostedSession {
+    created: Instant,
+    rounds: HashMap<Round, Vec<String>>,
+    /// The parties that named themselves, at most `MAX_PARTIES` of them,, with how many live views they follow the session in
+    parties: BTreeMap<String, (usize, BTreeSet<String>)>,
+}
+
+impl HostedSession {
+    /// Whether `party` is already named in the session or there's still room for it
+    fn has_room_for(&self, party: &str) -> bool {
+        self.parties.contains_key(party) || self.parties.len() < MAX_PARTIES
+    }
+}
+
+#[derive(Default)]
+struct Relay {
+    sessions: HashMap<String, HostedSession>,
+}
+
+impl Relay {
+    fn session(&mut self, session: &str) -> Option<&mut HostedSession> {
+        self.sessions.retain(|_, session| session.created.elapsed() < SESSION_TTL);
+        if !self.sessions.contains_key(session) && self.sessions.len() >= MAX_SESSIONS {
+            return None;
+        }
+        Some(self.sessions.entry(session.to_string()).or_insert_with(|| HostedSession {
+            created: Instant::now(),
+            rounds: HashMap::new(),
+            parties: BTreeMap::new(),
+        }))
+    }
+
+    fn post(&mut self, session: &str, round: Round, message: String, party: Option<&str>) -> StatusCode {
+        let hosted = match self.session(session) {
+            Some(hosted) => hosted,
+            None => return StatusCode::SERVICE_UNAVAILABLE,
+        };
+        if party.map_or(false, |party| !hosted.has_room_for(party)) {
+            return StatusCode::PAYLOAD_TOO_LARGE;
+        }
+        let messages = hosted.rounds.entry(round).or_default();
+        // Posting the same message again, e.g. when a party reruns a step, is harmless.
+        if !messages.contains(&message) {
+            if messages.len() >= MAX_MESSAGES {
+                return StatusCode::PAYLOAD_TOO_LARGE;
+            }
+            messages.push(message);
+        }
+        if let Some(party) = party {
+            hosted.parties.entry(party.to_string()).or_default().1.insert(round.path().to_string());
+        }
+        StatusCode::NO_CONTENT
+    }
+
+    /// A live view of the session was opened, or closed when `online` is false
+    fn follow(&mut self, session: &str, party: &str, online: bool) {
+        // A live view is still sent to a party that doesn't fit, it just isn't shown to the others.
+        if let Some(hosted) = self.session(session).filter(|hosted| hosted.has_room_for(party)) {
+            let connections = &mut hosted.parties.entry(party.to_string()).or_default().0;
+            *connections = if online { *connections + 1 } else { connections.saturating_sub(1) };
+        }
+    }
+
+    fn state(&self, session: &str) -> LiveState {
+        let hosted = match self.sessions.get(session) {
+            Some(hosted) => hosted,
+            None => return LiveState::default(),
+        };
+        LiveState {
+            parties: hosted
+                .parties
+                .iter()
+                .map(|(party, (connections, rounds))| {
+                    (party.clone(), PartyState { online: *connections > 0, rounds: rounds.clone() })
+                })
+                .collect(),
+            messages: hosted
+                .rounds
+                .iter()
+                .map(|(round, messages)| (round.path().to_string(), messages.len()))
+                .collect(),
+        }
+    }
+
+    fn fetch(&self, session: &str, round: Round) -> Vec<String> {
+        self.sessions
+            .get(session)
+            .filter(|session| session.created.elapsed() < SESSION_TTL)
+            .and_then(|session| session.rounds.get(&round))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Host signing sessions on `addr` until stopped. The relay only passes messages along, it never holds a secret:
+/// messages are signed by their senders, and can be sealed to their recipients with `--encrypt-to`.
+pub async fn serve(addr: SocketAddr) -> Result<(), Error> {
+    let (changes, _) = watch::channel(());
+    let shared = Arc::new(Shared { relay: Mutex::new(Relay::default()), changes });
+    let make_service = make_service_fn(move |_| {
+        let shared = shared.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |request| handle(shared.clone(), request))) }
+    });
+    let server = Server::try_bind(&addr).map_err(Error::RelayServerFailed)?.serve(make_service);
+    info!("Relaying signing sessions on http://{}", server.local_addr());
+    server.await.map_err(Error::RelayServerFailed)
+}
+
+/// The relay, and a signal to the live views that something changed
+struct Shared {
+    relay: Mutex<Relay>,
+    changes: watch::Sender<()>,
+}
+
+impl Shared {
+    fn change<T>(&self, change: impl FnOnce(&mut Relay) -> T) -> T {
+        let result = change(&mut self.relay.lock().unwrap());
+        self.changes.send_replace(());
+        result
+    }
+}
+
+async fn handle(shared: Arc<Shared>, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let path: Vec<String> = request.uri().path().trim_matches('/').split('/').map(String::from).collect();
+    let (session, round) = match &path[..] {
+        [sessions, session, round] if sessions == "sessions" && session.parse::<SessionId>().is_ok() => {
+            (session.clone(), round.clone())
+        }
+        _ => return Ok(status(StatusCode::NOT_FOUND)),
+    };
+    // Only keys are shown as parties, so the live view can't be used to pass anything else around.
+    let party = request
+        .uri()
+        .query()
+        .and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("party=")))
+        .or_else(|| request.headers().get(PARTY_HEADER).and_then(|party| party.to_str().ok()))
+        .and_then(|party| Pubkey::from_str(party).ok())
+        .map(|party| party.to_string());
+    if round == "live" {
+        return match *request.method() {
+            Method::GET => Ok(live(shared, request, session, party)),
+            _ => Ok(status(StatusCode::METHOD_NOT_ALLOWED)),
+        };
+    }
+    // Only the rounds of signing are relayed, so the relay can't be used to store anything else.
+    let round = match Round::from_path(&round) {
+        Some(round) => round,
+        None => return Ok(status(StatusCode::NOT_FOUND)),
+    };
+    match *request.method() {
+        Method::GET => {
+            let messages = shared.relay.lock().unwrap().fetch(&session, round);
+            let body = json!({ "messages": messages }).to_string();
+            Ok(Response::builder()
+                .header(hyper::header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .expect("the response is valid"))
+        }
+        Method::POST => {
+            let message = match read_message(request.into_body()).await {
+                Some(message) => message,
+                None => return Ok(status(StatusCode::BAD_REQUEST)),
+            };
+            Ok(status(shared.change(|relay| relay.post(&session, round, message, party.as_deref()))))
+        }
+        _ => Ok(status(StatusCode::METHOD_NOT_ALLOWED)),
+    }
+}
+
+/// Upgrade to a WebSocket that's sent the live view of `session` every time it changes
+fn live(shared: Arc<Shared>, request: Request<Body>, session: String, party: Option<String>) -> Response<Body> {
+    let key = match request.headers().get(SEC_WEBSOCKET_KEY) {
+        Some(key) => derive_accept_key(key.as_bytes()),
+        None => return status(StatusCode::BAD_REQUEST),
+    };
+    tokio::spawn(async move {
+        let upgraded = match hyper::upgrade::on(request).await {
+            Ok(upgraded) => upgraded,
+            Err(_) => return,
+        };
+        let mut ws = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+        let mut changes = shared.changes.subscribe();
+        if let Some(party) = &party {
+            shared.change(|relay| relay.follow(&session, party, true));
+        }
+        let mut sent = None;
+        loop {
+            let state = shared.relay.lock().unwrap().state(&session);
+            if sent.as_ref() != Some(&state) {
+                let text = serde_json::to_string(&state).expect("serializing to memory can't fail");
+                if ws.send(WsMessage::Text(text)).await.is_err() {
+                    break;
+                }
+                sent = Some(state);
+            }
+            tokio::select! {
+                changed = changes.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+                // Reading answers the pings, until the other side closes.
+                incoming = ws.next() => {
+                    if !matches!(incoming, Some(Ok(message)) if !message.is_close()) {
+                        break;
+                    }
+                }
+            }
+        }
+        if let Some(party) = &party {
+            shared.change(|relay| relay.follow(&session, party, false));
+        }
+    });
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(UPGRADE, "websocket")
+        .header(CONNECTION, "Upgrade")
+        .header(SEC_WEBSOCKET_ACCEPT, key)
+        .body(Body::empty())
+        .expect("the response is valid")
+}
+
+/// The body of a post, a message of at most `MAX_MESSAGE_LEN` bytes
+async fn read_message(mut body: Body) -> Option<String> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        bytes.extend(chunk.ok()?);
+        if bytes.len() > MAX_MESSAGE_LEN {
+            return None;
+        }
+    }
+    let message = String::from_utf8(bytes).ok()?.trim().to_string();
+    Some(message).filter(|message| !message.is_empty())
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    Response::builder().status(code).body(Body::empty()).expect("the response is valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use hyper::{Body, Request, StatusCode};
+    use tokio::sync::watch;
+
+    use crate::relay::{handle, Relay, Shared, MAX_MESSAGES, MAX_PARTIES};
+    use crate::serialization::SessionId;
+    use crate::transport::Round;
+
+    #[test]
+    fn test_relay() {
+        let mut relay = Relay::default();
+        assert_eq!(
+            relay.post("session", Round::FirstMessages, "aggmsg1_a".to_string(), Some("alice")),
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(relay.post("session", Round::FirstMessages, "aggmsg1_b".to_string(), None), StatusCode::NO_CONTENT);
+        // Posted twice, kept once.
+        assert_eq!(
+            relay.post("session", Round::FirstMessages, "aggmsg1_a".to_string(), Some("alice")),
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(relay.fetch("session", Round::FirstMessages), ["aggmsg1_a", "aggmsg1_b"]);
+        assert!(relay.fetch("session", Round::PartialSignatures).is_empty());
+        assert!(relay.fetch("other", Round::FirstMessages).is_empty());
+
+        relay.follow("session", "bob", true);
+        let state = relay.state("session");
+        assert_eq!(state.messages["1"], 2);
+        assert!(!state.parties["alice"].online && state.parties["alice"].rounds.contains("1"));
+        assert!(state.parties["bob"].online && state.parties["bob"].rounds.is_empty());
+        relay.follow("session", "bob", false);
+        assert!(!relay.state("session").parties["bob"].online);
+
+        for i in 1..MAX_MESSAGES {
+            relay.post("session", Round::PartialSignatures, format!("psig_{}", i), None);
+        }
+        assert_eq!(relay.post("session", Round::PartialSignatures, "psig_a".to_string(), None), StatusCode::NO_CONTENT);
+        assert_eq!(
+            relay.post("session", Round::PartialSignatures, "psig_b".to_string(), None),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+
+        // Alice and Bob named themselves already, the session is full with as many others as fit after them.
+        for i in 2..MAX_PARTIES {
+            relay.post("session", Round::FirstMessages, "aggmsg1_a".to_string(), Some(&format!("party-{}", i)));
+        }
+        let res = relay.post("session", Round::FirstMessages, "aggmsg1_c".to_string(), Some("carol"));
+        assert_eq!(res, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            relay.post("session", Round::FirstMessages, "aggmsg1_c".to_string(), Some("alice")),
+            StatusCode::NO_CONTENT
+        );
+        relay.follow("session", "carol", true);
+        assert_eq!(relay.state("session").parties.len(), MAX_PARTIES);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_round() {
+        let (changes, _) = watch::channel(());
+        let shared = Arc::new(Shared { relay: Mutex::new(Relay::default()), changes });
+        let session_id = SessionId::random().to_string();
+        let request = |method: &str, round: &str| {
+            let uri = format!("/sessions/{}/{}", session_id, round);
+            Request::builder().method(method).uri(uri).body(Body::from("aggmsg1_a")).unwrap()
+        };
+
+        let response = handle(shared.clone(), request("POST", "1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = handle(shared.clone(), request("POST", "3")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = handle(shared.clone(), request("GET", "3")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = handle(shared.clone(), request("POST", "live")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(shared.relay.lock().unwrap().state(&session_id).messages.keys().collect::<Vec<_>>(), ["1"]);
+    }
+}
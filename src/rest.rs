@@ -0,0 +1,492 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use hyper::body::HttpBody;
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::TransactionError;
+use tracing::info;
+
+use crate::daemon::{self, Daemon, Failure};
+use crate::send::{self, Preflight};
+use crate::serialization::{Encoding, Serialize};
+use crate::transaction::parse_unsigned_tx;
+use crate::{tss, Error};
+
+/// More than the body of any request needs, even with many parties
+const MAX_BODY_LEN: usize = 64 * 1024;
+
+/// Serve the JSON API of `daemon` on `addr` until stopped, reading the network with `rpc_client` and returning the
+/// messages of the party in `encoding`
+pub async fn serve(
+    addr: SocketAddr,
+    daemon: Arc<Daemon>,
+    rpc_client: RpcClient,
+    encoding: Encoding,
+) -> Result<(), Error> {
+    let api = Arc::new(Api { daemon, rpc_client, encoding });
+    let make_service = make_service_fn(move |_| {
+        let api = api.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |request| handle(api.clone(), request))) }
+    });
+    let server = Server::try_bind(&addr).map_err(|e| Error::DaemonFailed(e.to_string()))?.serve(make_service);
+    info!("Serving the JSON API on http://{}, described in /openapi.json", server.local_addr());
+    server.await.map_err(|e| Error::DaemonFailed(e.to_string()))
+}
+
+struct Api {
+    daemon: Arc<Daemon>,
+    rpc_client: RpcClient,
+    encoding: Encoding,
+}
+
+/// A failed request, answered with its status and `{"error": <message>}`, with the code of the error when there's one
+struct Failed(StatusCode, String, Option<u16>);
+
+impl From<Error> for Failed {
+    fn from(e: Error) -> Self {
+        let status = match Failure::of(&e) {
+            Failure::InvalidRequest => StatusCode::BAD_REQUEST,
+            Failure::NotFound => StatusCode::NOT_FOUND,
+            Failure::Conflict => StatusCode::CONFLICT,
+            Failure::Refused => StatusCode::FORBIDDEN,
+            Failure::NotReady => StatusCode::PRECONDITION_FAILED,
+            Failure::Full => StatusCode::SERVICE_UNAVAILABLE,
+            Failure::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self(status, e.to_string(), Some(e.code()))
+    }
+}
+
+#[derive(Deserialize)]
+struct AggregateKeysRequest {
+    keys: Vec<String>,
+}
+
+/// Either `unsigned_tx`, or a SOL transfer of `amount` to `to`. With `unsigned_tx`, `to` is the owner of the token
+/// account it sends to, for the signing policy to check.
+#[derive(Deserialize)]
+struct StepOneRequest {
+    keys: Vec<String>,
+    session_id: Option<String>,
+    amount: Option<f64>,
+    to: Option<String>,
+    memo: Option<String>,
+    recent_block_hash: Option<String>,
+    unsigned_tx: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StepTwoRequest {
+    session_id: String,
+    first_messages: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct AggregateRequest {
+    session_id: String,
+    signatures: Vec<String>,
+}
+
+async fn handle(api: Arc<Api>, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let path: Vec<String> = request.uri().path().trim_matches('/').split('/').map(String::from).collect();
+    let path: Vec<_> = path.iter().map(String::as_str).collect();
+    let method = request.method().clone();
+    let body = request.into_body();
+    let result = match (method, &path[..]) {
+        (Method::GET, ["openapi.json"]) => Ok(openapi()),
+        (Method::GET, ["balance", address]) => api.balance(address).await,
+        (Method::POST, ["aggregate-keys"]) => match read_json(body).await {
+            Ok(request) => api.aggregate_keys(request),
+            Err(failed) => Err(failed),
+        },
+        (Method::POST, ["agg-send-step-one"]) => match read_json(body).await {
+            Ok(request) => api.step_one(request).await,
+            Err(failed) => Err(failed),
+        },
+        (Method::GET, ["sessions"]) => api.sessions(),
+        (Method::GET, ["sessions", session_id]) => api.session(session_id),
+        (Method::POST, ["agg-send-step-two"]) => match read_json(body).await {
+            Ok(request) => api.step_two(request),
+            Err(failed) => Err(failed),
+        },
+        (Method::POST, ["aggregate-signatures-and-broadcast"]) => match read_json(body).await {
+            Ok(request) => api.aggregate(request).await,
+            Err(failed) => Err(failed),
+        },
+        _ => {
+            Err(Failed(StatusCode::NOT_FOUND, "No such endpoint, they're described in /openapi.json".to_string(), None))
+        }
+    };
+    let (status, body) = match result {
+        Ok(body) => (StatusCode::OK, body),
+        Err(Failed(status, error, None)) => (status, json!({ "error": error })),
+        Err(Failed(status, error, Some(code))) => (status, json!({ "error": error, "code": code })),
+    };
+    Ok(Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("the response is valid"))
+}
+
+impl Api {
+    async fn balance(&self, address: &str) -> Result<Value, Failed> {
+        let address: Pubkey = parse("address", address)?;
+        let lamports = self.rpc_client.get_balance(&address).await.map_err(Error::BalaceFailed)?;
+        Ok(json!({ "address": address.to_string(), "lamports": lamports }))
+    }
+
+    fn aggregate_keys(&self, request: AggregateKeysRequest) -> Result<Value, Failed> {
+        let keys = parse_all("key", &request.keys)?;
+        Ok(json!({ "address": tss::aggregate_pubkey(keys)?.to_string() }))
+    }
+
+    async fn step_one(&self, request: StepOneRequest) -> Result<Value, Failed> {
+        let keys: Vec<Pubkey> = parse_all("key", &request.keys)?;
+        let session_id = request.session_id.as_deref().map(|id| parse("session_id", id)).transpose()?;
+        let recent_block_hash: Option<Hash> =
+            request.recent_block_hash.as_deref().map(|hash| parse("recent_block_hash", hash)).transpose()?;
+        let to: Option<Pubkey> = request.to.as_deref().map(|to| parse("to", to)).transpose()?;
+        let mut message = match (&request.unsigned_tx, request.amount, &to) {
+            (Some(unsigned_tx), _, _) => parse_unsigned_tx(unsigned_tx)?,
+            (None, Some(amount), Some(to)) => {
+                let aggpubkey = tss::aggregate_pubkey(keys.clone())?;
+                let instructions =
+                    crate::transaction::transfer_instructions(amount, None, to, request.memo, None, &aggpubkey);
+                let mut message = crate::transaction::compile_message(&instructions, &aggpubkey, &[])?;
+                if recent_block_hash.is_none() {
+                    let hash = self.rpc_client.get_latest_blockhash().await.map_err(Error::RecentHashFailed)?;
+                    message.set_recent_blockhash(hash);
+                }
+                message
+            }
+            _ => {
+                let error = "Pass either unsigned_tx, or amount and to".to_string();
+                return Err(Failed(StatusCode::BAD_REQUEST, error, None));
+            }
+        };
+        if let Some(recent_block_hash) = recent_block_hash {
+            message.set_recent_blockhash(recent_block_hash);
+        }
+        let status = self.daemon.create_session(session_id, keys, message.clone(), to)?;
+        let mut session = self.session_json(status);
+        session["transaction"] = json!(base64::encode(message.serialize()));
+        Ok(session)
+    }
+
+    fn sessions(&self) -> Result<Value, Failed> {
+        let sessions: Vec<_> = self.daemon.sessions()?.into_iter().map(|status| self.session_json(status)).collect();
+        Ok(json!({ "sessions": sessions }))
+    }
+
+    fn session(&self, session_id: &str) -> Result<Value, Failed> {
+        Ok(self.session_json(self.daemon.status(parse("session_id", session_id)?)?))
+    }
+
+    fn step_two(&self, request: StepTwoRequest) -> Result<Value, Failed> {
+        let session_id = parse("session_id", &request.session_id)?;
+        for message in &request.first_messages {
+            self.daemon.submit_message(session_id, message)?;
+        }
+        let sig = self.daemon.partial_signature(session_id)?;
+        Ok(json!({ "partial_signature": sig.serialize_encoded(self.encoding).to_string() }))
+    }
+
+    /// Aggregate and send the transaction of a session, without waiting for it to be confirmed
+    async fn aggregate(&self, request: AggregateRequest) -> Result<Value, Failed> {
+        let session_id = parse("session_id", &request.session_id)?;
+        let tx = self.daemon.aggregate(session_id, &request.signatures)?;
+        send::check_block_hash(&self.rpc_client, &tx.message).await?;
+        let sig = match send::send_transaction(&self.rpc_client, &tx, Preflight::default()).await {
+            // Sent already, e.g. by another party that aggregated the same signatures.
+            Err(Error::PreflightFailed { error: TransactionError::AlreadyProcessed, .. }) => tx.signatures[0],
+            result => result?,
+        };
+        Ok(json!({ "transaction_id": sig.to_string() }))
+    }
+
+    fn session_json(&self, status: daemon::Status) -> Value {
+        let state = match status.state {
+            daemon::State::Collecting => "collecting",
+            daemon::State::Ready => "ready",
+            daemon::State::Signed => "signed",
+        };
+        json!({
+            "session_id": status.session_id.to_string(),
+            "state": state,
+            "keys": status.keys.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            "expires_in_secs": status.expires_in.as_secs(),
+            "first_message": status.first_message.serialize_encoded(self.encoding).to_string(),
+            "received": status.received.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            "missing": status.missing.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// A session as listed by the JSON API of a daemon
+#[derive(Deserialize)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub state: String,
+    pub keys: Vec<String>,
+    pub received: Vec<String>,
+    pub missing: Vec<String>,
+    pub expires_in_secs: u64,
+}
+
+/// The sessions of the daemon serving the JSON API on `url`, the oldest first
+pub async fn list_sessions(url: &str) -> Result<Vec<SessionSummary>, Error> {
+    #[derive(Deserialize)]
+    struct Sessions {
+        sessions: Vec<SessionSummary>,
+    }
+    let response =
+        reqwest::get(format!("{}/sessions", url.trim_end_matches('/'))).await.map_err(Error::DaemonUnreachable)?;
+    if !response.status().is_success() {
+        let body: Value = response.json().await.map_err(Error::DaemonUnreachable)?;
+        return Err(Error::DaemonFailed(body["error"].as_str().unwrap_or("no error message").to_string()));
+    }
+    Ok(response.json::<Sessions>().await.map_err(Error::DaemonUnreachable)?.sessions)
+}
+
+async fn read_json<T: DeserializeOwned>(mut body: Body) -> Result<T, Failed> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        bytes.extend(chunk.map_err(|e| Failed(StatusCode::BAD_REQUEST, e.to_string(), None))?);
+        if bytes.len() > MAX_BODY_LEN {
+            return Err(Failed(StatusCode::PAYLOAD_TOO_LARGE, "The request is too large".to_string(), None));
+        }
+    }
+    serde_json::from_slice(&bytes).map_err(|e| Failed(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e), None))
+}
+
+fn parse<T: FromStr>(field: &str, value: &str) -> Result<T, Failed> {
+    value.parse().map_err(|_| Failed(StatusCode::BAD_REQUEST, format!("Invalid {}: {}", field, value), None))
+}
+
+fn parse_all<T: FromStr>(field: &str, values: &[String]) -> Result<Vec<T>, Failed> {
+    values.iter().map(|value| parse(field, value)).collect()
+}
+
+/// The OpenAPI 3 description of the API, served in `/openapi.json` for generating clients and documentation
+pub fn openapi() -> Value {
+    let string = json!({ "type": "string" });
+    let described = |description: &str| json!({ "type": "string", "description": description });
+    let error = json!({ "description": "The request failed", "content": { "application/json": {
+        "schema": { "$ref": "#/components/schemas/Error" }
+    } } });
+    let ok = |description: &str, schema: &str| {
+        json!({
+            "200": { "description": description, "content": { "application/json": {
+                "schema": { "$ref": format!("#/components/schemas/{}", schema) }
+            } } },
+            "default": error,
+        })
+    };
+    let body = |schema: &str| {
+        json!({ "required": true, "content": { "application/json": {
+            "schema": { "$ref": format!("#/components/schemas/{}", schema) }
+        } } })
+    };
+    let path_parameter = |name: &str, description: &str| {
+        json!([{
+            "name": name,
+            "in": "path",
+            "required": true,
+            "description": description,
+            "schema": string,
+        }])
+    };
+    let strings = json!({ "type": "array", "items": { "type": "string" } });
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "solana-tss daemon",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Signs for the party of the daemon. Messages are passed as printed by the command line, \
+                            keys, addresses, session IDs and block hashes in Base58.",
+        },
+        "paths": {
+            "/balance/{address}": { "get": {
+                "operationId": "balance",
+                "summary": "The balance of an address, in lamports",
+                "parameters": path_parameter("address", "The address to read the balance of"),
+                "responses": ok("The balance", "Balance"),
+            } },
+            "/aggregate-keys": { "post": {
+                "operationId": "aggregateKeys",
+                "summary": "The aggregated address of the keys",
+                "requestBody": body("AggregateKeysRequest"),
+                "responses": ok("The aggregated address", "AggregatedKey"),
+            } },
+            "/agg-send-step-one": { "post": {
+                "operationId": "aggSendStepOne",
+                "summary": "Start a signing session for a transaction, making the first message of the party",
+                "requestBody": body("StepOneRequest"),
+                "responses": ok("The new session", "Session"),
+            } },
+            "/sessions": { "get": {
+                "operationId": "sessions",
+                "summary": "Where all the signing sessions of the party that didn't expire are at, the oldest first",
+                "responses": ok("The sessions", "Sessions"),
+            } },
+            "/sessions/{session_id}": { "get": {
+                "operationId": "session",
+                "summary": "Where a signing session is at",
+                "parameters": path_parameter("session_id", "The ID of the session"),
+                "responses": ok("The session", "Session"),
+            } },
+            "/agg-send-step-two": { "post": {
+                "operationId": "aggSendStepTwo",
+                "summary": "Submit the first messages of the other parties and sign, the same partial signature is \
+                            returned when asked again",
+                "requestBody": body("StepTwoRequest"),
+                "responses": ok("The partial signature of the party", "PartialSignature"),
+            } },
+            "/aggregate-signatures-and-broadcast": { "post": {
+                "operationId": "aggregateSignaturesAndBroadcast",
+                "summary": "Combine the partial signatures of the other parties with the party's own, and send the \
+                            transaction without waiting for it to be confirmed",
+                "requestBody": body("AggregateRequest"),
+                "responses": ok("The sent transaction", "Transaction"),
+            } },
+        },
+        "components": { "schemas": {
+            "Error": {
+                "type": "object",
+                "required": ["error"],
+                "properties": {
+                    "error": string,
+                    "code": {
+                        "type": "integer",
+                        "description": "The stable code of the error, its thousands the category, missing when the \
+                                        request itself couldn't be read",
+                    },
+                },
+            },
+            "Balance": {
+                "type": "object",
+                "properties": { "address": string, "lamports": { "type": "integer", "format": "int64" } },
+            },
+            "AggregateKeysRequest": { "type": "object", "required": ["keys"], "properties": { "keys": strings } },
+            "AggregatedKey": { "type": "object", "properties": { "address": string } },
+            "StepOneRequest": {
+                "type": "object",
+                "description": "Either unsigned_tx, or a SOL transfer of amount to to",
+                "required": ["keys"],
+                "properties": {
+                    "keys": strings,
+                    "session_id": described("The session ID the parties agreed on, a new one when not given"),
+                    "amount": { "type": "number", "description": "The SOL to send" },
+                    "to": described("The recipient, with unsigned_tx the owner of the token account it sends to"),
+                    "memo": string,
+                    "recent_block_hash": described("The latest one when not given for a transfer"),
+                    "unsigned_tx": described("A base64 transaction or message"),
+                },
+            },
+            "StepTwoRequest": {
+                "type": "object",
+                "required": ["session_id", "first_messages"],
+                "properties": { "session_id": string, "first_messages": strings },
+            },
+            "AggregateRequest": {
+                "type": "object",
+                "required": ["session_id", "signatures"],
+                "properties": { "session_id": string, "signatures": strings },
+            },
+            "Session": {
+                "type": "object",
+                "properties": {
+                    "session_id": string,
+                    "state": { "type": "string", "enum": ["collecting", "ready", "signed"] },
+                    "keys": strings,
+                    "expires_in_secs": {
+                        "type": "integer",
+                        "description": "How long until the session expires, unless a request comes for it meanwhile",
+                    },
+                    "first_message": described("The first message of the party, to pass on to the other parties"),
+                    "received": strings,
+                    "missing": strings,
+                    "transaction": described("The base64 message signed, returned when the session starts"),
+                },
+            },
+            "Sessions": {
+                "type": "object",
+                "properties": { "sessions": { "type": "array", "items": { "$ref": "#/components/schemas/Session" } } },
+            },
+            "PartialSignature": { "type": "object", "properties": { "partial_signature": string } },
+            "Transaction": { "type": "object", "properties": { "transaction_id": string } },
+        } },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use hyper::body::to_bytes;
+    use hyper::{Body, Request, StatusCode};
+    use serde_json::{json, Value};
+    use solana_client::nonblocking::rpc_client::RpcClient;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::audit::AuditLog;
+    use crate::daemon::Daemon;
+    use crate::network::Network;
+    use crate::policy::Policy;
+    use crate::rest::{handle, openapi, Api};
+    use crate::serialization::{Encoding, SessionId};
+    use crate::tss;
+
+    #[tokio::test]
+    async fn test_rest_api() {
+        let daemon = Daemon::new(Keypair::new(), Network::Localnet, Policy::default(), AuditLog::new(None));
+        let keys = vec![daemon.party(), Keypair::new().pubkey()];
+        // Nothing here reads the network.
+        let rpc_client = RpcClient::new("http://127.0.0.1:1".to_string());
+        let api = Arc::new(Api { daemon: Arc::new(daemon), rpc_client, encoding: Encoding::Bs58 });
+        let call = |method: &str, path: &str, body: Value| {
+            let request = Request::builder().method(method).uri(path).body(Body::from(body.to_string())).unwrap();
+            let api = api.clone();
+            async move {
+                let response = handle(api, request).await.unwrap();
+                let status = response.status();
+                (status, serde_json::from_slice::<Value>(&to_bytes(response.into_body()).await.unwrap()).unwrap())
+            }
+        };
+
+        let keys_json: Vec<_> = keys.iter().map(ToString::to_string).collect();
+        let (status, body) = call("POST", "/aggregate-keys", json!({ "keys": keys_json })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["address"], tss::aggregate_pubkey(keys).unwrap().to_string());
+        let (status, body) = call("POST", "/agg-send-step-one", json!({ "keys": keys_json })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].is_string());
+        let (status, body) = call("GET", &format!("/sessions/{}", SessionId::random()), Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], 7011);
+        let (status, body) = call("GET", "/sessions", Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["sessions"], json!([]));
+
+        // Every endpoint is described.
+        let (status, description) = call("GET", "/openapi.json", Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(description, openapi());
+        for path in
+            ["/balance/{address}", "/agg-send-step-one", "/agg-send-step-two", "/sessions", "/sessions/{session_id}"]
+        {
+            assert!(description["paths"][path].is_object(), "{} isn't described", path);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::Proxy;
+use solana_client::client_error::{ClientError, ClientErrorKind, Result as ClientResult};
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+use tracing::warn;
+
+use crate::http::HttpSender;
+use crate::Error;
+
+/// The wait before the first retry, it doubles with every retry after it
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Sends RPC requests over HTTP, retrying the ones that fail on the way to the node with exponential backoff.
+/// Requests the node itself rejects aren't retried, they would fail the same way.
+pub struct RetrySender {
+    sender: HttpSender,
+    retries: u32,
+}
+
+impl RetrySender {
+    pub fn new(url: String, timeout: Duration, retries: u32, proxy: Option<Proxy>) -> Result<Self, Error> {
+        Ok(Self { sender: HttpSender::new(url, timeout, proxy)?, retries })
+    }
+}
+
+#[async_trait]
+impl RpcSender for RetrySender {
+    async fn send(&self, request: RpcRequest, params: serde_json::Value) -> ClientResult<serde_json::Value> {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut retries = self.retries;
+        loop {
+            match self.sender.send(request, params.clone()).await {
+                Err(e) if retries > 0 && is_transient(&e) => {
+                    warn!("{} failed: {}, retrying in {:?}", request, e, backoff);
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    retries -= 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.sender.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.sender.url()
+    }
+}
+
+/// Whether `e` happened on the way to the node, like a timeout, a dropped connection or an overloaded node
+fn is_transient(e: &ClientError) -> bool {
+    match e.kind() {
+        ClientErrorKind::Io(_) => true,
+        ClientErrorKind::Reqwest(e) => {
+            e.status().map_or(true, |status| status.is_server_error() || status.as_u16() == 429)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use solana_client::client_error::ClientError;
+    use solana_client::rpc_request::RpcError;
+
+    use crate::retry::is_transient;
+
+    #[test]
+    fn test_transient_errors() {
+        assert!(is_transient(&ClientError::from(io::Error::new(io::ErrorKind::TimedOut, "timed out"))));
+        assert!(is_transient(&ClientError::from(io::Error::new(io::ErrorKind::ConnectionReset, "reset"))));
+        // The node answered, asking again gets the same answer.
+        assert!(!is_transient(&ClientError::from(RpcError::ForUser("AccountNotFound".to_string()))));
+        assert!(!is_transient(&ClientError::from(RpcError::RpcRequestError("invalid params".to_string()))));
+    }
+}
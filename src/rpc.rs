@@ -0,0 +1,289 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use solana_client::client_error::{ClientError, ClientErrorKind, Result as ClientResult};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcBlockConfig, RpcSendTransactionConfig, RpcSimulateTransactionConfig};
+use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+use solana_client::rpc_response::{Response, RpcResponseContext, RpcResult, RpcSimulateTransactionResult};
+use solana_sdk::account::Account;
+use solana_sdk::clock::Slot;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::{TransactionError, VersionedTransaction};
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus, UiConfirmedBlock};
+
+/// The requests sending a transaction takes, so it can be run against [`MockRpc`] as well as a node
+#[async_trait]
+pub trait RpcApi: Send + Sync {
+    fn commitment(&self) -> CommitmentConfig;
+
+    async fn get_latest_blockhash(&self) -> ClientResult<Hash>;
+
+    async fn is_blockhash_valid(&self, hash: &Hash, commitment: CommitmentConfig) -> ClientResult<bool>;
+
+    async fn send_transaction_with_config(
+        &self,
+        tx: &VersionedTransaction,
+        config: RpcSendTransactionConfig,
+    ) -> ClientResult<Signature>;
+
+    async fn get_signature_statuses(&self, signatures: &[Signature]) -> RpcResult<Vec<Option<TransactionStatus>>>;
+
+    async fn simulate_transaction_with_config(
+        &self,
+        tx: &VersionedTransaction,
+        config: RpcSimulateTransactionConfig,
+    ) -> RpcResult<RpcSimulateTransactionResult>;
+
+    async fn get_multiple_accounts(&self, keys: &[Pubkey]) -> ClientResult<Vec<Option<Account>>>;
+
+    async fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot>;
+
+    async fn get_block_height_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<u64>;
+
+    async fn get_blocks_with_commitment(
+        &self,
+        start: Slot,
+        end: Option<Slot>,
+        commitment: CommitmentConfig,
+    ) -> ClientResult<Vec<Slot>>;
+
+    async fn get_block_with_config(&self, slot: Slot, config: RpcBlockConfig) -> ClientResult<UiConfirmedBlock>;
+}
+
+#[async_trait]
+impl RpcApi for RpcClient {
+    fn commitment(&self) -> CommitmentConfig {
+        self.commitment()
+    }
+
+    async fn get_latest_blockhash(&self) -> ClientResult<Hash> {
+        self.get_latest_blockhash().await
+    }
+
+    async fn is_blockhash_valid(&self, hash: &Hash, commitment: CommitmentConfig) -> ClientResult<bool> {
+        self.is_blockhash_valid(hash, commitment).await
+    }
+
+    async fn send_transaction_with_config(
+        &self,
+        tx: &VersionedTransaction,
+        config: RpcSendTransactionConfig,
+    ) -> ClientResult<Signature> {
+        self.send_transaction_with_config(tx, config).await
+    }
+
+    async fn get_signature_statuses(&self, signatures: &[Signature]) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        self.get_signature_statuses(signatures).await
+    }
+
+    async fn simulate_transaction_with_config(
+        &self,
+        tx: &VersionedTransaction,
+        config: RpcSimulateTransactionConfig,
+    ) -> RpcResult<RpcSimulateTransactionResult> {
+        self.simulate_transaction_with_config(tx, config).await
+    }
+
+    async fn get_multiple_accounts(&self, keys: &[Pubkey]) -> ClientResult<Vec<Option<Account>>> {
+        self.get_multiple_accounts(keys).await
+    }
+
+    async fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        self.get_slot_with_commitment(commitment).await
+    }
+
+    async fn get_block_height_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<u64> {
+        self.get_block_height_with_commitment(commitment).await
+    }
+
+    async fn get_blocks_with_commitment(
+        &self,
+        start: Slot,
+        end: Option<Slot>,
+        commitment: CommitmentConfig,
+    ) -> ClientResult<Vec<Slot>> {
+        self.get_blocks_with_commitment(start, end, commitment).await
+    }
+
+    async fn get_block_with_config(&self, slot: Slot, config: RpcBlockConfig) -> ClientResult<UiConfirmedBlock> {
+        self.get_block_with_config(slot, config).await
+    }
+}
+
+/// A deterministic stand-in for a node: transactions with a valid block hash land in the next slot, finalized, unless
+/// told to fail their preflight or to be dropped. It knows no blocks, so block hashes have no known age.
+pub struct MockRpc {
+    commitment: CommitmentConfig,
+    state: Mutex<MockState>,
+}
+
+struct MockState {
+    slot: Slot,
+    latest_blockhash: Hash,
+    valid_blockhashes: HashSet<Hash>,
+    accounts: HashMap<Pubkey, Account>,
+    preflight_error: Option<(TransactionError, Vec<String>)>,
+    drop_transactions: bool,
+    sent: Vec<VersionedTransaction>,
+    landed: HashMap<Signature, Slot>,
+}
+
+impl MockRpc {
+    pub fn new(commitment: CommitmentConfig) -> Self {
+        let latest_blockhash = Hash::new_unique();
+        let state = MockState {
+            slot: 1,
+            latest_blockhash,
+            valid_blockhashes: HashSet::from([latest_blockhash]),
+            accounts: HashMap::new(),
+            preflight_error: None,
+            drop_transactions: false,
+            sent: Vec::new(),
+            landed: HashMap::new(),
+        };
+        Self { commitment, state: Mutex::new(state) }
+    }
+
+    /// Make a new latest block hash, the previous ones stay valid until expired
+    pub fn advance(&self) -> Hash {
+        let mut state = self.state.lock().unwrap();
+        state.slot += 1;
+        state.latest_blockhash = Hash::new_unique();
+        let hash = state.latest_blockhash;
+        state.valid_blockhashes.insert(hash);
+        hash
+    }
+
+    pub fn expire(&self, hash: &Hash) {
+        self.state.lock().unwrap().valid_blockhashes.remove(hash);
+    }
+
+    pub fn set_account(&self, key: Pubkey, account: Account) {
+        self.state.lock().unwrap().accounts.insert(key, account);
+    }
+
+    /// Fail the simulation of every transaction sent or simulated from now on
+    pub fn fail_preflight(&self, error: TransactionError, logs: Vec<String>) {
+        self.state.lock().unwrap().preflight_error = Some((error, logs));
+    }
+
+    /// Accept the transactions sent from now on without ever landing them, as a node failing to forward them
+    pub fn drop_transactions(&self) {
+        self.state.lock().unwrap().drop_transactions = true;
+    }
+
+    /// Every transaction sent, resent ones included
+    pub fn sent(&self) -> Vec<VersionedTransaction> {
+        self.state.lock().unwrap().sent.clone()
+    }
+
+    /// The simulation result of a transaction, the preflight error if one was set
+    fn simulation(state: &MockState, tx: &VersionedTransaction) -> RpcSimulateTransactionResult {
+        let (err, logs) = match &state.preflight_error {
+            Some((error, logs)) => (Some(error.clone()), logs.clone()),
+            None if !state.valid_blockhashes.contains(tx.message.recent_blockhash()) => {
+                (Some(TransactionError::BlockhashNotFound), Vec::new())
+            }
+            None => (None, Vec::new()),
+        };
+        RpcSimulateTransactionResult { err, logs: Some(logs), accounts: None, units_consumed: None, return_data: None }
+    }
+
+    fn response<T>(&self, value: T) -> Response<T> {
+        Response { context: RpcResponseContext::new(self.state.lock().unwrap().slot), value }
+    }
+}
+
+#[async_trait]
+impl RpcApi for MockRpc {
+    fn commitment(&self) -> CommitmentConfig {
+        self.commitment
+    }
+
+    async fn get_latest_blockhash(&self) -> ClientResult<Hash> {
+        Ok(self.state.lock().unwrap().latest_blockhash)
+    }
+
+    async fn is_blockhash_valid(&self, hash: &Hash, _commitment: CommitmentConfig) -> ClientResult<bool> {
+        Ok(self.state.lock().unwrap().valid_blockhashes.contains(hash))
+    }
+
+    async fn send_transaction_with_config(
+        &self,
+        tx: &VersionedTransaction,
+        config: RpcSendTransactionConfig,
+    ) -> ClientResult<Signature> {
+        let mut state = self.state.lock().unwrap();
+        let simulation = Self::simulation(&state, tx);
+        if !config.skip_preflight && simulation.err.is_some() {
+            let error = RpcError::RpcResponseError {
+                code: -32002,
+                message: "Transaction simulation failed".to_string(),
+                data: RpcResponseErrorData::SendTransactionPreflightFailure(simulation),
+            };
+            return Err(ClientErrorKind::RpcError(error).into());
+        }
+        state.sent.push(tx.clone());
+        let signature = tx.signatures[0];
+        if !state.drop_transactions && simulation.err.is_none() {
+            let slot = state.slot + 1;
+            state.landed.entry(signature).or_insert(slot);
+        }
+        Ok(signature)
+    }
+
+    async fn get_signature_statuses(&self, signatures: &[Signature]) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        let statuses = {
+            let state = self.state.lock().unwrap();
+            let status = |slot: &Slot| TransactionStatus {
+                slot: *slot,
+                confirmations: None,
+                status: Ok(()),
+                err: None,
+                confirmation_status: Some(TransactionConfirmationStatus::Finalized),
+            };
+            signatures.iter().map(|signature| state.landed.get(signature).map(status)).collect()
+        };
+        Ok(self.response(statuses))
+    }
+
+    async fn simulate_transaction_with_config(
+        &self,
+        tx: &VersionedTransaction,
+        _config: RpcSimulateTransactionConfig,
+    ) -> RpcResult<RpcSimulateTransactionResult> {
+        let simulation = Self::simulation(&self.state.lock().unwrap(), tx);
+        Ok(self.response(simulation))
+    }
+
+    async fn get_multiple_accounts(&self, keys: &[Pubkey]) -> ClientResult<Vec<Option<Account>>> {
+        let state = self.state.lock().unwrap();
+        Ok(keys.iter().map(|key| state.accounts.get(key).cloned()).collect())
+    }
+
+    async fn get_slot_with_commitment(&self, _commitment: CommitmentConfig) -> ClientResult<Slot> {
+        Ok(self.state.lock().unwrap().slot)
+    }
+
+    async fn get_block_height_with_commitment(&self, _commitment: CommitmentConfig) -> ClientResult<u64> {
+        Ok(self.state.lock().unwrap().slot)
+    }
+
+    async fn get_blocks_with_commitment(
+        &self,
+        _start: Slot,
+        _end: Option<Slot>,
+        _commitment: CommitmentConfig,
+    ) -> ClientResult<Vec<Slot>> {
+        Ok(Vec::new())
+    }
+
+    async fn get_block_with_config(&self, slot: Slot, _config: RpcBlockConfig) -> ClientResult<UiConfirmedBlock> {
+        Err(ClientError::from(ClientErrorKind::Custom(format!("The mock has no block in slot {}", slot))))
+    }
+}
@@ -0,0 +1,186 @@
+use std::convert::{TryFrom, TryInto};
+
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use curve25519_dalek::constants::X25519_BASEPOINT;
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::Scalar;
+use sha2::{Digest, Sha256, Sha512};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::serialization::{FieldError, Serialize};
+use crate::Error;
+
+const SEALED_PREFIX: &str = "sealed_";
+const SEALED_VERSION: u8 = 1;
+const KEY_DOMAIN: &[u8] = b"solana-tss sealed message";
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+/// Every key and content key encrypts a single plaintext, so the nonce can always be zero
+const ZERO_NONCE: [u8; 12] = [0; 12];
+
+/// A protocol message as passed on the command line, either in the clear or sealed to some of the parties with `seal`
+#[derive(Debug)]
+pub enum Sealable<T> {
+    Plain(T),
+    Sealed(String),
+}
+
+impl<T: Serialize> Sealable<T> {
+    /// The message, opened with `keypair` if it's sealed
+    pub fn open(self, keypair: Option<&Keypair>) -> Result<T, Error> {
+        match (self, keypair) {
+            (Self::Plain(message), _) => Ok(message),
+            (Self::Sealed(sealed), Some(keypair)) => open(&sealed, keypair),
+            (Self::Sealed(_), None) => Err(Error::MissingDecryptionKey),
+        }
+    }
+}
+
+/// A protocol message, or one sealed to this party with `--encrypt-to` which is opened once the keypair is known
+pub fn parse_sealable<T: Serialize>(s: &str) -> Result<Sealable<T>, Error> {
+    if is_sealed(s) {
+        return Ok(Sealable::Sealed(s.trim().to_string()));
+    }
+    Ok(Sealable::Plain(T::parse(s).with_field("message")?))
+}
+
+/// Open all of `messages`, the sealed ones with `keypair`
+pub fn open_all<T: Serialize>(messages: Vec<Sealable<T>>, keypair: Option<&Keypair>) -> Result<Vec<T>, Error> {
+    messages.into_iter().map(|message| message.open(keypair)).collect()
+}
+
+/// Encrypt `message` to the ed25519 keys of `recipients`, converted to X25519, so it can be relayed over untrusted
+/// channels without exposing its nonces, its signature, its session or its sender. Printed as `sealed_<Base58>`.
+/// The recipients aren't written in it either, each of them finds its own copy of the key by trying to decrypt them.
+pub fn seal<T: Serialize>(message: &T, recipients: &[Pubkey]) -> Result<String, Error> {
+    let content_key = Zeroizing::new(rand07::random::<[u8; KEY_LEN]>());
+    let ephemeral = secret_scalar(&Zeroizing::new(rand07::random::<[u8; KEY_LEN]>()));
+    let ephemeral_public = X25519_BASEPOINT * ephemeral;
+
+    let mut blob = vec![SEALED_VERSION];
+    blob.extend(ephemeral_public.as_bytes());
+    blob.push(u8::try_from(recipients.len()).map_err(|_| Error::TooManyRecipients(recipients.len()))?);
+    for recipient in recipients {
+        let recipient = montgomery(recipient)?;
+        let wrapped = wrapping_cipher(&(recipient * ephemeral), &ephemeral_public, &recipient)
+            .encrypt(Nonce::from_slice(&ZERO_NONCE), &content_key[..])
+            .expect("encryption can't fail");
+        blob.extend(wrapped);
+    }
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&*content_key))
+        .encrypt(Nonce::from_slice(&ZERO_NONCE), &**Zeroizing::new(message.serialize_versioned()))
+        .expect("encryption can't fail");
+    blob.extend(ciphertext);
+    Ok(format!("{}{}", SEALED_PREFIX, bs58::encode(blob).into_string()))
+}
+
+/// Decrypt a message sealed with `seal`, fails with `NotSealedToUs` when `keypair` isn't one of its recipients
+pub fn open<T: Serialize>(s: &str, keypair: &Keypair) -> Result<T, Error> {
+    let invalid = |e: &str| Error::InvalidSealedMessage(e.to_string());
+    let encoded = s.trim().strip_prefix(SEALED_PREFIX).ok_or_else(|| invalid("it isn't sealed"))?;
+    let blob = bs58::decode(encoded).into_vec().map_err(|_| invalid("it isn't Base58"))?;
+    if blob.len() < 1 + KEY_LEN + 1 {
+        return Err(invalid("it's too short"));
+    }
+    if blob[0] != SEALED_VERSION {
+        return Err(Error::InvalidSealedMessage(format!("unknown version {}", blob[0])));
+    }
+    let mut ephemeral_public = [0; KEY_LEN];
+    ephemeral_public.copy_from_slice(&blob[1..1 + KEY_LEN]);
+    let ephemeral_public = MontgomeryPoint(ephemeral_public);
+    let wrapped_len = usize::from(blob[1 + KEY_LEN]) * (KEY_LEN + TAG_LEN);
+    if blob.len() < 1 + KEY_LEN + 1 + wrapped_len {
+        return Err(invalid("it's too short"));
+    }
+    let (wrapped, ciphertext) = blob[1 + KEY_LEN + 1..].split_at(wrapped_len);
+
+    let mut seed = Sha512::digest(keypair.secret().as_bytes());
+    let secret = secret_scalar(seed[..KEY_LEN].try_into().expect("SHA-512 is longer than a key"));
+    seed.as_mut_slice().zeroize();
+    let public = X25519_BASEPOINT * secret;
+    let cipher = wrapping_cipher(&(ephemeral_public * secret), &ephemeral_public, &public);
+    let content_key = wrapped
+        .chunks(KEY_LEN + TAG_LEN)
+        .find_map(|wrapped| cipher.decrypt(Nonce::from_slice(&ZERO_NONCE), wrapped).ok())
+        .map(Zeroizing::new)
+        .ok_or(Error::NotSealedToUs)?;
+    let plaintext = ChaCha20Poly1305::new(Key::from_slice(&content_key))
+        .decrypt(Nonce::from_slice(&ZERO_NONCE), ciphertext)
+        .map(Zeroizing::new)
+        .map_err(|_| invalid("it was changed after it was sealed"))?;
+    T::deserialize_versioned(&plaintext).with_field("sealed message")
+}
+
+/// Whether `s` is a message sealed with `seal`, rather than a plain one
+pub fn is_sealed(s: &str) -> bool {
+    s.trim_start().starts_with(SEALED_PREFIX)
+}
+
+/// The X25519 public key of an ed25519 public key, the same point in Montgomery form
+fn montgomery(key: &Pubkey) -> Result<MontgomeryPoint, Error> {
+    CompressedEdwardsY(key.to_bytes())
+        .decompress()
+        .map(|point| point.to_montgomery())
+        .ok_or(Error::InvalidSealRecipient(*key))
+}
+
+/// A clamped X25519 secret, ed25519 derives its secret scalar from the first half of the SHA-512 of the seed the same way
+fn secret_scalar(bytes: &[u8; KEY_LEN]) -> Scalar {
+    let mut bytes = Zeroizing::new(*bytes);
+    bytes[0] &= 248;
+    bytes[31] &= 127;
+    bytes[31] |= 64;
+    Scalar::from_bits(*bytes)
+}
+
+/// The cipher of the copy of the content key for one recipient, keyed with the shared secret of the ephemeral key and it
+fn wrapping_cipher(
+    shared: &MontgomeryPoint,
+    ephemeral_public: &MontgomeryPoint,
+    recipient: &MontgomeryPoint,
+) -> ChaCha20Poly1305 {
+    let mut key = Sha256::new()
+        .chain(KEY_DOMAIN)
+        .chain(shared.as_bytes())
+        .chain(ephemeral_public.as_bytes())
+        .chain(recipient.as_bytes())
+        .finalize();
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    key.as_mut_slice().zeroize();
+    cipher
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::seal::{is_sealed, open, seal};
+    use crate::serialization::{AggMessage1, Encoding, Serialize, SessionId};
+    use crate::{tss, Error};
+
+    #[test]
+    fn test_seal() {
+        let parties: Vec<_> = (0..3).map(|_| Keypair::new()).collect();
+        let (first_msg, _) = tss::step_one(&parties[0], SessionId::random());
+        let recipients = [parties[1].pubkey(), parties[2].pubkey()];
+        let sealed = seal(&first_msg, &recipients).unwrap();
+        assert!(is_sealed(&sealed));
+
+        for party in &parties[1..] {
+            let opened: AggMessage1 = open(&sealed, party).unwrap();
+            assert_eq!(*opened.serialize_encoded(Encoding::Bs58), *first_msg.serialize_encoded(Encoding::Bs58));
+        }
+        // Not even the sender can open it without being one of the recipients.
+        assert!(matches!(open::<AggMessage1>(&sealed, &parties[0]), Err(Error::NotSealedToUs)));
+
+        let mut tampered = bs58::decode(&sealed["sealed_".len()..]).into_vec().unwrap();
+        *tampered.last_mut().unwrap() ^= 1;
+        let tampered = format!("sealed_{}", bs58::encode(tampered).into_string());
+        assert!(matches!(open::<AggMessage1>(&tampered, &parties[1]), Err(Error::InvalidSealedMessage(_))));
+        assert!(!is_sealed(&first_msg.serialize_encoded(Encoding::Bs58)));
+    }
+}
@@ -0,0 +1,58 @@
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{system_instruction, system_program};
+
+use crate::Error;
+
+/// The address of the account owned by `owner` that is derived from `wallet` and `seed`
+pub fn address(wallet: &Pubkey, seed: &str, owner: &Pubkey) -> Result<Pubkey, Error> {
+    Pubkey::create_with_seed(wallet, seed, owner).map_err(Error::InvalidSeed)
+}
+
+/// Create the account derived from `wallet` and `seed` with `space` bytes for `owner`, funded with `lamports` by the wallet.
+/// Unlike a fresh keypair the account's address doesn't sign, so the wallet is the only signer.
+pub fn create_instruction(
+    wallet: &Pubkey,
+    seed: &str,
+    lamports: u64,
+    space: u64,
+    owner: &Pubkey,
+) -> Result<(Pubkey, Instruction), Error> {
+    let account = address(wallet, seed, owner)?;
+    let instruction =
+        system_instruction::create_account_with_seed(wallet, &account, wallet, seed, lamports, space, owner);
+    Ok((account, instruction))
+}
+
+/// Transfer `lamports` from the system account derived from `wallet` and `seed` to `to`, the wallet signs for it
+pub fn transfer_instruction(wallet: &Pubkey, seed: &str, lamports: u64, to: &Pubkey) -> Result<Instruction, Error> {
+    let account = address(wallet, seed, &system_program::id())?;
+    Ok(system_instruction::transfer_with_seed(&account, wallet, seed.to_string(), &system_program::id(), to, lamports))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::message::Message;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::system_instruction::SystemInstruction;
+    use solana_sdk::system_program;
+
+    use crate::seed::{create_instruction, transfer_instruction};
+
+    #[test]
+    fn test_seed_account() {
+        let (wallet, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (account, create) = create_instruction(&wallet, "savings", 1_000_000, 0, &system_program::id()).unwrap();
+        let transfer = transfer_instruction(&wallet, "savings", 400_000, &to).unwrap();
+        // Funding and spending the sub-account in one transaction, with only the wallet signing.
+        let message = Message::new(&[create, transfer.clone()], Some(&wallet));
+        assert_eq!(message.header.num_required_signatures, 1);
+        assert_eq!(message.account_keys[0], wallet);
+        assert_eq!(transfer.accounts[0].pubkey, account);
+        assert!(matches!(
+            bincode::deserialize(&transfer.data),
+            Ok(SystemInstruction::TransferWithSeed { lamports: 400_000, from_seed, .. }) if from_seed == "savings"
+        ));
+        assert!(create_instruction(&wallet, &"x".repeat(33), 1, 0, &system_program::id()).is_err());
+    }
+}
@@ -0,0 +1,508 @@
+use std::time::Duration;
+
+use futures::future::try_join_all;
+use serde_json::json;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_config::{
+    RpcBlockConfig, RpcSendTransactionConfig, RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig,
+};
+use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+use solana_sdk::clock::{MAX_PROCESSING_AGE, MAX_RECENT_BLOCKHASHES};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::native_token::lamports_to_sol;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::VersionedTransaction;
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionDetails};
+use tracing::{info, warn};
+
+use crate::rpc::RpcApi;
+use crate::serialization::Format;
+use crate::Error;
+
+/// How the RPC node simulates a transaction before sending it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Preflight {
+    pub skip: bool,
+    /// The commitment of the bank to simulate against, the client's one when not given
+    pub commitment: Option<CommitmentConfig>,
+}
+
+/// Send `tx` without waiting for it to be confirmed. A failed simulation is reported with the program logs.
+pub async fn send_transaction(
+    rpc_client: &dyn RpcApi,
+    tx: &VersionedTransaction,
+    preflight: Preflight,
+) -> Result<Signature, Error> {
+    let config = RpcSendTransactionConfig {
+        skip_preflight: preflight.skip,
+        preflight_commitment: Some(preflight.commitment.unwrap_or_else(|| rpc_client.commitment()).commitment),
+        ..RpcSendTransactionConfig::default()
+    };
+    rpc_client.send_transaction_with_config(tx, config).await.map_err(send_failed)
+}
+
+fn send_failed(e: ClientError) -> Error {
+    if let ClientErrorKind::RpcError(RpcError::RpcResponseError {
+        data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+        ..
+    }) = e.kind()
+    {
+        if let Some(error) = &result.err {
+            return Error::PreflightFailed { error: error.clone(), logs: result.logs.clone().unwrap_or_default() };
+        }
+    }
+    Error::SendTransactionFailed(e)
+}
+
+/// How many blocks are fetched at once when looking for the one that produced a block hash
+const BLOCKS_PER_REQUEST: usize = 25;
+
+/// Check the block hash of `message` hasn't expired, printing how many more blocks it can land in when that can be told.
+/// A durable nonce never expires, so it isn't checked.
+pub async fn check_block_hash(rpc_client: &dyn RpcApi, message: &VersionedMessage) -> Result<(), Error> {
+    let tx = VersionedTransaction { signatures: Vec::new(), message: message.clone() };
+    if tx.uses_durable_nonce() {
+        return Ok(());
+    }
+    let hash = message.recent_blockhash();
+    let valid = rpc_client
+        .is_blockhash_valid(hash, CommitmentConfig::processed())
+        .await
+        .map_err(Error::BlockHashCheckFailed)?;
+    if !valid {
+        return Err(Error::BlockHashExpired(*hash));
+    }
+    match blocks_left(rpc_client, hash).await? {
+        Some(blocks) => info!("The block hash is valid for {} more blocks", blocks),
+        None => info!("The block hash is still valid"),
+    }
+    Ok(())
+}
+
+/// How many more blocks a transaction with the block hash `hash` can land in. The node doesn't tell a block hash's age,
+/// so it's found by looking through the recent blocks for the one that produced it, if it's confirmed yet.
+async fn blocks_left(rpc_client: &dyn RpcApi, hash: &Hash) -> Result<Option<u64>, Error> {
+    let commitment = CommitmentConfig::confirmed();
+    let slot = rpc_client.get_slot_with_commitment(commitment).await.map_err(Error::BlockHashCheckFailed)?;
+    let height = rpc_client.get_block_height_with_commitment(commitment).await.map_err(Error::BlockHashCheckFailed)?;
+    // Skipped slots don't produce blocks, so the last valid blocks span more slots than there are blocks.
+    let start = slot.saturating_sub(MAX_RECENT_BLOCKHASHES as u64);
+    let slots = rpc_client
+        .get_blocks_with_commitment(start, Some(slot), commitment)
+        .await
+        .map_err(Error::BlockHashCheckFailed)?;
+    let config = RpcBlockConfig {
+        transaction_details: Some(TransactionDetails::None),
+        rewards: Some(false),
+        commitment: Some(commitment),
+        max_supported_transaction_version: Some(0),
+        ..RpcBlockConfig::default()
+    };
+    let newest: Vec<_> = slots.into_iter().rev().take(MAX_PROCESSING_AGE).collect();
+    for chunk in newest.chunks(BLOCKS_PER_REQUEST) {
+        let blocks = try_join_all(chunk.iter().map(|&slot| rpc_client.get_block_with_config(slot, config)))
+            .await
+            .map_err(Error::BlockHashCheckFailed)?;
+        let produced = blocks.iter().find(|block| block.blockhash == hash.to_string());
+        if let Some(block_height) = produced.and_then(|block| block.block_height) {
+            return Ok(Some((block_height + MAX_PROCESSING_AGE as u64).saturating_sub(height)));
+        }
+    }
+    Ok(None)
+}
+
+/// How long to wait between checks of a sent transaction's status
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Wait for the transaction `sig` to reach the client's commitment, printing each commitment it reaches and in which slot.
+/// It was dropped if `recent_block_hash` expires before it lands. Until it lands, `resend` is sent again on every check,
+/// as nodes drop transactions they can't forward to the leader in time.
+pub async fn confirm(
+    rpc_client: &dyn RpcApi,
+    sig: &Signature,
+    recent_block_hash: &Hash,
+    resend: Option<&VersionedTransaction>,
+) -> Result<(), Error> {
+    let target = if rpc_client.commitment().is_finalized() {
+        TransactionConfirmationStatus::Finalized
+    } else if rpc_client.commitment().is_confirmed() {
+        TransactionConfirmationStatus::Confirmed
+    } else {
+        TransactionConfirmationStatus::Processed
+    };
+    let mut reached = None;
+    loop {
+        // Checked before the status, so a transaction landing in between isn't taken for dropped.
+        let expired = !rpc_client
+            .is_blockhash_valid(recent_block_hash, CommitmentConfig::processed())
+            .await
+            .map_err(Error::ConfirmingTransactionFailed)?;
+        let status = rpc_client
+            .get_signature_statuses(&[*sig])
+            .await
+            .map_err(Error::ConfirmingTransactionFailed)?
+            .value
+            .pop()
+            .flatten();
+        match status {
+            Some(status) => {
+                if let Some(error) = status.err {
+                    return Err(Error::TransactionFailed(error));
+                }
+                let level = status.confirmation_status.unwrap_or(TransactionConfirmationStatus::Processed);
+                if reached.as_ref().map_or(true, |reached| rank(reached) < rank(&level)) {
+                    info!("{:?} in slot {}", level, status.slot);
+                    if rank(&level) >= rank(&target) {
+                        return Ok(());
+                    }
+                    reached = Some(level);
+                }
+            }
+            None if expired => return Err(Error::TransactionDropped(*sig)),
+            // A processed transaction can still be lost with its fork, it may land again on another one.
+            None => {
+                if reached.take().is_some() {
+                    warn!("The transaction's fork was abandoned, waiting for it to land again");
+                }
+                if let Some(tx) = resend {
+                    // It was simulated when first sent, and the node shouldn't queue copies of its own.
+                    let config = RpcSendTransactionConfig {
+                        skip_preflight: true,
+                        max_retries: Some(0),
+                        ..RpcSendTransactionConfig::default()
+                    };
+                    // A failed resend is retried on the next check, and the expiry settles it in the end.
+                    if let Err(e) = rpc_client.send_transaction_with_config(tx, config).await {
+                        warn!("Resending the transaction failed: {}", e);
+                    }
+                }
+            }
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+fn rank(status: &TransactionConfirmationStatus) -> u8 {
+    match status {
+        TransactionConfirmationStatus::Processed => 0,
+        TransactionConfirmationStatus::Confirmed => 1,
+        TransactionConfirmationStatus::Finalized => 2,
+    }
+}
+
+/// Check `tx` is signed by `key`, alone or along with other signers, and that the signature is valid
+pub fn verify_signer(tx: &VersionedTransaction, key: &Pubkey) -> Result<(), Error> {
+    let mut signers = tx.message.static_account_keys().iter().take(tx.signatures.len());
+    let position = signers.position(|signer| signer == key).ok_or(Error::WrongTransactionSigner(*key))?;
+    if !tx.signatures[position].verify(key.as_ref(), &tx.message.serialize()) {
+        return Err(Error::InvalidSignature);
+    }
+    Ok(())
+}
+
+/// What a transaction would do if it were sent now
+#[derive(Debug)]
+pub struct Simulation {
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+    /// The accounts whose balance would change, with their balance before and after
+    pub balance_changes: Vec<(Pubkey, u64, u64)>,
+}
+
+impl Simulation {
+    pub fn print(&self, format: Format) {
+        match format {
+            Format::Text => {
+                println!("Program logs:\n{}", format_logs(&self.logs));
+                if let Some(units) = self.units_consumed {
+                    println!("Compute units consumed: {}", units);
+                }
+                println!("Balance changes:");
+                for (address, before, after) in &self.balance_changes {
+                    println!("  {}: {} SOL -> {} SOL", address, lamports_to_sol(*before), lamports_to_sol(*after));
+                }
+            }
+            Format::Json => {
+                let balance_changes: Vec<_> = self
+                    .balance_changes
+                    .iter()
+                    .map(|(address, before, after)| {
+                        json!({ "address": address.to_string(), "before": before, "after": after })
+                    })
+                    .collect();
+                println!(
+                    "{}",
+                    json!({
+                        "logs": self.logs,
+                        "units_consumed": self.units_consumed,
+                        "balance_changes": balance_changes,
+                    })
+                );
+            }
+        }
+    }
+}
+
+/// Simulate `tx` against the current state of the chain. An unsigned transaction is simulated with a fresh block hash,
+/// so it can be checked before the parties sign it.
+pub async fn simulate(rpc_client: &dyn RpcApi, tx: &VersionedTransaction, signed: bool) -> Result<Simulation, Error> {
+    // Transactions refer to at most a few dozen accounts, fewer than a single request can fetch.
+    let keys = tx.message.static_account_keys();
+    let before = rpc_client.get_multiple_accounts(keys).await.map_err(Error::SimulationFailed)?;
+    let before: Vec<_> = before.iter().map(|account| account.as_ref().map(|account| account.lamports)).collect();
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: signed,
+        replace_recent_blockhash: !signed,
+        commitment: Some(rpc_client.commitment()),
+        accounts: Some(RpcSimulateTransactionAccountsConfig {
+            // Base64 when not given, only the balances are used anyway.
+            encoding: None,
+            addresses: keys.iter().map(ToString::to_string).collect(),
+        }),
+        ..RpcSimulateTransactionConfig::default()
+    };
+    let result = rpc_client.simulate_transaction_with_config(tx, config).await.map_err(Error::SimulationFailed)?.value;
+    let logs = result.logs.unwrap_or_default();
+    if let Some(error) = result.err {
+        return Err(Error::PreflightFailed { error, logs });
+    }
+    let after: Vec<_> = result
+        .accounts
+        .unwrap_or_default()
+        .iter()
+        .map(|account| account.as_ref().map(|account| account.lamports))
+        .collect();
+    Ok(Simulation {
+        logs,
+        units_consumed: result.units_consumed,
+        balance_changes: balance_changes(keys, &before, &after),
+    })
+}
+
+/// The balances that differ, given the lamports of every account in `keys` before and after. Missing accounts hold none.
+fn balance_changes(keys: &[Pubkey], before: &[Option<u64>], after: &[Option<u64>]) -> Vec<(Pubkey, u64, u64)> {
+    keys.iter()
+        .zip(before.iter().zip(after))
+        .map(|(key, (before, after))| (*key, before.unwrap_or(0), after.unwrap_or(0)))
+        .filter(|(_, before, after)| before != after)
+        .collect()
+}
+
+/// Indent program logs by how deep the program emitting them was invoked, so inner instructions stand out
+pub fn format_logs(logs: &[String]) -> String {
+    let mut depth: usize = 0;
+    let mut formatted = String::new();
+    for log in logs {
+        // "Program <id> invoke [<depth>]" starts a program, "Program <id> success" or "failed: ..." ends it.
+        let words: Vec<_> = log.split_whitespace().collect();
+        let (line_depth, next_depth) = match words[..] {
+            ["Program", _, "invoke", invoke_depth] => {
+                let invoke_depth = invoke_depth.trim_matches(|c| c == '[' || c == ']').parse().unwrap_or(depth + 1);
+                (invoke_depth, invoke_depth)
+            }
+            ["Program", _, "success"] | ["Program", _, "failed:", ..] => (depth, depth.saturating_sub(1)),
+            _ => (depth, depth),
+        };
+        formatted.push_str(&"  ".repeat(line_depth));
+        formatted.push_str(log);
+        formatted.push('\n');
+        depth = next_depth;
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+    use solana_client::client_error::ClientError;
+    use solana_client::nonblocking::rpc_client::RpcClient;
+    use solana_client::rpc_request::{RpcError, RpcRequest, RpcResponseErrorData};
+    use solana_client::rpc_response::RpcSimulateTransactionResult;
+    use solana_sdk::commitment_config::CommitmentConfig;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::instruction::InstructionError;
+    use solana_sdk::message::{Message, VersionedMessage};
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::{Keypair, Signature, Signer};
+    use solana_sdk::system_instruction;
+    use solana_sdk::transaction::{Transaction, TransactionError, VersionedTransaction};
+
+    use crate::rpc::{MockRpc, RpcApi};
+    use crate::send::{
+        balance_changes, check_block_hash, confirm, format_logs, send_failed, send_transaction, verify_signer,
+        Preflight,
+    };
+    use crate::Error;
+
+    #[test]
+    fn test_preflight_failure() {
+        let logs = vec![
+            "Program 11111111111111111111111111111111 invoke [1]".to_string(),
+            "Transfer: insufficient lamports 5000, need 1000000000".to_string(),
+            "Program 11111111111111111111111111111111 failed: custom program error: 0x1".to_string(),
+        ];
+        let error = TransactionError::InstructionError(0, InstructionError::Custom(1));
+        let result = RpcSimulateTransactionResult {
+            err: Some(error.clone()),
+            logs: Some(logs.clone()),
+            accounts: None,
+            units_consumed: Some(150),
+            return_data: None,
+        };
+        let e = ClientError::from(RpcError::RpcResponseError {
+            code: -32002,
+            message: "Transaction simulation failed".to_string(),
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+        });
+        assert!(matches!(send_failed(e), Error::PreflightFailed { error: found, logs: found_logs }
+            if found == error && found_logs == logs));
+
+        let e = ClientError::from(RpcError::ForUser("node is behind".to_string()));
+        assert!(matches!(send_failed(e), Error::SendTransactionFailed(_)));
+    }
+
+    #[tokio::test]
+    async fn test_confirm() {
+        let (sig, recent_block_hash) = (Signature::new_unique(), Hash::new_unique());
+        let mocks = |valid: bool| {
+            HashMap::from([(RpcRequest::IsBlockhashValid, json!({ "context": { "slot": 1 }, "value": valid }))])
+        };
+        // The mock node has every transaction it knows finalized in slot 1.
+        let rpc_client = RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks(true));
+        confirm(&rpc_client, &sig, &recent_block_hash, None).await.unwrap();
+
+        let rpc_client = RpcClient::new_mock_with_mocks("instruction_error".to_string(), mocks(true));
+        assert!(matches!(
+            confirm(&rpc_client, &sig, &recent_block_hash, None).await,
+            Err(Error::TransactionFailed(TransactionError::InstructionError(0, _)))
+        ));
+
+        // An expired transaction can't land anymore, resending it is pointless.
+        let keypair = Keypair::new();
+        let instructions = [system_instruction::transfer(&keypair.pubkey(), &Pubkey::new_unique(), 1)];
+        let tx =
+            Transaction::new_signed_with_payer(&instructions, Some(&keypair.pubkey()), &[&keypair], recent_block_hash);
+        let tx = VersionedTransaction::from(tx);
+        let rpc_client = RpcClient::new_mock_with_mocks("sig_not_found".to_string(), mocks(false));
+        assert!(matches!(
+            confirm(&rpc_client, &sig, &recent_block_hash, Some(&tx)).await,
+            Err(Error::TransactionDropped(found)) if found == sig
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_check_block_hash() {
+        let payer = Pubkey::new_unique();
+        let instructions = [system_instruction::transfer(&payer, &Pubkey::new_unique(), 1)];
+        let mut message = Message::new_with_blockhash(&instructions, Some(&payer), &Hash::new_unique());
+        let expired = |valid: bool| {
+            HashMap::from([(RpcRequest::IsBlockhashValid, json!({ "context": { "slot": 1 }, "value": valid }))])
+        };
+        let rpc_client = RpcClient::new_mock_with_mocks("succeeds".to_string(), expired(false));
+        assert!(matches!(
+            check_block_hash(&rpc_client, &VersionedMessage::Legacy(message.clone())).await,
+            Err(Error::BlockHashExpired(hash)) if hash == message.recent_blockhash
+        ));
+
+        // A durable nonce isn't a recent block hash, but it doesn't expire.
+        let nonce_account = Pubkey::new_unique();
+        message = Message::new_with_nonce(instructions.to_vec(), Some(&payer), &nonce_account, &payer);
+        let rpc_client = RpcClient::new_mock_with_mocks("succeeds".to_string(), expired(false));
+        check_block_hash(&rpc_client, &VersionedMessage::Legacy(message)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_transaction() {
+        let rpc = MockRpc::new(CommitmentConfig::finalized());
+        let keypair = Keypair::new();
+        let signed = |recent_block_hash| {
+            let instructions = [system_instruction::transfer(&keypair.pubkey(), &Pubkey::new_unique(), 1)];
+            let tx = Transaction::new_signed_with_payer(
+                &instructions,
+                Some(&keypair.pubkey()),
+                &[&keypair],
+                recent_block_hash,
+            );
+            VersionedTransaction::from(tx)
+        };
+
+        let recent_block_hash = rpc.get_latest_blockhash().await.unwrap();
+        let tx = signed(recent_block_hash);
+        let sig = send_transaction(&rpc, &tx, Preflight::default()).await.unwrap();
+        confirm(&rpc, &sig, &recent_block_hash, Some(&tx)).await.unwrap();
+
+        // A block hash the node doesn't know, e.g. fetched from another cluster.
+        let result = send_transaction(&rpc, &signed(Hash::new_unique()), Preflight::default()).await;
+        assert!(matches!(result, Err(Error::PreflightFailed { error: TransactionError::BlockhashNotFound, .. })));
+
+        // Dropped on the way to the leader, and never landed before its block hash expired.
+        rpc.drop_transactions();
+        let recent_block_hash = rpc.advance();
+        let tx = signed(recent_block_hash);
+        let sig = send_transaction(&rpc, &tx, Preflight::default()).await.unwrap();
+        rpc.expire(&recent_block_hash);
+        let result = confirm(&rpc, &sig, &recent_block_hash, Some(&tx)).await;
+        assert!(matches!(result, Err(Error::TransactionDropped(found)) if found == sig));
+
+        let error = TransactionError::InstructionError(0, InstructionError::Custom(1));
+        rpc.fail_preflight(error.clone(), vec!["Program log: no".to_string()]);
+        let tx = signed(rpc.advance());
+        let result = send_transaction(&rpc, &tx, Preflight::default()).await;
+        assert!(matches!(result, Err(Error::PreflightFailed { error: found, .. }) if found == error));
+        // Skipping the preflight sends it anyway.
+        send_transaction(&rpc, &tx, Preflight { skip: true, commitment: None }).await.unwrap();
+        assert_eq!(rpc.sent().len(), 3);
+    }
+
+    #[test]
+    fn test_verify_signer() {
+        let (keypair, other) = (Keypair::new(), Keypair::new());
+        let instructions = [system_instruction::transfer(&keypair.pubkey(), &Pubkey::new_unique(), 1)];
+        let tx =
+            Transaction::new_signed_with_payer(&instructions, Some(&keypair.pubkey()), &[&keypair], Hash::new_unique());
+        let mut tx = VersionedTransaction::from(tx);
+        verify_signer(&tx, &keypair.pubkey()).unwrap();
+        assert!(
+            matches!(verify_signer(&tx, &other.pubkey()), Err(Error::WrongTransactionSigner(key)) if key == other.pubkey())
+        );
+
+        tx.signatures[0] = Signature::new_unique();
+        assert!(matches!(verify_signer(&tx, &keypair.pubkey()), Err(Error::InvalidSignature)));
+    }
+
+    #[test]
+    fn test_format_logs() {
+        let logs: Vec<_> = [
+            "Program Token1 invoke [1]",
+            "Program log: Instruction: Transfer",
+            "Program Token2 invoke [2]",
+            "Program log: inner",
+            "Program Token2 success",
+            "Program Token1 failed: insufficient funds",
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+        assert_eq!(
+            format_logs(&logs),
+            "  Program Token1 invoke [1]\n  Program log: Instruction: Transfer\n    Program Token2 invoke [2]\n    \
+             Program log: inner\n    Program Token2 success\n  Program Token1 failed: insufficient funds\n"
+        );
+    }
+
+    #[test]
+    fn test_balance_changes() {
+        let (payer, recipient, program) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let before = [Some(10_000), None, Some(1)];
+        let after = [Some(4_000), Some(1_000), Some(1)];
+        assert_eq!(
+            balance_changes(&[payer, recipient, program], &before, &after),
+            [(payer, 10_000, 4_000), (recipient, 0, 1_000)]
+        );
+    }
+}
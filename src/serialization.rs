@@ -1,27 +1,137 @@
-use std::fmt::{Display, Formatter};
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
 
-use curv::elliptic::curves::{DeserializationError, Point, PointFromBytesError, Scalar};
+use curv::elliptic::curves::{DeserializationError, Ed25519, Point, PointFromBytesError, Scalar};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use curve25519_dalek::scalar::Scalar as DalekScalar;
+use curve25519_dalek::traits::IsIdentity;
 use multi_party_eddsa::protocols::musig2::{PrivatePartialNonces, PublicPartialNonces};
+use sha2::{Digest, Sha256};
+use solana_sdk::hash::Hash;
 use solana_sdk::signature::Signature;
 use spl_memo::solana_program::pubkey::Pubkey;
+use zeroize::Zeroizing;
 
 #[derive(Debug)]
 pub enum Error {
     InputTooShort { expected: usize, found: usize },
     BadBase58(bs58::decode::Error),
+    BadBase64(base64::DecodeError),
+    BadHex(hex::FromHexError),
+    UnknownEncoding(String),
     InvalidPoint(PointFromBytesError),
     InvalidScalar(DeserializationError),
+    IdentityPoint,
+    LowOrderPoint,
+    PointNotInSubgroup,
+    NonCanonicalPoint,
+    NonCanonicalScalar,
     WrongTag { expected: Tag, found: Tag },
+    InvalidSessionId,
+    UnsupportedVersion(u8),
+    BadJson(serde_json::Error),
+    TrailingBytes { expected: usize, found: usize },
+    ChecksumMismatch,
+    WrongPrefix { expected: Tag, found: String },
+    ForgedSender(Pubkey),
+    EmptyCommitments,
 }
 
+/// The version of the wire format, every message starts with it so messages of different releases aren't mixed up.
+pub const FORMAT_VERSION: u8 = 5;
+/// Every message ends with a truncated SHA-256 of it, so a typo in a copy-pasted message isn't decoded as a different message.
+/// Unlike the rest of the format this doesn't change between versions.
+const CHECKSUM_LEN: usize = 4;
+/// Signed by the sender of a message along with it, so the signature can't pass for one over a transaction
+const SENDER_DOMAIN: &[u8] = b"solana-tss message";
+const SIGNATURE_LEN: usize = 64;
+
 // TODO: Also add a magic tag that is equal to all messages.
 
+/// A JSON wrapper around a serialized message, so other tools can route and log messages without decoding them.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonEnvelope {
+    #[serde(rename = "type")]
+    message_type: String,
+    version: u8,
+    encoding: Encoding,
+    /// The message itself, as printed in the text format
+    data: String,
+}
+
+/// The text encoding of messages exchanged between parties
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Bs58,
+    Base64,
+    Hex,
+}
+
+impl Encoding {
+    /// The order encodings are tried in when parsing a message, hex before base64 as most hex strings are also valid base64.
+    const ALL: [Encoding; 3] = [Encoding::Bs58, Encoding::Hex, Encoding::Base64];
+
+    pub fn encode(&self, b: &[u8]) -> String {
+        match self {
+            Self::Bs58 => bs58::encode(b).into_string(),
+            Self::Base64 => base64::encode(b),
+            Self::Hex => hex::encode(b),
+        }
+    }
+
+    pub fn decode(&self, s: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Bs58 => bs58::decode(s).into_vec().map_err(Error::BadBase58),
+            Self::Base64 => base64::decode(s).map_err(Error::BadBase64),
+            Self::Hex => hex::decode(s).map_err(Error::BadHex),
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bs58" => Ok(Self::Bs58),
+            "base64" => Ok(Self::Base64),
+            "hex" => Ok(Self::Hex),
+            _ => Err(Error::UnknownEncoding(s.to_string())),
+        }
+    }
+}
+
+/// How the messages are printed: as is, or in the JSON envelope
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = crate::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(crate::Error::WrongFormat(s.to_string())),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Tag {
     AggMessage1 = 0,
     AggMessage2 = 1,
     PartialSignature = 2,
     SecretAggStepOne = 3,
+    ThresholdKeyShare = 4,
+    ThresholdMessage1 = 5,
+    DkgMessage1 = 6,
+    DkgMessage2 = 7,
+    DkgSecretStepOne = 8,
+    KeyPop = 9,
     Unknown,
 }
 
@@ -32,11 +142,36 @@ impl From<u8> for Tag {
             _ if t == Tag::AggMessage2 as u8 => Tag::AggMessage2,
             _ if t == Tag::PartialSignature as u8 => Tag::PartialSignature,
             _ if t == Tag::SecretAggStepOne as u8 => Tag::SecretAggStepOne,
+            _ if t == Tag::ThresholdKeyShare as u8 => Tag::ThresholdKeyShare,
+            _ if t == Tag::ThresholdMessage1 as u8 => Tag::ThresholdMessage1,
+            _ if t == Tag::DkgMessage1 as u8 => Tag::DkgMessage1,
+            _ if t == Tag::DkgMessage2 as u8 => Tag::DkgMessage2,
+            _ if t == Tag::DkgSecretStepOne as u8 => Tag::DkgSecretStepOne,
+            _ if t == Tag::KeyPop as u8 => Tag::KeyPop,
             _ => Tag::Unknown,
         }
     }
 }
 
+impl Tag {
+    /// A short name printed before the base58 of a message, so people exchanging messages can tell them apart
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Tag::AggMessage1 => "aggmsg1",
+            Tag::AggMessage2 => "aggmsg2",
+            Tag::PartialSignature => "psig",
+            Tag::SecretAggStepOne => "secret1",
+            Tag::ThresholdKeyShare => "share",
+            Tag::ThresholdMessage1 => "tmsg1",
+            Tag::DkgMessage1 => "dkgmsg1",
+            Tag::DkgMessage2 => "dkgmsg2",
+            Tag::DkgSecretStepOne => "dkgsecret1",
+            Tag::KeyPop => "pop",
+            Tag::Unknown => "unknown",
+        }
+    }
+}
+
 impl Display for Tag {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -44,6 +179,12 @@ impl Display for Tag {
             Tag::AggMessage2 => f.write_str("Aggregate Message2"),
             Tag::PartialSignature => f.write_str("Partial Signature"),
             Tag::SecretAggStepOne => f.write_str("Secret State Aggregate1"),
+            Tag::ThresholdKeyShare => f.write_str("Threshold Key Share"),
+            Tag::ThresholdMessage1 => f.write_str("Threshold Message1"),
+            Tag::DkgMessage1 => f.write_str("DKG Message1"),
+            Tag::DkgMessage2 => f.write_str("DKG Message2"),
+            Tag::DkgSecretStepOne => f.write_str("Secret State DKG1"),
+            Tag::KeyPop => f.write_str("Key Proof of Possession"),
             Tag::Unknown => f.write_str("Unknown"),
         }
     }
@@ -56,11 +197,37 @@ impl Display for Error {
                 write!(f, "Input too short, expected: {}, found: {}", expected, found)
             }
             Self::BadBase58(e) => write!(f, "Invalid base58: {}", e),
+            Self::BadBase64(e) => write!(f, "Invalid base64: {}", e),
+            Self::BadHex(e) => write!(f, "Invalid hex: {}", e),
+            Self::UnknownEncoding(s) => write!(f, "Unknown encoding: {}, expected one of: bs58, base64, hex", s),
             Self::InvalidPoint(e) => write!(f, "Invalid Ed25519 Point: {}", e),
             Self::InvalidScalar(e) => write!(f, "Invalid Ed25519 Scalar: {}", e),
+            Self::IdentityPoint => write!(f, "Invalid Ed25519 Point: the identity point"),
+            Self::LowOrderPoint => write!(f, "Invalid Ed25519 Point: a point of low order"),
+            Self::PointNotInSubgroup => write!(f, "Invalid Ed25519 Point: not in the prime order subgroup"),
+            Self::NonCanonicalPoint => write!(f, "Invalid Ed25519 Point: not canonically encoded"),
+            Self::NonCanonicalScalar => write!(f, "Invalid Ed25519 Scalar: not canonically encoded"),
             Self::WrongTag { expected, found } => {
                 write!(f, "Expected to find message: {}, instead found: {}", expected, found)
             }
+            Self::InvalidSessionId => write!(f, "Invalid session ID, expected {} bytes", SessionId::LEN),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported message format version: {}, this release supports version {}, make sure all parties run the same release",
+                version, FORMAT_VERSION
+            ),
+            Self::BadJson(e) => write!(f, "Invalid JSON message: {}", e),
+            Self::TrailingBytes { expected, found } => {
+                write!(f, "Input too long, expected: {}, found: {}", expected, found)
+            }
+            Self::ChecksumMismatch => write!(f, "Checksum mismatch, the message was probably not copied correctly"),
+            Self::WrongPrefix { expected, found } => {
+                write!(f, "Expected a {} message to start with `{}_`, instead found `{}_`", expected, expected.prefix(), found)
+            }
+            Self::ForgedSender(sender) => {
+                write!(f, "The message isn't signed by its sender {}, it was forged or changed on the way", sender)
+            }
+            Self::EmptyCommitments => write!(f, "The DKG message commits to no coefficients, it needs at least one"),
         }
     }
 }
@@ -87,114 +254,642 @@ impl<T: Serialize> FieldError<T> for Result<T, Error> {
     }
 }
 
+/// Decode a point, rejecting the identity, points outside the prime order subgroup and non canonical encodings.
+/// A party could otherwise cancel out or malleate the values of the others, curv alone accepts some of these.
+pub fn point_from_bytes(b: &[u8]) -> Result<Point<Ed25519>, Error> {
+    let point = <[u8; 32]>::try_from(b).ok().and_then(|bytes| CompressedEdwardsY(bytes).decompress());
+    // Anything that isn't a point at all is left for curv to report.
+    if let Some(point) = point {
+        if point.compress().as_bytes() != b {
+            return Err(Error::NonCanonicalPoint);
+        }
+        if point.is_identity() {
+            return Err(Error::IdentityPoint);
+        }
+        if point.is_small_order() {
+            return Err(Error::LowOrderPoint);
+        }
+        if !point.is_torsion_free() {
+            return Err(Error::PointNotInSubgroup);
+        }
+    }
+    Ok(Point::from_bytes(b)?)
+}
+
+/// Decode a scalar, rejecting encodings of values that aren't reduced modulo the group order.
+pub fn scalar_from_bytes(b: &[u8]) -> Result<Scalar<Ed25519>, Error> {
+    if let Ok(bytes) = <[u8; 32]>::try_from(b) {
+        if DalekScalar::from_canonical_bytes(bytes).is_none() {
+            return Err(Error::NonCanonicalScalar);
+        }
+    }
+    Ok(Scalar::from_bytes(b)?)
+}
+
+/// The bytes of a secret scalar, like a key share or a private nonce, wiped once dropped
+pub fn secret_bytes(scalar: &Scalar<Ed25519>) -> Zeroizing<[u8; 32]> {
+    let mut bytes = Zeroizing::new([0u8; 32]);
+    bytes.copy_from_slice(&scalar.to_bytes());
+    bytes
+}
+
+/// What the sender of a message signs: the message up to the signature of its sender, which is always its last field
+pub fn sender_signed_bytes<T: Serialize>(message: &T) -> Vec<u8> {
+    let mut out = vec![0u8; message.size_hint()];
+    message.serialize(&mut out);
+    out.truncate(out.len() - SIGNATURE_LEN);
+    [SENDER_DOMAIN, &out].concat()
+}
+
+/// Reject a message that claims a `sender` who didn't sign it
+pub(crate) fn check_sender<T: Serialize>(message: &T, sender: &Pubkey, signature: &Signature) -> Result<(), Error> {
+    if !signature.verify(sender.as_ref(), &sender_signed_bytes(message)) {
+        return Err(Error::ForgedSender(*sender));
+    }
+    Ok(())
+}
+
+/// Writes the fields of a message one after the other into a buffer of its exact size.
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    /// Panics if the buffer is too short, which means `size_hint` is wrong.
+    fn put(&mut self, b: &[u8]) {
+        self.buf[self.pos..self.pos + b.len()].copy_from_slice(b);
+        self.pos += b.len();
+    }
+}
+
+/// Check the type of a message before anything else, so a message of the wrong type isn't reported as too short or invalid.
+fn check_tag(b: &[u8], expected: Tag) -> Result<(), Error> {
+    let found = Tag::from(*b.first().ok_or(Error::InputTooShort { expected: 1, found: 0 })?);
+    if found != expected {
+        return Err(Error::WrongTag { expected, found });
+    }
+    Ok(())
+}
+
 pub trait Serialize: Sized {
-    fn serialize_bs58(&self) -> String {
-        let mut vec = Vec::with_capacity(self.size_hint());
-        self.serialize(&mut vec);
-        bs58::encode(vec).into_string()
+    /// Serialize as sent between parties, prefixed by the name of the message type
+    fn serialize_encoded(&self, encoding: Encoding) -> Zeroizing<String> {
+        // Secret states and key shares go through here too, so the bytes are wiped once encoded and the string once dropped.
+        let serialized = Zeroizing::new(self.serialize_versioned());
+        let encoded = Zeroizing::new(encoding.encode(&serialized));
+        Zeroizing::new(format!("{}_{}", Tag::from(serialized[1]).prefix(), encoded.as_str()))
+    }
+    /// Serialize prefixed by the format version and followed by a checksum, as sent between parties.
+    fn serialize_versioned(&self) -> Vec<u8> {
+        let mut vec = vec![0u8; self.versioned_len()];
+        self.serialize_versioned_into(&mut vec);
+        vec
+    }
+    /// Like `serialize_versioned` but into a buffer of the caller, which must be exactly `versioned_len()` bytes long.
+    fn serialize_versioned_into(&self, out: &mut [u8]) {
+        assert_eq!(out.len(), self.versioned_len(), "the buffer must fit the message exactly");
+        let (versioned, checksum) = out.split_at_mut(out.len() - CHECKSUM_LEN);
+        versioned[0] = FORMAT_VERSION;
+        self.serialize(&mut versioned[1..]);
+        checksum.copy_from_slice(&Sha256::digest(versioned)[..CHECKSUM_LEN]);
+    }
+    /// The length of the message with its version and checksum
+    fn versioned_len(&self) -> usize {
+        1 + self.size_hint() + CHECKSUM_LEN
+    }
+    /// The message in a JSON envelope of its type, version and encoding
+    fn serialize_json(&self, encoding: Encoding) -> serde_json::Value {
+        let serialized = Zeroizing::new(self.serialize_versioned());
+        let envelope = JsonEnvelope {
+            message_type: format!("{:?}", Tag::from(serialized[1])),
+            version: serialized[0],
+            encoding,
+            data: self.serialize_encoded(encoding).to_string(),
+        };
+        serde_json::to_value(envelope).expect("the envelope is always valid JSON")
     }
-    fn serialize(&self, append_to: &mut Vec<u8>);
-    fn deserialize_bs58(s: impl AsRef<[u8]>) -> Result<Self, Error> {
-        let out = bs58::decode(s).into_vec().map_err(Error::BadBase58)?;
-        Self::deserialize(&out)
+    /// Write the message into `out`, which must be exactly `size_hint()` bytes long.
+    fn serialize(&self, out: &mut [u8]);
+    /// Parse a message given on the command line, either in a JSON envelope or in any of the encodings.
+    fn parse(s: &str) -> Result<Self, Error> {
+        if s.trim_start().starts_with('{') {
+            let envelope: JsonEnvelope = serde_json::from_str(s).map_err(Error::BadJson)?;
+            return Self::deserialize_encoded(envelope.data, envelope.encoding);
+        }
+        // The checksum tells which encoding the message is in, a string can be valid in more than one.
+        let (mut decoding_error, mut message_error) = (None, None);
+        for &encoding in &Encoding::ALL {
+            match Self::deserialize_encoded(s, encoding) {
+                Ok(message) => return Ok(message),
+                Err(e) if matches!(e, Error::BadBase58(_) | Error::BadBase64(_) | Error::BadHex(_)) => {
+                    decoding_error.get_or_insert(e);
+                }
+                Err(e) => {
+                    message_error.get_or_insert(e);
+                }
+            }
+        }
+        // Report what's wrong with the message in the first encoding it decoded in, rather than why it isn't base64.
+        Err(message_error.or(decoding_error).expect("there's more than one encoding"))
+    }
+    fn deserialize_encoded(s: impl AsRef<[u8]>, encoding: Encoding) -> Result<Self, Error> {
+        let s = s.as_ref();
+        // The prefix is optional, and `_` isn't a character of any of the encodings so there's no mistaking where it ends.
+        let (prefix, s) = match s.iter().position(|&c| c == b'_') {
+            Some(i) => (Some(&s[..i]), &s[i + 1..]),
+            None => (None, s),
+        };
+        let out = Zeroizing::new(encoding.decode(s)?);
+        let message = Self::deserialize_versioned(&out)?;
+        let tag = Tag::from(out[1]);
+        match prefix {
+            Some(prefix) if prefix != tag.prefix().as_bytes() => {
+                Err(Error::WrongPrefix { expected: tag, found: String::from_utf8_lossy(prefix).into_owned() })
+            }
+            _ => Ok(message),
+        }
+    }
+    fn deserialize_versioned(b: &[u8]) -> Result<Self, Error> {
+        if b.len() < 1 + CHECKSUM_LEN {
+            return Err(Error::InputTooShort { expected: 1 + CHECKSUM_LEN, found: b.len() });
+        }
+        // The checksum comes first, a typo in base58 changes all the bytes before it including the version.
+        let (versioned, checksum) = b.split_at(b.len() - CHECKSUM_LEN);
+        if Sha256::digest(versioned)[..CHECKSUM_LEN] != *checksum {
+            return Err(Error::ChecksumMismatch);
+        }
+        if versioned[0] != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion(versioned[0]));
+        }
+        let message = Self::deserialize(&versioned[1..])?;
+        // Anything after the message means it got corrupted or concatenated with something else.
+        if versioned.len() != 1 + message.size_hint() {
+            return Err(Error::TrailingBytes { expected: 1 + message.size_hint() + CHECKSUM_LEN, found: b.len() });
+        }
+        Ok(message)
     }
     // TODO: Use a cursor instead, more fail safe.
+    /// Read the message from `b` in place, only fields of variable length like DKG commitments are allocated.
     fn deserialize(b: &[u8]) -> Result<Self, Error>;
+    /// The exact length of the serialized message, longer input is rejected when deserializing.
     fn size_hint(&self) -> usize;
 }
 
-#[derive(Debug, PartialEq)]
+/// A random identifier of a signing session, so messages from one session can't be mixed into another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SessionId(pub [u8; SessionId::LEN]);
+
+impl SessionId {
+    pub const LEN: usize = 16;
+
+    pub fn random() -> Self {
+        Self(rand07::random())
+    }
+
+    fn from_slice(b: &[u8]) -> Self {
+        let mut id = [0u8; Self::LEN];
+        id.copy_from_slice(&b[..Self::LEN]);
+        Self(id)
+    }
+}
+
+impl Display for SessionId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&bs58::encode(self.0).into_string())
+    }
+}
+
+impl FromStr for SessionId {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let decoded = bs58::decode(s).into_vec().map_err(Error::BadBase58)?;
+        if decoded.len() != Self::LEN {
+            return Err(Error::InvalidSessionId);
+        }
+        Ok(Self::from_slice(&decoded))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct AggMessage1 {
+    pub session_id: SessionId,
     pub public_nonces: PublicPartialNonces,
     pub sender: Pubkey,
+    /// The signature of `sender` over the rest of the message, so no one else can send nonces in its name
+    pub sender_signature: Signature,
 }
 
 impl Serialize for AggMessage1 {
-    fn serialize(&self, append_to: &mut Vec<u8>) {
-        append_to.reserve(self.size_hint());
-        append_to.push(Tag::AggMessage1 as u8);
-        append_to.extend(&*self.public_nonces.R[0].to_bytes(true));
-        append_to.extend(&*self.public_nonces.R[1].to_bytes(true));
-        append_to.extend(self.sender.to_bytes());
+    fn serialize(&self, out: &mut [u8]) {
+        let mut out = Writer::new(out);
+        out.put(&[Tag::AggMessage1 as u8]);
+        out.put(&self.session_id.0);
+        out.put(&*self.public_nonces.R[0].to_bytes(true));
+        out.put(&*self.public_nonces.R[1].to_bytes(true));
+        out.put(&self.sender.to_bytes());
+        out.put(self.sender_signature.as_ref());
     }
     fn deserialize(b: &[u8]) -> Result<Self, Error> {
-        if b.len() < 1 + 32 + 32 + 32 {
-            return Err(Error::InputTooShort { expected: 1 + 32 + 32 + 32, found: b.len() });
-        }
-        let tag = Tag::from(b[0]);
-        if tag != Tag::AggMessage1 {
-            return Err(Error::WrongTag { expected: Tag::AggMessage1, found: tag });
+        check_tag(b, Tag::AggMessage1)?;
+        if b.len() < 1 + 16 + 32 + 32 + 32 + 64 {
+            return Err(Error::InputTooShort { expected: 1 + 16 + 32 + 32 + 32 + 64, found: b.len() });
         }
+        let session_id = SessionId::from_slice(&b[1..1 + 16]);
+        let b = &b[16..];
         let public_nonces =
-            PublicPartialNonces { R: [Point::from_bytes(&b[1..32 + 1])?, Point::from_bytes(&b[1 + 32..64 + 1])?] };
+            PublicPartialNonces { R: [point_from_bytes(&b[1..32 + 1])?, point_from_bytes(&b[1 + 32..64 + 1])?] };
         let sender = Pubkey::new(&b[64 + 1..64 + 32 + 1]);
-        Ok(Self { public_nonces, sender })
+        let sender_signature = Signature::new(&b[64 + 32 + 1..64 + 32 + 64 + 1]);
+        let message = Self { session_id, public_nonces, sender, sender_signature };
+        check_sender(&message, &message.sender, &message.sender_signature)?;
+        Ok(message)
     }
     fn size_hint(&self) -> usize {
-        1 + 32 + 32 + 32
+        1 + 16 + 32 + 32 + 32 + 64
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct PartialSignature(pub Signature);
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartialSignature {
+    pub session_id: SessionId,
+    /// The public key of the signer, so an invalid partial signature can be traced back to it
+    pub sender: Pubkey,
+    /// The recent block hash of the signed transaction, so parties that fetched different ones are caught before combining
+    pub block_hash: Hash,
+    /// The digest of the whole transaction that was signed, so a party given a different amount, recipient, memo or set
+    /// of keys is named before combining rather than making the combined signature invalid
+    pub digest: Hash,
+    pub signature: Signature,
+    /// The signature of `sender` over the rest of the message, so no one else can send a partial signature in its name
+    pub sender_signature: Signature,
+}
 
 impl Serialize for PartialSignature {
-    fn serialize(&self, append_to: &mut Vec<u8>) {
-        append_to.reserve(self.size_hint());
-        append_to.push(Tag::PartialSignature as u8);
-
-        append_to.extend(self.0.as_ref());
+    fn serialize(&self, out: &mut [u8]) {
+        let mut out = Writer::new(out);
+        out.put(&[Tag::PartialSignature as u8]);
+        out.put(&self.session_id.0);
+        out.put(&self.sender.to_bytes());
+        out.put(self.block_hash.as_ref());
+        out.put(self.digest.as_ref());
+        out.put(self.signature.as_ref());
+        out.put(self.sender_signature.as_ref());
     }
     fn deserialize(b: &[u8]) -> Result<Self, Error> {
-        if b.len() < 1 + 64 {
-            return Err(Error::InputTooShort { expected: 1 + 64, found: b.len() });
+        check_tag(b, Tag::PartialSignature)?;
+        if b.len() < 1 + 16 + 32 + 32 + 32 + 64 + 64 {
+            return Err(Error::InputTooShort { expected: 1 + 16 + 32 + 32 + 32 + 64 + 64, found: b.len() });
         }
-        let tag = Tag::from(b[0]);
-        if tag != Tag::PartialSignature {
-            return Err(Error::WrongTag { expected: Tag::PartialSignature, found: tag });
-        }
-        Ok(PartialSignature(Signature::new(&b[1..1 + 64])))
+        let session_id = SessionId::from_slice(&b[1..1 + 16]);
+        let sender = Pubkey::new(&b[1 + 16..1 + 16 + 32]);
+        let block_hash = Hash::new(&b[1 + 16 + 32..1 + 16 + 32 + 32]);
+        let digest = Hash::new(&b[1 + 16 + 32 + 32..1 + 16 + 32 + 32 + 32]);
+        let signature = Signature::new(&b[1 + 16 + 32 + 32 + 32..1 + 16 + 32 + 32 + 32 + 64]);
+        let sender_signature = Signature::new(&b[1 + 16 + 32 + 32 + 32 + 64..1 + 16 + 32 + 32 + 32 + 64 + 64]);
+        let message = PartialSignature { session_id, sender, block_hash, digest, signature, sender_signature };
+        check_sender(&message, &message.sender, &message.sender_signature)?;
+        Ok(message)
     }
     fn size_hint(&self) -> usize {
-        1 + 64
+        1 + 16 + 32 + 32 + 32 + 64 + 64
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(PartialEq)]
 pub struct SecretAggStepOne {
+    pub session_id: SessionId,
     pub private_nonces: PrivatePartialNonces,
     pub public_nonces: PublicPartialNonces,
 }
 
-impl Serialize for SecretAggStepOne {
-    fn serialize(&self, append_to: &mut Vec<u8>) {
-        append_to.reserve(self.size_hint());
-        append_to.push(Tag::SecretAggStepOne as u8);
+// Only for holding on to it until signing succeeded, the nonce journal still refuses signing twice with the copies.
+impl Clone for SecretAggStepOne {
+    fn clone(&self) -> Self {
+        Self {
+            session_id: self.session_id,
+            private_nonces: PrivatePartialNonces { r: self.private_nonces.r.clone() },
+            public_nonces: self.public_nonces.clone(),
+        }
+    }
+}
 
-        append_to.extend(&*self.private_nonces.r[0].to_bytes());
-        append_to.extend(&*self.private_nonces.r[1].to_bytes());
-        append_to.extend(&*self.public_nonces.R[0].to_bytes(true));
-        append_to.extend(&*self.public_nonces.R[1].to_bytes(true));
+// The secrets are left out of the debug output of the secret types, so they never end up in logs.
+impl Debug for SecretAggStepOne {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretAggStepOne")
+            .field("session_id", &self.session_id)
+            .field("private_nonces", &format_args!("<redacted>"))
+            .field("public_nonces", &self.public_nonces)
+            .finish()
     }
-    fn deserialize(b: &[u8]) -> Result<Self, Error> {
-        if b.len() < 1 + 64 + 64 {
-            return Err(Error::InputTooShort { expected: 1 + 64 + 64, found: b.len() });
-        }
+}
 
-        let tag = Tag::from(b[0]);
-        if tag != Tag::SecretAggStepOne {
-            return Err(Error::WrongTag { expected: Tag::SecretAggStepOne, found: tag });
+impl Serialize for SecretAggStepOne {
+    fn serialize(&self, out: &mut [u8]) {
+        let mut out = Writer::new(out);
+        out.put(&[Tag::SecretAggStepOne as u8]);
+        out.put(&self.session_id.0);
+        out.put(&*secret_bytes(&self.private_nonces.r[0]));
+        out.put(&*secret_bytes(&self.private_nonces.r[1]));
+        out.put(&*self.public_nonces.R[0].to_bytes(true));
+        out.put(&*self.public_nonces.R[1].to_bytes(true));
+    }
+    fn deserialize(b: &[u8]) -> Result<Self, Error> {
+        check_tag(b, Tag::SecretAggStepOne)?;
+        if b.len() < 1 + 16 + 64 + 64 {
+            return Err(Error::InputTooShort { expected: 1 + 16 + 64 + 64, found: b.len() });
         }
+        let session_id = SessionId::from_slice(&b[1..1 + 16]);
+        let b = &b[16..];
         let private_nonces =
-            PrivatePartialNonces { r: [Scalar::from_bytes(&b[1..1 + 32])?, Scalar::from_bytes(&b[1 + 32..1 + 64])?] };
+            PrivatePartialNonces { r: [scalar_from_bytes(&b[1..1 + 32])?, scalar_from_bytes(&b[1 + 32..1 + 64])?] };
         #[allow(non_snake_case)]
         let public_nonces = PublicPartialNonces {
-            R: [Point::from_bytes(&b[1 + 64..1 + 64 + 32])?, Point::from_bytes(&b[1 + 96..1 + 96 + 32])?],
+            R: [point_from_bytes(&b[1 + 64..1 + 64 + 32])?, point_from_bytes(&b[1 + 96..1 + 96 + 32])?],
         };
-        Ok(Self { private_nonces, public_nonces })
+        Ok(Self { session_id, private_nonces, public_nonces })
     }
     fn size_hint(&self) -> usize {
-        1 + 64 + 64
+        1 + 16 + 64 + 64
+    }
+}
+
+#[derive(PartialEq)]
+pub struct ThresholdKeyShare {
+    pub index: u16,
+    pub threshold: u16,
+    pub secret_share: Scalar<Ed25519>,
+    pub group_key: Pubkey,
+}
+
+impl Debug for ThresholdKeyShare {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThresholdKeyShare")
+            .field("index", &self.index)
+            .field("threshold", &self.threshold)
+            .field("secret_share", &format_args!("<redacted>"))
+            .field("group_key", &self.group_key)
+            .finish()
+    }
+}
+
+impl Serialize for ThresholdKeyShare {
+    fn serialize(&self, out: &mut [u8]) {
+        let mut out = Writer::new(out);
+        out.put(&[Tag::ThresholdKeyShare as u8]);
+        out.put(&self.index.to_le_bytes());
+        out.put(&self.threshold.to_le_bytes());
+        out.put(&*secret_bytes(&self.secret_share));
+        out.put(&self.group_key.to_bytes());
+    }
+    fn deserialize(b: &[u8]) -> Result<Self, Error> {
+        check_tag(b, Tag::ThresholdKeyShare)?;
+        if b.len() < 1 + 2 + 2 + 32 + 32 {
+            return Err(Error::InputTooShort { expected: 1 + 2 + 2 + 32 + 32, found: b.len() });
+        }
+        let index = u16::from_le_bytes([b[1], b[2]]);
+        let threshold = u16::from_le_bytes([b[3], b[4]]);
+        let secret_share = scalar_from_bytes(&b[5..5 + 32])?;
+        let group_key = Pubkey::new(&b[5 + 32..5 + 64]);
+        Ok(Self { index, threshold, secret_share, group_key })
+    }
+    fn size_hint(&self) -> usize {
+        1 + 2 + 2 + 32 + 32
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ThresholdMessage1 {
+    pub session_id: SessionId,
+    pub index: u16,
+    pub public_nonces: PublicPartialNonces,
+    /// The public key of the sender's share, the share times the generator
+    pub sender: Pubkey,
+    /// The signature of `sender` over the rest of the message, so the nonces can't be swapped on the way
+    pub sender_signature: Signature,
+}
+
+impl Serialize for ThresholdMessage1 {
+    fn serialize(&self, out: &mut [u8]) {
+        let mut out = Writer::new(out);
+        out.put(&[Tag::ThresholdMessage1 as u8]);
+        out.put(&self.session_id.0);
+        out.put(&self.index.to_le_bytes());
+        out.put(&*self.public_nonces.R[0].to_bytes(true));
+        out.put(&*self.public_nonces.R[1].to_bytes(true));
+        out.put(&self.sender.to_bytes());
+        out.put(self.sender_signature.as_ref());
+    }
+    fn deserialize(b: &[u8]) -> Result<Self, Error> {
+        check_tag(b, Tag::ThresholdMessage1)?;
+        if b.len() < 1 + 16 + 2 + 64 + 32 + 64 {
+            return Err(Error::InputTooShort { expected: 1 + 16 + 2 + 64 + 32 + 64, found: b.len() });
+        }
+        let session_id = SessionId::from_slice(&b[1..1 + 16]);
+        let b = &b[16..];
+        let index = u16::from_le_bytes([b[1], b[2]]);
+        let public_nonces =
+            PublicPartialNonces { R: [point_from_bytes(&b[3..3 + 32])?, point_from_bytes(&b[3 + 32..3 + 64])?] };
+        let sender = Pubkey::new(&b[3 + 64..3 + 64 + 32]);
+        let sender_signature = Signature::new(&b[3 + 64 + 32..3 + 64 + 32 + 64]);
+        let message = Self { session_id, index, public_nonces, sender, sender_signature };
+        check_sender(&message, &message.sender, &message.sender_signature)?;
+        Ok(message)
+    }
+    fn size_hint(&self) -> usize {
+        1 + 16 + 2 + 64 + 32 + 64
+    }
+}
+
+/// The public commitments to a party's DKG polynomial, with a proof of knowledge of its constant term.
+#[derive(Debug, PartialEq)]
+pub struct DkgMessage1 {
+    pub index: u16,
+    pub commitments: Vec<Point<Ed25519>>,
+    pub proof_nonce: Point<Ed25519>,
+    pub proof_response: Scalar<Ed25519>,
+    /// The signature of the constant term over the rest of the message, so the commitments can't be swapped on the way
+    pub sender_signature: Signature,
+}
+
+impl DkgMessage1 {
+    /// The key this party signs its DKG messages with, the commitment to its constant term
+    pub fn sender(&self) -> Pubkey {
+        Pubkey::new(&*self.commitments[0].to_bytes(true))
+    }
+}
+
+impl Serialize for DkgMessage1 {
+    fn serialize(&self, out: &mut [u8]) {
+        let mut out = Writer::new(out);
+        out.put(&[Tag::DkgMessage1 as u8]);
+        out.put(&self.index.to_le_bytes());
+        out.put(&(self.commitments.len() as u16).to_le_bytes());
+        out.put(&*self.proof_nonce.to_bytes(true));
+        out.put(&*self.proof_response.to_bytes());
+        for commitment in &self.commitments {
+            out.put(&*commitment.to_bytes(true));
+        }
+        out.put(self.sender_signature.as_ref());
+    }
+    fn deserialize(b: &[u8]) -> Result<Self, Error> {
+        check_tag(b, Tag::DkgMessage1)?;
+        if b.len() < 1 + 2 + 2 + 64 {
+            return Err(Error::InputTooShort { expected: 1 + 2 + 2 + 64, found: b.len() });
+        }
+        let index = u16::from_le_bytes([b[1], b[2]]);
+        let threshold = usize::from(u16::from_le_bytes([b[3], b[4]]));
+        // The constant term signs the message, so there's always one.
+        if threshold == 0 {
+            return Err(Error::EmptyCommitments);
+        }
+        if b.len() < 1 + 2 + 2 + 64 + threshold * 32 + 64 {
+            return Err(Error::InputTooShort { expected: 1 + 2 + 2 + 64 + threshold * 32 + 64, found: b.len() });
+        }
+        let proof_nonce = point_from_bytes(&b[5..5 + 32])?;
+        let proof_response = scalar_from_bytes(&b[5 + 32..5 + 64])?;
+        let commitments =
+            b[5 + 64..5 + 64 + threshold * 32].chunks(32).map(point_from_bytes).collect::<Result<_, _>>()?;
+        let sender_signature = Signature::new(&b[5 + 64 + threshold * 32..5 + 64 + threshold * 32 + 64]);
+        let message = Self { index, commitments, proof_nonce, proof_response, sender_signature };
+        check_sender(&message, &message.sender(), &message.sender_signature)?;
+        Ok(message)
+    }
+    fn size_hint(&self) -> usize {
+        1 + 2 + 2 + 64 + self.commitments.len() * 32 + 64
+    }
+}
+
+/// A share of `sender`'s DKG polynomial for `receiver`, this must only be seen by `receiver`.
+#[derive(PartialEq)]
+pub struct DkgMessage2 {
+    pub sender: u16,
+    pub receiver: u16,
+    pub share: Scalar<Ed25519>,
+    /// The key `sender` signs its DKG messages with, which must match the one of its first message
+    pub sender_key: Pubkey,
+    /// The signature of `sender_key` over the rest of the message, so no one else can deal a share in its name
+    pub sender_signature: Signature,
+}
+
+impl Debug for DkgMessage2 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DkgMessage2")
+            .field("sender", &self.sender)
+            .field("receiver", &self.receiver)
+            .field("share", &format_args!("<redacted>"))
+            .field("sender_key", &self.sender_key)
+            .field("sender_signature", &self.sender_signature)
+            .finish()
+    }
+}
+
+impl Serialize for DkgMessage2 {
+    fn serialize(&self, out: &mut [u8]) {
+        let mut out = Writer::new(out);
+        out.put(&[Tag::DkgMessage2 as u8]);
+        out.put(&self.sender.to_le_bytes());
+        out.put(&self.receiver.to_le_bytes());
+        out.put(&*secret_bytes(&self.share));
+        out.put(&self.sender_key.to_bytes());
+        out.put(self.sender_signature.as_ref());
+    }
+    fn deserialize(b: &[u8]) -> Result<Self, Error> {
+        check_tag(b, Tag::DkgMessage2)?;
+        if b.len() < 1 + 2 + 2 + 32 + 32 + 64 {
+            return Err(Error::InputTooShort { expected: 1 + 2 + 2 + 32 + 32 + 64, found: b.len() });
+        }
+        let sender = u16::from_le_bytes([b[1], b[2]]);
+        let receiver = u16::from_le_bytes([b[3], b[4]]);
+        let share = scalar_from_bytes(&b[5..5 + 32])?;
+        let sender_key = Pubkey::new(&b[5 + 32..5 + 32 + 32]);
+        let sender_signature = Signature::new(&b[5 + 32 + 32..5 + 32 + 32 + 64]);
+        let message = Self { sender, receiver, share, sender_key, sender_signature };
+        check_sender(&message, &message.sender_key, &message.sender_signature)?;
+        Ok(message)
+    }
+    fn size_hint(&self) -> usize {
+        1 + 2 + 2 + 32 + 32 + 64
+    }
+}
+
+#[derive(PartialEq)]
+pub struct DkgSecretStepOne {
+    pub index: u16,
+    pub parties: u16,
+    pub coefficients: Vec<Scalar<Ed25519>>,
+}
+
+impl Debug for DkgSecretStepOne {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DkgSecretStepOne")
+            .field("index", &self.index)
+            .field("parties", &self.parties)
+            .field("coefficients", &format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+impl Serialize for DkgSecretStepOne {
+    fn serialize(&self, out: &mut [u8]) {
+        let mut out = Writer::new(out);
+        out.put(&[Tag::DkgSecretStepOne as u8]);
+        out.put(&self.index.to_le_bytes());
+        out.put(&self.parties.to_le_bytes());
+        out.put(&(self.coefficients.len() as u16).to_le_bytes());
+        for coefficient in &self.coefficients {
+            out.put(&*secret_bytes(coefficient));
+        }
+    }
+    fn deserialize(b: &[u8]) -> Result<Self, Error> {
+        check_tag(b, Tag::DkgSecretStepOne)?;
+        if b.len() < 1 + 2 + 2 + 2 {
+            return Err(Error::InputTooShort { expected: 1 + 2 + 2 + 2, found: b.len() });
+        }
+        let index = u16::from_le_bytes([b[1], b[2]]);
+        let parties = u16::from_le_bytes([b[3], b[4]]);
+        let threshold = usize::from(u16::from_le_bytes([b[5], b[6]]));
+        if b.len() < 1 + 2 + 2 + 2 + threshold * 32 {
+            return Err(Error::InputTooShort { expected: 1 + 2 + 2 + 2 + threshold * 32, found: b.len() });
+        }
+        let coefficients = b[7..7 + threshold * 32].chunks(32).map(scalar_from_bytes).collect::<Result<_, _>>()?;
+        Ok(Self { index, parties, coefficients })
+    }
+    fn size_hint(&self) -> usize {
+        1 + 2 + 2 + 2 + self.coefficients.len() * 32
+    }
+}
+
+/// A signature by `key` over itself, proving that whoever contributed `key` to an aggregation also holds its secret key.
+#[derive(Debug, PartialEq)]
+pub struct KeyPop {
+    pub key: Pubkey,
+    pub signature: Signature,
+}
+
+impl Serialize for KeyPop {
+    fn serialize(&self, out: &mut [u8]) {
+        let mut out = Writer::new(out);
+        out.put(&[Tag::KeyPop as u8]);
+        out.put(&self.key.to_bytes());
+        out.put(self.signature.as_ref());
+    }
+    fn deserialize(b: &[u8]) -> Result<Self, Error> {
+        check_tag(b, Tag::KeyPop)?;
+        if b.len() < 1 + 32 + 64 {
+            return Err(Error::InputTooShort { expected: 1 + 32 + 64, found: b.len() });
+        }
+        let key = Pubkey::new(&b[1..1 + 32]);
+        let signature = Signature::new(&b[1 + 32..1 + 32 + 64]);
+        Ok(Self { key, signature })
+    }
+    fn size_hint(&self) -> usize {
+        1 + 32 + 64
     }
 }
 
@@ -202,11 +897,23 @@ impl Serialize for SecretAggStepOne {
 mod tests {
     use std::fmt::Debug;
 
+    use curv::elliptic::curves::{Ed25519, Point, Scalar};
+    use curve25519_dalek::constants::{BASEPOINT_ORDER, ED25519_BASEPOINT_POINT};
+    use curve25519_dalek::edwards::CompressedEdwardsY;
     use multi_party_eddsa::protocols::{musig2, ExpandedKeyPair};
-    use solana_sdk::signature::Signature;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::signature::{Keypair, Signature, Signer};
     use spl_memo::solana_program::pubkey::Pubkey;
 
-    use crate::serialization::{AggMessage1, PartialSignature, SecretAggStepOne, Serialize};
+    use sha2::{Digest, Sha256};
+
+    use crate::serialization::{
+        point_from_bytes, scalar_from_bytes, sender_signed_bytes, AggMessage1, DkgMessage1, DkgMessage2,
+        DkgSecretStepOne, Encoding, Error, KeyPop, PartialSignature, SecretAggStepOne, Serialize, SessionId, Tag,
+        FORMAT_VERSION,
+    };
+    use crate::threshold::ScalarSigner;
+    use crate::{dkg, threshold};
 
     #[derive(PartialEq, Debug)]
     struct PanicEq<T: PartialEq + Debug>(T);
@@ -218,26 +925,70 @@ mod tests {
     #[test]
     fn test_agg_msg1() {
         let mut msg = [0u8; 32];
-        let mut sender = [0u8; 32];
         for i in 0..u8::MAX {
-            sender.fill(i);
             msg.fill(i);
+            let sender = Keypair::new();
             let (_, public_nonces) = musig2::generate_partial_nonces(&ExpandedKeyPair::create(), Some(&msg));
-            let aggmsg1 = AggMessage1 { public_nonces, sender: Pubkey::new(&sender) };
-            let serialized = aggmsg1.serialize_bs58();
-            let deserialized = AggMessage1::deserialize_bs58(serialized).unwrap();
+            let mut aggmsg1 = AggMessage1 {
+                session_id: SessionId::random(),
+                public_nonces,
+                sender: sender.pubkey(),
+                sender_signature: Signature::default(),
+            };
+            aggmsg1.sender_signature = sender.sign_message(&sender_signed_bytes(&aggmsg1));
+            let serialized = aggmsg1.serialize_encoded(Encoding::Bs58);
+            let deserialized = AggMessage1::deserialize_encoded(serialized.as_str(), Encoding::Bs58).unwrap();
             assert_eq!(PanicEq(aggmsg1), PanicEq(deserialized));
         }
     }
 
+    #[test]
+    fn test_forged_sender() {
+        let sender = Keypair::new();
+        let mut partial_sig = PartialSignature {
+            session_id: SessionId::random(),
+            sender: sender.pubkey(),
+            block_hash: Hash::new_unique(),
+            digest: Hash::new_unique(),
+            signature: Signature::new(&[1; 64]),
+            sender_signature: Signature::default(),
+        };
+        partial_sig.sender_signature = sender.sign_message(&sender_signed_bytes(&partial_sig));
+        let serialized = partial_sig.serialize_versioned();
+        assert!(PartialSignature::deserialize_versioned(&serialized).is_ok());
+
+        // Claiming to be someone else, or changing any other field, breaks the signature of the sender.
+        let impostor = Keypair::new().pubkey();
+        let forged =
+            PartialSignature { sender: impostor, ..PartialSignature::deserialize_versioned(&serialized).unwrap() };
+        assert!(matches!(
+            PartialSignature::deserialize_versioned(&forged.serialize_versioned()),
+            Err(Error::ForgedSender(key)) if key == impostor
+        ));
+        let tampered = PartialSignature { block_hash: Hash::new_unique(), ..partial_sig };
+        assert!(matches!(
+            PartialSignature::deserialize_versioned(&tampered.serialize_versioned()),
+            Err(Error::ForgedSender(_))
+        ));
+    }
+
     #[test]
     fn test_agg_partial_signature() {
         let mut signature = [0u8; 64];
         for i in 0..u8::MAX {
             signature.fill(i);
-            let partial_sig = PartialSignature(Signature::new(&signature));
-            let serialized = partial_sig.serialize_bs58();
-            let deserialized = PartialSignature::deserialize_bs58(serialized).unwrap();
+            let sender = Keypair::new();
+            let mut partial_sig = PartialSignature {
+                session_id: SessionId::random(),
+                sender: sender.pubkey(),
+                block_hash: Hash::new_unique(),
+                digest: Hash::new_unique(),
+                signature: Signature::new(&signature),
+                sender_signature: Signature::default(),
+            };
+            partial_sig.sender_signature = sender.sign_message(&sender_signed_bytes(&partial_sig));
+            let serialized = partial_sig.serialize_encoded(Encoding::Bs58);
+            let deserialized = PartialSignature::deserialize_encoded(serialized.as_str(), Encoding::Bs58).unwrap();
             assert_eq!(PanicEq(partial_sig), PanicEq(deserialized));
         }
     }
@@ -249,10 +1000,263 @@ mod tests {
             data.fill(i);
             let (private_nonces, public_nonces) =
                 musig2::generate_partial_nonces(&ExpandedKeyPair::create(), Some(&data));
-            let secret_agg1 = SecretAggStepOne { private_nonces, public_nonces };
-            let serialized = secret_agg1.serialize_bs58();
-            let deserialized = SecretAggStepOne::deserialize_bs58(serialized).unwrap();
+            let secret_agg1 = SecretAggStepOne { session_id: SessionId::random(), private_nonces, public_nonces };
+            let serialized = secret_agg1.serialize_encoded(Encoding::Bs58);
+            let deserialized = SecretAggStepOne::deserialize_encoded(serialized.as_str(), Encoding::Bs58).unwrap();
             assert_eq!(PanicEq(secret_agg1), PanicEq(deserialized));
         }
+
+        let (private_nonces, public_nonces) = musig2::generate_partial_nonces(&ExpandedKeyPair::create(), None);
+        let private_nonce = format!("{:?}", private_nonces.r[0]);
+        let secret_agg1 = SecretAggStepOne { session_id: SessionId::random(), private_nonces, public_nonces };
+        assert!(!format!("{:?}", secret_agg1).contains(&private_nonce));
+    }
+
+    #[test]
+    fn test_session_id() {
+        for _ in 0..u8::MAX {
+            let session_id = SessionId::random();
+            assert_eq!(session_id.to_string().parse::<SessionId>().unwrap(), session_id);
+        }
+        assert!(matches!(bs58::encode([1u8; 15]).into_string().parse::<SessionId>(), Err(Error::InvalidSessionId)));
+    }
+
+    #[test]
+    fn test_format_version() {
+        let pop = KeyPop { key: Pubkey::new_unique(), signature: Signature::new(&[7u8; 64]) };
+        let serialized = pop.serialize_versioned();
+        assert_eq!(serialized[0], FORMAT_VERSION);
+        let mut serialized = serialized[..serialized.len() - 4].to_vec();
+        serialized[0] = FORMAT_VERSION + 1;
+        let serialized = with_checksum(serialized);
+        assert!(
+            matches!(KeyPop::deserialize_versioned(&serialized), Err(Error::UnsupportedVersion(v)) if v == FORMAT_VERSION + 1)
+        );
+        assert!(matches!(KeyPop::deserialize_versioned(&[]), Err(Error::InputTooShort { expected: 5, found: 0 })));
+    }
+
+    fn with_checksum(mut b: Vec<u8>) -> Vec<u8> {
+        let checksum = Sha256::digest(&b);
+        b.extend(&checksum[..4]);
+        b
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let pop = KeyPop { key: Pubkey::new_unique(), signature: Signature::new(&[7u8; 64]) };
+        let serialized = pop.serialize_versioned();
+        let len = serialized.len();
+        let mut extended = serialized[..len - 4].to_vec();
+        extended.push(0);
+        let res = KeyPop::deserialize_versioned(&with_checksum(extended));
+        assert!(matches!(res, Err(Error::TrailingBytes { expected, found }) if expected == len && found == len + 1));
+
+        // Two messages pasted together aren't one valid message.
+        let concatenated = [pop.serialize_versioned(), pop.serialize_versioned()].concat();
+        assert!(matches!(KeyPop::deserialize_versioned(&concatenated), Err(Error::ChecksumMismatch)));
+    }
+
+    #[test]
+    fn test_checksum() {
+        let pop = KeyPop { key: Pubkey::new_unique(), signature: Signature::new(&[7u8; 64]) };
+        let serialized = pop.serialize_encoded(Encoding::Bs58);
+        // Dropping or changing a single character while copying the message.
+        let mut dropped = serialized.clone();
+        dropped.remove(serialized.len() / 2);
+        assert!(matches!(KeyPop::deserialize_encoded(dropped.as_str(), Encoding::Bs58), Err(Error::ChecksumMismatch)));
+        let mut changed = serialized.as_bytes().to_vec();
+        changed[serialized.len() / 2] = if changed[serialized.len() / 2] == b'2' { b'3' } else { b'2' };
+        assert!(matches!(KeyPop::deserialize_encoded(changed, Encoding::Bs58), Err(Error::ChecksumMismatch)));
+    }
+
+    #[test]
+    fn test_wrong_message_type() {
+        // A key proof of possession is shorter than a partial signature, the type should still be checked first.
+        let pop = KeyPop { key: Pubkey::new_unique(), signature: Signature::new(&[7u8; 64]) };
+        let res = PartialSignature::deserialize_encoded(pop.serialize_encoded(Encoding::Bs58).as_str(), Encoding::Bs58);
+        assert!(matches!(res, Err(Error::WrongTag { expected: Tag::PartialSignature, found: Tag::KeyPop })));
+        let res = KeyPop::deserialize_encoded(SessionId::random().to_string(), Encoding::Bs58);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_json_envelope() {
+        let pop = KeyPop { key: Pubkey::new_unique(), signature: Signature::new(&[7u8; 64]) };
+        let json = pop.serialize_json(Encoding::Bs58);
+        assert_eq!(json["type"], "KeyPop");
+        assert_eq!(json["version"], FORMAT_VERSION);
+        assert_eq!(json["encoding"], "bs58");
+        assert_eq!(json["data"], *pop.serialize_encoded(Encoding::Bs58));
+        let json_hex = pop.serialize_json(Encoding::Hex);
+        assert_eq!(json_hex["data"], *pop.serialize_encoded(Encoding::Hex));
+        assert_eq!(
+            PanicEq(KeyPop::parse(&json_hex.to_string()).unwrap()),
+            PanicEq(KeyPop::parse(&json.to_string()).unwrap())
+        );
+        assert_eq!(PanicEq(KeyPop::parse(&json.to_string()).unwrap()), PanicEq(pop));
+        assert!(matches!(KeyPop::parse("{\"type\": \"KeyPop\"}"), Err(Error::BadJson(_))));
+    }
+
+    #[test]
+    fn test_prefix() {
+        let pop = KeyPop { key: Pubkey::new_unique(), signature: Signature::new(&[7u8; 64]) };
+        let serialized = pop.serialize_encoded(Encoding::Bs58);
+        assert!(serialized.starts_with("pop_"));
+        // The prefix is only for humans, a message without it is just as good.
+        let bare = serialized.trim_start_matches("pop_");
+        assert_eq!(PanicEq(KeyPop::deserialize_encoded(bare, Encoding::Bs58).unwrap()), PanicEq(pop));
+        let res = KeyPop::deserialize_encoded(format!("psig_{}", bare), Encoding::Bs58);
+        assert!(matches!(res, Err(Error::WrongPrefix { expected: Tag::KeyPop, found }) if found == "psig"));
+    }
+
+    #[test]
+    fn test_encodings() {
+        let pop = KeyPop { key: Pubkey::new_unique(), signature: Signature::new(&[7u8; 64]) };
+        for &encoding in &Encoding::ALL {
+            let serialized = pop.serialize_encoded(encoding);
+            assert!(serialized.starts_with("pop_"));
+            let deserialized = KeyPop::deserialize_encoded(serialized.as_str(), encoding).unwrap();
+            assert_eq!(deserialized.serialize_versioned(), pop.serialize_versioned());
+            // Parsing finds the encoding by itself.
+            assert_eq!(KeyPop::parse(&serialized).unwrap().serialize_versioned(), pop.serialize_versioned());
+        }
+        let hex = pop.serialize_encoded(Encoding::Hex);
+        assert_eq!(*hex, format!("pop_{}", hex::encode(pop.serialize_versioned())));
+        assert!(matches!(KeyPop::deserialize_encoded(hex.as_str(), Encoding::Bs58), Err(Error::BadBase58(_))));
+        // A typo is reported as such, not as an invalid base64 character.
+        let mut typo = pop.serialize_encoded(Encoding::Base64).as_bytes().to_vec();
+        typo[10] = if typo[10] == b'A' { b'B' } else { b'A' };
+        assert!(matches!(KeyPop::parse(std::str::from_utf8(&typo).unwrap()), Err(Error::ChecksumMismatch)));
+        assert!(matches!("base32".parse::<Encoding>(), Err(Error::UnknownEncoding(_))));
+    }
+
+    #[test]
+    fn test_degenerate_values() {
+        let generator = Point::<Ed25519>::generator().to_point();
+        assert_eq!(point_from_bytes(&generator.to_bytes(true)).unwrap(), generator);
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+        assert!(matches!(point_from_bytes(&identity), Err(Error::IdentityPoint)));
+        // y = 0 is a point of order 4
+        let low_order = CompressedEdwardsY([0u8; 32]).decompress().unwrap();
+        assert!(matches!(point_from_bytes(low_order.compress().as_bytes()), Err(Error::LowOrderPoint)));
+        let mixed = ED25519_BASEPOINT_POINT + low_order;
+        assert!(matches!(point_from_bytes(mixed.compress().as_bytes()), Err(Error::PointNotInSubgroup)));
+        // The identity again, with its y encoded as p + 1, and with the sign of x set
+        let mut non_canonical = [0xffu8; 32];
+        non_canonical[0] = 0xee;
+        non_canonical[31] = 0x7f;
+        assert!(matches!(point_from_bytes(&non_canonical), Err(Error::NonCanonicalPoint)));
+        identity[31] = 0x80;
+        assert!(matches!(point_from_bytes(&identity), Err(Error::NonCanonicalPoint)));
+
+        let scalar = Scalar::<Ed25519>::random();
+        assert_eq!(scalar_from_bytes(&scalar.to_bytes()).unwrap(), scalar);
+        assert!(matches!(scalar_from_bytes(&BASEPOINT_ORDER.to_bytes()), Err(Error::NonCanonicalScalar)));
+        assert!(matches!(scalar_from_bytes(&[0xffu8; 32]), Err(Error::NonCanonicalScalar)));
+    }
+
+    #[test]
+    fn test_serialize_into() {
+        let pop = KeyPop { key: Pubkey::new_unique(), signature: Signature::new(&[7u8; 64]) };
+        let mut buf = [0u8; 1 + 1 + 32 + 64 + 4];
+        assert_eq!(pop.versioned_len(), buf.len());
+        pop.serialize_versioned_into(&mut buf);
+        assert_eq!(buf.to_vec(), pop.serialize_versioned());
+        assert_eq!(PanicEq(KeyPop::deserialize_versioned(&buf).unwrap()), PanicEq(pop));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_serialize_into_short_buffer() {
+        let pop = KeyPop { key: Pubkey::new_unique(), signature: Signature::new(&[7u8; 64]) };
+        pop.serialize_versioned_into(&mut [0u8; 64]);
+    }
+
+    #[test]
+    fn test_key_pop() {
+        let mut data = [0u8; 64];
+        for i in 0..u8::MAX {
+            data.fill(i);
+            let pop = KeyPop { key: Pubkey::new(&data[..32]), signature: Signature::new(&data) };
+            let deserialized =
+                KeyPop::deserialize_encoded(pop.serialize_encoded(Encoding::Bs58).as_str(), Encoding::Bs58).unwrap();
+            assert_eq!(PanicEq(pop), PanicEq(deserialized));
+        }
+    }
+
+    #[test]
+    fn test_serialize_dkg() {
+        for threshold in 2..16u16 {
+            let coefficients: Vec<_> = (0..threshold).map(|_| Scalar::random()).collect();
+            let commitments = coefficients.iter().map(|c| Point::generator() * c).collect();
+            let signer = ScalarSigner(&coefficients[0]);
+
+            let mut msg1 = DkgMessage1 {
+                index: threshold,
+                commitments,
+                proof_nonce: Point::generator() * Scalar::random(),
+                proof_response: Scalar::random(),
+                sender_signature: Signature::default(),
+            };
+            msg1.sender_signature = signer.sign_message(&sender_signed_bytes(&msg1));
+            let deserialized =
+                DkgMessage1::deserialize_encoded(msg1.serialize_encoded(Encoding::Bs58).as_str(), Encoding::Bs58)
+                    .unwrap();
+            assert_eq!(PanicEq(msg1), PanicEq(deserialized));
+
+            let mut msg2 = DkgMessage2 {
+                sender: threshold,
+                receiver: 16,
+                share: Scalar::random(),
+                sender_key: signer.pubkey(),
+                sender_signature: Signature::default(),
+            };
+            msg2.sender_signature = signer.sign_message(&sender_signed_bytes(&msg2));
+            assert!(!format!("{:?}", msg2).contains(&format!("{:?}", msg2.share)));
+            let deserialized =
+                DkgMessage2::deserialize_encoded(msg2.serialize_encoded(Encoding::Bs58).as_str(), Encoding::Bs58)
+                    .unwrap();
+            assert_eq!(PanicEq(msg2), PanicEq(deserialized));
+
+            let secret = DkgSecretStepOne { index: threshold, parties: 16, coefficients };
+            let deserialized = DkgSecretStepOne::deserialize_encoded(
+                secret.serialize_encoded(Encoding::Bs58).as_str(),
+                Encoding::Bs58,
+            )
+            .unwrap();
+            assert_eq!(PanicEq(secret), PanicEq(deserialized));
+        }
+    }
+
+    #[test]
+    fn test_tampered_threshold_and_dkg_messages() {
+        fn tamper<T: Serialize>(message: &T, at: usize) -> Result<T, Error> {
+            let mut b = vec![0u8; message.size_hint()];
+            message.serialize(&mut b);
+            assert!(T::deserialize(&b).is_ok());
+            b[at] ^= 1;
+            T::deserialize(&b)
+        }
+
+        let shares = threshold::keygen(2, 3).unwrap();
+        let (msg1, _) = threshold::step_one(&shares[0], SessionId::random());
+        // The index, right after the tag and the session id.
+        assert!(matches!(tamper(&msg1, 1 + 16), Err(Error::ForgedSender(sender)) if sender == msg1.sender));
+
+        let (dkg_msg1, dkg_msgs2) = dkg::reshare_step_one(&shares[0], &[1, 2], 2, 3).unwrap();
+        assert!(matches!(tamper(&dkg_msg1, 1), Err(Error::ForgedSender(..))));
+        // The receiver, so a share can't be redirected to another party.
+        assert!(matches!(tamper(&dkg_msgs2[0], 3), Err(Error::ForgedSender(..))));
+        // Nor claimed by a key other than the dealer's.
+        let mut forged = vec![0u8; dkg_msgs2[0].size_hint()];
+        dkg_msgs2[0].serialize(&mut forged);
+        forged[1 + 2 + 2 + 32..1 + 2 + 2 + 32 + 32].copy_from_slice(&shares[1].group_key.to_bytes());
+        assert!(matches!(DkgMessage2::deserialize(&forged), Err(Error::ForgedSender(..))));
+        // A first message without even the constant term has no key to check it with.
+        let mut empty = vec![0u8; dkg_msg1.size_hint()];
+        dkg_msg1.serialize(&mut empty);
+        empty[3..5].copy_from_slice(&0u16.to_le_bytes());
+        assert!(matches!(DkgMessage1::deserialize(&empty), Err(Error::EmptyCommitments)));
     }
 }
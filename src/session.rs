@@ -0,0 +1,334 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use multi_party_eddsa::protocols::musig2::PublicPartialNonces;
+use sha2::{Digest, Sha512};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::journal::data_dir;
+use crate::serialization::{AggMessage1, FieldError, SecretAggStepOne, Serialize, ThresholdMessage1};
+use crate::Error;
+
+const SESSION_KEY_DOMAIN: &[u8] = b"solana-tss session";
+const NONCE_LEN: usize = 12;
+
+/// A signing session started with `--session <name>`, kept in `~/.solana-tss/sessions` between step one and step two
+/// so its secret state doesn't have to be copied from one command into the next.
+/// The secret state is encrypted with a key derived from the party's secret key (or key share), the rest is in the clear
+/// so sessions can be listed without it.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Session {
+    pub name: String,
+    pub session_id: String,
+    /// The public key of the party, or the aggregated public key and the index of the share of a threshold wallet,
+    /// which is also the name of its nonce journal
+    pub party: String,
+    /// Seconds since the Unix epoch
+    pub created: u64,
+    /// The message of step one, as printed to send to the other parties
+    pub first_message: String,
+    secret_state: String,
+}
+
+impl Session {
+    /// Encrypt `secret_state` with a key derived from `party_secret`, the secret key or key share of the party
+    pub fn new(
+        name: &str,
+        party: String,
+        party_secret: &[u8],
+        first_message: String,
+        secret_state: &SecretAggStepOne,
+    ) -> Result<Self, Error> {
+        check_name(name)?;
+        let nonce: [u8; NONCE_LEN] = rand07::random();
+        let plaintext = Zeroizing::new(secret_state.serialize_versioned());
+        let ciphertext =
+            cipher(party_secret).encrypt(Nonce::from_slice(&nonce), &**plaintext).expect("encryption can't fail");
+        let mut encrypted = nonce.to_vec();
+        encrypted.extend(ciphertext);
+        Ok(Self {
+            name: name.to_string(),
+            session_id: secret_state.session_id.to_string(),
+            party,
+            created: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs()),
+            first_message,
+            secret_state: bs58::encode(encrypted).into_string(),
+        })
+    }
+
+    /// The secret state, decrypted with the same secret the session was started with
+    pub fn secret_state(&self, party_secret: &[u8]) -> Result<SecretAggStepOne, Error> {
+        let wrong_key = || Error::SessionKeyMismatch(self.name.clone());
+        let encrypted = bs58::decode(&self.secret_state).into_vec().map_err(|_| wrong_key())?;
+        if encrypted.len() < NONCE_LEN {
+            return Err(wrong_key());
+        }
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        let plaintext = cipher(party_secret)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map(Zeroizing::new)
+            .map_err(|_| wrong_key())?;
+        SecretAggStepOne::deserialize_versioned(&plaintext).map_err(|_| wrong_key())
+    }
+
+    /// The public nonces of the session, from its first message, for aborting it without the secret key
+    pub fn public_nonces(&self) -> Result<PublicPartialNonces, Error> {
+        match AggMessage1::parse(&self.first_message) {
+            Ok(msg) => Ok(msg.public_nonces),
+            Err(_) => Ok(ThresholdMessage1::parse(&self.first_message).with_field("first message")?.public_nonces),
+        }
+    }
+}
+
+/// Where the sessions are kept between step one and step two. Implement it to keep them in another database, the
+/// secret states are encrypted before they get there.
+pub trait SessionStore: Send + Sync {
+    fn get(&self, name: &str) -> Result<Session, Error>;
+
+    /// Store a new session, an existing session of the same name is never overwritten
+    fn put(&self, session: &Session) -> Result<(), Error>;
+
+    /// Remove a session, once it's signed with or abandoned
+    fn delete(&self, name: &str) -> Result<(), Error>;
+
+    /// All the sessions, oldest first
+    fn list(&self) -> Result<Vec<Session>, Error>;
+}
+
+/// The session store of the sled database at `db`, or of the files in `~/.solana-tss/sessions` if none
+pub fn open(db: Option<&Path>) -> Result<Box<dyn SessionStore>, Error> {
+    match db {
+        Some(db) => Ok(Box::new(SledStore::open(db)?)),
+        None => Ok(Box::new(FileStore::open()?)),
+    }
+}
+
+/// The sessions in a directory, `~/.solana-tss/sessions` by default, one JSON file each
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn open() -> Result<Self, Error> {
+        Self::at(data_dir()?.join("sessions"))
+    }
+
+    pub fn at(dir: PathBuf) -> Result<Self, Error> {
+        fs::create_dir_all(&dir).map_err(Error::SessionStoreFailed)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf, Error> {
+        check_name(name)?;
+        Ok(self.dir.join(format!("{}.json", name)))
+    }
+}
+
+impl SessionStore for FileStore {
+    fn get(&self, name: &str) -> Result<Session, Error> {
+        let contents = fs::read_to_string(self.path(name)?).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Error::NoSuchSession(name.to_string()),
+            _ => Error::SessionStoreFailed(e),
+        })?;
+        serde_json::from_str(&contents).map_err(|_| Error::CorruptSession(name.to_string()))
+    }
+
+    fn put(&self, session: &Session) -> Result<(), Error> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(self.path(&session.name)?).map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => Error::SessionExists(session.name.clone()),
+            _ => Error::SessionStoreFailed(e),
+        })?;
+        let json = serde_json::to_string_pretty(session).expect("serializing to memory can't fail");
+        writeln!(file, "{}", json).map_err(Error::SessionStoreFailed)
+    }
+
+    fn delete(&self, name: &str) -> Result<(), Error> {
+        fs::remove_file(self.path(name)?).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Error::NoSuchSession(name.to_string()),
+            _ => Error::SessionStoreFailed(e),
+        })
+    }
+
+    fn list(&self) -> Result<Vec<Session>, Error> {
+        let mut sessions = Vec::new();
+        for entry in fs::read_dir(&self.dir).map_err(Error::SessionStoreFailed)? {
+            let path = entry.map_err(Error::SessionStoreFailed)?.path();
+            if let Some(name) = path.file_stem().filter(|_| path.extension() == Some("json".as_ref())) {
+                sessions.push(self.get(&name.to_string_lossy())?);
+            }
+        }
+        sessions.sort_by_key(|session| session.created);
+        Ok(sessions)
+    }
+}
+
+/// The sessions in a sled database, as JSON by name
+pub struct SledStore {
+    db: sled::Db,
+}
+
+impl SledStore {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        Ok(Self { db: sled::open(path).map_err(sled_failed)? })
+    }
+}
+
+impl SessionStore for SledStore {
+    fn get(&self, name: &str) -> Result<Session, Error> {
+        check_name(name)?;
+        let json = self.db.get(name).map_err(sled_failed)?.ok_or_else(|| Error::NoSuchSession(name.to_string()))?;
+        serde_json::from_slice(&json).map_err(|_| Error::CorruptSession(name.to_string()))
+    }
+
+    fn put(&self, session: &Session) -> Result<(), Error> {
+        check_name(&session.name)?;
+        let json = serde_json::to_vec(session).expect("serializing to memory can't fail");
+        self.db
+            .compare_and_swap(&session.name, None::<&[u8]>, Some(json))
+            .map_err(sled_failed)?
+            .map_err(|_| Error::SessionExists(session.name.clone()))?;
+        self.db.flush().map_err(sled_failed)?;
+        Ok(())
+    }
+
+    fn delete(&self, name: &str) -> Result<(), Error> {
+        check_name(name)?;
+        self.db.remove(name).map_err(sled_failed)?.ok_or_else(|| Error::NoSuchSession(name.to_string()))?;
+        self.db.flush().map_err(sled_failed)?;
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<Session>, Error> {
+        let mut sessions = Vec::new();
+        for entry in self.db.iter() {
+            let (name, json) = entry.map_err(sled_failed)?;
+            let name = String::from_utf8_lossy(&name);
+            sessions.push(serde_json::from_slice(&json).map_err(|_| Error::CorruptSession(name.to_string()))?);
+        }
+        sessions.sort_by_key(|session: &Session| session.created);
+        Ok(sessions)
+    }
+}
+
+fn sled_failed(e: sled::Error) -> Error {
+    Error::SessionStoreFailed(e.into())
+}
+
+/// The sessions in memory only, for tests and for processes that don't outlive their sessions
+#[derive(Default)]
+pub struct MemoryStore {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl SessionStore for MemoryStore {
+    fn get(&self, name: &str) -> Result<Session, Error> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(name).cloned().ok_or_else(|| Error::NoSuchSession(name.to_string()))
+    }
+
+    fn put(&self, session: &Session) -> Result<(), Error> {
+        check_name(&session.name)?;
+        match self.sessions.lock().unwrap().entry(session.name.clone()) {
+            Entry::Occupied(_) => Err(Error::SessionExists(session.name.clone())),
+            Entry::Vacant(entry) => {
+                entry.insert(session.clone());
+                Ok(())
+            }
+        }
+    }
+
+    fn delete(&self, name: &str) -> Result<(), Error> {
+        let removed = self.sessions.lock().unwrap().remove(name);
+        removed.map(drop).ok_or_else(|| Error::NoSuchSession(name.to_string()))
+    }
+
+    fn list(&self) -> Result<Vec<Session>, Error> {
+        let mut sessions: Vec<_> = self.sessions.lock().unwrap().values().cloned().collect();
+        sessions.sort_by_key(|session| session.created);
+        Ok(sessions)
+    }
+}
+
+/// Names become file names, so they can't have path separators or dots
+fn check_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(Error::WrongSessionName(name.to_string()));
+    }
+    Ok(())
+}
+
+fn cipher(party_secret: &[u8]) -> ChaCha20Poly1305 {
+    let mut key = Sha512::new().chain(SESSION_KEY_DOMAIN).chain(party_secret).finalize();
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key[..32]));
+    key.as_mut_slice().zeroize();
+    cipher
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::Keypair;
+
+    use crate::serialization::{Encoding, Serialize, SessionId};
+    use crate::session::{FileStore, MemoryStore, Session, SessionStore, SledStore};
+    use crate::{tss, Error};
+
+    #[test]
+    fn test_session() {
+        let keypair = Keypair::new();
+        let (first_msg, secret) = tss::step_one(&keypair, SessionId::random());
+        let first_msg = first_msg.serialize_encoded(Encoding::Bs58).to_string();
+        let secret_key = keypair.secret().to_bytes();
+        let session = Session::new("payroll-1", "party".to_string(), &secret_key, first_msg, &secret).unwrap();
+        assert_eq!(session.session_id, secret.session_id.to_string());
+        assert!(!serde_json::to_string(&session)
+            .unwrap()
+            .contains(&secret.serialize_encoded(Encoding::Bs58)["secret1_".len()..]));
+
+        let parsed: Session = serde_json::from_str(&serde_json::to_string(&session).unwrap()).unwrap();
+        assert_eq!(parsed.secret_state(&secret_key).unwrap().serialize_versioned(), secret.serialize_versioned());
+        let other = Keypair::new().secret().to_bytes();
+        assert!(matches!(parsed.secret_state(&other), Err(Error::SessionKeyMismatch(_))));
+
+        for name in ["", "../keys", "a.json", "a/b"] {
+            let res = Session::new(name, "party".to_string(), &secret_key, String::new(), &secret);
+            assert!(matches!(res, Err(Error::WrongSessionName(_))));
+        }
+    }
+
+    #[test]
+    fn test_session_stores() {
+        let keypair = Keypair::new();
+        let secret_key = keypair.secret().to_bytes();
+        let dir = std::env::temp_dir().join(format!("solana-tss-sessions-{}", SessionId::random()));
+        let stores: Vec<Box<dyn SessionStore>> = vec![
+            Box::new(MemoryStore::default()),
+            Box::new(FileStore::at(dir.join("files")).unwrap()),
+            Box::new(SledStore::open(&dir.join("sled")).unwrap()),
+        ];
+        for store in stores {
+            let (_, secret) = tss::step_one(&keypair, SessionId::random());
+            let session = Session::new("payroll-1", "party".to_string(), &secret_key, String::new(), &secret).unwrap();
+            store.put(&session).unwrap();
+            assert!(matches!(store.put(&session), Err(Error::SessionExists(_))));
+            let stored = store.get("payroll-1").unwrap();
+            assert_eq!(stored.secret_state(&secret_key).unwrap().serialize_versioned(), secret.serialize_versioned());
+            assert_eq!(store.list().unwrap().len(), 1);
+            store.delete("payroll-1").unwrap();
+            assert!(matches!(store.get("payroll-1"), Err(Error::NoSuchSession(_))));
+            assert!(matches!(store.delete("payroll-1"), Err(Error::NoSuchSession(_))));
+            assert!(store.list().unwrap().is_empty());
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
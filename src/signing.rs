@@ -0,0 +1,215 @@
+//! The signing steps of [`tss`] as a session whose type follows its rounds, so the order of the protocol is checked by
+//! the compiler: partial signatures can only be combined by a `Session<Round3>`, which is made by signing a
+//! `Session<Round2>`, which is only made once the first messages of all the other parties were checked. Signing takes
+//! the session by value together with its secret nonces, so they can't be used twice.
+//!
+//! ```compile_fail
+//! # fn combine(session: solana_tss::signing::Session<solana_tss::signing::Round2>) {
+//! // Not signed yet.
+//! session.combine(Vec::new());
+//! # }
+//! ```
+//!
+//! A session that fails is gone with its nonces, which were never used to sign: start a new one. Nonces are only safe
+//! from reuse within the process though, a party that can be restarted mid-session should also record them in a
+//! [`NonceJournal`](crate::journal::NonceJournal).
+
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::VersionedTransaction;
+
+use crate::serialization::{check_sender, AggMessage1, PartialSignature, SecretAggStepOne, SessionId};
+use crate::{tss, Error};
+
+/// The first message was made, the first messages of the other parties are awaited
+pub struct Round1 {
+    secret_state: SecretAggStepOne,
+}
+
+/// The first messages of all the parties are in and checked, the party can sign
+pub struct Round2 {
+    secret_state: SecretAggStepOne,
+    first_messages: Vec<AggMessage1>,
+}
+
+/// The party signed, the partial signatures of the others are awaited
+pub struct Round3 {
+    first_messages: Vec<AggMessage1>,
+    partial_signature: PartialSignature,
+}
+
+/// The signing of `message` by one party of the aggregated key of `keys`, in round `R`
+pub struct Session<R> {
+    keypair: Keypair,
+    keys: Vec<Pubkey>,
+    message: VersionedMessage,
+    first_message: AggMessage1,
+    round: R,
+}
+
+impl<R> Session<R> {
+    pub fn session_id(&self) -> SessionId {
+        self.first_message.session_id
+    }
+
+    /// The first message of the party, to send to all the others
+    pub fn first_message(&self) -> &AggMessage1 {
+        &self.first_message
+    }
+
+    pub fn message(&self) -> &VersionedMessage {
+        &self.message
+    }
+}
+
+impl Session<Round1> {
+    /// Start signing `message` as `keypair`, one of `keys`. All the parties need to use the same `session_id`.
+    pub fn new(
+        keypair: Keypair,
+        keys: Vec<Pubkey>,
+        message: VersionedMessage,
+        session_id: SessionId,
+    ) -> Result<Self, Error> {
+        if !keys.contains(&keypair.pubkey()) {
+            return Err(Error::KeyPairIsNotInKeys);
+        }
+        tss::check_signer(&message, tss::aggregate_pubkey(keys.clone())?)?;
+        let (first_message, secret_state) = tss::step_one(&keypair, session_id);
+        Ok(Session { keypair, keys, message, first_message, round: Round1 { secret_state } })
+    }
+
+    /// Take the first messages of the other parties, which must be one from each, signed by its sender and of this
+    /// session. A message received twice counts once.
+    pub fn receive(self, first_messages: Vec<AggMessage1>) -> Result<Session<Round2>, Error> {
+        let mut received: Vec<AggMessage1> = Vec::new();
+        for message in first_messages {
+            if message.session_id != self.session_id() {
+                return Err(Error::SessionMismatch);
+            }
+            if message.sender == self.keypair.pubkey() || !self.keys.contains(&message.sender) {
+                return Err(Error::NotAnotherParty(message.sender));
+            }
+            check_sender(&message, &message.sender, &message.sender_signature)
+                .map_err(|error| Error::DeserializationFailed { error, field_name: "first message" })?;
+            match received.iter().find(|msg| msg.sender == message.sender) {
+                Some(other) if *other != message => return Err(Error::FirstMessageConflict(message.sender)),
+                Some(_) => {}
+                None => received.push(message),
+            }
+        }
+        let missing: Vec<_> = self
+            .keys
+            .iter()
+            .filter(|key| **key != self.keypair.pubkey() && !received.iter().any(|msg| msg.sender == **key))
+            .copied()
+            .collect();
+        if !missing.is_empty() {
+            return Err(Error::MissingFirstMessages(missing));
+        }
+        let Session { keypair, keys, message, first_message, round: Round1 { secret_state } } = self;
+        Ok(Session { keypair, keys, message, first_message, round: Round2 { secret_state, first_messages: received } })
+    }
+}
+
+impl Session<Round2> {
+    /// Make the partial signature of the party, using up its nonces
+    pub fn sign(self) -> Result<Session<Round3>, Error> {
+        let Session { keypair, keys, message, first_message, round: Round2 { secret_state, first_messages } } = self;
+        let partial_signature = tss::step_two(
+            Keypair::from_bytes(&keypair.to_bytes()).expect("the keypair is valid"),
+            message.clone(),
+            keys.clone(),
+            Vec::new(),
+            first_messages.clone(),
+            secret_state,
+        )?;
+        Ok(Session { keypair, keys, message, first_message, round: Round3 { first_messages, partial_signature } })
+    }
+}
+
+impl Session<Round3> {
+    /// The partial signature of the party, to send to whoever combines them
+    pub fn partial_signature(&self) -> &PartialSignature {
+        &self.round.partial_signature
+    }
+
+    /// Combine the partial `signatures` of the other parties with the party's own into the signed transaction,
+    /// naming the party whose signature is invalid if the result is
+    pub fn combine(self, mut signatures: Vec<PartialSignature>) -> Result<VersionedTransaction, Error> {
+        let own = self.round.partial_signature;
+        if !signatures.iter().any(|sig| sig.sender == own.sender) {
+            signatures.push(own);
+        }
+        let mut first_messages = self.round.first_messages;
+        first_messages.push(self.first_message);
+        tss::sign_and_broadcast(self.message, self.keys, first_messages, signatures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::{Message, VersionedMessage};
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::system_instruction;
+
+    use crate::serialization::{self, SessionId};
+    use crate::signing::Session;
+    use crate::{tss, Error};
+
+    #[test]
+    fn test_signing_session() {
+        let parties: Vec<_> = (0..3).map(|_| Keypair::new()).collect();
+        let keys: Vec<_> = parties.iter().map(Keypair::pubkey).collect();
+        let aggpubkey = tss::aggregate_pubkey(keys.clone()).unwrap();
+        let instruction = system_instruction::transfer(&aggpubkey, &Keypair::new().pubkey(), 1);
+        let message = VersionedMessage::Legacy(Message::new_with_blockhash(
+            &[instruction],
+            Some(&aggpubkey),
+            &Hash::new_unique(),
+        ));
+        let start = |session_id| -> Vec<_> {
+            parties
+                .iter()
+                .map(|party| {
+                    let keypair = Keypair::from_bytes(&party.to_bytes()).unwrap();
+                    Session::new(keypair, keys.clone(), message.clone(), session_id).unwrap()
+                })
+                .collect()
+        };
+
+        let sessions = start(SessionId::random());
+        let first_messages: Vec<_> = sessions.iter().map(|session| session.first_message().clone()).collect();
+        let mut sessions = sessions.into_iter();
+        // Not from all the others yet.
+        let result = sessions.next().unwrap().receive(vec![first_messages[1].clone()]);
+        assert!(matches!(result, Err(Error::MissingFirstMessages(missing)) if missing == vec![keys[2]]));
+        // The nonces of another party in the name of the sender.
+        let mut forged = first_messages[2].clone();
+        forged.public_nonces = first_messages[0].public_nonces.clone();
+        let result = sessions.next().unwrap().receive(vec![first_messages[0].clone(), forged]);
+        assert!(matches!(
+            result,
+            Err(Error::DeserializationFailed { error: serialization::Error::ForgedSender(sender), .. }) if sender == keys[2]
+        ));
+        // Its own.
+        let result = sessions.next().unwrap().receive(first_messages);
+        assert!(matches!(result, Err(Error::NotAnotherParty(party)) if party == keys[2]));
+
+        let sessions = start(SessionId::random());
+        let first_messages: Vec<_> = sessions.iter().map(|session| session.first_message().clone()).collect();
+        let signed: Vec<_> = sessions
+            .into_iter()
+            .map(|session| {
+                let own = session.first_message().sender;
+                // Received twice counts once.
+                let others = first_messages.iter().chain(&first_messages).filter(|msg| msg.sender != own).cloned();
+                session.receive(others.collect()).unwrap().sign().unwrap()
+            })
+            .collect();
+        let signatures = signed[1..].iter().map(|session| session.partial_signature().clone()).collect();
+        let tx = signed.into_iter().next().unwrap().combine(signatures).unwrap();
+        assert!(tx.verify_with_results().iter().all(|valid| *valid));
+    }
+}
@@ -0,0 +1,50 @@
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::stake::instruction as stake_instruction;
+use solana_sdk::stake::state::{Authorized, Lockup};
+
+use crate::Error;
+
+/// Create a stake account holding `lamports`, with `wallet` paying for it and being both its staker and withdrawer.
+/// Its address is derived from `wallet` and `seed`, so the wallet is the only one signing.
+pub fn create_instructions(wallet: &Pubkey, seed: &str, lamports: u64) -> Result<(Pubkey, Vec<Instruction>), Error> {
+    let stake_account =
+        Pubkey::create_with_seed(wallet, seed, &solana_sdk::stake::program::id()).map_err(Error::InvalidSeed)?;
+    let instructions = stake_instruction::create_account_with_seed(
+        wallet,
+        &stake_account,
+        wallet,
+        seed,
+        &Authorized::auto(wallet),
+        &Lockup::default(),
+        lamports,
+    );
+    Ok((stake_account, instructions))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::message::Message;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::stake::instruction::StakeInstruction;
+
+    use crate::stake::create_instructions;
+
+    #[test]
+    fn test_create_stake_account() {
+        let wallet = Pubkey::new_unique();
+        let (stake_account, instructions) = create_instructions(&wallet, "stake", 5_000_000_000).unwrap();
+        assert_eq!(
+            stake_account,
+            Pubkey::create_with_seed(&wallet, "stake", &solana_sdk::stake::program::id()).unwrap()
+        );
+        let message = Message::new(&instructions, Some(&wallet));
+        assert_eq!(message.header.num_required_signatures, 1);
+        match bincode::deserialize(&instructions[1].data) {
+            Ok(StakeInstruction::Initialize(authorized, _)) => {
+                assert_eq!((authorized.staker, authorized.withdrawer), (wallet, wallet))
+            }
+            other => panic!("expected the stake account to be initialized, got {:?}", other),
+        }
+    }
+}
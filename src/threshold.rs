@@ -0,0 +1,293 @@
+#![allow(non_snake_case)]
+
+use curv::arithmetic::Converter;
+use curv::elliptic::curves::{Ed25519, Point, Scalar};
+use curv::BigInt;
+use multi_party_eddsa::protocols::musig2::{PrivatePartialNonces, PublicPartialNonces};
+use sha2::{Digest, Sha512};
+use solana_sdk::signature::{Signature, Signer, SignerError};
+use solana_sdk::{message::VersionedMessage, pubkey::Pubkey, transaction::VersionedTransaction};
+use tracing::instrument;
+
+use crate::serialization::{
+    scalar_from_bytes, secret_bytes, sender_signed_bytes, PartialSignature, SecretAggStepOne, SessionId,
+    ThresholdKeyShare, ThresholdMessage1,
+};
+use crate::{tss, Error};
+
+/// Generate a fresh key and split it into `parties` shares, any `threshold` of them can sign together.
+/// The dealer running this learns the full key, so it should be run on a trusted machine and the shares removed after.
+pub fn keygen(threshold: u16, parties: u16) -> Result<Vec<ThresholdKeyShare>, Error> {
+    if threshold < 2 || threshold > parties {
+        return Err(Error::InvalidThreshold { threshold, parties });
+    }
+    // f(x) = secret + a_1*x + ... + a_(t-1)*x^(t-1), party `i` gets f(i).
+    let coefficients: Vec<Scalar<Ed25519>> = (0..threshold).map(|_| Scalar::random()).collect();
+    let group_point = Point::generator() * &coefficients[0];
+    let group_key = Pubkey::new(&*group_point.to_bytes(true));
+
+    Ok((1..=parties)
+        .map(|index| ThresholdKeyShare {
+            index,
+            threshold,
+            secret_share: evaluate_polynomial(&coefficients, index),
+            group_key,
+        })
+        .collect())
+}
+
+/// Generate the nonces for a threshold signing session, the message goes to all the other signing parties.
+/// All the signing parties need to use the same `session_id`.
+#[instrument(name = "round_1", skip_all, fields(session_id = %session_id, index = share.index))]
+pub fn step_one(share: &ThresholdKeyShare, session_id: SessionId) -> (ThresholdMessage1, SecretAggStepOne) {
+    let private_nonces = PrivatePartialNonces { r: [Scalar::random(), Scalar::random()] };
+    let public_nonces = PublicPartialNonces {
+        R: [Point::generator() * &private_nonces.r[0], Point::generator() * &private_nonces.r[1]],
+    };
+
+    // The share sends its nonces in its own name, the same way it sends its partial signature.
+    let share_signer = ScalarSigner(&share.secret_share);
+    let mut msg1 = ThresholdMessage1 {
+        session_id,
+        index: share.index,
+        public_nonces: public_nonces.clone(),
+        sender: share_signer.pubkey(),
+        sender_signature: Signature::default(),
+    };
+    msg1.sender_signature = share_signer.sign_message(&sender_signed_bytes(&msg1));
+    (msg1, SecretAggStepOne { session_id, private_nonces, public_nonces })
+}
+
+#[instrument(name = "round_2", skip_all, fields(session_id = %secret_state.session_id, index = share.index))]
+pub fn step_two(
+    share: ThresholdKeyShare,
+    message: VersionedMessage,
+    first_messages: Vec<ThresholdMessage1>,
+    secret_state: SecretAggStepOne,
+) -> Result<PartialSignature, Error> {
+    if first_messages.iter().any(|msg1| msg1.session_id != secret_state.session_id) {
+        return Err(Error::SessionMismatch);
+    }
+    // All the parties need to agree on the order of the nonces, so sort them by index.
+    let mut signers: Vec<_> = first_messages.into_iter().map(|msg1| (msg1.index, msg1.public_nonces.R)).collect();
+    signers.push((share.index, secret_state.public_nonces.R.clone()));
+    signers.sort_by_key(|(index, _)| *index);
+    if let Some(w) = signers.windows(2).find(|w| w[0].0 == w[1].0) {
+        return Err(Error::DuplicateSignerIndex(w[0].0));
+    }
+    if signers.len() < usize::from(share.threshold) {
+        return Err(Error::NotEnoughSigners { threshold: share.threshold, found: signers.len() });
+    }
+
+    tss::check_signer(&message, share.group_key)?;
+    let session_id = secret_state.session_id;
+    let signer = ThresholdPartialSigner { share, secret_state, signers };
+    let signature = signer.sign_message(&message.serialize());
+    // A bad partial signature would only be found once all the others are in, and fail everyone's signing.
+    if !signer.verify(&message.serialize(), &signature) {
+        return Err(Error::InvalidOwnPartialSignature);
+    }
+    // The share signs the message in its own name, the group key itself is the same for all the parties.
+    let share_signer = ScalarSigner(&signer.share.secret_share);
+    let mut sig = PartialSignature {
+        session_id,
+        sender: share_signer.pubkey(),
+        block_hash: *message.recent_blockhash(),
+        digest: tss::transaction_digest(&message),
+        signature,
+        sender_signature: Signature::default(),
+    };
+    sig.sender_signature = share_signer.sign_message(&sender_signed_bytes(&sig));
+    Ok(sig)
+}
+
+/// Combine the partial signatures of at least `threshold` parties over `message` into a transaction signed by `group_key`
+#[instrument(name = "aggregate", skip_all, fields(group_key = %group_key))]
+pub fn sign_and_broadcast(
+    message: VersionedMessage,
+    group_key: Pubkey,
+    signatures: Vec<PartialSignature>,
+) -> Result<VersionedTransaction, Error> {
+    tss::combine_partial_signatures(group_key, message, &signatures)
+}
+
+pub(crate) fn evaluate_polynomial(coefficients: &[Scalar<Ed25519>], x: u16) -> Scalar<Ed25519> {
+    let x = Scalar::from(x);
+    coefficients.iter().rev().fold(Scalar::zero(), |acc, coefficient| acc * &x + coefficient)
+}
+
+/// The lagrange coefficient of `index` for interpolating f(0) out of the shares of `indices`
+pub(crate) fn lagrange_coefficient(index: u16, indices: impl Iterator<Item = u16>) -> Scalar<Ed25519> {
+    let (numerator, denominator) = indices
+        .filter(|&j| j != index)
+        .fold((Scalar::<Ed25519>::from(1u16), Scalar::<Ed25519>::from(1u16)), |(num, den), j| {
+            (num * Scalar::from(j), den * (Scalar::from(j) - Scalar::from(index)))
+        });
+    // Indices are distinct and non zero, so the denominator is never zero.
+    numerator * denominator.invert().expect("distinct indices")
+}
+
+pub(crate) fn hash_to_scalar(parts: &[&[u8]]) -> Scalar<Ed25519> {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    // Ed25519 reads the hash as little endian, while `BigInt` is big endian.
+    let mut digest = hasher.finalize().to_vec();
+    digest.reverse();
+    Scalar::from_bigint(&BigInt::from_bytes(&digest))
+}
+
+struct ThresholdPartialSigner {
+    share: ThresholdKeyShare,
+    secret_state: SecretAggStepOne,
+    signers: Vec<(u16, [Point<Ed25519>; 2])>,
+}
+
+impl ThresholdPartialSigner {
+    /// The binding factor of this share's nonces, the group nonce and the challenge for signing `message`
+    fn binding(&self, message: &[u8]) -> (Scalar<Ed25519>, Point<Ed25519>, Scalar<Ed25519>) {
+        let group_key = self.share.group_key.to_bytes();
+        let mut encoded_nonces = Vec::with_capacity(self.signers.len() * (2 + 64));
+        for (index, R) in &self.signers {
+            encoded_nonces.extend(index.to_le_bytes());
+            encoded_nonces.extend(&*R[0].to_bytes(true));
+            encoded_nonces.extend(&*R[1].to_bytes(true));
+        }
+        // Binding every nonce to the message and to the full signing set, as in FROST.
+        let binding_factor = |index: u16| {
+            hash_to_scalar(&[b"solana-tss frost binding", &group_key, message, &encoded_nonces, &index.to_le_bytes()])
+        };
+
+        let R = self
+            .signers
+            .iter()
+            .fold(Point::zero(), |R, (index, nonces)| R + &nonces[0] + &nonces[1] * binding_factor(*index));
+        let challenge = hash_to_scalar(&[&*R.to_bytes(true), &group_key, message]);
+        (binding_factor(self.share.index), R, challenge)
+    }
+
+    /// Check a partial signature of this share against its public nonces and key, the way the aggregation would
+    fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+        let (binding_factor, R, challenge) = self.binding(message);
+        let lambda = lagrange_coefficient(self.share.index, self.signers.iter().map(|(index, _)| *index));
+        let nonces = &self.signers.iter().find(|(index, _)| *index == self.share.index).expect("the share signs").1;
+        // s_i*G == R_i1 + rho_i*R_i2 + c*lambda_i*X_i
+        let expected = &nonces[0]
+            + &nonces[1] * binding_factor
+            + Point::generator() * &self.share.secret_share * lambda * challenge;
+        signature.as_ref()[..32] == *R.to_bytes(true)
+            && scalar_from_bytes(&signature.as_ref()[32..]).map_or(false, |s| Point::generator() * s == expected)
+    }
+}
+
+impl Signer for ThresholdPartialSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.share.group_key)
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let (binding_factor, R, challenge) = self.binding(message);
+        let lambda = lagrange_coefficient(self.share.index, self.signers.iter().map(|(index, _)| *index));
+
+        let s = &self.secret_state.private_nonces.r[0]
+            + &self.secret_state.private_nonces.r[1] * binding_factor
+            + lambda * &self.share.secret_share * challenge;
+        let mut sig_bytes = [0u8; 64];
+        sig_bytes[..32].copy_from_slice(&*R.to_bytes(true));
+        sig_bytes[32..].copy_from_slice(&s.to_bytes());
+        Ok(Signature::new(&sig_bytes))
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// Signs with a scalar, such as a key share or a DKG constant term, as a plain ed25519 key whose public key is the
+/// scalar times the generator. There's no seed behind it, so the nonce is derived from the scalar itself and the message.
+pub(crate) struct ScalarSigner<'a>(pub(crate) &'a Scalar<Ed25519>);
+
+impl Signer for ScalarSigner<'_> {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(Pubkey::new(&*(Point::generator() * self.0).to_bytes(true)))
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let public_key = self.try_pubkey()?.to_bytes();
+        let nonce = hash_to_scalar(&[b"solana-tss share nonce", &*secret_bytes(self.0), message]);
+        let R = Point::generator() * &nonce;
+        let challenge = hash_to_scalar(&[&*R.to_bytes(true), &public_key, message]);
+        let s = nonce + challenge * self.0;
+        let mut sig_bytes = [0u8; 64];
+        sig_bytes[..32].copy_from_slice(&*R.to_bytes(true));
+        sig_bytes[32..].copy_from_slice(&s.to_bytes());
+        Ok(Signature::new(&sig_bytes))
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::VersionedMessage;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::serialization::{Serialize, SessionId};
+    use crate::threshold::{keygen, sign_and_broadcast, step_one, step_two};
+    use crate::transaction::create_unsigned_transaction;
+    use crate::Error;
+
+    fn clone_serialize<T: Serialize>(t: &T) -> T {
+        let mut v = vec![0u8; t.size_hint()];
+        t.serialize(&mut v);
+        T::deserialize(&v).unwrap()
+    }
+
+    fn sign_with(signing: &[usize], threshold: u16, parties: u16) -> Result<(), Error> {
+        let shares = keygen(threshold, parties).unwrap();
+        let group_key = shares[0].group_key;
+        let to = Keypair::generate(&mut rand07::thread_rng()).pubkey();
+        let memo = Some("test_threshold".to_string());
+        let mut message = create_unsigned_transaction(0.5, None, &to, memo, None, &group_key).message;
+        message.recent_blockhash = Hash::new_unique();
+        let message = VersionedMessage::Legacy(message);
+        let session_id = SessionId::random();
+
+        let (first_msgs, first_secrets): (Vec<_>, Vec<_>) =
+            signing.iter().map(|&i| step_one(&shares[i], session_id)).unzip();
+        let partial_sigs = signing
+            .iter()
+            .zip(first_secrets)
+            .enumerate()
+            .map(|(i, (&party, secret))| {
+                let mut first_msgs: Vec<_> = first_msgs.iter().map(clone_serialize).collect();
+                first_msgs.remove(i);
+                let share = clone_serialize(&shares[party]);
+                // Going through the wire format checks the share signed the partial signature in its own name.
+                step_two(share, message.clone(), first_msgs, secret).map(|sig| clone_serialize(&sig))
+            })
+            .collect::<Result<_, _>>()?;
+
+        sign_and_broadcast(message, group_key, partial_sigs).map(|_| ())
+    }
+
+    #[test]
+    fn test_threshold_subsets() {
+        sign_with(&[0, 1], 2, 3).unwrap();
+        sign_with(&[0, 2], 2, 3).unwrap();
+        sign_with(&[2, 1], 2, 3).unwrap();
+        sign_with(&[0, 1, 2], 2, 3).unwrap();
+        sign_with(&[4, 0, 2], 3, 5).unwrap();
+    }
+
+    #[test]
+    fn test_threshold_not_enough_signers() {
+        assert!(matches!(sign_with(&[0, 1], 3, 3), Err(Error::NotEnoughSigners { threshold: 3, found: 2 })));
+        assert!(matches!(keygen(1, 3), Err(Error::InvalidThreshold { threshold: 1, parties: 3 })));
+        assert!(matches!(keygen(4, 3), Err(Error::InvalidThreshold { threshold: 4, parties: 3 })));
+    }
+}
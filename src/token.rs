@@ -0,0 +1,309 @@
+#[cfg(feature = "client")]
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+#[cfg(feature = "client")]
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_instruction;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+#[cfg(feature = "client")]
+use spl_token::state::Mint;
+#[cfg(feature = "client")]
+use spl_token_2022::extension::memo_transfer::MemoTransfer;
+use spl_token_2022::extension::transfer_fee::instruction::transfer_checked_with_fee;
+#[cfg(feature = "client")]
+use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+#[cfg(feature = "client")]
+use spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use spl_token_2022::instruction::AuthorityType;
+
+use crate::Error;
+
+/// An SPL token to transfer instead of SOL.
+/// All of it is part of the signed instructions, so all parties must use the same values.
+#[derive(Debug, Clone, Copy)]
+pub struct Token {
+    pub mint: Pubkey,
+    pub decimals: u8,
+    /// The program owning the mint, either the original token program or Token-2022
+    pub program_id: Pubkey,
+    /// The fee a Token-2022 mint with the transfer fee extension withholds from the transfer, in the token's smallest units
+    pub transfer_fee: Option<u64>,
+    /// Create the recipient's associated token account if it doesn't exist yet, paid for by the sender
+    pub fund_recipient: bool,
+}
+
+impl Token {
+    /// A token as given on the command line, `transfer_fee` is in units of the token
+    pub fn new(mint: Pubkey, decimals: u8, token_2022: bool, transfer_fee: Option<f64>) -> Self {
+        let program_id = if token_2022 { spl_token_2022::id() } else { spl_token::id() };
+        let transfer_fee = transfer_fee.map(|fee| spl_token::ui_amount_to_amount(fee, decimals));
+        Self { mint, decimals, program_id, transfer_fee, fund_recipient: false }
+    }
+
+    /// Look up the program, decimals and transfer fee of `mint` on chain, for transferring `amount` tokens
+    #[cfg(feature = "client")]
+    pub async fn fetch(rpc_client: &RpcClient, mint: Pubkey, amount: f64) -> Result<Self, Error> {
+        let account = rpc_client.get_account(&mint).await.map_err(Error::MintLookupFailed)?;
+        if account.owner == spl_token::id() {
+            let decimals = Mint::unpack(&account.data).map_err(|_| Error::NotATokenMint(mint))?.decimals;
+            return Ok(Self { mint, decimals, program_id: spl_token::id(), transfer_fee: None, fund_recipient: false });
+        }
+        if account.owner != spl_token_2022::id() {
+            return Err(Error::NotATokenMint(mint));
+        }
+        let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data)
+            .map_err(|_| Error::NotATokenMint(mint))?;
+        let decimals = state.base.decimals;
+        let transfer_fee = match state.get_extension::<TransferFeeConfig>() {
+            Ok(config) => {
+                let epoch = rpc_client.get_epoch_info().await.map_err(Error::MintLookupFailed)?.epoch;
+                let amount = spl_token::ui_amount_to_amount(amount, decimals);
+                Some(config.calculate_epoch_fee(epoch, amount).ok_or(Error::TransferFeeOverflow)?)
+            }
+            Err(_) => None,
+        };
+        Ok(Self { mint, decimals, program_id: spl_token_2022::id(), transfer_fee, fund_recipient: false })
+    }
+
+    /// Fail before signing if the token account of `to` only accepts transfers that come with a memo, and there's none
+    #[cfg(feature = "client")]
+    pub async fn check_recipient(&self, rpc_client: &RpcClient, to: &Pubkey, has_memo: bool) -> Result<(), Error> {
+        if self.program_id != spl_token_2022::id() || has_memo {
+            return Ok(());
+        }
+        // A missing account fails the transfer anyway, it isn't for this check to report.
+        let account = match rpc_client.get_account(&self.associated_account(to)).await {
+            Ok(account) => account,
+            Err(_) => return Ok(()),
+        };
+        let state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data);
+        match state.as_ref().map(|state| state.get_extension::<MemoTransfer>()) {
+            Ok(Ok(memo_transfer)) if bool::from(memo_transfer.require_incoming_transfer_memos) => {
+                Err(Error::MemoRequired(*to))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The associated token account of `wallet` for this token
+    pub fn associated_account(&self, wallet: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(wallet, &self.mint, &self.program_id)
+    }
+
+    /// Create the associated token account of `to` with `payer` paying the rent, if asked to fund the recipient.
+    /// This doesn't fail if the account already exists, so it can be signed for without checking.
+    pub fn create_recipient_instruction(&self, payer: &Pubkey, to: &Pubkey) -> Option<Instruction> {
+        self.fund_recipient.then(|| create_associated_token_account_idempotent(payer, to, &self.mint, &self.program_id))
+    }
+
+    /// Transfer `amount` tokens from the associated token account of `from` to the associated token account of `to`
+    pub fn transfer_instruction(&self, amount: f64, from: &Pubkey, to: &Pubkey) -> Instruction {
+        let source = self.associated_account(from);
+        let destination = self.associated_account(to);
+        let amount = spl_token::ui_amount_to_amount(amount, self.decimals);
+        match self.transfer_fee {
+            Some(fee) => transfer_checked_with_fee(
+                &self.program_id,
+                &source,
+                &self.mint,
+                &destination,
+                from,
+                &[],
+                amount,
+                self.decimals,
+                fee,
+            ),
+            None => spl_token_2022::instruction::transfer_checked(
+                &self.program_id,
+                &source,
+                &self.mint,
+                &destination,
+                from,
+                &[],
+                amount,
+                self.decimals,
+            ),
+        }
+        .expect("the token program ID is always right")
+    }
+
+    /// Mint `amount` tokens to the associated token account of `to`, `authority` must be the mint authority
+    pub fn mint_to_instruction(&self, amount: f64, authority: &Pubkey, to: &Pubkey) -> Instruction {
+        let amount = spl_token::ui_amount_to_amount(amount, self.decimals);
+        spl_token_2022::instruction::mint_to_checked(
+            &self.program_id,
+            &self.mint,
+            &self.associated_account(to),
+            authority,
+            &[],
+            amount,
+            self.decimals,
+        )
+        .expect("the token program ID is always right")
+    }
+
+    /// Hand the mint or freeze authority of the mint from `authority` to `new_authority`, or disable it for good
+    pub fn set_authority_instruction(
+        &self,
+        authority_type: AuthorityType,
+        authority: &Pubkey,
+        new_authority: Option<&Pubkey>,
+    ) -> Instruction {
+        spl_token_2022::instruction::set_authority(
+            &self.program_id,
+            &self.mint,
+            new_authority,
+            authority_type,
+            authority,
+            &[],
+        )
+        .expect("the token program ID is always right")
+    }
+
+    /// Freeze or thaw the token `account`, `authority` must be the freeze authority
+    pub fn freeze_instruction(&self, freeze: bool, account: &Pubkey, authority: &Pubkey) -> Instruction {
+        let instruction = if freeze {
+            spl_token_2022::instruction::freeze_account
+        } else {
+            spl_token_2022::instruction::thaw_account
+        };
+        instruction(&self.program_id, account, &self.mint, authority, &[])
+            .expect("the token program ID is always right")
+    }
+}
+
+/// Wrap `lamports` into the associated wrapped SOL account of `owner`, creating it if it doesn't exist yet
+pub fn wrap_sol_instructions(owner: &Pubkey, lamports: u64) -> Vec<Instruction> {
+    let native = Token::new(spl_token::native_mint::id(), spl_token::native_mint::DECIMALS, false, None);
+    let account = native.associated_account(owner);
+    vec![
+        create_associated_token_account_idempotent(owner, owner, &native.mint, &native.program_id),
+        system_instruction::transfer(owner, &account, lamports),
+        spl_token::instruction::sync_native(&native.program_id, &account)
+            .expect("the token program ID is always right"),
+    ]
+}
+
+/// Unwrap all the wrapped SOL of `owner` by closing its associated wrapped SOL account, which also returns its rent
+pub fn unwrap_sol_instruction(owner: &Pubkey) -> Instruction {
+    let native = Token::new(spl_token::native_mint::id(), spl_token::native_mint::DECIMALS, false, None);
+    spl_token::instruction::close_account(&native.program_id, &native.associated_account(owner), owner, owner, &[])
+        .expect("the token program ID is always right")
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::message::Message;
+    use solana_sdk::pubkey::Pubkey;
+    use spl_associated_token_account::get_associated_token_address;
+    use spl_token::instruction::TokenInstruction;
+    use spl_token_2022::extension::transfer_fee::instruction::TransferFeeInstruction;
+    use spl_token_2022::instruction::TokenInstruction as Token2022Instruction;
+
+    use crate::token::{unwrap_sol_instruction, wrap_sol_instructions, Token};
+    use crate::transaction::create_unsigned_transaction;
+
+    #[test]
+    fn test_transfer_checked() {
+        let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let token = Token::new(Pubkey::new_unique(), 6, false, None);
+        let tx = create_unsigned_transaction(1.5, Some(token), &to, None, None, &from);
+        let ins = &tx.message.instructions[0];
+        assert_eq!(tx.message.account_keys[usize::from(ins.program_id_index)], spl_token::id());
+        let accounts: Vec<_> = ins.accounts.iter().map(|&i| tx.message.account_keys[usize::from(i)]).collect();
+        assert_eq!(
+            accounts,
+            [
+                get_associated_token_address(&from, &token.mint),
+                token.mint,
+                get_associated_token_address(&to, &token.mint),
+                from
+            ]
+        );
+        assert!(matches!(
+            TokenInstruction::unpack(&ins.data),
+            Ok(TokenInstruction::TransferChecked { amount: 1_500_000, decimals: 6 })
+        ));
+    }
+
+    #[test]
+    fn test_token_2022() {
+        let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let token = Token::new(Pubkey::new_unique(), 2, true, Some(0.05));
+        let memo = Some("invoice 42".to_string());
+        let tx = create_unsigned_transaction(10.0, Some(token), &to, memo, None, &from);
+        // The memo comes right before the transfer, for accounts that require memos.
+        let program_ids: Vec<_> = tx
+            .message
+            .instructions
+            .iter()
+            .map(|ins| tx.message.account_keys[usize::from(ins.program_id_index)])
+            .collect();
+        assert_eq!(program_ids, [spl_memo::id(), spl_token_2022::id()]);
+        let ins = &tx.message.instructions[1];
+        assert_eq!(tx.message.account_keys[usize::from(ins.accounts[0])], token.associated_account(&from));
+        assert_eq!(tx.message.account_keys[usize::from(ins.accounts[2])], token.associated_account(&to));
+        assert!(matches!(
+            Token2022Instruction::unpack(&ins.data),
+            Ok(Token2022Instruction::TransferFeeExtension(TransferFeeInstruction::TransferCheckedWithFee {
+                amount: 1000,
+                decimals: 2,
+                fee: 5
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_mint_to() {
+        let (wallet, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let token = Token { fund_recipient: true, ..Token::new(Pubkey::new_unique(), 6, false, None) };
+        let instructions: Vec<_> = token
+            .create_recipient_instruction(&wallet, &to)
+            .into_iter()
+            .chain([token.mint_to_instruction(2.5, &wallet, &to)])
+            .collect();
+        let message = Message::new(&instructions, Some(&wallet));
+        // The mint authority pays for the recipient's account, so it is still the only signer.
+        assert_eq!(message.header.num_required_signatures, 1);
+        let ins = &message.instructions[1];
+        assert_eq!(message.account_keys[usize::from(ins.accounts[1])], get_associated_token_address(&to, &token.mint));
+        assert!(matches!(
+            TokenInstruction::unpack(&ins.data),
+            Ok(TokenInstruction::MintToChecked { amount: 2_500_000, decimals: 6 })
+        ));
+    }
+
+    #[test]
+    fn test_wrap_sol() {
+        let owner = Pubkey::new_unique();
+        let message = Message::new(&wrap_sol_instructions(&owner, 1_000_000), Some(&owner));
+        assert_eq!(message.header.num_required_signatures, 1);
+        // The lamports go to the owner's wrapped SOL account, and are then counted as tokens.
+        let wsol = get_associated_token_address(&owner, &spl_token::native_mint::id());
+        let ins = &message.instructions[2];
+        assert_eq!(message.account_keys[usize::from(ins.accounts[0])], wsol);
+        assert!(matches!(TokenInstruction::unpack(&ins.data), Ok(TokenInstruction::SyncNative)));
+
+        let ins = unwrap_sol_instruction(&owner);
+        assert_eq!((ins.accounts[0].pubkey, ins.accounts[1].pubkey), (wsol, owner));
+        assert!(matches!(TokenInstruction::unpack(&ins.data), Ok(TokenInstruction::CloseAccount)));
+    }
+
+    #[test]
+    fn test_fund_recipient() {
+        let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let token = Token { fund_recipient: true, ..Token::new(Pubkey::new_unique(), 6, false, None) };
+        let tx = create_unsigned_transaction(1.0, Some(token), &to, Some("memo".to_string()), None, &from);
+        let program_ids: Vec<_> = tx
+            .message
+            .instructions
+            .iter()
+            .map(|ins| tx.message.account_keys[usize::from(ins.program_id_index)])
+            .collect();
+        assert_eq!(program_ids, [spl_associated_token_account::id(), spl_memo::id(), spl_token::id()]);
+        // The sender pays for the recipient's account, so it is still the only signer.
+        assert_eq!(tx.message.header.num_required_signatures, 1);
+    }
+}
@@ -0,0 +1,121 @@
+use bincode::Options as _;
+use solana_sdk::address_lookup_table_account::AddressLookupTableAccount;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::{v0, Message, VersionedMessage};
+use solana_sdk::native_token;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::{Transaction, VersionedTransaction};
+
+use crate::token::Token;
+use crate::Error;
+
+/// A base64 bincode transaction, as dApps hand them out for signing, or just its message
+pub fn parse_unsigned_tx(s: &str) -> Result<VersionedMessage, Error> {
+    deserialize_unsigned_tx(&base64::decode(s.trim()).map_err(|_| Error::InvalidUnsignedTransaction)?)
+}
+
+/// A bincode transaction, or just its message
+pub fn deserialize_unsigned_tx(bytes: &[u8]) -> Result<VersionedMessage, Error> {
+    let options = bincode::DefaultOptions::new().with_fixint_encoding().reject_trailing_bytes();
+    let message = match options.deserialize::<VersionedTransaction>(bytes) {
+        Ok(tx) => tx.message,
+        Err(_) => options.deserialize::<VersionedMessage>(bytes).map_err(|_| Error::InvalidUnsignedTransaction)?,
+    };
+    message.sanitize(true).map_err(|_| Error::InvalidUnsignedTransaction)?;
+    Ok(message)
+}
+
+/// The unsigned transaction of a transfer from `payer`, see `transfer_instructions`
+pub fn create_unsigned_transaction(
+    amount: f64,
+    token: Option<Token>,
+    to: &Pubkey,
+    memo: Option<String>,
+    nonce_account: Option<Pubkey>,
+    payer: &Pubkey,
+) -> Transaction {
+    let instructions = transfer_instructions(amount, token, to, memo, nonce_account, payer);
+    Transaction::new_unsigned(Message::new(&instructions, Some(payer)))
+}
+
+/// A legacy message, or a v0 message if there are lookup tables to refer to its accounts through
+pub fn compile_message(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Result<VersionedMessage, Error> {
+    if lookup_tables.is_empty() {
+        return Ok(VersionedMessage::Legacy(Message::new(instructions, Some(payer))));
+    }
+    let message =
+        v0::Message::try_compile(payer, instructions, lookup_tables, Hash::default()).map_err(Error::CompileFailed)?;
+    Ok(VersionedMessage::V0(message))
+}
+
+/// The instructions sending `amount` of SOL, or of `token`, from `payer` to `to`, with a memo if given and advancing
+/// `nonce_account` first if given, for a durable nonce to stand in for the block hash
+pub fn transfer_instructions(
+    amount: f64,
+    token: Option<Token>,
+    to: &Pubkey,
+    memo: Option<String>,
+    nonce_account: Option<Pubkey>,
+    payer: &Pubkey,
+) -> Vec<Instruction> {
+    // The payer is the nonce authority, so it stays the only signer.
+    let advance_nonce_ins =
+        nonce_account.map(|nonce_account| system_instruction::advance_nonce_account(&nonce_account, payer));
+    let memo_ins =
+        memo.map(|memo| Instruction { program_id: spl_memo::id(), accounts: Vec::new(), data: memo.into_bytes() });
+    let transfer_ins: Vec<_> = match token {
+        None => {
+            let transfer_ins = system_instruction::transfer(payer, to, native_token::sol_to_lamports(amount));
+            std::iter::once(transfer_ins).chain(memo_ins).collect()
+        }
+        // Token-2022 accounts can require a memo right before the transfer.
+        Some(token) => token
+            .create_recipient_instruction(payer, to)
+            .into_iter()
+            .chain(memo_ins)
+            .chain(std::iter::once(token.transfer_instruction(amount, payer, to)))
+            .collect(),
+    };
+    // Advancing the nonce must come first.
+    advance_nonce_ins.into_iter().chain(transfer_ins).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::VersionedMessage;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::transaction::VersionedTransaction;
+
+    use crate::transaction::{compile_message, parse_unsigned_tx, transfer_instructions};
+    use crate::Error;
+
+    #[test]
+    fn test_parse_unsigned_tx() {
+        let (payer, nonce_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let instructions = transfer_instructions(
+            1.0,
+            None,
+            &Pubkey::new_unique(),
+            Some("memo".to_string()),
+            Some(nonce_account),
+            &payer,
+        );
+        assert_eq!(instructions.len(), 3);
+        let mut message = compile_message(&instructions, &payer, &[]).unwrap();
+        message.set_recent_blockhash(Hash::new_unique());
+        assert!(matches!(message, VersionedMessage::Legacy(_)));
+
+        // A transaction as dApps hand them out, or only its message.
+        let tx = VersionedTransaction { signatures: vec![Default::default()], message: message.clone() };
+        assert_eq!(parse_unsigned_tx(&base64::encode(bincode::serialize(&tx).unwrap())).unwrap(), message);
+        assert_eq!(parse_unsigned_tx(&base64::encode(message.serialize())).unwrap(), message);
+        assert!(matches!(parse_unsigned_tx("not base64"), Err(Error::InvalidUnsignedTransaction)));
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+
+use crate::seal::parse_sealable;
+use crate::serialization::{AggMessage1, PartialSignature, Serialize, SessionId};
+use crate::Error;
+
+/// How often the default `Transport::recv` lists the messages again
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long the default `Transport::recv` waits for the other parties before giving up
+const WAIT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// The rounds of a signing session the parties exchange messages in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Round {
+    /// `Message 1` of step one
+    FirstMessages,
+    /// The partial signatures of step two
+    PartialSignatures,
+}
+
+impl Round {
+    pub fn path(&self) -> &'static str {
+        match self {
+            Self::FirstMessages => "1",
+            Self::PartialSignatures => "2",
+        }
+    }
+
+    /// The round of `path`, as in `Round::path`
+    pub fn from_path(path: &str) -> Option<Self> {
+        [Self::FirstMessages, Self::PartialSignatures].iter().copied().find(|round| round.path() == path)
+    }
+}
+
+/// A protocol message that can be exchanged over a transport, it says who sent it in which session
+pub trait Relayed: Serialize {
+    fn sender(&self) -> Pubkey;
+    fn session_id(&self) -> SessionId;
+}
+
+impl Relayed for AggMessage1 {
+    fn sender(&self) -> Pubkey {
+        self.sender
+    }
+
+    fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+}
+
+impl Relayed for PartialSignature {
+    fn sender(&self) -> Pubkey {
+        self.sender
+    }
+
+    fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+}
+
+/// A channel the parties of a signing session exchange their messages over. Messages go through it as printed, plain
+/// or sealed, and are checked by whoever receives them, so a transport doesn't have to be trusted: implementing `send`
+/// and `list` is enough to plug in another channel, e.g. a chat bot or a message queue.
+#[async_trait]
+pub trait Transport: Send {
+    /// The session the messages are exchanged for
+    fn session_id(&self) -> SessionId;
+
+    /// Make the message of this party in `round` available to the other parties
+    async fn send(&mut self, round: Round, message: &str) -> Result<(), Error>;
+
+    /// The messages of `round` available so far, from anyone and as they were sent
+    async fn list(&mut self, round: Round) -> Result<Vec<String>, Error>;
+
+    /// Wait until the messages of `round` include one of every one of `senders`, opening the sealed ones with
+    /// `keypair`, and return them as they were sent. By default `list` is polled every few seconds.
+    async fn recv(
+        &mut self,
+        round: Round,
+        senders: &[Pubkey],
+        keypair: Option<&Keypair>,
+    ) -> Result<Vec<String>, Error> {
+        let start = Instant::now();
+        loop {
+            let messages = self.list(round).await?;
+            if missing(&messages, self.session_id(), round, senders, keypair)?.is_empty() {
+                return Ok(messages);
+            }
+            if start.elapsed() > WAIT_TIMEOUT {
+                return Err(Error::TransportTimeout);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Wait for the message of every one of `senders` in `round` on `transport`, see `Transport::recv`
+pub async fn recv<T: Relayed>(
+    transport: &mut dyn Transport,
+    round: Round,
+    senders: &[Pubkey],
+    keypair: Option<&Keypair>,
+) -> Result<Vec<T>, Error> {
+    let messages = transport.recv(round, senders, keypair).await?;
+    let messages = accept(messages, transport.session_id(), senders, keypair)?;
+    // The transport gave up waiting for some of them, like `P2p` does.
+    if messages.len() < senders.len() {
+        return Err(Error::TransportTimeout);
+    }
+    Ok(messages)
+}
+
+/// The messages of `senders` in `session_id` out of the `posted` ones, opening the sealed ones with `keypair`.
+/// Anyone can post to most transports, so messages of anyone else, of another session, sealed to someone else or not
+/// valid are ignored, and only the first message of every sender counts.
+pub fn accept<T: Relayed>(
+    posted: Vec<String>,
+    session_id: SessionId,
+    senders: &[Pubkey],
+    keypair: Option<&Keypair>,
+) -> Result<Vec<T>, Error> {
+    let mut messages: Vec<T> = Vec::new();
+    for posted in posted {
+        let message = match parse_sealable::<T>(&posted).and_then(|message| message.open(keypair)) {
+            Ok(message) => message,
+            // Nothing sealed can be opened then, waiting for more of them wouldn't help.
+            Err(Error::MissingDecryptionKey) => return Err(Error::MissingDecryptionKey),
+            Err(_) => continue,
+        };
+        let sender = message.sender();
+        if message.session_id() == session_id
+            && senders.contains(&sender)
+            && !messages.iter().any(|message| message.sender() == sender)
+        {
+            messages.push(message);
+        }
+    }
+    Ok(messages)
+}
+
+/// Who sent `message` of `round` in which session, opening it with `keypair` if it's sealed
+pub fn origin(message: &str, round: Round, keypair: Option<&Keypair>) -> Result<(Pubkey, SessionId), Error> {
+    fn open<T: Relayed>(message: &str, keypair: Option<&Keypair>) -> Result<(Pubkey, SessionId), Error> {
+        let message = parse_sealable::<T>(message)?.open(keypair)?;
+        Ok((message.sender(), message.session_id()))
+    }
+    match round {
+        Round::FirstMessages => open::<AggMessage1>(message, keypair),
+        Round::PartialSignatures => open::<PartialSignature>(message, keypair),
+    }
+}
+
+/// The ones of `senders` that have no message of `round` in `session_id` among the `posted` ones
+pub fn missing(
+    posted: &[String],
+    session_id: SessionId,
+    round: Round,
+    senders: &[Pubkey],
+    keypair: Option<&Keypair>,
+) -> Result<Vec<Pubkey>, Error> {
+    let mut found = Vec::new();
+    for posted in posted {
+        match origin(posted, round, keypair) {
+            Ok((sender, id)) if id == session_id => found.push(sender),
+            Err(Error::MissingDecryptionKey) => return Err(Error::MissingDecryptionKey),
+            _ => {}
+        }
+    }
+    Ok(senders.iter().filter(|sender| !found.contains(sender)).copied().collect())
+}
+
+/// The messages are passed around by hand: this party's are printed, and the missing ones of the others are asked for
+/// to be pasted
+pub struct Manual {
+    session_id: SessionId,
+    messages: HashMap<Round, Vec<String>>,
+}
+
+impl Manual {
+    pub fn new(session_id: SessionId) -> Self {
+        Self { session_id, messages: HashMap::new() }
+    }
+
+    /// Start with the `messages` of `round` already at hand, e.g. those that arrived over another transport
+    pub fn with(mut self, round: Round, messages: Vec<String>) -> Self {
+        self.messages.entry(round).or_default().extend(messages);
+        self
+    }
+}
+
+#[async_trait]
+impl Transport for Manual {
+    fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+
+    async fn send(&mut self, _round: Round, _message: &str) -> Result<(), Error> {
+        // It's printed already, for the party to pass on.
+        Ok(())
+    }
+
+    async fn list(&mut self, round: Round) -> Result<Vec<String>, Error> {
+        Ok(self.messages.get(&round).cloned().unwrap_or_default())
+    }
+
+    async fn recv(
+        &mut self,
+        round: Round,
+        senders: &[Pubkey],
+        keypair: Option<&Keypair>,
+    ) -> Result<Vec<String>, Error> {
+        let messages = self.messages.entry(round).or_default();
+        for sender in missing(messages, self.session_id, round, senders, keypair)? {
+            let line = read_line(&format!("The message of {} didn't arrive, paste it: ", sender))?;
+            let (found, _) = origin(&line, round, keypair)?;
+            if found != sender {
+                return Err(Error::UnexpectedSender { expected: sender, found });
+            }
+            messages.push(line);
+        }
+        Ok(messages.clone())
+    }
+}
+
+/// Prompt for a line of the standard input on the standard error, without the line break
+fn read_line(prompt: &str) -> Result<String, Error> {
+    eprint!("{}", prompt);
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line).map_err(Error::StdinFailed)?;
+    Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::serialization::{AggMessage1, Encoding, Serialize, SessionId};
+    use crate::transport::{missing, recv, Manual, Round, Transport};
+    use crate::{tss, Error};
+
+    /// A transport as an integrator would plug in, only implementing `send` and `list`
+    struct Queue {
+        session_id: SessionId,
+        messages: Vec<(Round, String)>,
+    }
+
+    #[async_trait]
+    impl Transport for Queue {
+        fn session_id(&self) -> SessionId {
+            self.session_id
+        }
+
+        async fn send(&mut self, round: Round, message: &str) -> Result<(), Error> {
+            self.messages.push((round, message.to_string()));
+            Ok(())
+        }
+
+        async fn list(&mut self, round: Round) -> Result<Vec<String>, Error> {
+            Ok(self.messages.iter().filter(|(r, _)| *r == round).map(|(_, message)| message.clone()).collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transport() {
+        let session_id = SessionId::random();
+        let parties: Vec<_> = (0..2).map(|_| Keypair::new()).collect();
+        let senders: Vec<_> = parties.iter().map(|party| party.pubkey()).collect();
+        let mut queue = Queue { session_id, messages: Vec::new() };
+        let (other_session, _) = tss::step_one(&parties[0], SessionId::random());
+        queue.send(Round::FirstMessages, &other_session.serialize_encoded(Encoding::Bs58)).await.unwrap();
+        queue.send(Round::FirstMessages, "not a message").await.unwrap();
+        for party in &parties {
+            let (first_msg, _) = tss::step_one(party, session_id);
+            queue.send(Round::FirstMessages, &first_msg.serialize_encoded(Encoding::Hex)).await.unwrap();
+        }
+
+        let messages: Vec<AggMessage1> = recv(&mut queue, Round::FirstMessages, &senders, None).await.unwrap();
+        assert_eq!(messages.iter().map(|msg| msg.sender).collect::<Vec<_>>(), senders);
+        let posted = queue.list(Round::FirstMessages).await.unwrap();
+        assert!(missing(&posted, session_id, Round::FirstMessages, &senders, None).unwrap().is_empty());
+        let posted = queue.list(Round::PartialSignatures).await.unwrap();
+        assert_eq!(missing(&posted, session_id, Round::PartialSignatures, &senders, None).unwrap(), senders);
+
+        // Nothing is asked for once every message is at hand.
+        let mut manual =
+            Manual::new(session_id).with(Round::FirstMessages, queue.list(Round::FirstMessages).await.unwrap());
+        let messages: Vec<AggMessage1> = recv(&mut manual, Round::FirstMessages, &senders, None).await.unwrap();
+        assert_eq!(messages.len(), 2);
+    }
+}
@@ -3,106 +3,304 @@
 use curv::elliptic::curves::{Ed25519, Point, Scalar};
 use multi_party_eddsa::protocols::musig2::{self, PrivatePartialNonces, PublicPartialNonces};
 use multi_party_eddsa::protocols::ExpandedKeyPair;
+use solana_sdk::hash::{hash, Hash};
 use solana_sdk::signature::{Keypair, Signature, Signer, SignerError};
-use solana_sdk::{hash::Hash, pubkey::Pubkey, transaction::Transaction};
+use solana_sdk::{message::VersionedMessage, pubkey::Pubkey, transaction::VersionedTransaction};
+use tracing::{debug, instrument, warn};
 
-use crate::serialization::{AggMessage1, Error as DeserializationError, PartialSignature, SecretAggStepOne};
-use crate::{create_unsigned_transaction, Error};
+use crate::serialization::{
+    point_from_bytes, scalar_from_bytes, sender_signed_bytes, AggMessage1, KeyPop, PartialSignature, SecretAggStepOne,
+    SessionId,
+};
+use crate::{threshold, Error};
 
 /// Create the aggregate public key, pass key=None if you don't care about the coefficient
 pub fn key_agg(keys: Vec<Pubkey>, key: Option<Pubkey>) -> Result<musig2::PublicKeyAgg, Error> {
+    if keys.is_empty() {
+        return Err(Error::NoKeys);
+    }
     let convert_keys = |k: Pubkey| {
-        Point::from_bytes(&k.to_bytes()).map_err(|e| Error::DeserializationFailed {
-            error: DeserializationError::InvalidPoint(e),
-            field_name: "keys",
-        })
+        point_from_bytes(&k.to_bytes()).map_err(|error| Error::DeserializationFailed { error, field_name: "keys" })
     };
     let keys: Vec<_> = keys.into_iter().map(convert_keys).collect::<Result<_, _>>()?;
     let key = key.map(convert_keys).unwrap_or_else(|| Ok(keys[0].clone()))?;
     musig2::PublicKeyAgg::key_aggregation_n(keys, &key).ok_or(Error::KeyPairIsNotInKeys)
 }
 
+/// The address of the aggregated key of `keys`
+pub fn aggregate_pubkey(keys: Vec<Pubkey>) -> Result<Pubkey, Error> {
+    Ok(Pubkey::new(&*key_agg(keys, None)?.agg_public_key.to_bytes(true)))
+}
+
+/// The SHA-256 of the serialized message, which holds the amount, the recipient, the memo, the block hash and,
+/// as the aggregated key is a signer, the set of keys
+pub fn transaction_digest(message: &VersionedMessage) -> Hash {
+    hash(&message.serialize())
+}
+
+/// The aggregated key must be one of the signers of the message, the position of its signature
+pub(crate) fn check_signer(message: &VersionedMessage, aggpubkey: Pubkey) -> Result<usize, Error> {
+    let mut signers = message.static_account_keys().iter().take(usize::from(message.header().num_required_signatures));
+    signers.position(|signer| *signer == aggpubkey).ok_or(Error::WrongTransactionSigner(aggpubkey))
+}
+
+const KEY_POP_DOMAIN: &[u8] = b"solana-tss key proof of possession";
+
+/// Prove possession of the secret key of `keypair`, so it can't be a rogue key derived from the other parties' keys
+pub fn key_pop(keypair: &Keypair) -> KeyPop {
+    let key = keypair.pubkey();
+    let signature = keypair.sign_message(&[KEY_POP_DOMAIN, key.as_ref()].concat());
+    KeyPop { key, signature }
+}
+
+/// Make sure every key in `keys` comes with a valid proof of possession
+pub fn verify_key_pops(keys: &[Pubkey], pops: &[KeyPop]) -> Result<(), Error> {
+    for key in keys {
+        let pop = pops.iter().find(|pop| pop.key == *key).ok_or(Error::MissingProofOfPossession(*key))?;
+        if !pop.signature.verify(key.as_ref(), &[KEY_POP_DOMAIN, key.as_ref()].concat()) {
+            return Err(Error::InvalidProofOfPossession(*key));
+        }
+    }
+    Ok(())
+}
+
 /// Generate Message1 which contains nonce, public nonce, and commitment to nonces
-pub fn step_one(keypair: Keypair) -> (AggMessage1, SecretAggStepOne) {
+/// All the parties of a signing session need to use the same `session_id`.
+#[instrument(name = "round_1", skip_all, fields(session_id = %session_id, party = %keypair.pubkey()))]
+pub fn step_one(keypair: &Keypair, session_id: SessionId) -> (AggMessage1, SecretAggStepOne) {
     let extended_kepair = ExpandedKeyPair::create_from_private_key(keypair.secret().to_bytes());
     // we don't really need to pass a message here.
     let (private_nonces, public_nonces) = musig2::generate_partial_nonces(&extended_kepair, None);
+    let secret_state = SecretAggStepOne { session_id, private_nonces, public_nonces };
+    debug!("Made the nonces of the session");
+    (first_message(keypair, &secret_state), secret_state)
+}
 
-    (
-        AggMessage1 { sender: keypair.pubkey(), public_nonces: public_nonces.clone() },
-        SecretAggStepOne { private_nonces, public_nonces },
-    )
+/// The first message of step one again, out of its secret state
+pub fn first_message(keypair: &Keypair, secret_state: &SecretAggStepOne) -> AggMessage1 {
+    let mut first_msg = AggMessage1 {
+        session_id: secret_state.session_id,
+        sender: keypair.pubkey(),
+        public_nonces: secret_state.public_nonces.clone(),
+        sender_signature: Signature::default(),
+    };
+    first_msg.sender_signature = keypair.sign_message(&sender_signed_bytes(&first_msg));
+    first_msg
 }
 
-#[allow(clippy::too_many_arguments)]
+/// Pass the keys of the inner aggregated key in `inner_keys` if `keypair` signs as a part of an aggregated key that is itself one of `keys`,
+/// every party of the inner key then takes part in the outer signing session directly.
+/// All the parties must sign exactly the same `message`, including its recent block hash.
+#[instrument(name = "round_2", skip_all, fields(session_id = %secret_state.session_id, party = %keypair.pubkey()))]
 pub fn step_two(
     keypair: Keypair,
-    amount: f64,
-    to: Pubkey,
-    memo: Option<String>,
-    recent_block_hash: Hash,
+    message: VersionedMessage,
     keys: Vec<Pubkey>,
+    inner_keys: Vec<Pubkey>,
     first_messages: Vec<AggMessage1>,
     secret_state: SecretAggStepOne,
 ) -> Result<PartialSignature, Error> {
+    // Nonces from another session (or replayed from an old one) must never be mixed into this one.
+    if first_messages.iter().any(|msg1| msg1.session_id != secret_state.session_id) {
+        return Err(Error::SessionMismatch);
+    }
+    debug!(senders = ?first_messages.iter().map(|msg1| msg1.sender.to_string()).collect::<Vec<_>>(), "First messages");
     let other_nonces: Vec<_> = first_messages.into_iter().map(|msg1| msg1.public_nonces.R).collect();
 
     // Generate the aggregate key together with the coefficient of the current keypair
-    let aggkey = key_agg(keys, Some(keypair.pubkey()))?;
+    let aggkey = if inner_keys.is_empty() {
+        key_agg(keys, Some(keypair.pubkey()))?
+    } else {
+        // The inner key's share of the signature is split between its parties, by their coefficients in the inner key.
+        let inner_aggkey = key_agg(inner_keys, Some(keypair.pubkey()))?;
+        let outer_aggkey = key_agg(keys, Some(Pubkey::new(&*inner_aggkey.agg_public_key.to_bytes(true))))?;
+        musig2::PublicKeyAgg {
+            agg_public_key: outer_aggkey.agg_public_key,
+            musig_coefficient: outer_aggkey.musig_coefficient * inner_aggkey.musig_coefficient,
+        }
+    };
     let aggpubkey = Pubkey::new(&*aggkey.agg_public_key.to_bytes(true));
     let extended_kepair = ExpandedKeyPair::create_from_private_key(keypair.secret().to_bytes());
 
-    // Create the unsigned transaction
-    let mut tx = create_unsigned_transaction(amount, &to, memo, &aggpubkey);
+    check_signer(&message, aggpubkey)?;
 
-    let signer = PartialSigner {
-        signer_private_nonce: secret_state.private_nonces,
-        signer_public_nonce: secret_state.public_nonces,
-        other_nonces,
-        extended_kepair,
-        aggregated_pubkey: aggkey,
-    };
+    let mut nonces = vec![secret_state.public_nonces.clone()];
+    nonces.extend(other_nonces.iter().map(|R| PublicPartialNonces { R: R.clone() }));
+    let binding = NonceBinding::new(&nonces, &aggkey.agg_public_key, &message.serialize());
+    let public_key = point_from_bytes(&keypair.pubkey().to_bytes())
+        .map_err(|error| Error::DeserializationFailed { error, field_name: "keypair" })?;
+    let coefficient = aggkey.musig_coefficient.clone();
+
+    let session_id = secret_state.session_id;
+    let signer = PartialSigner { secret_state, other_nonces, extended_kepair, aggregated_pubkey: aggkey };
     // Sign the transaction using a custom `PartialSigner`, this is required to comply with Solana's API.
-    tx.sign(&[&signer], recent_block_hash);
-    let sig = tx.signatures[0];
-    Ok(PartialSignature(sig))
+    let signature = signer.sign_message(&message.serialize());
+    // A bad partial signature would only be found once all the others are in, and fail everyone's signing.
+    if !binding.verify(&signature, &nonces[0], &public_key, &coefficient) {
+        return Err(Error::InvalidOwnPartialSignature);
+    }
+    let block_hash = *message.recent_blockhash();
+    let digest = transaction_digest(&message);
+    let mut sig = PartialSignature {
+        session_id,
+        sender: keypair.pubkey(),
+        block_hash,
+        digest,
+        signature,
+        sender_signature: Signature::default(),
+    };
+    sig.sender_signature = keypair.sign_message(&sender_signed_bytes(&sig));
+    debug!(block_hash = %block_hash, digest = %digest, "Signed the transaction");
+    Ok(sig)
 }
 
+#[instrument(name = "aggregate", skip_all, fields(aggpubkey = tracing::field::Empty))]
 pub fn sign_and_broadcast(
-    amount: f64,
-    to: Pubkey,
-    memo: Option<String>,
-    recent_block_hash: Hash,
+    message: VersionedMessage,
     keys: Vec<Pubkey>,
+    first_messages: Vec<AggMessage1>,
     signatures: Vec<PartialSignature>,
-) -> Result<Transaction, Error> {
-    let aggkey = key_agg(keys, None)?;
-    let aggpubkey = Pubkey::new(&*aggkey.agg_public_key.to_bytes(true));
+) -> Result<VersionedTransaction, Error> {
+    let aggpubkey = aggregate_pubkey(keys.clone())?;
+    tracing::Span::current().record("aggpubkey", tracing::field::display(aggpubkey));
+    debug!(senders = ?signatures.iter().map(|sig| sig.sender.to_string()).collect::<Vec<_>>(), "Partial signatures");
+    let message_data = message.serialize();
+    match combine_partial_signatures(aggpubkey, message, &signatures) {
+        // If we have the parties' nonces, find out which one of them caused the failure.
+        Err(e @ (Error::MismatchMessages | Error::InvalidSignature)) if !first_messages.is_empty() => {
+            warn!("The combined signature is invalid, checking the partial signatures one by one");
+            verify_partial_signatures(&keys, &first_messages, &signatures, &message_data)?;
+            Err(e)
+        }
+        result => result,
+    }
+}
+
+/// Check every partial signature against the key and nonces of its sender, fails with the first invalid one.
+/// The parties of a nested key sign in its place with coefficients of inner keys that aren't known here, so when none
+/// of the other parties is at fault it fails without blaming anyone.
+fn verify_partial_signatures(
+    keys: &[Pubkey],
+    first_messages: &[AggMessage1],
+    signatures: &[PartialSignature],
+    message: &[u8],
+) -> Result<(), Error> {
+    // The parties are the senders rather than `keys`, which only has the nested keys themselves.
+    let nonces: Vec<_> = signatures
+        .iter()
+        .map(|sig| {
+            let msg1 = first_messages.iter().find(|msg1| msg1.sender == sig.sender);
+            msg1.map(|msg1| msg1.public_nonces.clone()).ok_or(Error::MissingFirstMessage(sig.sender))
+        })
+        .collect::<Result<_, _>>()?;
+    let aggkey = key_agg(keys.to_vec(), None)?;
+    let binding = NonceBinding::new(&nonces, &aggkey.agg_public_key, message);
+
+    let mut nested = false;
+    for (sig, party_nonces) in signatures.iter().zip(&nonces) {
+        if !keys.contains(&sig.sender) {
+            nested = true;
+            continue;
+        }
+        let public_key =
+            point_from_bytes(&sig.sender.to_bytes()).map_err(|_| Error::InvalidPartialSignature(sig.sender))?;
+        let coefficient = key_agg(keys.to_vec(), Some(sig.sender))?.musig_coefficient;
+        if !binding.verify(&sig.signature, party_nonces, &public_key, &coefficient) {
+            return Err(Error::InvalidPartialSignature(sig.sender));
+        }
+    }
+    if nested {
+        return Err(Error::UnattributablePartialSignature);
+    }
+    Ok(())
+}
+
+/// The group nonce, nonce coefficient and challenge of a signing session, all a partial signature is checked with
+struct NonceBinding {
+    R: Point<Ed25519>,
+    b: Scalar<Ed25519>,
+    challenge: Scalar<Ed25519>,
+}
+
+impl NonceBinding {
+    fn new(nonces: &[PublicPartialNonces], agg_public_key: &Point<Ed25519>, message: &[u8]) -> Self {
+        // Take the nonce coefficient `b` from musig2 itself instead of duplicating its hashing,
+        // signing with the private nonces (0, 1) and a zero key coefficient gives exactly `b`.
+        let other_nonces: Vec<_> = nonces[1..].iter().map(|n| n.R.clone()).collect();
+        let probe = musig2::partial_sign(
+            &other_nonces,
+            PrivatePartialNonces { r: [Scalar::zero(), Scalar::from(1u16)] },
+            nonces[0].clone(),
+            &musig2::PublicKeyAgg { agg_public_key: agg_public_key.clone(), musig_coefficient: Scalar::zero() },
+            &ExpandedKeyPair::create(),
+            message,
+        );
+        let (R, b) = (probe.R, probe.my_partial_s);
+        let challenge = threshold::hash_to_scalar(&[&*R.to_bytes(true), &*agg_public_key.to_bytes(true), message]);
+        Self { R, b, challenge }
+    }
+
+    /// Whether `signature` is the partial signature of the party with `nonces`, `public_key` and key `coefficient`
+    fn verify(
+        &self,
+        signature: &Signature,
+        nonces: &PublicPartialNonces,
+        public_key: &Point<Ed25519>,
+        coefficient: &Scalar<Ed25519>,
+    ) -> bool {
+        // s_i*G == R_i1 + b*R_i2 + c*a_i*X_i
+        let expected = &nonces.R[0] + &nonces.R[1] * &self.b + public_key * coefficient * &self.challenge;
+        signature.as_ref()[..32] == *self.R.to_bytes(true)
+            && scalar_from_bytes(&signature.as_ref()[32..]).map_or(false, |s| Point::generator() * s == expected)
+    }
+}
 
+/// Add up partial signatures that share the same `R` and attach the result to the `message` signed by `aggpubkey`
+pub fn combine_partial_signatures(
+    aggpubkey: Pubkey,
+    message: VersionedMessage,
+    signatures: &[PartialSignature],
+) -> Result<VersionedTransaction, Error> {
+    let position = check_signer(&message, aggpubkey)?;
+    if signatures.is_empty() {
+        return Err(Error::NoPartialSignatures);
+    }
+    if signatures[1..].iter().any(|s| s.session_id != signatures[0].session_id) {
+        return Err(Error::SessionMismatch);
+    }
+    // Signatures over different block hashes add up to garbage, tell who signed which one instead.
+    if let Some(sig) = signatures.iter().find(|s| s.block_hash != *message.recent_blockhash()) {
+        return Err(Error::BlockHashMismatch {
+            party: sig.sender,
+            signed: sig.block_hash,
+            expected: *message.recent_blockhash(),
+        });
+    }
+    // Same for any other difference in the transaction, like an amount typed wrong by one of the parties.
+    let digest = transaction_digest(&message);
+    if let Some(sig) = signatures.iter().find(|s| s.digest != digest) {
+        return Err(Error::TransactionDigestMismatch { party: sig.sender, signed: sig.digest, expected: digest });
+    }
     // Make sure all the `R`s are the same
-    if !signatures[1..].iter().map(|s| &s.0.as_ref()[..32]).all(|s| s == &signatures[0].0.as_ref()[..32]) {
+    if !signatures[1..]
+        .iter()
+        .map(|s| &s.signature.as_ref()[..32])
+        .all(|s| s == &signatures[0].signature.as_ref()[..32])
+    {
         return Err(Error::MismatchMessages);
     }
-    let deserialize_R = |s| {
-        Point::from_bytes(s).map_err(|e| Error::DeserializationFailed {
-            error: DeserializationError::InvalidPoint(e),
-            field_name: "signatures",
-        })
-    };
-    let deserialize_s = |s| {
-        Scalar::from_bytes(s).map_err(|e| Error::DeserializationFailed {
-            error: DeserializationError::InvalidScalar(e),
-            field_name: "signatures",
-        })
-    };
+    let deserialize_R =
+        |s| point_from_bytes(s).map_err(|error| Error::DeserializationFailed { error, field_name: "signatures" });
+    let deserialize_s =
+        |s| scalar_from_bytes(s).map_err(|error| Error::DeserializationFailed { error, field_name: "signatures" });
 
     let first_sig = musig2::PartialSignature {
-        R: deserialize_R(&signatures[0].0.as_ref()[..32])?,
-        my_partial_s: deserialize_s(&signatures[0].0.as_ref()[32..])?,
+        R: deserialize_R(&signatures[0].signature.as_ref()[..32])?,
+        my_partial_s: deserialize_s(&signatures[0].signature.as_ref()[32..])?,
     };
 
     let partial_sigs: Vec<_> =
-        signatures[1..].iter().map(|s| deserialize_s(&s.0.as_ref()[32..])).collect::<Result<_, _>>()?;
+        signatures[1..].iter().map(|s| deserialize_s(&s.signature.as_ref()[32..])).collect::<Result<_, _>>()?;
 
     // Add the signatures up
     let full_sig = musig2::aggregate_partial_signatures(&first_sig, &partial_sigs);
@@ -112,23 +310,18 @@ pub fn sign_and_broadcast(
     sig_bytes[32..].copy_from_slice(&full_sig.s.to_bytes());
     let sig = Signature::new(&sig_bytes);
 
-    // Create the same transaction again
-    let mut tx = create_unsigned_transaction(amount, &to, memo, &aggpubkey);
-    // Insert the recent_block_hash and the signature to the right places
-    tx.message.recent_blockhash = recent_block_hash;
-    assert_eq!(tx.signatures.len(), 1);
-    tx.signatures[0] = sig;
-
-    // Make sure the resulting transaction is actually valid.
-    if tx.verify().is_err() {
+    // Make sure the resulting signature is actually valid.
+    if !sig.verify(aggpubkey.as_ref(), &message.serialize()) {
         return Err(Error::InvalidSignature);
     }
-    Ok(tx)
+    // Attach the signature to the message that was signed, other signers add theirs with `--signer`.
+    let mut tx_signatures = vec![Signature::default(); usize::from(message.header().num_required_signatures)];
+    tx_signatures[position] = sig;
+    Ok(VersionedTransaction { signatures: tx_signatures, message })
 }
 
 struct PartialSigner {
-    signer_private_nonce: PrivatePartialNonces,
-    signer_public_nonce: PublicPartialNonces,
+    secret_state: SecretAggStepOne,
     other_nonces: Vec<[Point<Ed25519>; 2]>,
     extended_kepair: ExpandedKeyPair,
     aggregated_pubkey: musig2::PublicKeyAgg,
@@ -142,8 +335,8 @@ impl Signer for PartialSigner {
     fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
         let sig = musig2::partial_sign(
             &self.other_nonces,
-            self.signer_private_nonce.clone(),
-            self.signer_public_nonce.clone(),
+            self.secret_state.private_nonces.clone(),
+            self.secret_state.public_nonces.clone(),
             &self.aggregated_pubkey,
             &self.extended_kepair,
             message,
@@ -161,11 +354,18 @@ impl Signer for PartialSigner {
 
 #[cfg(test)]
 mod tests {
-    use crate::native_token::lamports_to_sol;
-    use crate::serialization::Serialize;
-    use crate::tss::{key_agg, sign_and_broadcast, step_one, step_two};
+    use crate::serialization::{sender_signed_bytes, Serialize, SessionId};
+    use crate::transaction::create_unsigned_transaction;
+    use crate::tss::{
+        aggregate_pubkey, key_agg, key_pop, sign_and_broadcast, step_one, step_two, transaction_digest, verify_key_pops,
+    };
+    use crate::Error;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::{Message, VersionedMessage};
+    use solana_sdk::native_token::lamports_to_sol;
     use solana_sdk::pubkey::Pubkey;
-    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::signature::{Keypair, Signature, Signer};
+    use solana_sdk::system_instruction;
     use solana_streamer::socket::SocketAddrSpace;
     use solana_test_validator::TestValidator;
 
@@ -173,10 +373,22 @@ mod tests {
         Keypair::from_bytes(&k.to_bytes()).unwrap()
     }
     fn clone_serialize<T: Serialize>(t: &T) -> T {
-        let mut v = Vec::new();
+        let mut v = vec![0u8; t.size_hint()];
         t.serialize(&mut v);
         T::deserialize(&v).unwrap()
     }
+    fn transfer_message(
+        amount: f64,
+        to: Pubkey,
+        memo: Option<String>,
+        keys: &[Pubkey],
+        recent_block_hash: Hash,
+    ) -> VersionedMessage {
+        let aggpubkey = aggregate_pubkey(keys.to_vec()).unwrap();
+        let mut message = create_unsigned_transaction(amount, None, &to, memo, None, &aggpubkey).message;
+        message.recent_blockhash = recent_block_hash;
+        VersionedMessage::Legacy(message)
+    }
     #[test]
     fn test_roundtrip() {
         let n = 5;
@@ -193,12 +405,14 @@ mod tests {
 
         // step 1
         let to = Keypair::generate(&mut rng);
-        let (first_msgs, first_secrets): (Vec<_>, Vec<_>) = keys.iter().map(clone_keypair).map(step_one).unzip();
+        let session_id = SessionId::random();
+        let (first_msgs, first_secrets): (Vec<_>, Vec<_>) = keys.iter().map(|k| step_one(k, session_id)).unzip();
 
         let recent_block_hash = rpc_client.get_latest_blockhash().unwrap();
         // step 2
         let amount = lamports_to_sol(full_amount / 2);
         let memo = Some("test_roundtrip".to_string());
+        let message = transfer_message(amount, to.pubkey(), memo, &pubkeys, recent_block_hash);
 
         let partial_sigs: Vec<_> = keys
             .iter()
@@ -208,15 +422,268 @@ mod tests {
             .map(|(i, (key, secret))| {
                 let mut first_msgs: Vec<_> = first_msgs.iter().map(clone_serialize).collect();
                 first_msgs.remove(i);
-                step_two(key, amount, to.pubkey(), memo.clone(), recent_block_hash, pubkeys.clone(), first_msgs, secret)
-                    .unwrap()
+                step_two(key, message.clone(), pubkeys.clone(), vec![], first_msgs, secret).unwrap()
             })
             .collect();
 
-        let full_tx = sign_and_broadcast(amount, to.pubkey(), memo, recent_block_hash, pubkeys, partial_sigs).unwrap();
+        let full_tx = sign_and_broadcast(message, pubkeys, first_msgs, partial_sigs).unwrap();
         let sig = rpc_client.send_transaction(&full_tx).unwrap();
 
         // Wait for confirmation
         rpc_client.confirm_transaction_with_spinner(&sig, &recent_block_hash, rpc_client.commitment()).unwrap();
     }
+
+    #[test]
+    fn test_identify_invalid_partial_signature() {
+        let mut rng = rand07::thread_rng();
+        let keys: Vec<_> = (0..3).map(|_| Keypair::generate(&mut rng)).collect();
+        let pubkeys: Vec<_> = keys.iter().map(|k| k.pubkey()).collect();
+        let to = Keypair::generate(&mut rng).pubkey();
+        let message = transfer_message(0.1, to, None, &pubkeys, Hash::new_unique());
+        let session_id = SessionId::random();
+        let (first_msgs, first_secrets): (Vec<_>, Vec<_>) = keys.iter().map(|k| step_one(k, session_id)).unzip();
+
+        let mut partial_sigs: Vec<_> = keys
+            .iter()
+            .map(clone_keypair)
+            .zip(first_secrets)
+            .enumerate()
+            .map(|(i, (key, secret))| {
+                let mut first_msgs: Vec<_> = first_msgs.iter().map(clone_serialize).collect();
+                first_msgs.remove(i);
+                step_two(key, message.clone(), pubkeys.clone(), vec![], first_msgs, secret).unwrap()
+            })
+            .collect();
+        let mut sig_bytes: [u8; 64] = partial_sigs[1].signature.into();
+        sig_bytes[32] ^= 1;
+        partial_sigs[1].signature = Signature::new(&sig_bytes);
+        // The party sends its bad partial signature in its own name.
+        partial_sigs[1].sender_signature = keys[1].sign_message(&sender_signed_bytes(&partial_sigs[1]));
+
+        // Without the first messages there's no way of telling who's at fault.
+        let sigs: Vec<_> = partial_sigs.iter().map(clone_serialize).collect();
+        let res = sign_and_broadcast(message.clone(), pubkeys.clone(), vec![], sigs);
+        assert!(matches!(res, Err(Error::InvalidSignature)));
+        let res = sign_and_broadcast(message, pubkeys.clone(), first_msgs, partial_sigs);
+        assert!(matches!(res, Err(Error::InvalidPartialSignature(k)) if k == pubkeys[1]));
+    }
+
+    #[test]
+    fn test_block_hash_mismatch() {
+        let mut rng = rand07::thread_rng();
+        let keys: Vec<_> = (0..3).map(|_| Keypair::generate(&mut rng)).collect();
+        let pubkeys: Vec<_> = keys.iter().map(|k| k.pubkey()).collect();
+        let to = Keypair::generate(&mut rng).pubkey();
+        let message = transfer_message(0.1, to, None, &pubkeys, Hash::new_unique());
+        // The last party fetched a different block hash than the others.
+        let stale_hash = Hash::new_unique();
+        let stale_message = transfer_message(0.1, to, None, &pubkeys, stale_hash);
+        let session_id = SessionId::random();
+        let (first_msgs, first_secrets): (Vec<_>, Vec<_>) = keys.iter().map(|k| step_one(k, session_id)).unzip();
+
+        let partial_sigs: Vec<_> = keys
+            .iter()
+            .map(clone_keypair)
+            .zip(first_secrets)
+            .enumerate()
+            .map(|(i, (key, secret))| {
+                let mut first_msgs: Vec<_> = first_msgs.iter().map(clone_serialize).collect();
+                first_msgs.remove(i);
+                let message = if i == 2 { stale_message.clone() } else { message.clone() };
+                step_two(key, message, pubkeys.clone(), vec![], first_msgs, secret).unwrap()
+            })
+            .collect();
+        let expected = *message.recent_blockhash();
+        let res = sign_and_broadcast(message, pubkeys.clone(), vec![], partial_sigs);
+        assert!(matches!(res, Err(Error::BlockHashMismatch { party, signed, expected: found })
+            if party == pubkeys[2] && signed == stale_hash && found == expected));
+    }
+
+    #[test]
+    fn test_transaction_digest_mismatch() {
+        let mut rng = rand07::thread_rng();
+        let keys: Vec<_> = (0..3).map(|_| Keypair::generate(&mut rng)).collect();
+        let pubkeys: Vec<_> = keys.iter().map(|k| k.pubkey()).collect();
+        let to = Keypair::generate(&mut rng).pubkey();
+        let block_hash = Hash::new_unique();
+        let message = transfer_message(10.0, to, None, &pubkeys, block_hash);
+        // The second party typed 1.0 instead of 10.0, with the same block hash.
+        let typo_message = transfer_message(1.0, to, None, &pubkeys, block_hash);
+        let session_id = SessionId::random();
+        let (first_msgs, first_secrets): (Vec<_>, Vec<_>) = keys.iter().map(|k| step_one(k, session_id)).unzip();
+
+        let partial_sigs: Vec<_> = keys
+            .iter()
+            .map(clone_keypair)
+            .zip(first_secrets)
+            .enumerate()
+            .map(|(i, (key, secret))| {
+                let mut first_msgs: Vec<_> = first_msgs.iter().map(clone_serialize).collect();
+                first_msgs.remove(i);
+                let message = if i == 1 { typo_message.clone() } else { message.clone() };
+                step_two(key, message, pubkeys.clone(), vec![], first_msgs, secret).unwrap()
+            })
+            .collect();
+        let expected = transaction_digest(&message);
+        let res = sign_and_broadcast(message, pubkeys.clone(), vec![], partial_sigs);
+        assert!(matches!(res, Err(Error::TransactionDigestMismatch { party, signed, expected: found })
+            if party == pubkeys[1] && signed == transaction_digest(&typo_message) && found == expected));
+    }
+
+    #[test]
+    fn test_nested_aggregation() {
+        let mut rng = rand07::thread_rng();
+        let keys: Vec<_> = (0..3).map(|_| Keypair::generate(&mut rng)).collect();
+        let pubkeys: Vec<_> = keys.iter().map(|k| k.pubkey()).collect();
+        // The first two keys make up an inner key, which signs together with the third one.
+        let inner_keys = pubkeys[..2].to_vec();
+        let inner_key = Pubkey::new(&*key_agg(inner_keys.clone(), None).unwrap().agg_public_key.to_bytes(true));
+        let outer_keys = vec![inner_key, pubkeys[2]];
+        let to = Keypair::generate(&mut rng).pubkey();
+        let message = transfer_message(0.1, to, None, &outer_keys, Hash::new_unique());
+        let session_id = SessionId::random();
+        let (first_msgs, first_secrets): (Vec<_>, Vec<_>) = keys.iter().map(|k| step_one(k, session_id)).unzip();
+
+        let partial_sigs: Vec<_> = keys
+            .iter()
+            .map(clone_keypair)
+            .zip(first_secrets)
+            .enumerate()
+            .map(|(i, (key, secret))| {
+                let mut first_msgs: Vec<_> = first_msgs.iter().map(clone_serialize).collect();
+                first_msgs.remove(i);
+                let inner_keys = if i < 2 { inner_keys.clone() } else { vec![] };
+                step_two(key, message.clone(), outer_keys.clone(), inner_keys, first_msgs, secret).unwrap()
+            })
+            .collect();
+        sign_and_broadcast(message, outer_keys, vec![], partial_sigs).unwrap();
+    }
+
+    #[test]
+    fn test_nested_invalid_partial_signature() {
+        let mut rng = rand07::thread_rng();
+        let keys: Vec<_> = (0..3).map(|_| Keypair::generate(&mut rng)).collect();
+        let pubkeys: Vec<_> = keys.iter().map(|k| k.pubkey()).collect();
+        let inner_keys = pubkeys[..2].to_vec();
+        let inner_key = Pubkey::new(&*key_agg(inner_keys.clone(), None).unwrap().agg_public_key.to_bytes(true));
+        let outer_keys = vec![inner_key, pubkeys[2]];
+        let to = Keypair::generate(&mut rng).pubkey();
+        let message = transfer_message(0.1, to, None, &outer_keys, Hash::new_unique());
+        let session_id = SessionId::random();
+        let (first_msgs, first_secrets): (Vec<_>, Vec<_>) = keys.iter().map(|k| step_one(k, session_id)).unzip();
+
+        let partial_sigs: Vec<_> = keys
+            .iter()
+            .map(clone_keypair)
+            .zip(first_secrets)
+            .enumerate()
+            .map(|(i, (key, secret))| {
+                let mut first_msgs: Vec<_> = first_msgs.iter().map(clone_serialize).collect();
+                first_msgs.remove(i);
+                let inner_keys = if i < 2 { inner_keys.clone() } else { vec![] };
+                step_two(key, message.clone(), outer_keys.clone(), inner_keys, first_msgs, secret).unwrap()
+            })
+            .collect();
+        let tampered = |i: usize| {
+            let mut sigs: Vec<_> = partial_sigs.iter().map(clone_serialize).collect();
+            let mut sig_bytes: [u8; 64] = sigs[i].signature.into();
+            sig_bytes[32] ^= 1;
+            sigs[i].signature = Signature::new(&sig_bytes);
+            sigs[i].sender_signature = keys[i].sign_message(&sender_signed_bytes(&sigs[i]));
+            sigs
+        };
+
+        // A party of the outer key is checked as usual.
+        let res = sign_and_broadcast(
+            message.clone(),
+            outer_keys.clone(),
+            first_msgs.iter().map(clone_serialize).collect(),
+            tampered(2),
+        );
+        assert!(matches!(res, Err(Error::InvalidPartialSignature(k)) if k == pubkeys[2]));
+        // A party of the inner key can't be checked, and no honest party is blamed in its place.
+        let res = sign_and_broadcast(message, outer_keys, first_msgs, tampered(1));
+        assert!(matches!(res, Err(Error::UnattributablePartialSignature)));
+    }
+
+    #[test]
+    fn test_no_keys_or_signatures() {
+        assert!(matches!(aggregate_pubkey(vec![]), Err(Error::NoKeys)));
+        let pubkeys: Vec<_> = (0..2).map(|_| Keypair::new().pubkey()).collect();
+        let message = transfer_message(0.1, Pubkey::new_unique(), None, &pubkeys, Hash::new_unique());
+        assert!(matches!(sign_and_broadcast(message.clone(), vec![], vec![], vec![]), Err(Error::NoKeys)));
+        assert!(matches!(sign_and_broadcast(message, pubkeys, vec![], vec![]), Err(Error::NoPartialSignatures)));
+    }
+
+    #[test]
+    fn test_wrong_transaction_signer() {
+        let mut rng = rand07::thread_rng();
+        let keys: Vec<_> = (0..2).map(|_| Keypair::generate(&mut rng)).collect();
+        let pubkeys: Vec<_> = keys.iter().map(|k| k.pubkey()).collect();
+        let session_id = SessionId::random();
+        let (first_msgs, mut first_secrets): (Vec<_>, Vec<_>) = keys.iter().map(|k| step_one(k, session_id)).unzip();
+
+        // The aggregated key can't sign a transaction it isn't a signer of.
+        let to = Pubkey::new_unique();
+        let message =
+            VersionedMessage::Legacy(create_unsigned_transaction(0.1, None, &to, None, None, &pubkeys[0]).message);
+        let res = step_two(
+            clone_keypair(&keys[0]),
+            message,
+            pubkeys.clone(),
+            vec![],
+            first_msgs[1..].iter().map(clone_serialize).collect(),
+            first_secrets.remove(0),
+        );
+        let aggpubkey = aggregate_pubkey(pubkeys).unwrap();
+        assert!(matches!(res, Err(Error::WrongTransactionSigner(k)) if k == aggpubkey));
+    }
+
+    #[test]
+    fn test_other_signer() {
+        let mut rng = rand07::thread_rng();
+        let keys: Vec<_> = (0..2).map(|_| Keypair::generate(&mut rng)).collect();
+        let pubkeys: Vec<_> = keys.iter().map(|k| k.pubkey()).collect();
+        let aggpubkey = aggregate_pubkey(pubkeys.clone()).unwrap();
+        let session_id = SessionId::random();
+        let (first_msgs, first_secrets): (Vec<_>, Vec<_>) = keys.iter().map(|k| step_one(k, session_id)).unzip();
+
+        // The wallet can be one signer among several, here with someone else paying the fee.
+        let payer = Keypair::generate(&mut rng);
+        let instructions = [system_instruction::transfer(&aggpubkey, &Pubkey::new_unique(), 1)];
+        let message = Message::new_with_blockhash(&instructions, Some(&payer.pubkey()), &Hash::new_unique());
+        let message = VersionedMessage::Legacy(message);
+        let partial_sigs: Vec<_> = keys
+            .iter()
+            .map(clone_keypair)
+            .zip(first_secrets)
+            .enumerate()
+            .map(|(i, (key, secret))| {
+                let mut first_msgs: Vec<_> = first_msgs.iter().map(clone_serialize).collect();
+                first_msgs.remove(i);
+                step_two(key, message.clone(), pubkeys.clone(), vec![], first_msgs, secret).unwrap()
+            })
+            .collect();
+        let mut tx = sign_and_broadcast(message, pubkeys, first_msgs, partial_sigs).unwrap();
+        assert_eq!(tx.signatures[0], Signature::default());
+        // The audit log records the aggregated key's signature, not the missing one of the fee payer.
+        assert_eq!(crate::audit::signature(&tx, aggpubkey).unwrap(), tx.signatures[1].to_string());
+        tx.signatures[0] = payer.sign_message(&tx.message.serialize());
+        assert!(tx.verify_with_results().into_iter().all(|valid| valid));
+    }
+
+    #[test]
+    fn test_key_pop() {
+        let mut rng = rand07::thread_rng();
+        let keys: Vec<_> = (0..3).map(|_| Keypair::generate(&mut rng)).collect();
+        let pubkeys: Vec<_> = keys.iter().map(|k| k.pubkey()).collect();
+        let mut pops: Vec<_> = keys.iter().map(key_pop).collect();
+        verify_key_pops(&pubkeys, &pops).unwrap();
+
+        // A signature over anything else isn't a proof of possession.
+        pops[1].signature = keys[1].sign_message(pubkeys[1].as_ref());
+        assert!(matches!(verify_key_pops(&pubkeys, &pops), Err(Error::InvalidProofOfPossession(k)) if k == pubkeys[1]));
+        pops.remove(1);
+        assert!(matches!(verify_key_pops(&pubkeys, &pops), Err(Error::MissingProofOfPossession(k)) if k == pubkeys[1]));
+    }
 }
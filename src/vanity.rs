@@ -0,0 +1,118 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use tracing::info;
+
+use crate::{tss, Error};
+
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// What the grinding looks for: an address starting with `prefix`
+pub struct Vanity {
+    prefix: String,
+    ignore_case: bool,
+}
+
+impl Vanity {
+    /// Fails when no address can start with `prefix`, Base58 has no 0, O, I and l
+    pub fn new(prefix: &str, ignore_case: bool) -> Result<Self, Error> {
+        let valid =
+            |c: char| BASE58_ALPHABET.contains(c) || (ignore_case && BASE58_ALPHABET.contains(c.to_ascii_uppercase()));
+        if prefix.is_empty() || !prefix.chars().all(valid) {
+            return Err(Error::WrongVanityPrefix(prefix.to_string()));
+        }
+        let prefix = if ignore_case { prefix.to_lowercase() } else { prefix.to_string() };
+        Ok(Self { prefix, ignore_case })
+    }
+
+    fn matches(&self, address: &Pubkey) -> bool {
+        let address = address.to_string();
+        if self.ignore_case {
+            address.to_lowercase().starts_with(&self.prefix)
+        } else {
+            address.starts_with(&self.prefix)
+        }
+    }
+
+    /// Generate keys on `threads` threads until the address of one of them starts with the prefix.
+    /// Every character of the prefix makes it take about 58 times longer.
+    pub fn grind(&self, threads: usize) -> Keypair {
+        self.grind_with(threads, |keypair| keypair.pubkey())
+    }
+
+    /// Generate keys until the aggregated address of `other_keys` followed by the new key starts with the prefix,
+    /// for the last party to join a wallet to give it a recognizable address.
+    pub fn grind_aggregated(&self, threads: usize, other_keys: &[Pubkey]) -> Result<Keypair, Error> {
+        let aggregate = |keypair: &Keypair| {
+            let mut keys = other_keys.to_vec();
+            keys.push(keypair.pubkey());
+            tss::aggregate_pubkey(keys)
+        };
+        // Only the other keys can be invalid, check them once rather than on every try.
+        aggregate(&Keypair::new())?;
+        Ok(self.grind_with(threads, |keypair| aggregate(keypair).expect("the other keys are valid")))
+    }
+
+    fn grind_with<F>(&self, threads: usize, address: F) -> Keypair
+    where
+        F: Fn(&Keypair) -> Pubkey + Sync,
+    {
+        let found = AtomicBool::new(false);
+        let tries = AtomicU64::new(0);
+        let result = Mutex::new(None);
+        thread::scope(|scope| {
+            for _ in 0..threads.max(1) {
+                scope.spawn(|| {
+                    let mut rng = rand07::thread_rng();
+                    while !found.load(Ordering::Relaxed) {
+                        let keypair = Keypair::generate(&mut rng);
+                        if self.matches(&address(&keypair)) && !found.swap(true, Ordering::Relaxed) {
+                            *result.lock().unwrap() = Some(keypair);
+                            return;
+                        }
+                        let tries = tries.fetch_add(1, Ordering::Relaxed) + 1;
+                        if tries % 1_000_000 == 0 {
+                            info!("Tried {} keys", tries);
+                        }
+                    }
+                });
+            }
+        });
+        result.into_inner().unwrap().expect("the threads only stop once one of them found a key")
+    }
+}
+
+/// The number of threads to grind on when not given, one per CPU
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, usize::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::vanity::Vanity;
+    use crate::{tss, Error};
+
+    #[test]
+    fn test_grind() {
+        let keypair = Vanity::new("A", false).unwrap().grind(2);
+        assert!(keypair.pubkey().to_string().starts_with('A'));
+        let keypair = Vanity::new("z", true).unwrap().grind(2);
+        assert!(keypair.pubkey().to_string().to_lowercase().starts_with('z'));
+
+        let others = [Keypair::new().pubkey(), Keypair::new().pubkey()];
+        let keypair = Vanity::new("B", false).unwrap().grind_aggregated(2, &others).unwrap();
+        let aggregated = tss::aggregate_pubkey(vec![others[0], others[1], keypair.pubkey()]).unwrap();
+        assert!(aggregated.to_string().starts_with('B'));
+
+        assert!(matches!(Vanity::new("0x", false), Err(Error::WrongVanityPrefix(_))));
+        assert!(matches!(Vanity::new("", false), Err(Error::WrongVanityPrefix(_))));
+        // A lowercase l can match an uppercase L when the case doesn't matter.
+        assert!(matches!(Vanity::new("l", false), Err(Error::WrongVanityPrefix(_))));
+        assert!(Vanity::new("l", true).is_ok());
+    }
+}
@@ -0,0 +1,73 @@
+//! The protocol for JavaScript, so a web page can be one of the parties, under camelCase names. A failure throws an
+//! `Error` with the message of the command line.
+
+use wasm_bindgen::prelude::*;
+
+use crate::bindings;
+
+#[wasm_bindgen(js_name = generateKeypair)]
+pub fn generate_keypair() -> String {
+    bindings::generate_keypair()
+}
+
+#[wasm_bindgen(js_name = publicKey)]
+pub fn public_key(keypair: &str) -> Result<String, JsError> {
+    Ok(bindings::public_key(keypair)?)
+}
+
+#[wasm_bindgen(js_name = aggregateKeys)]
+pub fn aggregate_keys(keys: Vec<String>) -> Result<String, JsError> {
+    Ok(bindings::aggregate_keys(&keys)?)
+}
+
+#[wasm_bindgen(js_name = newSessionId)]
+pub fn new_session_id() -> String {
+    bindings::new_session_id()
+}
+
+/// What `stepOne` returns, as JavaScript has no tuples: `firstMessage` to send and `secretState` to keep
+#[wasm_bindgen]
+pub struct StepOne {
+    first_message: String,
+    secret_state: String,
+}
+
+#[wasm_bindgen]
+impl StepOne {
+    #[wasm_bindgen(getter, js_name = firstMessage)]
+    pub fn first_message(&self) -> String {
+        self.first_message.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = secretState)]
+    pub fn secret_state(&self) -> String {
+        self.secret_state.clone()
+    }
+}
+
+#[wasm_bindgen(js_name = stepOne)]
+pub fn step_one(keypair: &str, session_id: &str) -> Result<StepOne, JsError> {
+    let (first_message, secret_state) = bindings::step_one(keypair, session_id)?;
+    Ok(StepOne { first_message, secret_state })
+}
+
+#[wasm_bindgen(js_name = stepTwo)]
+pub fn step_two(
+    keypair: &str,
+    unsigned_tx: &str,
+    keys: Vec<String>,
+    first_messages: Vec<String>,
+    secret_state: &str,
+) -> Result<String, JsError> {
+    Ok(bindings::step_two(keypair, unsigned_tx, &keys, &first_messages, secret_state)?)
+}
+
+#[wasm_bindgen(js_name = aggregateSignatures)]
+pub fn aggregate_signatures(
+    unsigned_tx: &str,
+    keys: Vec<String>,
+    first_messages: Vec<String>,
+    signatures: Vec<String>,
+) -> Result<String, JsError> {
+    Ok(bindings::aggregate_signatures(unsigned_tx, &keys, &first_messages, &signatures)?)
+}
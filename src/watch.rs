@@ -0,0 +1,48 @@
+use futures::StreamExt;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::native_token::lamports_to_sol;
+use solana_sdk::pubkey::Pubkey;
+use tracing::info;
+
+use crate::history::format_amount;
+use crate::Error;
+
+/// Print every change of the balance of `address` and every transaction mentioning it as they happen, until the
+/// connection to `url`, the RPC node's websocket endpoint, closes. Starts from the balance `lamports`.
+pub async fn watch(url: &str, address: &Pubkey, commitment: CommitmentConfig, mut lamports: u64) -> Result<(), Error> {
+    let client = PubsubClient::new(url).await.map_err(Error::WatchFailed)?;
+    let config = RpcAccountInfoConfig {
+        // Base58, the default, can't encode accounts of more than 128 bytes.
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(commitment),
+        ..RpcAccountInfoConfig::default()
+    };
+    let (mut accounts, _) = client.account_subscribe(address, Some(config)).await.map_err(Error::WatchFailed)?;
+    let filter = RpcTransactionLogsFilter::Mentions(vec![address.to_string()]);
+    let config = RpcTransactionLogsConfig { commitment: Some(commitment) };
+    let (mut transactions, _) = client.logs_subscribe(filter, config).await.map_err(Error::WatchFailed)?;
+    info!("Watching {}, balance: {} SOL", address, lamports_to_sol(lamports));
+    loop {
+        tokio::select! {
+            Some(account) = accounts.next() => {
+                let change = i128::from(account.value.lamports) - i128::from(lamports);
+                lamports = account.value.lamports;
+                if change != 0 {
+                    let balance = lamports_to_sol(lamports);
+                    println!("Slot {}: balance {} SOL ({})", account.context.slot, balance, format_amount(change, 9));
+                }
+            }
+            Some(transaction) = transactions.next() => {
+                let status = match transaction.value.err {
+                    Some(e) => format!("failed: {}", e),
+                    None => "succeeded".to_string(),
+                };
+                println!("Slot {}: transaction {} {}", transaction.context.slot, transaction.value.signature, status);
+            }
+            else => return Err(Error::WatchClosed),
+        }
+    }
+}